@@ -0,0 +1,204 @@
+use nalgebra_glm::{Quat, Vec3, Vec4};
+
+use crate::core::ecs::Entity;
+
+/// How values between two keyframes are computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Holds each keyframe's value until the next one.
+    Step,
+    /// Interpolates linearly, spherically for rotations.
+    #[default]
+    Linear,
+    /// Interpolates with a cubic Hermite spline. Each keyframe stores an in-tangent, a
+    /// value and an out-tangent, in that order.
+    CubicSpline,
+}
+
+/// The keyframe values of one animated property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelValues {
+    Translation(Vec<Vec3>),
+    Rotation(Vec<Quat>),
+    Scale(Vec<Vec3>),
+    /// Morph target weights, with one value per target for each keyframe.
+    Weights(Vec<f32>),
+}
+
+/// Keyframes animating one property of one entity.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationChannel {
+    /// The entity whose `Transform` or morph weights are animated.
+    pub target: Entity,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds, in increasing order.
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+}
+
+/// A named set of channels played together, such as a walk cycle.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Time of the last keyframe of any channel, in seconds.
+    pub duration: f32,
+    pub channels: Vec<AnimationChannel>,
+}
+
+/// The value of a channel at one point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChannelSample {
+    Translation(Vec3),
+    Rotation(Quat),
+    Scale(Vec3),
+    /// One weight per morph target.
+    Weights(Vec<f32>),
+}
+
+impl AnimationChannel {
+    /// Computes the channel's value at `time`.
+    ///
+    /// Times before the first or after the last keyframe hold that keyframe's value.
+    ///
+    /// # Returns
+    ///
+    /// The interpolated value, or `None` if the channel has no keyframes or fewer values
+    /// than keyframes.
+    pub fn sample(&self, time: f32) -> Option<ChannelSample> {
+        let keys = self.times.len();
+        let per_key = match self.interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        if keys == 0 {
+            return None;
+        }
+
+        let sampler = Sampler::new(&self.times, self.interpolation, time);
+        Some(match &self.values {
+            ChannelValues::Translation(values) => ChannelSample::Translation(sampler.sample(values, 1, 0)?),
+            ChannelValues::Rotation(values) => {
+                ChannelSample::Rotation(nalgebra_glm::quat_normalize(&sampler.sample(values, 1, 0)?))
+            }
+            ChannelValues::Scale(values) => ChannelSample::Scale(sampler.sample(values, 1, 0)?),
+            ChannelValues::Weights(values) => {
+                let targets = values.len() / (keys * per_key);
+                let weights = (0..targets).map(|target| sampler.sample(values, targets, target));
+                ChannelSample::Weights(weights.collect::<Option<Vec<f32>>>()?)
+            }
+        })
+    }
+}
+
+impl AnimationClip {
+    /// Creates a clip lasting until the last keyframe of its channels.
+    pub fn new(name: &str, channels: Vec<AnimationChannel>) -> AnimationClip {
+        let duration = channels
+            .iter()
+            .filter_map(|channel| channel.times.last().copied())
+            .fold(0.0, f32::max);
+        AnimationClip { name: name.to_string(), duration, channels }
+    }
+}
+
+/// Interpolates spherically between two rotations, along the shortest arc.
+pub(super) fn slerp(from: &Quat, to: &Quat, t: f32) -> Quat {
+    let to = if from.dot(to) < 0.0 { -to } else { *to };
+    if from.dot(&to) > 0.9995 {
+        // Nearly identical rotations make slerp divide by almost zero.
+        return nalgebra_glm::quat_normalize(&Quat::from(from.coords.lerp(&to.coords, t)));
+    }
+    nalgebra_glm::quat_slerp(from, &to, t)
+}
+
+/// A value that can be interpolated between keyframes.
+trait Keyframe: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+    /// Evaluates the cubic Hermite basis, weighting the two values and two tangents.
+    fn hermite(values: [Self; 4], weights: [f32; 4]) -> Self;
+}
+
+impl Keyframe for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+
+    fn hermite(values: [f32; 4], weights: [f32; 4]) -> f32 {
+        values.iter().zip(weights).map(|(value, weight)| value * weight).sum()
+    }
+}
+
+impl Keyframe for Vec3 {
+    fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+        self + (other - self) * t
+    }
+
+    fn hermite(values: [Vec3; 4], weights: [f32; 4]) -> Vec3 {
+        values.iter().zip(weights).map(|(value, weight)| value * weight).sum()
+    }
+}
+
+impl Keyframe for Quat {
+    fn lerp(self, other: Quat, t: f32) -> Quat {
+        slerp(&self, &other, t)
+    }
+
+    /// Splines the raw components; the caller normalizes the result.
+    fn hermite(values: [Quat; 4], weights: [f32; 4]) -> Quat {
+        Quat::from(values.iter().zip(weights).map(|(value, weight)| value.coords * weight).sum::<Vec4>())
+    }
+}
+
+/// The keyframes around a point in time and how far between them it is.
+struct Sampler {
+    interpolation: Interpolation,
+    previous: usize,
+    next: usize,
+    /// Position between `previous` and `next`, from `0` to `1`.
+    t: f32,
+    /// Seconds between `previous` and `next`, which scales cubic spline tangents.
+    span: f32,
+}
+
+impl Sampler {
+    fn new(times: &[f32], interpolation: Interpolation, time: f32) -> Sampler {
+        let last = times.len() - 1;
+        let next = times.partition_point(|&key| key <= time);
+        let (previous, next) = match next {
+            0 => (0, 0),
+            next if next > last => (last, last),
+            next => (next - 1, next),
+        };
+
+        let span = times[next] - times[previous];
+        let t = if span > 0.0 { ((time - times[previous]) / span).clamp(0.0, 1.0) } else { 0.0 };
+        Sampler { interpolation, previous, next, t, span }
+    }
+
+    /// Interpolates one element of keyframes holding `width` elements each.
+    fn sample<T: Keyframe>(&self, values: &[T], width: usize, element: usize) -> Option<T> {
+        let value = |key: usize, part: usize| match self.interpolation {
+            // Cubic spline keyframes store an in-tangent, a value and an out-tangent.
+            Interpolation::CubicSpline => values.get((key * 3 + part) * width + element).copied(),
+            _ => values.get(key * width + element).copied(),
+        };
+
+        match self.interpolation {
+            Interpolation::Step => value(self.previous, 0),
+            Interpolation::Linear => Some(value(self.previous, 0)?.lerp(value(self.next, 0)?, self.t)),
+            Interpolation::CubicSpline => {
+                let (t, t2, t3) = (self.t, self.t * self.t, self.t * self.t * self.t);
+                let weights = [
+                    2.0 * t3 - 3.0 * t2 + 1.0,
+                    (t3 - 2.0 * t2 + t) * self.span,
+                    -2.0 * t3 + 3.0 * t2,
+                    (t3 - t2) * self.span,
+                ];
+                // The previous value and out-tangent, then the next value and in-tangent.
+                let points =
+                    [value(self.previous, 1)?, value(self.previous, 2)?, value(self.next, 1)?, value(self.next, 0)?];
+                Some(T::hermite(points, weights))
+            }
+        }
+    }
+}
@@ -0,0 +1,7 @@
+mod clip;
+mod player;
+mod skin;
+
+pub use clip::{AnimationChannel, AnimationClip, ChannelSample, ChannelValues, Interpolation};
+pub use player::{animate, AnimationLayer, AnimationPlayer, LayerBlend, PlayingClip};
+pub use skin::{update_skins, Skin};
@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use nalgebra_glm::{Quat, Vec3, Vec4};
+
+use super::clip::slerp;
+use super::{AnimationClip, ChannelSample};
+use crate::core::components::{MorphWeights, Transform};
+use crate::core::ecs::{Entity, World};
+
+/// How an `AnimationLayer` combines with the layers below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayerBlend {
+    /// Replaces the pose below, mixed in by the layer weight.
+    #[default]
+    Override,
+    /// Adds how far each clip moved from its first keyframe on top of the pose below,
+    /// e.g. breathing or recoil over any locomotion.
+    Additive,
+}
+
+/// A clip playing on an `AnimationLayer`.
+#[derive(Clone, Debug)]
+pub struct PlayingClip {
+    clip: Rc<AnimationClip>,
+    time: f32,
+    weight: f32,
+    target_weight: f32,
+    /// How fast `weight` moves to `target_weight`, per second.
+    fade_rate: f32,
+    /// Whether the clip is removed once it faded out.
+    stopping: bool,
+}
+
+impl PlayingClip {
+    fn new(clip: Rc<AnimationClip>, weight: f32) -> PlayingClip {
+        PlayingClip { clip, time: 0.0, weight, target_weight: weight, fade_rate: 0.0, stopping: false }
+    }
+
+    /// Returns the clip being played.
+    pub fn get_clip(&self) -> &Rc<AnimationClip> {
+        &self.clip
+    }
+
+    /// Returns the playback position, in seconds.
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// Jumps to a playback position, in seconds.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Returns how strongly the clip contributes to its layer, including crossfades.
+    pub fn get_weight(&self) -> f32 {
+        self.weight
+    }
+}
+
+/// A set of clips blended together, then combined with the layers below.
+///
+/// Within an `LayerBlend::Override` layer, clips are averaged by their weights, so two
+/// clips at `0.5` mix evenly. When the weights add up to less than one, the rest of the
+/// pose comes from the layers below.
+#[derive(Clone, Debug)]
+pub struct AnimationLayer {
+    /// How strongly the layer affects the pose, from `0` to `1`.
+    pub weight: f32,
+    pub blend: LayerBlend,
+    /// Whether clips restart after their last keyframe instead of holding it.
+    pub looping: bool,
+    /// Playback speed multiplier. Negative speeds play backwards.
+    pub speed: f32,
+    clips: Vec<PlayingClip>,
+}
+
+impl AnimationLayer {
+    /// Creates an empty, looping layer at full weight.
+    pub fn new(blend: LayerBlend) -> AnimationLayer {
+        AnimationLayer { weight: 1.0, blend, looping: true, speed: 1.0, clips: Vec::new() }
+    }
+
+    /// Stops every clip and plays `clip` from its start at full weight.
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        self.clips.clear();
+        self.clips.push(PlayingClip::new(clip, 1.0));
+    }
+
+    /// Fades `clip` in while fading every other clip out.
+    ///
+    /// A clip that is already playing continues from where it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `clip` - The clip to transition to.
+    /// * `duration` - How long the transition takes, in seconds.
+    pub fn crossfade(&mut self, clip: Rc<AnimationClip>, duration: f32) {
+        if duration <= 0.0 {
+            self.play(clip);
+            return;
+        }
+
+        for playing in &mut self.clips {
+            let (target_weight, stopping) = if Rc::ptr_eq(&playing.clip, &clip) { (1.0, false) } else { (0.0, true) };
+            playing.fade_rate = (target_weight - playing.weight).abs() / duration;
+            playing.target_weight = target_weight;
+            playing.stopping = stopping;
+        }
+        if !self.clips.iter().any(|playing| Rc::ptr_eq(&playing.clip, &clip)) {
+            let incoming = PlayingClip { target_weight: 1.0, fade_rate: 1.0 / duration, ..PlayingClip::new(clip, 0.0) };
+            self.clips.push(incoming);
+        }
+    }
+
+    /// Sets how strongly `clip` contributes to the layer, starting it if needed.
+    ///
+    /// Unlike `play`, other clips keep playing, which allows blending e.g. walk and run
+    /// cycles by speed.
+    pub fn blend(&mut self, clip: Rc<AnimationClip>, weight: f32) {
+        match self.clips.iter_mut().find(|playing| Rc::ptr_eq(&playing.clip, &clip)) {
+            Some(playing) => {
+                playing.weight = weight;
+                playing.target_weight = weight;
+                playing.fade_rate = 0.0;
+                playing.stopping = false;
+            }
+            None => self.clips.push(PlayingClip::new(clip, weight)),
+        }
+    }
+
+    /// Stops every clip.
+    pub fn stop(&mut self) {
+        self.clips.clear();
+    }
+
+    /// Returns the clips playing on the layer.
+    pub fn get_clips(&self) -> &[PlayingClip] {
+        &self.clips
+    }
+
+    /// Finds a playing clip by name, e.g. to seek it.
+    pub fn get_clip_mut(&mut self, name: &str) -> Option<&mut PlayingClip> {
+        self.clips.iter_mut().find(|playing| playing.clip.name == name)
+    }
+
+    /// Checks whether the layer does not loop and every clip reached its end.
+    pub fn is_finished(&self) -> bool {
+        let at_end = |playing: &PlayingClip| match self.speed < 0.0 {
+            true => playing.time <= 0.0,
+            false => playing.time >= playing.clip.duration,
+        };
+        !self.looping && self.clips.iter().all(at_end)
+    }
+
+    /// Moves every clip forward by `delta` seconds and advances crossfades.
+    pub fn advance(&mut self, delta: f32) {
+        for playing in &mut self.clips {
+            let duration = playing.clip.duration;
+            playing.time += delta * self.speed;
+            playing.time = match self.looping && duration > 0.0 {
+                true => playing.time.rem_euclid(duration),
+                false => playing.time.clamp(0.0, duration),
+            };
+
+            let step = playing.fade_rate * delta.abs();
+            playing.weight = match playing.weight < playing.target_weight {
+                true => (playing.weight + step).min(playing.target_weight),
+                false => (playing.weight - step).max(playing.target_weight),
+            };
+        }
+        self.clips.retain(|playing| !playing.stopping || playing.weight > 0.0);
+    }
+}
+
+/// Plays animation clips on the entities they target.
+///
+/// Layers are evaluated bottom to top, starting from the rest pose: the `Transform` and
+/// `MorphWeights` an entity had when a clip first animated it. Attach it to any entity, such as the `root`
+/// of a `GltfScene`, and the scene advances it every frame.
+///
+/// [`GltfScene`]: crate::core::GltfScene
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    layers: Vec<AnimationLayer>,
+    speed: f32,
+    paused: bool,
+    rest: HashMap<Entity, Pose>,
+}
+
+impl AnimationPlayer {
+    /// Creates a player with one empty `LayerBlend::Override` layer.
+    pub fn new() -> AnimationPlayer {
+        AnimationPlayer {
+            layers: vec![AnimationLayer::new(LayerBlend::Override)],
+            speed: 1.0,
+            paused: false,
+            rest: HashMap::new(),
+        }
+    }
+
+    /// Plays `clip` on the base layer, see `AnimationLayer::play`.
+    pub fn play(&mut self, clip: Rc<AnimationClip>) {
+        self.layers[0].play(clip);
+    }
+
+    /// Transitions the base layer to `clip`, see `AnimationLayer::crossfade`.
+    pub fn crossfade(&mut self, clip: Rc<AnimationClip>, duration: f32) {
+        self.layers[0].crossfade(clip, duration);
+    }
+
+    /// Adds a layer on top of the others.
+    ///
+    /// # Returns
+    ///
+    /// The index of the layer, for `get_layer_mut`.
+    pub fn add_layer(&mut self, layer: AnimationLayer) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    /// Returns a layer, `0` being the base layer.
+    pub fn get_layer(&self, index: usize) -> Option<&AnimationLayer> {
+        self.layers.get(index)
+    }
+
+    /// Returns a layer mutably, `0` being the base layer.
+    pub fn get_layer_mut(&mut self, index: usize) -> Option<&mut AnimationLayer> {
+        self.layers.get_mut(index)
+    }
+
+    /// Returns the playback speed multiplier applied to every layer.
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /// Sets the playback speed multiplier applied to every layer.
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Checks whether playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes playback. Paused players still apply their pose.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Advances every layer by `delta` seconds, unless paused.
+    pub fn advance(&mut self, delta: f32) {
+        if self.paused {
+            return;
+        }
+        for layer in &mut self.layers {
+            layer.advance(delta * self.speed);
+        }
+    }
+
+    /// Evaluates the layers and writes the resulting pose to the targets' `Transform`s,
+    /// and `MorphWeights` for targets with animated weights.
+    pub fn apply(&mut self, world: &mut World) {
+        let AnimationPlayer { layers, rest, .. } = self;
+        let mut pose: HashMap<Entity, Pose> = HashMap::new();
+
+        for layer in layers.iter().filter(|layer| layer.weight > 0.0) {
+            match layer.blend {
+                LayerBlend::Override => {
+                    let mut mixes: HashMap<Entity, Mix> = HashMap::new();
+                    for playing in layer.clips.iter().filter(|playing| playing.weight > 0.0) {
+                        for channel in &playing.clip.channels {
+                            if let Some(sample) = channel.sample(playing.time) {
+                                mixes.entry(channel.target).or_default().add(&sample, playing.weight);
+                            }
+                        }
+                    }
+                    for (entity, mix) in mixes {
+                        if let Some(pose) = posed(&mut pose, rest, world, entity) {
+                            mix.apply(pose, layer.weight);
+                        }
+                    }
+                }
+                LayerBlend::Additive => {
+                    for playing in layer.clips.iter().filter(|playing| playing.weight > 0.0) {
+                        for channel in &playing.clip.channels {
+                            let Some(&start) = channel.times.first() else { continue };
+                            let (Some(sample), Some(reference)) = (channel.sample(playing.time), channel.sample(start))
+                            else {
+                                continue;
+                            };
+                            if let Some(pose) = posed(&mut pose, rest, world, channel.target) {
+                                add(pose, &sample, &reference, playing.weight * layer.weight);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (entity, pose) in pose {
+            world.insert(entity, pose.transform);
+            if !pose.weights.is_empty() {
+                world.insert(entity, MorphWeights(pose.weights));
+            }
+        }
+    }
+}
+
+impl Default for AnimationPlayer {
+    fn default() -> Self {
+        AnimationPlayer::new()
+    }
+}
+
+/// Advances every `AnimationPlayer` in `world` and applies its pose.
+///
+/// Runs before `World::update_transforms` so the new pose is propagated the same frame.
+pub fn animate(world: &mut World, delta: f32) {
+    let players: Vec<Entity> = world.query::<AnimationPlayer>().map(|(entity, _)| entity).collect();
+    for entity in players {
+        // The player is taken out so it can write to the transforms of the world it lives in.
+        let Some(mut player) = world.remove::<AnimationPlayer>(entity) else { continue };
+        player.advance(delta);
+        player.apply(world);
+        world.insert(entity, player);
+    }
+}
+
+/// The animated state of one entity.
+#[derive(Clone, Debug)]
+struct Pose {
+    transform: Transform,
+    weights: Vec<f32>,
+}
+
+/// Returns the pose of an entity so far, starting from its rest pose.
+///
+/// # Returns
+///
+/// `None` if the entity has been despawned.
+fn posed<'a>(
+    pose: &'a mut HashMap<Entity, Pose>,
+    rest: &mut HashMap<Entity, Pose>,
+    world: &World,
+    entity: Entity,
+) -> Option<&'a mut Pose> {
+    if !world.is_alive(entity) {
+        return None;
+    }
+    let rest = rest.entry(entity).or_insert_with(|| Pose {
+        transform: world.get::<Transform>(entity).copied().unwrap_or_default(),
+        weights: world.get::<MorphWeights>(entity).map_or_else(Vec::new, |weights| weights.0.clone()),
+    });
+    Some(pose.entry(entity).or_insert_with(|| rest.clone()))
+}
+
+/// The weighted sum of the samples of one entity on an override layer.
+#[derive(Default)]
+struct Mix {
+    translation: Vec3,
+    translation_weight: f32,
+    rotation: Vec4,
+    rotation_weight: f32,
+    scale: Vec3,
+    scale_weight: f32,
+    weights: Vec<f32>,
+    weights_weight: f32,
+}
+
+impl Mix {
+    fn add(&mut self, sample: &ChannelSample, weight: f32) {
+        match sample {
+            ChannelSample::Translation(translation) => {
+                self.translation += translation * weight;
+                self.translation_weight += weight;
+            }
+            ChannelSample::Rotation(rotation) => {
+                // q and -q are the same rotation, keep them on one hemisphere so they add up.
+                let sign = if self.rotation.dot(&rotation.coords) < 0.0 { -1.0 } else { 1.0 };
+                self.rotation += rotation.coords * sign * weight;
+                self.rotation_weight += weight;
+            }
+            ChannelSample::Scale(scale) => {
+                self.scale += scale * weight;
+                self.scale_weight += weight;
+            }
+            ChannelSample::Weights(weights) => {
+                if self.weights.len() < weights.len() {
+                    self.weights.resize(weights.len(), 0.0);
+                }
+                for (sum, value) in self.weights.iter_mut().zip(weights) {
+                    *sum += value * weight;
+                }
+                self.weights_weight += weight;
+            }
+        }
+    }
+
+    /// Mixes the averaged samples over `pose` by the layer `weight`.
+    fn apply(&self, pose: &mut Pose, weight: f32) {
+        let transform = &mut pose.transform;
+        if self.translation_weight > 0.0 {
+            let amount = self.translation_weight.min(1.0) * weight;
+            let translation = self.translation / self.translation_weight;
+            transform.translation = transform.translation.lerp(&translation, amount);
+        }
+        if self.rotation_weight > 0.0 {
+            let amount = self.rotation_weight.min(1.0) * weight;
+            let rotation = nalgebra_glm::quat_normalize(&Quat::from(self.rotation));
+            transform.rotation = slerp(&transform.rotation, &rotation, amount);
+        }
+        if self.scale_weight > 0.0 {
+            let amount = self.scale_weight.min(1.0) * weight;
+            let scale = self.scale / self.scale_weight;
+            transform.scale = transform.scale.lerp(&scale, amount);
+        }
+        if self.weights_weight > 0.0 {
+            let amount = self.weights_weight.min(1.0) * weight;
+            if pose.weights.len() < self.weights.len() {
+                pose.weights.resize(self.weights.len(), 0.0);
+            }
+            for (current, sum) in pose.weights.iter_mut().zip(&self.weights) {
+                *current += (sum / self.weights_weight - *current) * amount;
+            }
+        }
+    }
+}
+
+/// Adds how far `sample` moved from `reference` to `pose`, scaled by `amount`.
+fn add(pose: &mut Pose, sample: &ChannelSample, reference: &ChannelSample, amount: f32) {
+    let transform = &mut pose.transform;
+    match (sample, reference) {
+        (ChannelSample::Translation(value), ChannelSample::Translation(reference)) => {
+            transform.translation += (value - reference) * amount;
+        }
+        (ChannelSample::Rotation(value), ChannelSample::Rotation(reference)) => {
+            let delta = slerp(&Quat::identity(), &(reference.conjugate() * value), amount);
+            transform.rotation = nalgebra_glm::quat_normalize(&(transform.rotation * delta));
+        }
+        (ChannelSample::Scale(value), ChannelSample::Scale(reference)) => {
+            let ratio = value.zip_map(reference, |value, reference| match reference != 0.0 {
+                true => value / reference,
+                false => 1.0,
+            });
+            transform.scale.component_mul_assign(&Vec3::repeat(1.0).lerp(&ratio, amount));
+        }
+        (ChannelSample::Weights(values), ChannelSample::Weights(references)) => {
+            if pose.weights.len() < values.len() {
+                pose.weights.resize(values.len(), 0.0);
+            }
+            for ((current, value), reference) in pose.weights.iter_mut().zip(values).zip(references) {
+                *current += (value - reference) * amount;
+            }
+        }
+        _ => {}
+    }
+}
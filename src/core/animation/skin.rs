@@ -0,0 +1,65 @@
+use nalgebra_glm::Mat4;
+
+use crate::core::components::{GlobalTransform, MeshRenderer};
+use crate::core::ecs::{Entity, World};
+
+/// The joints deforming a skinned mesh.
+///
+/// Vertex joint indices refer to positions in `joints`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Skin {
+    /// The entities whose `GlobalTransform`s pose the joints.
+    pub joints: Vec<Entity>,
+    /// Matrices moving the mesh from model space into each joint's space at bind time,
+    /// one per joint.
+    pub inverse_bind_matrices: Vec<Mat4>,
+}
+
+impl Skin {
+    /// Computes the matrices posing a mesh with this skin, for
+    /// `MeshRenderer::set_joint_matrices`.
+    ///
+    /// # Arguments
+    ///
+    /// * `world` - The world holding the joints' `GlobalTransform`s.
+    /// * `mesh_transform` - The global transform of the skinned mesh.
+    ///
+    /// # Returns
+    ///
+    /// One matrix per joint, mapping the bind pose into the mesh's space. Joints without
+    /// a `GlobalTransform` keep their bind pose.
+    pub fn joint_matrices(&self, world: &World, mesh_transform: &Mat4) -> Vec<Mat4> {
+        let to_mesh = mesh_transform.try_inverse().unwrap_or_else(Mat4::identity);
+        self.joints
+            .iter()
+            .enumerate()
+            .map(|(index, &joint)| {
+                let inverse_bind = self.inverse_bind_matrices.get(index).copied().unwrap_or_else(Mat4::identity);
+                match world.get::<GlobalTransform>(joint) {
+                    Some(GlobalTransform(global)) => to_mesh * global * inverse_bind,
+                    None => Mat4::identity(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Uploads the joint matrices of every entity with both a `Skin` and a `MeshRenderer`.
+///
+/// Expects `World::update_transforms` to have run since the joints last moved.
+pub fn update_skins(world: &mut World) {
+    let palettes: Vec<(Entity, Vec<Mat4>)> = world
+        .query::<Skin>()
+        .filter(|&(entity, _)| world.has::<MeshRenderer>(entity))
+        .map(|(entity, skin)| {
+            let mesh_transform = world.get::<GlobalTransform>(entity).map_or_else(Mat4::identity, |global| global.0);
+            (entity, skin.joint_matrices(world, &mesh_transform))
+        })
+        .collect();
+
+    for (entity, palette) in palettes {
+        if let Some(render) = world.get_mut::<MeshRenderer>(entity) {
+            render.set_joint_matrices(&palette);
+        }
+    }
+}
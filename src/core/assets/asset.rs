@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{Rgb32FImage, RgbaImage};
+
+use crate::core::atlas::{AtlasLayout, TextureAtlas};
+use crate::core::compressed::CompressedImage;
+use crate::core::mesh::ObjModel;
+use crate::opengl::{Cubemap, ShaderProgram, Texture, TextureFormat};
+
+/// A type that `AssetServer::load` and `AssetServer::load_async` can read from a file.
+///
+/// Loading is split in two so the slow part can run in the background: `decode` reads
+/// and parses the file on any thread, then `upload` creates the asset from the decoded
+/// data on the main thread, where the GL context is current.
+///
+/// Assets loaded through the `AssetServer` are loaded again when one of their `sources`
+/// changes while hot reloading is enabled.
+///
+/// Types without an implementation can still be stored with `Assets::add` or loaded
+/// with `AssetServer::load_with`, e.g. a `PbrMaterial` built from loaded textures.
+pub trait Asset: Sized + 'static {
+    /// What `decode` hands over to `upload`.
+    type Data: Send + 'static;
+
+    /// Reads and parses the file at `path`. Called on a worker thread for background loads.
+    ///
+    /// # Returns
+    ///
+    /// The decoded data, or an `Err(String)` naming the file if it cannot be read.
+    fn decode(path: &Path) -> Result<Self::Data, String>;
+
+    /// Creates the asset from decoded data on the main thread.
+    ///
+    /// # Returns
+    ///
+    /// The asset, or an `Err(String)` if the data cannot be uploaded.
+    fn upload(data: Self::Data) -> Result<Self, String>;
+
+    /// Returns the files `decode` reads for `path`, which are watched for hot reloading.
+    fn sources(path: &Path) -> Vec<PathBuf> {
+        vec![path.to_path_buf()]
+    }
+}
+
+/// What `Texture::decode` hands over to `Texture::upload`.
+pub enum TextureData {
+    /// A decoded image, uploaded as an sRGB color texture, or a linear one through
+    /// `AssetServer::load_texture`.
+    Image(RgbaImage),
+    /// Blocks read from a KTX2 or DDS file, uploaded in the format they are stored in.
+    Compressed(CompressedImage),
+}
+
+impl Asset for Texture {
+    type Data = TextureData;
+
+    /// Reads a KTX2 or DDS file as a `CompressedImage`, or decodes any other image to
+    /// RGBA. Images are uploaded as sRGB color textures; load linear data such as normal
+    /// maps through `AssetServer::load_texture` instead, or store it in a linear
+    /// compressed format.
+    fn decode(path: &Path) -> Result<TextureData, String> {
+        let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+        if extension == "ktx2" || extension == "dds" {
+            return CompressedImage::load(path).map(TextureData::Compressed);
+        }
+        let image = image::open(path).map_err(|err| format!("Cannot load texture {}: {err}", path.display()))?;
+        Ok(TextureData::Image(image.to_rgba8()))
+    }
+
+    fn upload(data: TextureData) -> Result<Self, String> {
+        Ok(upload_texture(data, true))
+    }
+}
+
+impl Asset for Cubemap {
+    type Data = Rgb32FImage;
+
+    /// Reads an equirectangular image, which is projected onto faces a quarter of its
+    /// width wide like `Cubemap::from_equirectangular` does.
+    fn decode(path: &Path) -> Result<Rgb32FImage, String> {
+        let image = image::open(path).map_err(|err| format!("Cannot load environment {}: {err}", path.display()))?;
+        Ok(image.to_rgb32f())
+    }
+
+    fn upload(image: Rgb32FImage) -> Result<Self, String> {
+        let (width, height) = image.dimensions();
+        Ok(Cubemap::from_equirectangular_data(image.as_raw(), width, height, (width / 4).max(1)))
+    }
+}
+
+impl Asset for ShaderProgram {
+    /// The vertex and fragment sources.
+    type Data = (String, String);
+
+    /// Reads a vertex shader at `path` and the fragment shader next to it with the
+    /// `frag` extension, e.g. `sprite.vert` and `sprite.frag`.
+    fn decode(path: &Path) -> Result<(String, String), String> {
+        Ok((read_to_string(path)?, read_to_string(&path.with_extension("frag"))?))
+    }
+
+    fn upload((vertex_src, fragment_src): (String, String)) -> Result<Self, String> {
+        ShaderProgram::try_new(&vertex_src, &fragment_src)
+    }
+
+    fn sources(path: &Path) -> Vec<PathBuf> {
+        vec![path.to_path_buf(), path.with_extension("frag")]
+    }
+}
+
+impl Asset for TextureAtlas {
+    type Data = AtlasLayout;
+
+    /// Reads an atlas manifest written by `AtlasLayout::save` and its page images.
+    fn decode(path: &Path) -> Result<AtlasLayout, String> {
+        AtlasLayout::load(path)
+    }
+
+    fn upload(layout: AtlasLayout) -> Result<Self, String> {
+        Ok(TextureAtlas::from_layout(&layout))
+    }
+}
+
+impl Asset for ObjModel {
+    type Data = ObjModel;
+
+    fn decode(path: &Path) -> Result<ObjModel, String> {
+        ObjModel::load(path)
+    }
+
+    fn upload(model: ObjModel) -> Result<Self, String> {
+        Ok(model)
+    }
+}
+
+impl Asset for String {
+    type Data = String;
+
+    /// Reads a UTF-8 text file, such as a configuration file parsed by the game.
+    fn decode(path: &Path) -> Result<String, String> {
+        read_to_string(path)
+    }
+
+    fn upload(text: String) -> Result<Self, String> {
+        Ok(text)
+    }
+}
+
+impl Asset for Vec<u8> {
+    type Data = Vec<u8>;
+
+    /// Reads the raw bytes of a file, such as a save game or a custom binary format.
+    fn decode(path: &Path) -> Result<Vec<u8>, String> {
+        fs::read(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))
+    }
+
+    fn upload(bytes: Vec<u8>) -> Result<Self, String> {
+        Ok(bytes)
+    }
+}
+
+/// Uploads decoded texture data, as an sRGB color texture or a linear one. Compressed
+/// images keep the color space of their format.
+pub(super) fn upload_texture(data: TextureData, srgb: bool) -> Texture {
+    match data {
+        TextureData::Image(image) => {
+            let format = if srgb { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+            Texture::from_data(image.width(), image.height(), format, image.as_raw())
+        }
+        TextureData::Compressed(image) => image.upload(),
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))
+}
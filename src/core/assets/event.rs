@@ -0,0 +1,51 @@
+use std::any::TypeId;
+use std::path::PathBuf;
+
+use super::{AssetId, Handle};
+
+/// What happened to an asset in an `AssetEvent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetEventKind {
+    /// A background load or dependent build finished.
+    Loaded,
+    /// A changed file was loaded again, or an asset was rebuilt after a dependency
+    /// reloaded. Every handle now refers to the new version.
+    Reloaded,
+    /// A load, reload or build failed. Assets that failed to reload keep their previous
+    /// version and still report `LoadState::Loaded`.
+    Failed,
+}
+
+/// Reports an asset finishing loading or changing, see `AssetServer::get_events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetEvent {
+    /// What happened.
+    pub kind: AssetEventKind,
+    /// The type of the asset.
+    pub type_id: TypeId,
+    /// The id of the asset in the store of its type.
+    pub id: AssetId,
+    /// The file the asset was loaded from, or `None` for built assets.
+    pub path: Option<PathBuf>,
+}
+
+impl AssetEvent {
+    pub(super) fn new<T: 'static>(kind: AssetEventKind, handle: &Handle<T>) -> AssetEvent {
+        AssetEvent {
+            kind,
+            type_id: TypeId::of::<T>(),
+            id: handle.get_id(),
+            path: handle.get_path().map(PathBuf::from),
+        }
+    }
+
+    /// Checks whether the event is about an asset of type `T`.
+    pub fn is<T: 'static>(&self) -> bool {
+        self.type_id == TypeId::of::<T>()
+    }
+
+    /// Checks whether the event is about the asset of `handle`.
+    pub fn concerns<T: 'static>(&self, handle: &Handle<T>) -> bool {
+        self.is::<T>() && self.id == handle.get_id()
+    }
+}
@@ -0,0 +1,286 @@
+use std::any::TypeId;
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+/// Identifies an asset in an `Assets` store. Ids are never reused within a store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(pub(super) u64);
+
+/// Whether an asset can be used yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    /// The asset or one of its dependencies is still being read, decoded or uploaded.
+    Loading,
+    /// The asset and all of its dependencies are ready.
+    Loaded,
+    /// The asset or one of its dependencies could not be loaded, for this reason.
+    Failed(String),
+}
+
+impl LoadState {
+    /// Combines the states of an asset and one of its dependencies: failures win over
+    /// loads in progress, which win over finished loads.
+    pub(super) fn and(self, other: LoadState) -> LoadState {
+        match (self, other) {
+            (LoadState::Failed(err), _) | (_, LoadState::Failed(err)) => LoadState::Failed(err),
+            (LoadState::Loading, _) | (_, LoadState::Loading) => LoadState::Loading,
+            _ => LoadState::Loaded,
+        }
+    }
+}
+
+/// The shared state behind the handles of one asset.
+pub(super) struct Slot<T> {
+    id: AssetId,
+    path: Option<PathBuf>,
+    value: RefCell<Option<Rc<T>>>,
+    state: RefCell<LoadState>,
+    dependencies: RefCell<Vec<UntypedHandle>>,
+}
+
+impl<T> Drop for Slot<T> {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            log::debug!("Unloaded {}", path.display());
+        }
+    }
+}
+
+/// Type-erased access to the state of a `Slot<T>`.
+trait Tracked {
+    fn get_type_id(&self) -> TypeId;
+    fn get_id(&self) -> AssetId;
+    fn get_path(&self) -> Option<&Path>;
+    fn get_load_state(&self) -> LoadState;
+}
+
+impl<T: 'static> Tracked for Slot<T> {
+    fn get_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+
+    fn get_id(&self) -> AssetId {
+        self.id
+    }
+
+    fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn get_load_state(&self) -> LoadState {
+        Slot::get_load_state(self)
+    }
+}
+
+impl<T> Slot<T> {
+    /// Returns the state of the asset combined with the states of its dependencies.
+    fn get_load_state(&self) -> LoadState {
+        let state = self.state.borrow().clone();
+        let dependencies = self.dependencies.borrow();
+        dependencies.iter().fold(state, |state, dependency| state.and(dependency.get_load_state()))
+    }
+}
+
+/// A strong, cheaply cloneable reference to an asset in an `Assets` store.
+///
+/// The asset stays loaded while any strong handle to it exists and is dropped, freeing
+/// its GL objects, together with the last one. Handles of assets loaded in the
+/// background exist before the asset does; check `get_load_state` before using them.
+pub struct Handle<T> {
+    slot: Rc<Slot<T>>,
+}
+
+impl<T> Handle<T> {
+    /// Creates the first handle to an asset, loaded if `value` is given.
+    pub(super) fn new(id: AssetId, path: Option<PathBuf>, value: Option<T>) -> Handle<T> {
+        let state = if value.is_some() { LoadState::Loaded } else { LoadState::Loading };
+        Handle {
+            slot: Rc::new(Slot {
+                id,
+                path,
+                value: RefCell::new(value.map(Rc::new)),
+                state: RefCell::new(state),
+                dependencies: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Returns the id of the asset in its store.
+    pub fn get_id(&self) -> AssetId {
+        self.slot.id
+    }
+
+    /// Returns the path the asset was loaded from, or `None` if it was added directly.
+    pub fn get_path(&self) -> Option<&Path> {
+        self.slot.path.as_deref()
+    }
+
+    /// Returns whether the asset and every asset it depends on are ready.
+    pub fn get_load_state(&self) -> LoadState {
+        self.slot.get_load_state()
+    }
+
+    /// Checks whether the asset and every asset it depends on are ready.
+    pub fn is_loaded(&self) -> bool {
+        self.get_load_state() == LoadState::Loaded
+    }
+
+    /// Borrows the asset.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the asset itself hasn't finished loading, or is
+    /// being replaced.
+    pub fn get(&self) -> Ref<'_, T> {
+        Ref::map(self.slot.value.borrow(), |value| value.as_deref().expect("asset is not loaded"))
+    }
+
+    /// Borrows the asset, or returns `None` if it hasn't finished loading.
+    pub fn try_get(&self) -> Option<Ref<'_, T>> {
+        Ref::filter_map(self.slot.value.borrow(), |value| value.as_deref()).ok()
+    }
+
+    /// Replaces the asset in place and marks it loaded. Every handle to it sees the change.
+    pub fn set(&self, value: T) {
+        *self.slot.value.borrow_mut() = Some(Rc::new(value));
+        *self.slot.state.borrow_mut() = LoadState::Loaded;
+    }
+
+    /// Marks the asset as failed to load, keeping the current value if there is one.
+    pub(super) fn set_failed(&self, err: String) {
+        *self.slot.state.borrow_mut() = LoadState::Failed(err);
+    }
+
+    /// Returns the load state of the asset itself, ignoring its dependencies.
+    pub(super) fn get_own_load_state(&self) -> LoadState {
+        self.slot.state.borrow().clone()
+    }
+
+    /// Makes the asset report `Loading` or `Failed` while `dependency` does, such as a
+    /// material while its textures load. The dependency is kept loaded with the asset.
+    pub fn add_dependency(&self, dependency: UntypedHandle) {
+        self.slot.dependencies.borrow_mut().push(dependency);
+    }
+
+    /// Returns how many strong handles to the asset exist, including this one.
+    pub fn strong_count(&self) -> usize {
+        Rc::strong_count(&self.slot)
+    }
+
+    /// Creates a weak handle, which doesn't keep the asset loaded.
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            id: self.slot.id,
+            slot: Rc::downgrade(&self.slot),
+        }
+    }
+}
+
+impl<T: 'static> Handle<T> {
+    /// Creates a strong handle that only tracks the asset's load state, so handles of
+    /// different types can be listed together as dependencies.
+    pub fn untyped(&self) -> UntypedHandle {
+        UntypedHandle { slot: self.slot.clone() }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle { slot: Rc::clone(&self.slot) }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.slot.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle").field("id", &self.slot.id).field("path", &self.slot.path).finish()
+    }
+}
+
+/// A reference to an asset that doesn't keep it loaded, such as a cache entry.
+pub struct WeakHandle<T> {
+    id: AssetId,
+    slot: Weak<Slot<T>>,
+}
+
+impl<T> WeakHandle<T> {
+    /// Returns the id of the asset in its store, even once it was unloaded.
+    pub fn get_id(&self) -> AssetId {
+        self.id
+    }
+
+    /// Returns a strong handle to the asset, or `None` if it was unloaded.
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.slot.upgrade().map(|slot| Handle { slot })
+    }
+
+    /// Checks whether the asset is still loaded.
+    pub fn is_alive(&self) -> bool {
+        self.slot.strong_count() > 0
+    }
+}
+
+impl<T> Clone for WeakHandle<T> {
+    fn clone(&self) -> Self {
+        WeakHandle {
+            id: self.id,
+            slot: Weak::clone(&self.slot),
+        }
+    }
+}
+
+impl<T> fmt::Debug for WeakHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakHandle").field("id", &self.id).field("alive", &self.is_alive()).finish()
+    }
+}
+
+/// A strong handle to an asset of any type, which only tells whether it is loaded.
+#[derive(Clone)]
+pub struct UntypedHandle {
+    slot: Rc<dyn Tracked>,
+}
+
+impl UntypedHandle {
+    /// Returns the type of the asset.
+    pub fn get_type_id(&self) -> TypeId {
+        self.slot.get_type_id()
+    }
+
+    /// Returns the id of the asset in the store of its type.
+    pub fn get_id(&self) -> AssetId {
+        self.slot.get_id()
+    }
+
+    /// Returns the path the asset was loaded from, or `None` if it was added directly.
+    pub fn get_path(&self) -> Option<&Path> {
+        self.slot.get_path()
+    }
+
+    /// Returns whether the asset and every asset it depends on are ready.
+    pub fn get_load_state(&self) -> LoadState {
+        self.slot.get_load_state()
+    }
+}
+
+impl fmt::Debug for UntypedHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UntypedHandle").field("id", &self.get_id()).field("path", &self.get_path()).finish()
+    }
+}
@@ -0,0 +1,13 @@
+mod asset;
+mod event;
+mod handle;
+mod server;
+mod store;
+mod watcher;
+mod workers;
+
+pub use asset::{Asset, TextureData};
+pub use event::{AssetEvent, AssetEventKind};
+pub use handle::{AssetId, Handle, LoadState, UntypedHandle, WeakHandle};
+pub use server::AssetServer;
+pub use store::Assets;
@@ -0,0 +1,536 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::asset::upload_texture;
+use super::store::canonical_path;
+use super::watcher::FileWatcher;
+use super::workers::{JobResult, Workers};
+use super::{Asset, AssetEvent, AssetEventKind, Assets, Handle, LoadState, TextureData, UntypedHandle};
+use crate::opengl::Texture;
+
+/// Default time `AssetServer::update` may spend creating assets each frame.
+const DEFAULT_UPLOAD_BUDGET: Duration = Duration::from_millis(4);
+
+/// Default time a changed file must stay unchanged before it is reloaded.
+const DEFAULT_RELOAD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Type-erased access to an `Assets<T>`.
+trait AssetStorage {
+    fn remove_unused(&mut self) -> usize;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: 'static> AssetStorage for Assets<T> {
+    fn remove_unused(&mut self) -> usize {
+        Assets::remove_unused(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Reads a file on a worker thread.
+type Decode<D> = Arc<dyn Fn(&Path) -> Result<D, String> + Send + Sync>;
+
+/// Creates an asset from decoded data on the main thread.
+type Upload<T, D> = Rc<dyn Fn(D) -> Result<T, String>>;
+
+/// Stores the result of a finished decode job in its asset, returning what happened.
+type Finish = Box<dyn FnOnce(JobResult) -> Option<AssetEvent>>;
+
+/// Starts loading an asset again after its file changed. Returns `false` once the asset
+/// was unloaded.
+type Reload = Rc<dyn Fn(&mut AssetServer) -> bool>;
+
+/// An asset built from others, see `AssetServer::add_when_loaded`.
+struct Build {
+    dependencies: Vec<UntypedHandle>,
+    /// Builds the asset, or stores the error of a dependency, and returns what happened.
+    run: Box<dyn Fn(Result<(), String>) -> Option<AssetEvent>>,
+    /// Checks whether the asset is still alive.
+    alive: Box<dyn Fn() -> bool>,
+    /// Whether the asset waits to be built, initially or after a dependency reloaded.
+    pending: bool,
+}
+
+/// Holds one `Assets` store per asset type, so meshes, textures, shaders, materials and
+/// any other resource can be loaded once and shared through handles.
+///
+/// Files can be loaded right away with `load`, or in the background with `load_async`:
+/// worker threads read and decode them, and `update` creates the assets on the main
+/// thread within a time budget per frame, so large files don't stall rendering.
+///
+/// With hot reloading, enabled by default in debug builds, the files of assets loaded
+/// through the server are watched, and changed ones are loaded again in the background
+/// and replace the asset in place. Assets built with `add_when_loaded` are rebuilt when
+/// one of their dependencies reloads. Systems learn about it from `get_events`.
+///
+/// A scene's server is reachable from every system as `Scene::assets`, and the engine
+/// calls `update` at the start of every frame.
+pub struct AssetServer {
+    stores: HashMap<TypeId, Box<dyn AssetStorage>>, // One store per asset type
+    workers: Option<Workers>,                       // Started by the first background load
+    decoding: HashMap<u64, Finish>,                 // Decode jobs in flight, by job id
+    finished: VecDeque<(u64, JobResult)>,           // Decoded files waiting for upload
+    builds: Vec<Build>,                             // Assets built from other assets
+    events: Vec<AssetEvent>,                        // What happened in the last `update`
+    upload_budget: Duration,                        // Time `update` may spend per frame
+    next_job: u64,                                  // Id of the next decode job
+    reloads: HashMap<PathBuf, Vec<Reload>>,         // How to reload assets, by source file
+    hot_reload: bool,                               // Whether source files are watched
+    watcher: Option<FileWatcher>,                   // Started by the first hot-reloadable load
+    reload_debounce: Duration,                      // Quiet time before a changed file reloads
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        AssetServer {
+            stores: HashMap::new(),
+            workers: None,
+            decoding: HashMap::new(),
+            finished: VecDeque::new(),
+            builds: Vec::new(),
+            events: Vec::new(),
+            upload_budget: DEFAULT_UPLOAD_BUDGET,
+            next_job: 0,
+            reloads: HashMap::new(),
+            hot_reload: cfg!(debug_assertions),
+            watcher: None,
+            reload_debounce: DEFAULT_RELOAD_DEBOUNCE,
+        }
+    }
+}
+
+impl AssetServer {
+    /// Creates a server without any stores.
+    pub fn new() -> AssetServer {
+        AssetServer::default()
+    }
+
+    /// Returns the store of assets of type `T`, or `None` if none was ever added.
+    pub fn get_assets<T: 'static>(&self) -> Option<&Assets<T>> {
+        let store = self.stores.get(&TypeId::of::<T>())?;
+        store.as_any().downcast_ref::<Assets<T>>()
+    }
+
+    /// Returns the store of assets of type `T`, creating it if needed.
+    pub fn get_assets_mut<T: 'static>(&mut self) -> &mut Assets<T> {
+        self.stores
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Assets::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<Assets<T>>()
+            .expect("asset store registered under the wrong type")
+    }
+
+    /// Adds an asset that wasn't loaded from a file, see `Assets::add`.
+    pub fn add<T: 'static>(&mut self, value: T) -> Handle<T> {
+        self.get_assets_mut().add(value)
+    }
+
+    /// Returns the asset loaded from `path` if it is still alive, or loads it right
+    /// away, see `Assets::load`. The asset is reloaded in the background when its files
+    /// change.
+    pub fn load<T: Asset>(&mut self, path: impl AsRef<Path>) -> Result<Handle<T>, String> {
+        let path = canonical_path(path.as_ref());
+        let assets = self.get_assets_mut::<T>();
+        if let Some(handle) = assets.find_loadable(&path) {
+            return Ok(handle);
+        }
+
+        let handle = assets.load(&path)?;
+        self.watch(&handle, T::sources(&path), Arc::new(T::decode), Rc::new(T::upload));
+        Ok(handle)
+    }
+
+    /// Returns the texture loaded from `path` if it is still alive, or loads it right
+    /// away, like `load::<Texture>` but in the color space of the data.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The image file.
+    /// * `srgb` - `true` for color data such as albedo maps, `false` for linear data
+    ///   such as normal or roughness maps. Both can be loaded from one file and are
+    ///   separate assets; the linear one's path ends in `#linear`. Compressed files keep
+    ///   the color space of their format either way.
+    ///
+    /// # Returns
+    ///
+    /// A strong handle to the texture, or an `Err(String)` if the file cannot be loaded.
+    pub fn load_texture(&mut self, path: impl AsRef<Path>, srgb: bool) -> Result<Handle<Texture>, String> {
+        if srgb {
+            return self.load(path);
+        }
+        let path = canonical_path(path.as_ref());
+        let key = labeled_path(&path, "linear");
+        let assets = self.get_assets_mut::<Texture>();
+        if let Some(handle) = assets.find_loadable(&key) {
+            return Ok(handle);
+        }
+
+        let texture = upload_texture(Texture::decode(&path)?, false);
+        log::debug!("Loaded {}", key.display());
+        let handle = assets.insert(Some(key), Some(texture));
+        // Reloads decode the image itself rather than the labeled path of the asset.
+        let source = path.clone();
+        let decode: Decode<TextureData> = Arc::new(move |_| Texture::decode(&source));
+        self.watch(&handle, vec![path], decode, Rc::new(|data| Ok(upload_texture(data, false))));
+        Ok(handle)
+    }
+
+    /// Returns the asset loaded from `path` if it is still alive, or loads it right
+    /// away with `loader`, see `Assets::load_with`. The asset isn't hot reloaded.
+    pub fn load_with<T: 'static>(
+        &mut self,
+        path: impl AsRef<Path>,
+        loader: impl FnOnce(&Path) -> Result<T, String>,
+    ) -> Result<Handle<T>, String> {
+        self.get_assets_mut().load_with(path, loader)
+    }
+
+    /// Returns the part of a file named `label` if it is still alive, or creates it
+    /// with `loader`, such as a texture embedded in a glTF file. The asset's path is the
+    /// file's followed by `#label`, and it isn't hot reloaded.
+    ///
+    /// # Returns
+    ///
+    /// A strong handle to the asset, or the `Err(String)` of `loader`.
+    pub fn load_labeled<T: 'static>(
+        &mut self,
+        path: impl AsRef<Path>,
+        label: &str,
+        loader: impl FnOnce() -> Result<T, String>,
+    ) -> Result<Handle<T>, String> {
+        let key = labeled_path(&canonical_path(path.as_ref()), label);
+        let assets = self.get_assets_mut::<T>();
+        if let Some(handle) = assets.find_loadable(&key) {
+            return Ok(handle);
+        }
+        Ok(assets.insert(Some(key), Some(loader()?)))
+    }
+
+    /// Returns the asset loaded, or being loaded, from `path`, or starts loading it in
+    /// the background with `Asset::decode` and `Asset::upload`.
+    ///
+    /// # Returns
+    ///
+    /// A handle that reports `LoadState::Loading` until the asset is uploaded by
+    /// `update`, or `LoadState::Failed` if the file cannot be loaded.
+    pub fn load_async<T: Asset>(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = canonical_path(path.as_ref());
+        if let Some(handle) = self.get_assets_mut::<T>().find_loadable(&path) {
+            return handle;
+        }
+        self.start_load(path.clone(), T::sources(&path), Arc::new(T::decode), Rc::new(T::upload))
+    }
+
+    /// Like `load_async`, with custom decode and upload steps for types without an
+    /// `Asset` implementation or files that load differently. Both steps run again
+    /// when the file changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to load. Paths naming the same file share one asset.
+    /// * `decode` - Reads the file on a worker thread.
+    /// * `upload` - Creates the asset from the decoded data on the main thread.
+    pub fn load_async_with<T: 'static, D: Send + 'static>(
+        &mut self,
+        path: impl AsRef<Path>,
+        decode: impl Fn(&Path) -> Result<D, String> + Send + Sync + 'static,
+        upload: impl Fn(D) -> Result<T, String> + 'static,
+    ) -> Handle<T> {
+        let path = canonical_path(path.as_ref());
+        if let Some(handle) = self.get_assets_mut::<T>().find_loadable(&path) {
+            return handle;
+        }
+        self.start_load(path.clone(), vec![path], Arc::new(decode), Rc::new(upload))
+    }
+
+    /// Adds an asset built from others once they have loaded, such as a material from
+    /// textures loaded in the background.
+    ///
+    /// The returned handle depends on every handle in `dependencies`: it reports
+    /// `LoadState::Loading` until `build` has run in `update` after all of them loaded, and
+    /// `LoadState::Failed` as soon as one of them fails, in which case `build` doesn't
+    /// run. `build` runs again whenever a dependency is reloaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `dependencies` - The assets `build` uses, from `Handle::untyped`.
+    /// * `build` - Creates the asset on the main thread, e.g. reading the dependencies
+    ///   with `Handle::get`.
+    pub fn add_when_loaded<T: 'static>(
+        &mut self,
+        dependencies: &[UntypedHandle],
+        build: impl Fn() -> Result<T, String> + 'static,
+    ) -> Handle<T> {
+        let handle = self.get_assets_mut::<T>().insert(None, None);
+        for dependency in dependencies {
+            handle.add_dependency(dependency.clone());
+        }
+
+        let (target, alive) = (handle.downgrade(), handle.downgrade());
+        self.builds.push(Build {
+            dependencies: dependencies.to_vec(),
+            run: Box::new(move |dependencies| {
+                let handle = target.upgrade()?;
+                Some(finish_load(&handle, dependencies.and_then(|()| build())))
+            }),
+            alive: Box::new(move || alive.is_alive()),
+            pending: true,
+        });
+        handle
+    }
+
+    /// Returns what happened to assets during the last `update`: background loads and
+    /// builds finishing or failing, and assets reloading.
+    pub fn get_events(&self) -> &[AssetEvent] {
+        &self.events
+    }
+
+    /// Returns the time `update` may spend creating assets each frame.
+    pub fn get_upload_budget(&self) -> Duration {
+        self.upload_budget
+    }
+
+    /// Sets the time `update` may spend creating assets each frame. At least one
+    /// decoded file is uploaded per call whatever the budget, so loads always progress.
+    pub fn set_upload_budget(&mut self, budget: Duration) {
+        self.upload_budget = budget;
+    }
+
+    /// Checks whether source files are watched and reloaded when they change.
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload
+    }
+
+    /// Enables or disables hot reloading. It is enabled by default in debug builds only,
+    /// and never available when foux is built without the `hot-reload` feature.
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+        if !enabled {
+            self.watcher = None;
+            return;
+        }
+        let sources: Vec<PathBuf> = self.reloads.keys().cloned().collect();
+        if let Some(watcher) = self.get_watcher() {
+            for source in &sources {
+                watcher.watch(source);
+            }
+        }
+    }
+
+    /// Returns how long a changed file must stay unchanged before it is reloaded.
+    pub fn get_reload_debounce(&self) -> Duration {
+        self.reload_debounce
+    }
+
+    /// Sets how long a changed file must stay unchanged before it is reloaded, so files
+    /// saved in several writes are only reloaded once they are complete.
+    pub fn set_reload_debounce(&mut self, debounce: Duration) {
+        self.reload_debounce = debounce;
+    }
+
+    /// Checks whether any background load or build hasn't finished yet.
+    pub fn is_loading(&self) -> bool {
+        !self.decoding.is_empty() || self.builds.iter().any(|build| build.pending)
+    }
+
+    /// Starts reloading changed files, uploads files decoded in the background and
+    /// builds assets whose dependencies have loaded, until the upload budget is spent.
+    /// Must be called on the thread that owns the GL context; the engine calls it at the
+    /// start of every frame.
+    pub fn update(&mut self) {
+        let start = Instant::now();
+        self.events.clear();
+        self.reload_changed();
+        if let Some(workers) = &self.workers {
+            self.finished.extend(workers.finished());
+        }
+
+        // Whether an asset was created this frame; the first one ignores the budget.
+        let mut created = false;
+        while !created || start.elapsed() < self.upload_budget {
+            let Some((job, result)) = self.finished.pop_front() else { break };
+            if let Some(event) = self.decoding.remove(&job).and_then(|finish| finish(result)) {
+                self.record(event);
+                created = true;
+            }
+        }
+
+        self.builds.retain(|build| (build.alive)());
+        let mut built = Vec::new();
+        for build in &mut self.builds {
+            if !build.pending || (created && start.elapsed() >= self.upload_budget) {
+                continue;
+            }
+            let state = build
+                .dependencies
+                .iter()
+                .fold(LoadState::Loaded, |state, dependency| state.and(dependency.get_load_state()));
+            let dependencies = match state {
+                LoadState::Loading => continue,
+                LoadState::Loaded => Ok(()),
+                LoadState::Failed(err) => Err(err),
+            };
+            build.pending = false;
+            built.extend((build.run)(dependencies));
+            created = true;
+        }
+        for event in built {
+            self.record(event);
+        }
+    }
+
+    /// Forgets the entries of unloaded assets in every store, see `Assets::remove_unused`.
+    ///
+    /// # Returns
+    ///
+    /// How many entries were removed.
+    pub fn remove_unused(&mut self) -> usize {
+        self.stores.values_mut().map(|store| store.remove_unused()).sum()
+    }
+
+    /// Inserts a handle for `path` and decodes the file in the background.
+    fn start_load<T: 'static, D: Send + 'static>(
+        &mut self,
+        path: PathBuf,
+        sources: Vec<PathBuf>,
+        decode: Decode<D>,
+        upload: Upload<T, D>,
+    ) -> Handle<T> {
+        let handle = self.get_assets_mut::<T>().insert(Some(path), None);
+        self.decode(&handle, decode.clone(), upload.clone());
+        self.watch(&handle, sources, decode, upload);
+        handle
+    }
+
+    /// Queues the decode of the file of `handle`, whose result `update` uploads into it.
+    fn decode<T: 'static, D: Send + 'static>(&mut self, handle: &Handle<T>, decode: Decode<D>, upload: Upload<T, D>) {
+        let Some(path) = handle.get_path().map(PathBuf::from) else { return };
+        let job = self.next_job;
+        self.next_job += 1;
+
+        // Only a weak handle waits for the upload, so an asset dropped while loading is
+        // never uploaded.
+        let weak = handle.downgrade();
+        let origin = path.clone();
+        self.decoding.insert(
+            job,
+            Box::new(move |result| {
+                let handle = weak.upgrade()?;
+                let data = *result.downcast::<Result<D, String>>().expect("decode job returned the wrong type");
+                let value = data.and_then(|data| upload(data).map_err(|err| format!("{}: {err}", origin.display())));
+                Some(finish_load(&handle, value))
+            }),
+        );
+        self.workers.get_or_insert_with(Workers::new).spawn(job, move || Box::new(decode(&path)));
+    }
+
+    /// Registers how to reload the asset of `handle` when one of `sources` changes.
+    fn watch<T: 'static, D: Send + 'static>(
+        &mut self,
+        handle: &Handle<T>,
+        sources: Vec<PathBuf>,
+        decode: Decode<D>,
+        upload: Upload<T, D>,
+    ) {
+        let weak = handle.downgrade();
+        let reload: Reload = Rc::new(move |server| match weak.upgrade() {
+            Some(handle) => {
+                server.decode(&handle, decode.clone(), upload.clone());
+                true
+            }
+            None => false,
+        });
+
+        for source in sources {
+            if let Some(watcher) = self.get_watcher() {
+                watcher.watch(&source);
+            }
+            self.reloads.entry(source).or_default().push(reload.clone());
+        }
+    }
+
+    /// Returns the file watcher, starting it if hot reloading is enabled.
+    fn get_watcher(&mut self) -> Option<&mut FileWatcher> {
+        if self.hot_reload && self.watcher.is_none() {
+            match FileWatcher::new() {
+                Ok(watcher) => self.watcher = Some(watcher),
+                Err(err) => {
+                    log::warn!("{err}; hot reloading is disabled");
+                    self.hot_reload = false;
+                }
+            }
+        }
+        self.watcher.as_mut()
+    }
+
+    /// Starts reloading every asset whose source files changed and settled.
+    fn reload_changed(&mut self) {
+        let Some(watcher) = &mut self.watcher else { return };
+        for path in watcher.changed(self.reload_debounce) {
+            let Some(reloads) = self.reloads.remove(&path) else { continue };
+            log::info!("Reloading {}", path.display());
+            let alive: Vec<Reload> = reloads.into_iter().filter(|reload| reload(self)).collect();
+            if !alive.is_empty() {
+                self.reloads.insert(path, alive);
+            }
+        }
+    }
+
+    /// Stores an event and marks the builds depending on a changed asset for rebuilding.
+    fn record(&mut self, event: AssetEvent) {
+        if event.kind != AssetEventKind::Failed {
+            for build in &mut self.builds {
+                let depends = build
+                    .dependencies
+                    .iter()
+                    .any(|dependency| dependency.get_type_id() == event.type_id && dependency.get_id() == event.id);
+                build.pending |= depends;
+            }
+        }
+        self.events.push(event);
+    }
+}
+
+/// Returns the path of the part of a file named `label`.
+fn labeled_path(path: &Path, label: &str) -> PathBuf {
+    let mut labeled = path.as_os_str().to_owned();
+    labeled.push("#");
+    labeled.push(label);
+    PathBuf::from(labeled)
+}
+
+/// Stores the outcome of loading or building an asset and returns what happened.
+///
+/// An asset that fails to reload keeps its previous version.
+fn finish_load<T: 'static>(handle: &Handle<T>, value: Result<T, String>) -> AssetEvent {
+    let reloaded = handle.try_get().is_some();
+    let origin = handle.get_path().map_or(String::from("built asset"), |path| path.display().to_string());
+    match value {
+        Ok(value) => {
+            log::debug!("{} {origin}", if reloaded { "Reloaded" } else { "Loaded" });
+            handle.set(value);
+            let kind = if reloaded { AssetEventKind::Reloaded } else { AssetEventKind::Loaded };
+            AssetEvent::new(kind, handle)
+        }
+        Err(err) => {
+            log::warn!("{err}");
+            if !reloaded {
+                handle.set_failed(err);
+            }
+            AssetEvent::new(AssetEventKind::Failed, handle)
+        }
+    }
+}
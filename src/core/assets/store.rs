@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{Asset, AssetId, Handle, LoadState, WeakHandle};
+
+/// Entries of unloaded assets are pruned once the store holds this many, or twice as
+/// many as were loaded after the last pruning.
+const MIN_PRUNE_THRESHOLD: usize = 16;
+
+/// A store of assets of one type, shared through `Handle`s.
+///
+/// The store only holds weak references: an asset is unloaded as soon as its last strong
+/// handle is dropped, and loading the same path again while it is alive, or still
+/// loading, returns the existing asset instead of a copy.
+pub struct Assets<T> {
+    entries: HashMap<AssetId, WeakHandle<T>>, // Every asset added, possibly unloaded since
+    paths: HashMap<PathBuf, AssetId>,         // Assets loaded from a file, by canonical path
+    next_id: u64,                             // Id of the next asset
+    prune_threshold: usize,                   // Entry count at which unloaded ones are pruned
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Assets {
+            entries: HashMap::new(),
+            paths: HashMap::new(),
+            next_id: 0,
+            prune_threshold: MIN_PRUNE_THRESHOLD,
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    /// Creates an empty store.
+    pub fn new() -> Assets<T> {
+        Assets::default()
+    }
+
+    /// Adds an asset that wasn't loaded from a file, such as a material built in code.
+    ///
+    /// # Returns
+    ///
+    /// The first strong handle to the asset.
+    pub fn add(&mut self, value: T) -> Handle<T> {
+        self.insert(None, Some(value))
+    }
+
+    /// Returns the asset loaded from `path` if it is still alive, or loads it with `loader`.
+    ///
+    /// Use this instead of `load` for types without an `Asset` implementation, or to
+    /// load a file differently than the implementation does. Assets that failed to load
+    /// are loaded again.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to load. Paths naming the same file share one asset.
+    /// * `loader` - Reads the asset from the file.
+    ///
+    /// # Returns
+    ///
+    /// A strong handle to the asset, or the `Err(String)` of `loader`.
+    pub fn load_with(
+        &mut self,
+        path: impl AsRef<Path>,
+        loader: impl FnOnce(&Path) -> Result<T, String>,
+    ) -> Result<Handle<T>, String> {
+        let path = canonical_path(path.as_ref());
+        if let Some(handle) = self.find_loadable(&path) {
+            return Ok(handle);
+        }
+
+        let value = loader(&path)?;
+        log::debug!("Loaded {}", path.display());
+        Ok(self.insert(Some(path), Some(value)))
+    }
+
+    /// Returns the asset with the given id, or `None` if it was unloaded.
+    pub fn get(&self, id: AssetId) -> Option<Handle<T>> {
+        self.entries.get(&id).and_then(WeakHandle::upgrade)
+    }
+
+    /// Returns the asset loaded, or being loaded, from `path`, or `None` if there is none.
+    pub fn get_by_path(&self, path: impl AsRef<Path>) -> Option<Handle<T>> {
+        let path = canonical_path(path.as_ref());
+        self.paths.get(&path).and_then(|id| self.get(*id))
+    }
+
+    /// Returns strong handles to every live asset, in no particular order.
+    pub fn handles(&self) -> impl Iterator<Item = Handle<T>> + '_ {
+        self.entries.values().filter_map(WeakHandle::upgrade)
+    }
+
+    /// Returns how many assets are alive, including ones still loading.
+    pub fn len(&self) -> usize {
+        self.entries.values().filter(|handle| handle.is_alive()).count()
+    }
+
+    /// Checks whether no asset is alive.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets the entries of unloaded assets. This happens on its own as assets are
+    /// added, so it only needs to be called to release memory right away.
+    ///
+    /// # Returns
+    ///
+    /// How many entries were removed.
+    pub fn remove_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, handle| handle.is_alive());
+        self.paths.retain(|_, id| self.entries.contains_key(id));
+        self.prune_threshold = (self.entries.len() * 2).max(MIN_PRUNE_THRESHOLD);
+        before - self.entries.len()
+    }
+
+    /// Returns the asset of a canonical path unless it failed to load.
+    pub(super) fn find_loadable(&self, path: &Path) -> Option<Handle<T>> {
+        let handle = self.paths.get(path).and_then(|id| self.get(*id))?;
+        (!matches!(handle.get_own_load_state(), LoadState::Failed(_))).then_some(handle)
+    }
+
+    /// Adds an asset, or a handle to fill in later if `value` is `None`.
+    pub(super) fn insert(&mut self, path: Option<PathBuf>, value: Option<T>) -> Handle<T> {
+        if self.entries.len() >= self.prune_threshold {
+            self.remove_unused();
+        }
+
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+        if let Some(path) = &path {
+            self.paths.insert(path.clone(), id);
+        }
+        let handle = Handle::new(id, path, value);
+        self.entries.insert(id, handle.downgrade());
+        handle
+    }
+}
+
+impl<T: Asset> Assets<T> {
+    /// Returns the asset loaded from `path` if it is still alive, or loads it right away
+    /// with `Asset::decode` and `Asset::upload`.
+    ///
+    /// # Returns
+    ///
+    /// A strong handle to the asset, or an `Err(String)` if the file cannot be loaded.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<Handle<T>, String> {
+        self.load_with(path, |path| T::upload(T::decode(path)?).map_err(|err| format!("{}: {err}", path.display())))
+    }
+}
+
+/// Returns the path assets loaded from `path` are keyed by, so different spellings of
+/// one file share an asset. Paths that don't exist are kept as they are.
+pub(super) fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
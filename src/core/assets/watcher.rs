@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "hot-reload")]
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Watches the directories of loaded files and reports the files that changed.
+///
+/// Directories are watched instead of files because editors often save by replacing
+/// the file, which ends a watch on the file itself.
+pub(super) struct FileWatcher {
+    #[cfg(feature = "hot-reload")]
+    watcher: RecommendedWatcher,
+    events: Receiver<PathBuf>,           // Files changed, as reported by the watcher thread
+    directories: HashSet<PathBuf>,       // Directories watched so far
+    changes: HashMap<PathBuf, Instant>,  // Files changed, by the time of their last change
+}
+
+impl FileWatcher {
+    /// Starts watching, or returns an `Err(String)` if the platform's watcher cannot be
+    /// created or the crate was built without the `hot-reload` feature.
+    #[cfg(feature = "hot-reload")]
+    pub(super) fn new() -> Result<FileWatcher, String> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else { return };
+            if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                for path in event.paths {
+                    let _ = sender.send(path);
+                }
+            }
+        })
+        .map_err(|err| format!("Cannot watch asset files: {err}"))?;
+
+        Ok(FileWatcher {
+            watcher,
+            events,
+            directories: HashSet::new(),
+            changes: HashMap::new(),
+        })
+    }
+
+    #[cfg(not(feature = "hot-reload"))]
+    pub(super) fn new() -> Result<FileWatcher, String> {
+        Err("Cannot watch asset files: foux was built without the hot-reload feature".to_string())
+    }
+
+    /// Starts watching the directory of `path`, an absolute path, if it isn't already.
+    pub(super) fn watch(&mut self, path: &Path) {
+        let Some(directory) = path.parent() else { return };
+        if self.directories.contains(directory) {
+            return;
+        }
+        #[cfg(feature = "hot-reload")]
+        if let Err(err) = self.watcher.watch(directory, RecursiveMode::NonRecursive) {
+            log::warn!("Cannot watch {} for changes: {err}", directory.display());
+            return;
+        }
+        self.directories.insert(directory.to_path_buf());
+    }
+
+    /// Returns the files that changed and then stayed unchanged for `debounce`, so a
+    /// file written in several steps is only reported once it is complete.
+    pub(super) fn changed(&mut self, debounce: Duration) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for path in self.events.try_iter() {
+            self.changes.insert(path, now);
+        }
+
+        let settled: Vec<PathBuf> = self
+            .changes
+            .iter()
+            .filter(|(_, changed)| now.duration_since(**changed) >= debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.changes.remove(path);
+        }
+        settled
+    }
+}
@@ -0,0 +1,83 @@
+use std::any::Any;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Most threads decoding assets, leaving cores for the main thread and the driver.
+const MAX_WORKERS: usize = 4;
+
+/// The result of a job, downcast by whoever queued it.
+pub(super) type JobResult = Box<dyn Any + Send>;
+
+type Job = Box<dyn FnOnce() -> JobResult + Send>;
+
+/// A pool of threads running decode jobs in the background.
+pub(super) struct Workers {
+    jobs: Option<Sender<(u64, Job)>>,   // Queue the threads take jobs from
+    results: Receiver<(u64, JobResult)>, // Finished jobs, by the id they were queued with
+    cancelled: Arc<AtomicBool>,          // Set on drop so queued jobs are skipped
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Workers {
+    /// Starts one thread per core, up to `MAX_WORKERS`.
+    pub(super) fn new() -> Workers {
+        let (jobs, queue) = mpsc::channel::<(u64, Job)>();
+        let (result_sender, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let count = thread::available_parallelism().map_or(2, |count| count.get().clamp(1, MAX_WORKERS));
+        let threads = (0..count)
+            .map(|index| {
+                let queue = Arc::clone(&queue);
+                let results: Sender<(u64, JobResult)> = result_sender.clone();
+                let cancelled = Arc::clone(&cancelled);
+                thread::Builder::new()
+                    .name(format!("foux-assets-{index}"))
+                    .spawn(move || loop {
+                        // The lock is released before running the job, so threads decode concurrently.
+                        let Ok((id, job)) = queue.lock().map_err(drop).and_then(|queue| queue.recv().map_err(drop))
+                        else {
+                            break;
+                        };
+                        if cancelled.load(Ordering::Relaxed) || results.send((id, job())).is_err() {
+                            break;
+                        }
+                    })
+                    .expect("Cannot spawn an asset loading thread")
+            })
+            .collect();
+
+        Workers {
+            jobs: Some(jobs),
+            results,
+            cancelled,
+            threads,
+        }
+    }
+
+    /// Queues a job. Its result is returned by `finished` under `id`.
+    pub(super) fn spawn(&self, id: u64, job: impl FnOnce() -> JobResult + Send + 'static) {
+        if let Some(jobs) = &self.jobs {
+            let _ = jobs.send((id, Box::new(job)));
+        }
+    }
+
+    /// Returns the results of the jobs finished since the last call.
+    pub(super) fn finished(&self) -> impl Iterator<Item = (u64, JobResult)> + '_ {
+        self.results.try_iter()
+    }
+}
+
+impl Drop for Workers {
+    /// Skips the queued jobs and waits for the running ones, so no thread outlives the server.
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use image::{imageops, RgbaImage};
+
+use super::{AtlasLayout, AtlasPacker, AtlasRect, AtlasRegion, AtlasSettings};
+
+/// Image extensions `AtlasBuilder::add_directory` picks up.
+const IMAGE_EXTENSIONS: [&str; 3] = ["png", "jpg", "jpeg"];
+
+/// Collects images and packs them into the pages of an `AtlasLayout`.
+///
+/// Packing every image at once sorts them from largest to smallest first, which packs
+/// tighter than adding them one by one to a `TextureAtlas`. The layout can be uploaded
+/// right away, or saved as an offline step, e.g. from a build script:
+///
+/// ```no_run
+/// use foux::core::atlas::{AtlasBuilder, AtlasSettings};
+///
+/// let mut builder = AtlasBuilder::new(AtlasSettings::default().with_page_size(1024, 1024));
+/// builder.add_directory("art/sprites").unwrap();
+/// builder.build().unwrap().save("assets/sprites.json").unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct AtlasBuilder {
+    pub settings: AtlasSettings,
+    images: Vec<(String, RgbaImage)>, // Images to pack, in the order they were added
+}
+
+impl AtlasBuilder {
+    /// Creates a builder without images.
+    pub fn new(settings: AtlasSettings) -> AtlasBuilder {
+        AtlasBuilder { settings, images: Vec::new() }
+    }
+
+    /// Returns how many images were added.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Checks whether no image was added.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Adds an image to pack, looked up by `name` in the atlas.
+    pub fn add(&mut self, name: impl Into<String>, image: RgbaImage) {
+        self.images.push((name.into(), image));
+    }
+
+    /// Adds an image file, named after the file without its extension.
+    ///
+    /// # Returns
+    ///
+    /// An `Err(String)` if the image cannot be decoded.
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let image = image::open(path).map_err(|err| format!("Cannot load image {}: {err}", path.display()))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        self.add(name, image.to_rgba8());
+        Ok(())
+    }
+
+    /// Adds every PNG and JPEG file directly inside `directory`, see `add_file`.
+    ///
+    /// # Returns
+    ///
+    /// How many images were added, or an `Err(String)` if the directory or one of the
+    /// images cannot be read.
+    pub fn add_directory(&mut self, directory: impl AsRef<Path>) -> Result<usize, String> {
+        let directory = directory.as_ref();
+        let entries = fs::read_dir(directory).map_err(|err| format!("Cannot read {}: {err}", directory.display()))?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let extension = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+                path.is_file() && IMAGE_EXTENSIONS.contains(&extension.as_str())
+            })
+            .collect();
+        // Directory order differs between platforms; sort it so builds are reproducible.
+        paths.sort();
+        for path in &paths {
+            self.add_file(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Packs the images into as many pages as needed.
+    ///
+    /// Each page is cropped to the images it holds, so a single small page is produced
+    /// when everything fits.
+    ///
+    /// # Returns
+    ///
+    /// The layout, or an `Err(String)` if two images share a name or an image is larger
+    /// than a page.
+    pub fn build(&self) -> Result<AtlasLayout, String> {
+        let mut names = HashSet::new();
+        if let Some((name, _)) = self.images.iter().find(|(name, _)| !names.insert(name.as_str())) {
+            return Err(format!("Atlas contains two images named {name}"));
+        }
+
+        let prepared = (self.images.iter())
+            .map(|(name, image)| prepare(name, image, &self.settings))
+            .collect::<Result<Vec<Prepared>, String>>()?;
+        let mut order: Vec<usize> = (0..prepared.len()).filter(|&i| !prepared[i].is_empty()).collect();
+        order.sort_by_key(|&i| {
+            let (width, height) = prepared[i].image.dimensions();
+            Reverse((width.max(height), width * height))
+        });
+
+        let mut packers = Vec::new();
+        let mut placements = vec![(0, AtlasRect::default()); prepared.len()];
+        for i in order {
+            placements[i] = place(&mut packers, &self.settings, &prepared[i]);
+        }
+
+        // Crop every page to its images, keeping the padding along the right and bottom.
+        let mut sizes = vec![(0, 0); packers.len()];
+        for (i, (page, rect)) in placements.iter().enumerate() {
+            if !prepared[i].is_empty() {
+                let (width, height) = &mut sizes[*page];
+                *width = (*width).max(rect.right() + self.settings.padding);
+                *height = (*height).max(rect.bottom() + self.settings.padding);
+            }
+        }
+        let mut pages: Vec<RgbaImage> = sizes.iter().map(|&(width, height)| RgbaImage::new(width, height)).collect();
+
+        let mut regions = Vec::new();
+        for (i, (page, rect)) in placements.into_iter().enumerate() {
+            if !prepared[i].is_empty() {
+                imageops::replace(&mut pages[page], &prepared[i].image, rect.x as i64, rect.y as i64);
+            }
+            regions.push(prepared[i].region(&self.images[i].0, page, rect, &self.settings, sizes.get(page).copied()));
+        }
+
+        log::debug!("Packed {} images into {} atlas pages", regions.len(), pages.len());
+        Ok(AtlasLayout { settings: self.settings, pages, regions })
+    }
+}
+
+/// An image ready to be packed: trimmed, then extruded.
+pub(super) struct Prepared {
+    pub(super) image: RgbaImage, // Trimmed image with its border repeated `extrude` times
+    trim: (u32, u32),            // Columns and rows trimmed off the left and top
+    content: (u32, u32),         // Size of the trimmed image, without extrusion
+    source: (u32, u32),          // Size of the original image
+}
+
+impl Prepared {
+    /// Checks whether nothing is left to pack after trimming.
+    pub(super) fn is_empty(&self) -> bool {
+        self.content.0 == 0 || self.content.1 == 0
+    }
+
+    /// Creates the region of the image once placed at `rect`, as returned by `place`.
+    pub(super) fn region(
+        &self,
+        name: &str,
+        page: usize,
+        rect: AtlasRect,
+        settings: &AtlasSettings,
+        page_size: Option<(u32, u32)>,
+    ) -> AtlasRegion {
+        let content = if self.is_empty() {
+            AtlasRect::default()
+        } else {
+            AtlasRect::new(rect.x + settings.extrude, rect.y + settings.extrude, self.content.0, self.content.1)
+        };
+        AtlasRegion::new(name, page, content, self.trim, self.source, page_size.unwrap_or((1, 1)))
+    }
+}
+
+/// Trims and extrudes an image as `settings` ask.
+///
+/// # Returns
+///
+/// The prepared image, or an `Err(String)` if it is larger than a page once extruded and
+/// padded.
+pub(super) fn prepare(name: &str, image: &RgbaImage, settings: &AtlasSettings) -> Result<Prepared, String> {
+    let (width, height) = image.dimensions();
+    let bounds = if settings.trim { opaque_bounds(image) } else { AtlasRect::new(0, 0, width, height) };
+    if bounds.width == 0 || bounds.height == 0 {
+        return Ok(Prepared {
+            image: RgbaImage::new(0, 0),
+            trim: (0, 0),
+            content: (0, 0),
+            source: (width, height),
+        });
+    }
+
+    // Check the size before extruding, so huge settings fail instead of allocating.
+    let border = 2 * (settings.extrude as u64 + settings.padding as u64);
+    let (padded_width, padded_height) = (bounds.width as u64 + border, bounds.height as u64 + border);
+    if padded_width > settings.page_width as u64 || padded_height > settings.page_height as u64 {
+        return Err(format!(
+            "Image {name} ({padded_width}x{padded_height} texels with padding) doesn't fit in {}x{} atlas pages",
+            settings.page_width, settings.page_height
+        ));
+    }
+
+    // Every texel of the extruded image copies the nearest texel of the trimmed one.
+    let extrude = settings.extrude;
+    let extruded = RgbaImage::from_fn(bounds.width + 2 * extrude, bounds.height + 2 * extrude, |x, y| {
+        let x = x.saturating_sub(extrude).min(bounds.width - 1);
+        let y = y.saturating_sub(extrude).min(bounds.height - 1);
+        *image.get_pixel(bounds.x + x, bounds.y + y)
+    });
+    Ok(Prepared {
+        image: extruded,
+        trim: (bounds.x, bounds.y),
+        content: (bounds.width, bounds.height),
+        source: (width, height),
+    })
+}
+
+/// Finds room for a prepared image in the first page that fits it, adding a page if
+/// none does.
+///
+/// `prepare` checked that the image fits in a page.
+///
+/// # Returns
+///
+/// The page index and the texels of the extruded image in that page.
+pub(super) fn place(
+    packers: &mut Vec<AtlasPacker>,
+    settings: &AtlasSettings,
+    prepared: &Prepared,
+) -> (usize, AtlasRect) {
+    // Packers work on the page without its left and top padding, and every image
+    // reserves the padding on its right and bottom.
+    let padding = settings.padding;
+    let (width, height) = (prepared.image.width() + padding, prepared.image.height() + padding);
+    let bin_width = settings.page_width - padding;
+    let bin_height = settings.page_height - padding;
+    let placed = packers.iter_mut().enumerate().find_map(|(page, packer)| Some((page, packer.insert(width, height)?)));
+    let (page, slot) = match placed {
+        Some(placed) => placed,
+        None => {
+            let mut packer = AtlasPacker::new(bin_width, bin_height);
+            let slot = packer.insert(width, height).expect("image fits an empty page");
+            packers.push(packer);
+            (packers.len() - 1, slot)
+        }
+    };
+    (page, AtlasRect::new(slot.x + padding, slot.y + padding, prepared.image.width(), prepared.image.height()))
+}
+
+/// Returns the smallest rectangle holding every texel that isn't fully transparent.
+fn opaque_bounds(image: &RgbaImage) -> AtlasRect {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] > 0 {
+            (min_x, min_y) = (min_x.min(x), min_y.min(y));
+            (max_x, max_y) = (max_x.max(x), max_y.max(y));
+        }
+    }
+    if min_x == u32::MAX {
+        return AtlasRect::default();
+    }
+    AtlasRect::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgba;
+
+    /// Creates an opaque image filled with one color.
+    fn square(size: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(size, size, Rgba([value, value, value, 255]))
+    }
+
+    #[test]
+    fn build_spills_onto_new_pages() {
+        let settings = AtlasSettings::default().with_page_size(10, 8).with_padding(1).with_extrude(0);
+        let mut builder = AtlasBuilder::new(settings);
+        builder.add("c", square(1, 3));
+        builder.add("a", square(6, 1));
+        builder.add("b", square(6, 2));
+        let layout = builder.build().unwrap();
+
+        // The small image goes next to the first large one instead of opening a page.
+        let regions: Vec<_> = (layout.regions.iter()).map(|region| (region.page, region.rect)).collect();
+        let (small, large) = (AtlasRect::new(8, 1, 1, 1), AtlasRect::new(1, 1, 6, 6));
+        assert_eq!(regions, [(0, small), (0, large), (1, large)]);
+        assert_eq!(layout.pages.len(), 2);
+        assert_eq!(layout.pages[0].dimensions(), (10, 8));
+        assert_eq!(layout.pages[1].get_pixel(1, 1), &Rgba([2, 2, 2, 255]));
+    }
+
+    #[test]
+    fn build_rejects_images_larger_than_a_page() {
+        let settings = AtlasSettings::default().with_page_size(8, 8).with_padding(1).with_extrude(0);
+        let mut builder = AtlasBuilder::new(settings);
+        builder.add("fits", square(6, 1));
+        builder.add("big", square(7, 1));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            "Image big (9x9 texels with padding) doesn't fit in 8x8 atlas pages"
+        );
+    }
+
+    #[test]
+    fn build_rejects_overflowing_settings_without_allocating() {
+        let settings = AtlasSettings::default().with_padding(u32::MAX).with_extrude(u32::MAX);
+        let mut builder = AtlasBuilder::new(settings);
+        builder.add("a", square(1, 1));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            "Image a (17179869181x17179869181 texels with padding) doesn't fit in 2048x2048 atlas pages"
+        );
+    }
+
+    #[test]
+    fn build_rejects_duplicate_names() {
+        let mut builder = AtlasBuilder::new(AtlasSettings::default());
+        builder.add("a", square(1, 1));
+        builder.add("a", square(1, 1));
+        assert_eq!(builder.build().unwrap_err(), "Atlas contains two images named a");
+    }
+}
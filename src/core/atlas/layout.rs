@@ -0,0 +1,278 @@
+use std::fs;
+use std::path::Path;
+
+use image::RgbaImage;
+use nalgebra_glm::Vec2;
+use serde::{Deserialize, Serialize};
+
+use super::AtlasRect;
+
+/// How images are laid out in an atlas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasSettings {
+    /// Largest width of a page in texels. Pages built offline shrink to fit their images.
+    pub page_width: u32,
+    /// Largest height of a page in texels.
+    pub page_height: u32,
+    /// Empty texels kept between images and around the page border.
+    pub padding: u32,
+    /// How many times the border texels of each image are repeated around it, so linear
+    /// filtering at the edge of a region doesn't blend in its neighbours.
+    pub extrude: u32,
+    /// Whether fully transparent rows and columns around images are cut off before
+    /// packing. `AtlasRegion` keeps the offsets needed to draw them at their original size.
+    pub trim: bool,
+}
+
+impl Default for AtlasSettings {
+    fn default() -> Self {
+        AtlasSettings {
+            page_width: 2048,
+            page_height: 2048,
+            padding: 2,
+            extrude: 1,
+            trim: true,
+        }
+    }
+}
+
+impl AtlasSettings {
+    /// Sets the largest size of a page.
+    pub fn with_page_size(mut self, width: u32, height: u32) -> AtlasSettings {
+        self.page_width = width;
+        self.page_height = height;
+        self
+    }
+
+    /// Sets the empty texels kept between images.
+    pub fn with_padding(mut self, padding: u32) -> AtlasSettings {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets how many times border texels are repeated around each image.
+    pub fn with_extrude(mut self, extrude: u32) -> AtlasSettings {
+        self.extrude = extrude;
+        self
+    }
+
+    /// Sets whether transparent borders are trimmed.
+    pub fn with_trim(mut self, trim: bool) -> AtlasSettings {
+        self.trim = trim;
+        self
+    }
+}
+
+/// Where an image ended up in an atlas.
+///
+/// UVs follow the image convention, with `v = 0` at the top of a page, like `MeshBuilder`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub name: String,
+    /// Index of the page holding the image.
+    pub page: usize,
+    /// Texels of the trimmed image in its page, without extrusion.
+    pub rect: AtlasRect,
+    /// Texel coordinates of the top-left corner of `rect`, divided by the page size.
+    pub uv_min: Vec2,
+    /// Texel coordinates just past the bottom-right corner of `rect`, divided by the page size.
+    pub uv_max: Vec2,
+    /// Columns trimmed off the left of the original image.
+    pub trim_x: u32,
+    /// Rows trimmed off the top of the original image.
+    pub trim_y: u32,
+    /// Width of the original image, before trimming.
+    pub source_width: u32,
+    /// Height of the original image, before trimming.
+    pub source_height: u32,
+}
+
+impl AtlasRegion {
+    /// Creates a region, computing its UVs from the size of its page.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the image was added as.
+    /// * `page` - Index of the page holding the image.
+    /// * `rect` - Texels of the trimmed image in its page.
+    /// * `trim` - Columns and rows trimmed off the left and top of the original image.
+    /// * `source_size` - Size of the original image.
+    /// * `page_size` - Size of the page.
+    pub(super) fn new(
+        name: &str,
+        page: usize,
+        rect: AtlasRect,
+        trim: (u32, u32),
+        source_size: (u32, u32),
+        page_size: (u32, u32),
+    ) -> AtlasRegion {
+        let size = Vec2::new(page_size.0.max(1) as f32, page_size.1.max(1) as f32);
+        AtlasRegion {
+            name: name.to_string(),
+            page,
+            rect,
+            uv_min: Vec2::new(rect.x as f32, rect.y as f32).component_div(&size),
+            uv_max: Vec2::new(rect.right() as f32, rect.bottom() as f32).component_div(&size),
+            trim_x: trim.0,
+            trim_y: trim.1,
+            source_width: source_size.0,
+            source_height: source_size.1,
+        }
+    }
+
+    /// Checks whether the image was fully transparent and trimmed away, in which case
+    /// there is nothing to draw.
+    pub fn is_empty(&self) -> bool {
+        self.rect.width == 0 || self.rect.height == 0
+    }
+}
+
+/// A packed atlas on the CPU: the page images and the region of every image.
+///
+/// Layouts come from `AtlasBuilder::build`, or from a manifest written by `save`, and
+/// are uploaded as a `TextureAtlas`. Building once offline and loading the manifest at
+/// runtime skips the packing and the decoding of every separate image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasLayout {
+    pub settings: AtlasSettings,
+    pub pages: Vec<RgbaImage>,
+    /// Regions in the order the images were added.
+    pub regions: Vec<AtlasRegion>,
+}
+
+impl AtlasLayout {
+    /// Returns the region of the image added as `name`.
+    pub fn get_region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.iter().find(|region| region.name == name)
+    }
+
+    /// Writes the pages as PNG images next to `manifest_path`, followed by a JSON manifest
+    /// naming them and listing the regions.
+    ///
+    /// Pages are named after the manifest, e.g. `sprites.png` for `sprites.json`, with
+    /// their index appended when there are several: `sprites_0.png`, `sprites_1.png`.
+    ///
+    /// # Returns
+    ///
+    /// An `Err(String)` if a file cannot be written.
+    pub fn save(&self, manifest_path: impl AsRef<Path>) -> Result<(), String> {
+        let manifest_path = manifest_path.as_ref();
+        let stem = manifest_path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut pages = Vec::new();
+        for (index, page) in self.pages.iter().enumerate() {
+            let image = match self.pages.len() {
+                1 => format!("{stem}.png"),
+                _ => format!("{stem}_{index}.png"),
+            };
+            let path = manifest_path.with_file_name(&image);
+            page.save(&path).map_err(|err| format!("Cannot write {}: {err}", path.display()))?;
+            pages.push(ManifestPage { image, width: page.width(), height: page.height() });
+        }
+
+        let manifest = Manifest {
+            page_width: self.settings.page_width,
+            page_height: self.settings.page_height,
+            padding: self.settings.padding,
+            extrude: self.settings.extrude,
+            trim: self.settings.trim,
+            pages,
+            regions: self.regions.iter().map(ManifestRegion::from).collect(),
+        };
+        let json = serde_json::to_string_pretty(&manifest).map_err(|err| err.to_string())?;
+        fs::write(manifest_path, json).map_err(|err| format!("Cannot write {}: {err}", manifest_path.display()))
+    }
+
+    /// Reads a manifest written by `save` and the page images it names.
+    ///
+    /// # Returns
+    ///
+    /// The layout, or an `Err(String)` if a file cannot be read or the manifest doesn't
+    /// match its pages.
+    pub fn load(manifest_path: impl AsRef<Path>) -> Result<AtlasLayout, String> {
+        let manifest_path = manifest_path.as_ref();
+        let origin = manifest_path.display();
+        let json = fs::read_to_string(manifest_path).map_err(|err| format!("Cannot read {origin}: {err}"))?;
+        let manifest: Manifest = serde_json::from_str(&json).map_err(|err| format!("Invalid atlas {origin}: {err}"))?;
+
+        let mut pages = Vec::new();
+        for page in &manifest.pages {
+            let path = manifest_path.with_file_name(&page.image);
+            let image = image::open(&path).map_err(|err| format!("Cannot load atlas page {}: {err}", path.display()))?;
+            pages.push(image.to_rgba8());
+        }
+
+        let mut regions = Vec::new();
+        for region in &manifest.regions {
+            let rect = AtlasRect::new(region.x, region.y, region.width, region.height);
+            let page_size = pages.get(region.page).map(|page| page.dimensions());
+            // Fully trimmed images take no space, so they may name any page.
+            let empty = rect.width == 0 || rect.height == 0;
+            let fits = page_size.is_some_and(|(width, height)| rect.right() <= width && rect.bottom() <= height);
+            if !empty && !fits {
+                return Err(format!("Invalid atlas {origin}: region {} lies outside its page", region.name));
+            }
+            let (trim, source_size) = ((region.trim_x, region.trim_y), (region.source_width, region.source_height));
+            let page_size = page_size.unwrap_or((1, 1));
+            regions.push(AtlasRegion::new(&region.name, region.page, rect, trim, source_size, page_size));
+        }
+
+        let settings = AtlasSettings {
+            page_width: manifest.page_width,
+            page_height: manifest.page_height,
+            padding: manifest.padding,
+            extrude: manifest.extrude,
+            trim: manifest.trim,
+        };
+        Ok(AtlasLayout { settings, pages, regions })
+    }
+}
+
+/// The JSON form of an `AtlasLayout`, with pages stored as image files.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    page_width: u32,
+    page_height: u32,
+    padding: u32,
+    extrude: u32,
+    trim: bool,
+    pages: Vec<ManifestPage>,
+    regions: Vec<ManifestRegion>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestPage {
+    image: String, // File name, relative to the manifest
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestRegion {
+    name: String,
+    page: usize,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    trim_x: u32,
+    trim_y: u32,
+    source_width: u32,
+    source_height: u32,
+}
+
+impl From<&AtlasRegion> for ManifestRegion {
+    fn from(region: &AtlasRegion) -> ManifestRegion {
+        ManifestRegion {
+            name: region.name.clone(),
+            page: region.page,
+            x: region.rect.x,
+            y: region.rect.y,
+            width: region.rect.width,
+            height: region.rect.height,
+            trim_x: region.trim_x,
+            trim_y: region.trim_y,
+            source_width: region.source_width,
+            source_height: region.source_height,
+        }
+    }
+}
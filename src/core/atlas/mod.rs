@@ -0,0 +1,9 @@
+mod builder;
+mod layout;
+mod packer;
+mod texture_atlas;
+
+pub use builder::AtlasBuilder;
+pub use layout::{AtlasLayout, AtlasRegion, AtlasSettings};
+pub use packer::{AtlasPacker, AtlasRect};
+pub use texture_atlas::TextureAtlas;
@@ -0,0 +1,183 @@
+/// An axis-aligned rectangle of texels in an atlas page.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct AtlasRect {
+    pub x: u32,      // Left edge
+    pub y: u32,      // Top edge
+    pub width: u32,  // Width in texels
+    pub height: u32, // Height in texels
+}
+
+impl AtlasRect {
+    /// Creates a rectangle from its top-left corner and size.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> AtlasRect {
+        AtlasRect { x, y, width, height }
+    }
+
+    /// Returns the x coordinate just past the right edge.
+    pub fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    /// Returns the y coordinate just past the bottom edge.
+    pub fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+
+    /// Checks whether the rectangles share any texel.
+    pub fn intersects(&self, other: &AtlasRect) -> bool {
+        self.x < other.right() && other.x < self.right() && self.y < other.bottom() && other.y < self.bottom()
+    }
+
+    /// Checks whether `other` lies entirely inside this rectangle.
+    pub fn contains(&self, other: &AtlasRect) -> bool {
+        other.x >= self.x && other.y >= self.y && other.right() <= self.right() && other.bottom() <= self.bottom()
+    }
+}
+
+/// Packs rectangles into a fixed-size bin with the MaxRects algorithm, using the best
+/// short side fit heuristic (Jylänki, "A Thousand Ways to Pack the Bin", 2010).
+///
+/// The packer keeps the list of maximal free rectangles, so rectangles can be added one
+/// at a time, such as glyphs rasterized on demand, while still packing tightly.
+#[derive(Clone, Debug)]
+pub struct AtlasPacker {
+    width: u32,           // Width of the bin
+    height: u32,          // Height of the bin
+    free: Vec<AtlasRect>, // Maximal free rectangles, possibly overlapping
+    used_area: u64,       // Texels covered by placed rectangles
+}
+
+impl AtlasPacker {
+    /// Creates an empty bin.
+    pub fn new(width: u32, height: u32) -> AtlasPacker {
+        AtlasPacker {
+            width,
+            height,
+            free: vec![AtlasRect::new(0, 0, width, height)],
+            used_area: 0,
+        }
+    }
+
+    /// Returns the width of the bin.
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the bin.
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the fraction of the bin covered by placed rectangles, from 0 to 1.
+    pub fn get_occupancy(&self) -> f32 {
+        let area = self.width as u64 * self.height as u64;
+        if area == 0 {
+            return 0.0;
+        }
+        self.used_area as f32 / area as f32
+    }
+
+    /// Places a rectangle of the given size.
+    ///
+    /// # Returns
+    ///
+    /// Where the rectangle was placed, or `None` if no free space fits it.
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<AtlasRect> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        // Best short side fit: the free rectangle leaving the smallest leftover on its
+        // tighter side, ties broken by the leftover on the other side.
+        let best = self
+            .free
+            .iter()
+            .filter(|free| free.width >= width && free.height >= height)
+            .min_by_key(|free| {
+                let (dx, dy) = (free.width - width, free.height - height);
+                (dx.min(dy), dx.max(dy))
+            })?;
+
+        let rect = AtlasRect::new(best.x, best.y, width, height);
+        self.occupy(rect);
+        Some(rect)
+    }
+
+    /// Marks a rectangle as used, such as one read back from a saved atlas.
+    pub fn occupy(&mut self, rect: AtlasRect) {
+        let mut split = Vec::new();
+        self.free.retain(|free| {
+            if !free.intersects(&rect) {
+                return true;
+            }
+            // Keep the maximal parts of `free` on each side of `rect`.
+            if rect.x > free.x {
+                split.push(AtlasRect::new(free.x, free.y, rect.x - free.x, free.height));
+            }
+            if rect.right() < free.right() {
+                split.push(AtlasRect::new(rect.right(), free.y, free.right() - rect.right(), free.height));
+            }
+            if rect.y > free.y {
+                split.push(AtlasRect::new(free.x, free.y, free.width, rect.y - free.y));
+            }
+            if rect.bottom() < free.bottom() {
+                split.push(AtlasRect::new(free.x, rect.bottom(), free.width, free.bottom() - rect.bottom()));
+            }
+            false
+        });
+        self.free.extend(split);
+        self.prune();
+        self.used_area += rect.width as u64 * rect.height as u64;
+    }
+
+    /// Removes free rectangles contained in another one.
+    fn prune(&mut self) {
+        let mut i = 0;
+        while i < self.free.len() {
+            let rect = self.free[i];
+            let contained = self.free.iter().enumerate().any(|(j, other)| {
+                // Of two equal rectangles, only the first one is kept.
+                j != i && other.contains(&rect) && (other != &rect || j < i)
+            });
+            if contained {
+                self.free.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_fills_the_bin_then_returns_none() {
+        let mut packer = AtlasPacker::new(4, 4);
+        assert_eq!(packer.insert(4, 2), Some(AtlasRect::new(0, 0, 4, 2)));
+        assert_eq!(packer.insert(2, 2), Some(AtlasRect::new(0, 2, 2, 2)));
+        assert_eq!(packer.insert(2, 2), Some(AtlasRect::new(2, 2, 2, 2)));
+        assert_eq!(packer.get_occupancy(), 1.0);
+        assert_eq!(packer.insert(1, 1), None);
+    }
+
+    #[test]
+    fn insert_rejects_rectangles_larger_than_the_bin() {
+        let mut packer = AtlasPacker::new(4, 4);
+        assert_eq!(packer.insert(5, 1), None);
+        assert_eq!(packer.insert(1, 5), None);
+        assert_eq!(packer.insert(u32::MAX, u32::MAX), None);
+        assert_eq!(packer.insert(0, 4), None);
+        assert_eq!(packer.get_occupancy(), 0.0);
+    }
+
+    #[test]
+    fn occupy_leaves_room_around_the_rectangle() {
+        let mut packer = AtlasPacker::new(4, 4);
+        packer.occupy(AtlasRect::new(1, 1, 2, 2));
+        assert_eq!(packer.insert(4, 2), None);
+        assert_eq!(packer.insert(4, 1), Some(AtlasRect::new(0, 0, 4, 1)));
+        assert_eq!(packer.insert(4, 1), Some(AtlasRect::new(0, 3, 4, 1)));
+    }
+}
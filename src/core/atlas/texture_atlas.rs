@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+
+use super::builder::{place, prepare};
+use super::{AtlasLayout, AtlasPacker, AtlasRect, AtlasRegion, AtlasSettings};
+use crate::opengl::{Texture, TextureFormat};
+
+/// Images packed into a few textures, so sprites and glyphs drawn from them can share a
+/// texture and be batched together.
+///
+/// Pages are `Srgb8Alpha8` textures without mipmaps, sampled linearly and clamped to
+/// their edges. Regions are looked up by the name their image was added as.
+///
+/// Atlases are either uploaded from an `AtlasLayout` packed ahead of time, or filled at
+/// runtime with `insert`, such as a glyph cache rasterizing characters as they are first
+/// drawn. Both can be combined: images inserted into an uploaded atlas go into the free
+/// space of its pages, or into new pages of `AtlasSettings::page_width` by `page_height`.
+pub struct TextureAtlas {
+    settings: AtlasSettings,       // Layout of inserted images
+    pages: Vec<Texture>,           // One texture per page
+    packers: Vec<AtlasPacker>,     // Free space of each page
+    regions: Vec<AtlasRegion>,     // Every image, in insertion order
+    names: HashMap<String, usize>, // Index of each region, by name
+}
+
+impl TextureAtlas {
+    /// Creates an atlas without pages, to be filled with `insert`.
+    pub fn new(settings: AtlasSettings) -> TextureAtlas {
+        TextureAtlas {
+            settings,
+            pages: Vec::new(),
+            packers: Vec::new(),
+            regions: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Uploads the pages of a packed layout.
+    pub fn from_layout(layout: &AtlasLayout) -> TextureAtlas {
+        let mut atlas = TextureAtlas::new(layout.settings);
+        let padding = layout.settings.padding;
+        for page in &layout.pages {
+            let texture = Texture::new(page.width(), page.height(), TextureFormat::Srgb8Alpha8);
+            texture.bind(0);
+            texture.update_region(0, 0, page.width(), page.height(), page.as_raw());
+            texture.unbind(0);
+            atlas.pages.push(texture);
+            let (width, height) = (page.width().saturating_sub(padding), page.height().saturating_sub(padding));
+            atlas.packers.push(AtlasPacker::new(width, height));
+        }
+
+        // Rebuild the free space of each page from the space its regions reserve.
+        let extrude = layout.settings.extrude;
+        for region in &layout.regions {
+            let packer = atlas.packers.get_mut(region.page).filter(|_| !region.is_empty());
+            if let Some(packer) = packer {
+                packer.occupy(AtlasRect::new(
+                    region.rect.x.saturating_sub(extrude + padding),
+                    region.rect.y.saturating_sub(extrude + padding),
+                    region.rect.width + 2 * extrude + padding,
+                    region.rect.height + 2 * extrude + padding,
+                ));
+            }
+            atlas.names.insert(region.name.clone(), atlas.regions.len());
+            atlas.regions.push(region.clone());
+        }
+        atlas
+    }
+
+    /// Packs an image into the atlas and uploads it, trimming and extruding it as the
+    /// atlas' settings ask.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to look the region up by.
+    /// * `image` - The image to add.
+    ///
+    /// # Returns
+    ///
+    /// The region of the image, or an `Err(String)` if the name is taken or the image is
+    /// larger than a page.
+    pub fn insert(&mut self, name: &str, image: &RgbaImage) -> Result<&AtlasRegion, String> {
+        if self.names.contains_key(name) {
+            return Err(format!("Atlas already contains an image named {name}"));
+        }
+
+        let prepared = prepare(name, image, &self.settings)?;
+        let (mut page, mut rect) = (0, AtlasRect::default());
+        if !prepared.is_empty() {
+            (page, rect) = place(&mut self.packers, &self.settings, &prepared);
+            if page == self.pages.len() {
+                self.pages.push(self.create_page());
+            }
+            let texture = &self.pages[page];
+            texture.bind(0);
+            texture.update_region(rect.x, rect.y, rect.width, rect.height, prepared.image.as_raw());
+            texture.unbind(0);
+        }
+
+        let page_size = self.pages.get(page).map(|texture| (texture.get_width(), texture.get_height()));
+        self.names.insert(name.to_string(), self.regions.len());
+        self.regions.push(prepared.region(name, page, rect, &self.settings, page_size));
+        Ok(&self.regions[self.regions.len() - 1])
+    }
+
+    /// Returns the settings inserted images are laid out with.
+    pub fn get_settings(&self) -> &AtlasSettings {
+        &self.settings
+    }
+
+    /// Returns the texture of a page, or `None` if there is no such page.
+    pub fn get_page(&self, page: usize) -> Option<&Texture> {
+        self.pages.get(page)
+    }
+
+    /// Returns how many pages the atlas has.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Returns the region of the image added as `name`.
+    pub fn get_region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.names.get(name).map(|&index| &self.regions[index])
+    }
+
+    /// Returns the index of the region of the image added as `name`, which stays valid
+    /// for the lifetime of the atlas and is cheaper to store than the name.
+    pub fn get_region_index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// Returns the region at an index from `get_region_index`.
+    pub fn get_region_at(&self, index: usize) -> Option<&AtlasRegion> {
+        self.regions.get(index)
+    }
+
+    /// Returns every region, in the order the images were added.
+    pub fn get_regions(&self) -> &[AtlasRegion] {
+        &self.regions
+    }
+
+    /// Creates an empty, transparent page of the largest size.
+    fn create_page(&self) -> Texture {
+        let (width, height) = (self.settings.page_width, self.settings.page_height);
+        let texture = Texture::new(width, height, TextureFormat::Srgb8Alpha8);
+        texture.bind(0);
+        texture.update_region(0, 0, width, height, &vec![0u8; width as usize * height as usize * 4]);
+        texture.unbind(0);
+        texture
+    }
+}
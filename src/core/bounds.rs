@@ -0,0 +1,155 @@
+use nalgebra_glm::{self as glm, Mat4, Vec3, Vec4};
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    /// The corner with the smallest coordinates.
+    pub min: Vec3,
+    /// The corner with the largest coordinates.
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Creates a box from its two extreme corners.
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Returns the smallest box containing every point, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Aabb> {
+        points.into_iter().fold(None, |bounds, point| match bounds {
+            Some(bounds) => Some(bounds.extended(&point)),
+            None => Some(Aabb::new(point, point)),
+        })
+    }
+
+    /// Returns the middle of the box.
+    pub fn get_center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns half the size of the box along each axis.
+    pub fn get_half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns the smallest box containing both this box and `point`.
+    pub fn extended(&self, point: &Vec3) -> Aabb {
+        Aabb::new(glm::min2(&self.min, point), glm::max2(&self.max, point))
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(glm::min2(&self.min, &other.min), glm::max2(&self.max, &other.max))
+    }
+
+    /// Checks whether `point` lies inside the box or on its faces.
+    pub fn contains(&self, point: &Vec3) -> bool {
+        (0..3).all(|axis| self.min[axis] <= point[axis] && point[axis] <= self.max[axis])
+    }
+
+    /// Returns the axis-aligned box enclosing this box after `transform`, which may
+    /// be larger than the transformed box itself when it rotates.
+    pub fn transformed(&self, transform: &Mat4) -> Aabb {
+        let center = (transform * self.get_center().push(1.0)).xyz();
+        let rotation = glm::mat4_to_mat3(transform).abs();
+        let half_extents = rotation * self.get_half_extents();
+        Aabb::new(center - half_extents, center + half_extents)
+    }
+}
+
+/// A sphere enclosing a mesh, cheaper to test than its `Aabb`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    /// The middle of the sphere.
+    pub center: Vec3,
+    /// The distance from the center to the sphere's surface.
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Creates a sphere from its center and radius.
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    /// Returns a sphere around the center of the points' bounding box containing every
+    /// point, or `None` if there are none.
+    pub fn from_points(points: &[Vec3]) -> Option<BoundingSphere> {
+        let center = Aabb::from_points(points.iter().copied())?.get_center();
+        let radius = points.iter().map(|point| glm::distance2(&center, point)).fold(0.0, f32::max).sqrt();
+        Some(BoundingSphere::new(center, radius))
+    }
+
+    /// Returns the sphere enclosing this sphere after `transform`, scaled by the
+    /// transform's largest axis scale.
+    pub fn transformed(&self, transform: &Mat4) -> BoundingSphere {
+        let center = (transform * self.center.push(1.0)).xyz();
+        let scale = (0..3).map(|axis| transform.fixed_view::<3, 1>(0, axis).norm()).fold(0.0, f32::max);
+        BoundingSphere::new(center, self.radius * scale)
+    }
+}
+
+/// The six planes enclosing what a camera sees, used to skip drawing anything outside.
+///
+/// Planes are stored as `(normal, distance)` with normals pointing inwards.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum of a world-to-clip matrix, e.g. a camera's projection times
+    /// its view matrix.
+    pub fn from_matrix(view_projection: &Mat4) -> Frustum {
+        let row = |index: usize| view_projection.row(index).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| {
+            let length = plane.xyz().norm();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+        Frustum { planes }
+    }
+
+    /// Returns the planes as `(normal, distance)`: left, right, bottom, top, near, far.
+    pub fn get_planes(&self) -> &[Vec4; 6] {
+        &self.planes
+    }
+
+    /// Checks whether any part of the sphere may be inside the frustum.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.xyz().dot(&sphere.center) + plane.w >= -sphere.radius)
+    }
+
+    /// Checks whether any part of the box may be inside the frustum.
+    ///
+    /// Large boxes just outside a corner of the frustum can pass, which only costs a
+    /// draw that the GPU clips away.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal.
+            let corner = Vec3::from_fn(|axis, _| if plane[axis] >= 0.0 { aabb.max[axis] } else { aabb.min[axis] });
+            plane.xyz().dot(&corner) + plane.w >= 0.0
+        })
+    }
+}
+
+/// How many meshes the last frame drew and how many it skipped as off-screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CullingStats {
+    /// Meshes inside the camera's frustum.
+    pub visible: usize,
+    /// Meshes outside the camera's frustum that were not drawn.
+    pub culled: usize,
+}
+
+impl CullingStats {
+    /// Returns the number of meshes tested.
+    pub fn total(&self) -> usize {
+        self.visible + self.culled
+    }
+}
@@ -0,0 +1,160 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+use crate::core::{BoundingSphere, Frustum};
+
+/// A perspective or orthographic camera looking from a position towards a target point.
+#[derive(Clone, Debug)]
+pub struct Camera {
+    position: Vec3,
+    target: Vec3,
+    up: Vec3,
+    fov_y: f32,
+    /// Height of the view volume when orthographic.
+    orthographic_height: Option<f32>,
+    aspect: f32,
+    near: f32,
+    far: f32,
+}
+
+impl Camera {
+    /// Creates a new camera at `position` looking towards `target`.
+    ///
+    /// The camera starts with a 60° vertical field of view and a `0.1..1000.0` depth range.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The world-space position of the camera.
+    /// * `target` - The world-space point the camera looks at.
+    pub fn new(position: Vec3, target: Vec3) -> Camera {
+        Camera {
+            position,
+            target,
+            up: Vec3::y(),
+            fov_y: 60f32.to_radians(),
+            orthographic_height: None,
+            aspect: 1.0,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+
+    /// Returns the world-to-view matrix.
+    pub fn view_matrix(&self) -> Mat4 {
+        nalgebra_glm::look_at(&self.position, &self.target, &self.up)
+    }
+
+    /// Returns the view-to-clip projection matrix.
+    pub fn projection_matrix(&self) -> Mat4 {
+        match self.orthographic_height {
+            Some(height) => {
+                let (half_width, half_height) = (height * self.aspect / 2.0, height / 2.0);
+                nalgebra_glm::ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            }
+            None => nalgebra_glm::perspective(self.aspect, self.fov_y, self.near, self.far),
+        }
+    }
+
+    /// Returns the planes enclosing what the camera sees, for culling.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&(self.projection_matrix() * self.view_matrix()))
+    }
+
+    /// Returns the position of the camera.
+    pub fn get_position(&self) -> &Vec3 {
+        &self.position
+    }
+
+    /// Moves the camera to a new position, keeping its target.
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    /// Returns the point the camera looks at.
+    pub fn get_target(&self) -> &Vec3 {
+        &self.target
+    }
+
+    /// Points the camera towards a new target.
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+    }
+
+    /// Returns the up direction of the view.
+    pub fn get_up(&self) -> &Vec3 {
+        &self.up
+    }
+
+    /// Sets the up direction of the view, which must not be parallel to the view direction.
+    pub fn set_up(&mut self, up: Vec3) {
+        self.up = up;
+    }
+
+    /// Places the camera at the origin of `transform`, looking down its `-Z` axis with
+    /// its `+Y` axis up, as glTF cameras and camera entities are oriented.
+    pub fn set_from_transform(&mut self, transform: &Mat4) {
+        self.position = (transform * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+        self.target = self.position + (transform * Vec4::new(0.0, 0.0, -1.0, 0.0)).xyz();
+        self.up = (transform * Vec4::new(0.0, 1.0, 0.0, 0.0)).xyz();
+    }
+
+    /// Checks whether the camera uses an orthographic projection.
+    pub fn is_orthographic(&self) -> bool {
+        self.orthographic_height.is_some()
+    }
+
+    /// Sets the perspective projection parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `fov_y` - Vertical field of view in radians.
+    /// * `near` - Distance to the near clipping plane.
+    /// * `far` - Distance to the far clipping plane.
+    pub fn set_perspective(&mut self, fov_y: f32, near: f32, far: f32) {
+        self.orthographic_height = None;
+        self.fov_y = fov_y;
+        self.near = near;
+        self.far = far;
+    }
+
+    /// Switches to an orthographic projection.
+    ///
+    /// # Arguments
+    ///
+    /// * `height` - Height of the view volume in world units; the width follows the aspect ratio.
+    /// * `near` - Distance to the near clipping plane.
+    /// * `far` - Distance to the far clipping plane.
+    pub fn set_orthographic(&mut self, height: f32, near: f32, far: f32) {
+        self.orthographic_height = Some(height);
+        self.near = near;
+        self.far = far;
+    }
+
+    /// Sets the width-over-height aspect ratio of the viewport.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+    }
+
+    /// Returns how much of the viewport's height a sphere covers, `1` filling it.
+    ///
+    /// Spheres around the camera position count as filling the viewport.
+    pub fn screen_size(&self, sphere: &BoundingSphere) -> f32 {
+        let visible_height = match self.orthographic_height {
+            Some(height) => height,
+            None => {
+                let distance = nalgebra_glm::distance(&self.position, &sphere.center);
+                if distance <= sphere.radius {
+                    return 1.0;
+                }
+                2.0 * distance * (self.fov_y / 2.0).tan()
+            }
+        };
+        2.0 * sphere.radius / visible_height
+    }
+}
+
+impl Default for Camera {
+    /// Creates a camera three units along `+Z` looking at the origin.
+    fn default() -> Self {
+        Camera::new(Vec3::new(0.0, 0.0, 3.0), Vec3::zeros())
+    }
+}
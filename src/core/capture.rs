@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::JoinHandle;
+
+use image::RgbaImage;
+
+use crate::opengl::{BufferObject, BufferTarget, BufferUsage, Fence, Framebuffer};
+
+/// How many readbacks may be in flight before `capture` waits for the oldest one.
+const MAX_IN_FLIGHT: usize = 4;
+
+/// The path a PNG was written to, or why it could not be.
+type CaptureResult = Result<PathBuf, String>;
+
+/// The worker thread encoding and writing PNGs.
+struct Encoder {
+    sender: Sender<(RgbaImage, PathBuf)>,
+    worker: JoinHandle<()>,
+}
+
+/// A readback whose pixels are still being copied by the GPU.
+struct PendingCapture {
+    buffer: BufferObject,
+    fence: Fence,
+    width: u32,
+    height: u32,
+    path: PathBuf,
+}
+
+/// Saves framebuffer contents to PNG files without stalling the GPU.
+///
+/// `capture` only queues a copy into a pixel pack buffer; `poll` maps the buffers the
+/// GPU has finished with, and the PNGs are encoded on a worker thread.
+pub struct FrameCapture {
+    pending: VecDeque<PendingCapture>,
+    free: Vec<(usize, BufferObject)>,
+    encoder: Option<Encoder>,
+    result_sender: Sender<CaptureResult>,
+    results: Receiver<CaptureResult>,
+}
+
+impl FrameCapture {
+    /// Creates a capture queue with no readbacks in flight.
+    pub fn new() -> FrameCapture {
+        FrameCapture::default()
+    }
+
+    /// Queues a copy of a framebuffer's color to be saved as a PNG.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The framebuffer to read, or `None` for the window's own.
+    /// * `width` - Width of the framebuffer in pixels.
+    /// * `height` - Height of the framebuffer in pixels.
+    /// * `path` - Where the PNG is written.
+    pub fn capture<P: AsRef<Path>>(&mut self, source: Option<&Framebuffer>, width: u32, height: u32, path: P) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        if self.pending.len() >= MAX_IN_FLIGHT {
+            if let Some(oldest) = self.pending.pop_front() {
+                oldest.fence.wait(u64::MAX);
+                self.finish(oldest);
+            }
+        }
+
+        let size = width as usize * height as usize * 4;
+        let buffer = match self.free.iter().position(|(capacity, _)| *capacity == size) {
+            Some(index) => self.free.swap_remove(index).1,
+            None => {
+                let buffer = BufferObject::new(BufferTarget::PixelPackBuffer, BufferUsage::StreamRead);
+                buffer.bind();
+                buffer.allocate(size);
+                buffer.unbind();
+                buffer
+            }
+        };
+
+        Framebuffer::bind_target(source);
+        Framebuffer::read_pixels_into(&buffer, width, height);
+        self.pending.push_back(PendingCapture {
+            buffer,
+            fence: Fence::new(),
+            width,
+            height,
+            path: path.as_ref().to_path_buf(),
+        });
+    }
+
+    /// Hands every readback the GPU has finished over to the encoder.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every PNG written since the last call: its path, or an
+    /// `Err(String)` if the buffer could not be mapped or the file could not be written.
+    pub fn poll(&mut self) -> Vec<CaptureResult> {
+        while self.pending.front().is_some_and(|capture| capture.fence.is_signaled()) {
+            if let Some(capture) = self.pending.pop_front() {
+                self.finish(capture);
+            }
+        }
+        self.results.try_iter().collect()
+    }
+
+    /// Waits until every queued capture has been written to disk.
+    ///
+    /// # Returns
+    ///
+    /// The outcome of every PNG written since the last `poll`.
+    pub fn flush(&mut self) -> Vec<CaptureResult> {
+        while let Some(capture) = self.pending.pop_front() {
+            capture.fence.wait(u64::MAX);
+            self.finish(capture);
+        }
+        if let Some(Encoder { sender, worker }) = self.encoder.take() {
+            drop(sender);
+            let _ = worker.join();
+        }
+        self.results.try_iter().collect()
+    }
+
+    /// Checks whether any capture is still being read back or encoded.
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty() || self.encoder.is_some()
+    }
+
+    fn finish(&mut self, capture: PendingCapture) {
+        let PendingCapture {
+            buffer,
+            width,
+            height,
+            path,
+            ..
+        } = capture;
+        let size = width as usize * height as usize * 4;
+
+        buffer.bind();
+        let pixels = buffer.map_read(size, |bytes| bytes.to_vec());
+        buffer.unbind();
+        self.free.push((size, buffer));
+
+        let Some(pixels) = pixels else {
+            let _ = self.result_sender.send(Err(format!("Couldn't map the readback for {}", path.display())));
+            return;
+        };
+        let mut image = RgbaImage::from_raw(width, height, pixels).expect("Readback matches the capture size");
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        let results = &self.result_sender;
+        let encoder = self.encoder.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<(RgbaImage, PathBuf)>();
+            let results = results.clone();
+            let worker = std::thread::spawn(move || {
+                for (image, path) in receiver {
+                    let _ = results.send(save_png(&image, &path).map(|()| path));
+                }
+            });
+            Encoder { sender, worker }
+        });
+        let _ = encoder.sender.send((image, path));
+    }
+}
+
+impl Default for FrameCapture {
+    fn default() -> Self {
+        let (result_sender, results) = mpsc::channel();
+        FrameCapture {
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            encoder: None,
+            result_sender,
+            results,
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    /// Finishes writing every queued capture, so no frame is lost on shutdown.
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Dumps every rendered frame as a numbered PNG, advancing time by a fixed step.
+///
+/// While a window is recording, the engine ignores wall-clock time so the output plays
+/// back smoothly at `frame_rate` however long each frame took to render and save.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    directory: PathBuf,
+    frame_rate: u32,
+    next_frame: u32,
+}
+
+impl Recording {
+    /// Creates a recording writing `frame_000000.png`, `frame_000001.png`, ... into `directory`.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Where the frames are written. It is created if missing.
+    /// * `frame_rate` - Frames per second of the output.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `frame_rate` is zero.
+    pub fn new<P: AsRef<Path>>(directory: P, frame_rate: u32) -> Recording {
+        assert!(frame_rate > 0, "Recording frame rate must be positive");
+        Recording {
+            directory: directory.as_ref().to_path_buf(),
+            frame_rate,
+            next_frame: 0,
+        }
+    }
+
+    /// Returns the directory frames are written into.
+    pub fn get_directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Returns the frames per second of the output.
+    pub fn get_frame_rate(&self) -> u32 {
+        self.frame_rate
+    }
+
+    /// Returns the time between two recorded frames, in seconds.
+    pub fn get_timestep(&self) -> f32 {
+        1.0 / self.frame_rate as f32
+    }
+
+    /// Returns how many frames have been recorded so far.
+    pub fn get_frame_count(&self) -> u32 {
+        self.next_frame
+    }
+
+    /// Returns the path of the next frame and advances the frame counter.
+    pub(crate) fn next_path(&mut self) -> PathBuf {
+        let path = self.directory.join(format!("frame_{:06}.png", self.next_frame));
+        self.next_frame += 1;
+        path
+    }
+}
+
+fn save_png(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| format!("Couldn't create {}: {error}", parent.display()))?;
+    }
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|error| format!("Couldn't write {}: {error}", path.display()))
+}
@@ -0,0 +1,83 @@
+use crate::device::bytes_of;
+use crate::opengl::IndexType;
+
+/// Index data of a mesh, in the smallest integer type that fits its vertex count.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Indices {
+    /// Vertices are drawn in order with `glDrawArrays`.
+    #[default]
+    None,
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Returns how many indices there are; `0` for `None`.
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::None => 0,
+            Indices::U8(indices) => indices.len(),
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    /// Checks whether there are no indices.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the integer type of the indices, or `None` for non-indexed meshes.
+    pub fn index_type(&self) -> Option<IndexType> {
+        match self {
+            Indices::None => None,
+            Indices::U8(_) => Some(IndexType::UnsignedByte),
+            Indices::U16(_) => Some(IndexType::UnsignedShort),
+            Indices::U32(_) => Some(IndexType::UnsignedInt),
+        }
+    }
+
+    /// Returns the index at `position` widened to `u32`.
+    pub fn get(&self, position: usize) -> Option<u32> {
+        match self {
+            Indices::None => None,
+            Indices::U8(indices) => indices.get(position).map(|&index| index as u32),
+            Indices::U16(indices) => indices.get(position).map(|&index| index as u32),
+            Indices::U32(indices) => indices.get(position).copied(),
+        }
+    }
+
+    /// Iterates over the indices widened to `u32`.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len()).filter_map(|position| self.get(position))
+    }
+
+    /// Returns the indices as bytes, for uploading to an element buffer.
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::None => &[],
+            Indices::U8(indices) => bytes_of(indices),
+            Indices::U16(indices) => bytes_of(indices),
+            Indices::U32(indices) => bytes_of(indices),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Indices {
+    fn from(indices: Vec<u8>) -> Self {
+        Indices::U8(indices)
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
+}
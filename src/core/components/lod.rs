@@ -0,0 +1,176 @@
+use std::cmp::Ordering;
+
+use nalgebra_glm::Mat4;
+
+use crate::core::components::MeshRenderer;
+use crate::core::{BoundingSphere, Camera};
+
+/// What the thresholds of a `Lod` are compared against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LodMetric {
+    /// World-space distance from the camera to the center of the finest level's bounds.
+    /// A level is used while the distance is below its threshold.
+    #[default]
+    Distance,
+    /// Fraction of the viewport height the finest level's bounding sphere covers, see
+    /// `Camera::screen_size`. A level is used while the size is at least its threshold.
+    ScreenSize,
+}
+
+/// One mesh of a `Lod` and the threshold up to which it is drawn.
+pub struct LodLevel {
+    /// The mesh drawn for this level.
+    pub render: MeshRenderer,
+    /// The distance below which, or the screen size from which, this level is used.
+    pub threshold: f32,
+}
+
+/// A component drawing one of several versions of a mesh depending on how far from the
+/// camera or how large on screen it is.
+///
+/// Levels go from the most to the least detailed, with increasing distance thresholds
+/// or decreasing screen size thresholds. Past the last level's threshold nothing is
+/// drawn; give it a threshold of `f32::INFINITY` or `0.0` to always draw it. Lower levels
+/// can be generated with `MeshBuilder::simplify`.
+///
+/// `Scene::update_world` moves every level to the entity's `GlobalTransform` and picks
+/// the level for the scene's camera.
+pub struct Lod {
+    /// The meshes from the most to the least detailed.
+    pub levels: Vec<LodLevel>,
+    /// What the level thresholds are compared against.
+    pub metric: LodMetric,
+    /// How far past a threshold the metric must move before the level changes, as a
+    /// fraction of the metric, so objects right at a threshold don't flicker between
+    /// levels. `0.1` needs 10 % more distance or screen size.
+    pub hysteresis: f32,
+    /// Seconds over which a new level dithers in while the old one dithers out, or `0`
+    /// to switch at once.
+    pub crossfade: f32,
+    current: usize,          // Selected level, `levels.len()` when nothing is drawn
+    previous: Option<usize>, // Level fading out, if crossfading
+    fade: f32,               // Progress of the crossfade from 0 to 1
+}
+
+impl Lod {
+    /// Creates a `Lod` without levels.
+    ///
+    /// # Arguments
+    ///
+    /// * `metric` - What the level thresholds are compared against.
+    pub fn new(metric: LodMetric) -> Lod {
+        Lod {
+            levels: Vec::new(),
+            metric,
+            hysteresis: 0.1,
+            crossfade: 0.0,
+            current: 0,
+            previous: None,
+            fade: 1.0,
+        }
+    }
+
+    /// Appends a level less detailed than the previous ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `render` - The mesh drawn for the level.
+    /// * `threshold` - The distance below which, or the screen size from which, the level is used.
+    pub fn with_level(mut self, render: MeshRenderer, threshold: f32) -> Lod {
+        self.levels.push(LodLevel { render, threshold });
+        self
+    }
+
+    /// Returns the index of the level drawn, or `None` if the object is past the last
+    /// level's threshold.
+    pub fn get_current_level(&self) -> Option<usize> {
+        (self.current < self.levels.len()).then_some(self.current)
+    }
+
+    /// Moves every level to `transform`.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        for level in &mut self.levels {
+            level.render.set_transform(transform);
+        }
+    }
+
+    /// Returns the meshes to draw: the current level and, while crossfading, the one
+    /// fading out.
+    pub fn renders(&self) -> impl Iterator<Item = &MeshRenderer> {
+        let fading = self.previous.and_then(|previous| self.levels.get(previous));
+        self.levels.get(self.current).into_iter().chain(fading).map(|level| &level.render)
+    }
+
+    /// Picks the level to draw for `camera` and advances the crossfade by `delta` seconds.
+    pub fn update(&mut self, camera: &Camera, delta: f32) {
+        let Some(value) = self.measure(camera) else { return };
+
+        let level = match self.level_for(value).cmp(&self.current) {
+            // Coarser levels need the metric to be `hysteresis` further past the threshold,
+            // finer levels `hysteresis` further back.
+            Ordering::Greater => self.level_for(self.biased(value, true)).max(self.current),
+            Ordering::Less => self.level_for(self.biased(value, false)).min(self.current),
+            Ordering::Equal => self.current,
+        };
+        if level != self.current {
+            self.previous = (self.crossfade > 0.0).then_some(self.current);
+            self.current = level;
+            self.fade = 0.0;
+        }
+
+        if let Some(previous) = self.previous {
+            self.fade = if self.crossfade > 0.0 { (self.fade + delta / self.crossfade).min(1.0) } else { 1.0 };
+            if self.fade >= 1.0 {
+                self.previous = None;
+                if let Some(level) = self.levels.get_mut(previous) {
+                    level.render.set_dither_fade(0.0);
+                }
+            }
+        }
+
+        let fade = self.fade;
+        let previous = self.previous;
+        for (index, level) in self.levels.iter_mut().enumerate() {
+            let dither = match previous {
+                // A fade of exactly zero would draw every pixel instead of none.
+                Some(_) if index == self.current => fade.max(f32::EPSILON),
+                Some(previous) if index == previous => -fade,
+                _ => 0.0,
+            };
+            level.render.set_dither_fade(dither);
+        }
+    }
+
+    /// Returns the metric of the finest level for `camera`, or `None` without levels.
+    fn measure(&self, camera: &Camera) -> Option<f32> {
+        let finest = &self.levels.first()?.render;
+        let sphere = finest.get_bounding_sphere().unwrap_or_else(|| {
+            let transform = finest.get_transform();
+            BoundingSphere::new(transform.fixed_view::<3, 1>(0, 3).into(), 0.0)
+        });
+        Some(match self.metric {
+            LodMetric::Distance => nalgebra_glm::distance(camera.get_position(), &sphere.center),
+            LodMetric::ScreenSize => camera.screen_size(&sphere),
+        })
+    }
+
+    /// Returns the first level whose threshold admits `value`, or `levels.len()` if none does.
+    fn level_for(&self, value: f32) -> usize {
+        self.levels
+            .iter()
+            .position(|level| match self.metric {
+                LodMetric::Distance => value < level.threshold,
+                LodMetric::ScreenSize => value >= level.threshold,
+            })
+            .unwrap_or(self.levels.len())
+    }
+
+    /// Moves `value` by the hysteresis towards finer or coarser levels.
+    fn biased(&self, value: f32, towards_finer: bool) -> f32 {
+        let factor = 1.0 + self.hysteresis.max(0.0);
+        match (self.metric, towards_finer) {
+            (LodMetric::Distance, true) | (LodMetric::ScreenSize, false) => value / factor,
+            (LodMetric::Distance, false) | (LodMetric::ScreenSize, true) => value * factor,
+        }
+    }
+}
@@ -1,113 +1,551 @@
-use crate::opengl::{BufferObject, ShaderProgram, VertexArrayObject, VertexAttribPointer};
-use crate::opengl::{BufferTarget, BufferUsage, DataType};
+use crate::core::assets::Handle;
+use crate::core::material::{forward_shader, Material, JOINT_PALETTE_BLOCK, MAX_JOINTS, MAX_MORPH_TARGETS};
+use crate::core::mesh::Mesh;
+use crate::core::{Aabb, BoundingSphere, Frustum, RenderContext};
+use crate::device::{bytes_of, BufferHandle, DrawCall, ExternalTexture, GpuCache, GpuResources, RenderDevice};
+use crate::device::{Resource, ShaderHandle, UniformValue};
+use crate::opengl::{BufferTarget, BufferUsage, PipelineState, Topology};
 
-use std::mem::size_of;
+use log::warn;
+use nalgebra_glm::Mat4;
 
-/// A struct responsible for rendering a mesh in OpenGL.
+/// Texture unit the scene's environment cube map is bound to while drawing meshes.
+pub const ENVIRONMENT_TEXTURE_UNIT: u32 = 15;
+
+/// Texture unit the morph target deltas of a mesh are bound to while drawing it.
+pub const MORPH_TEXTURE_UNIT: u32 = 13;
+
+/// A contiguous part of a mesh to draw instead of the whole of it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrawRange {
+    /// The first index to draw, or the first vertex for non-indexed meshes.
+    pub first: u32,
+    /// How many indices, or vertices for non-indexed meshes, to draw.
+    pub count: u32,
+    /// Added to every index before fetching the vertex. Ignored for non-indexed meshes.
+    pub base_vertex: i32,
+}
+
+/// The joint palette of a skinned mesh on one device, and which palette it holds.
+struct JointPalette {
+    buffer: BufferHandle,
+    version: u64,
+}
+
+impl GpuResources for JointPalette {
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::Buffer(self.buffer)]
+    }
+}
+
+/// Draws a shared `Mesh` with a shared `Material` at its own transform.
 ///
-/// It handles setting up vertex buffers, element buffers, and shader programs
-/// for rendering a 3D object using OpenGL.
+/// The mesh and material are held by handle, so any number of renderers can show one
+/// uploaded model. What differs between them lives here: the transform, pipeline
+/// state, skinning pose, morph weights and how much of the mesh is drawn.
 pub struct MeshRenderer {
-    vertex_array: VertexArrayObject, // Vertex array object for binding
-    shader_program: ShaderProgram,   // Shader program for rendering
-    element_buffer: BufferObject,    // Element buffer object (index buffer)
-    vertex_buffer: BufferObject,     // Vertex buffer object
-    vertices: Vec<f32>,              // Vertex data (positions, normals, etc.)
-    indicies: Vec<u32>,              // Index data for elements
+    mesh: Handle<Mesh>,                    // Vertex and index buffers, shared
+    material: Handle<Material>,            // Built-in material or user shader program, shared
+    transform: Mat4,                       // Model-to-world matrix
+    pipeline_state: PipelineState,         // Depth, blend, cull and stencil state of the draw
+    topology: Topology,                    // How vertices are assembled into primitives
+    draw_range: Option<DrawRange>,         // Part of the mesh to draw, or all of it
+    primitive_restart: bool,               // Whether the largest index restarts strips
+    joint_matrices: Option<Vec<Mat4>>,     // Skinning matrices, if the mesh is skinned
+    joint_version: u64,                    // Bumped whenever the joint matrices change
+    joint_palette: GpuCache<JointPalette>, // The joint matrices uploaded per device
+    morph_weights: Vec<f32>,               // Weight of each morph target
+    culling: bool,                         // Whether the mesh is skipped when off-screen
+    dither_fade: f32,                      // Fraction of pixels kept while crossfading
 }
 
 impl MeshRenderer {
-    /// Creates a new `MeshRenderer` with provided vertex and index data, and shader program.
+    /// Creates a new `MeshRenderer` drawing `mesh` with `material`.
+    ///
+    /// Transparent materials start with `PipelineState::transparent()`, everything else
+    /// with `PipelineState::opaque()`. The mesh is drawn as `Topology::Triangles`.
+    ///
+    /// A `ShaderProgram` material may declare any of `u_model`, `u_view`, `u_projection`,
+    /// `u_camera_position`, `samplerCube u_environment` and `u_dither_fade`; the ones it
+    /// declares are set on every `render`. Skinned and morphed meshes also set the
+    /// deformation uniforms and `JointPalette` block of the built-in vertex shader when
+    /// the program declares them the same way.
     ///
     /// # Arguments
     ///
-    /// * `vertices` - A vector of `f32` representing the vertex data (e.g., positions).
-    /// * `indicies` - A vector of `u32` representing the index data (element indices).
-    /// * `shaders` - The `ShaderProgram` to use for rendering the mesh.
+    /// * `mesh` - The vertex and index data to draw.
+    /// * `material` - The `ShaderProgram` or built-in material to use for rendering the mesh.
     ///
     /// # Returns
     ///
     /// A new instance of `MeshRenderer`.
-    pub fn new(vertices: Vec<f32>, indicies: Vec<u32>, shaders: ShaderProgram) -> MeshRenderer {
-        let vertex_array = VertexArrayObject::new();
-        let vertex_buffer = BufferObject::new(BufferTarget::ArrayBuffer, BufferUsage::StaticDraw);
-        let element_buffer = BufferObject::new(BufferTarget::ElementArrayBuffer, BufferUsage::StaticDraw);
+    pub fn new(mesh: Handle<Mesh>, material: Handle<Material>) -> MeshRenderer {
+        let transparent = material.try_get().is_some_and(|material| material.is_transparent());
+        let pipeline_state = if transparent { PipelineState::transparent() } else { PipelineState::opaque() };
 
-        vertex_array.bind();
+        MeshRenderer {
+            mesh,
+            material,
+            transform: Mat4::identity(),
+            pipeline_state,
+            topology: Topology::Triangles,
+            draw_range: None,
+            primitive_restart: false,
+            joint_matrices: None,
+            joint_version: 0,
+            joint_palette: GpuCache::new(),
+            morph_weights: Vec::new(),
+            culling: true,
+            dither_fade: 0.0,
+        }
+    }
 
-        vertex_buffer.bind();
-        vertex_buffer.data(&vertices);
+    /// Returns the mesh that is drawn.
+    pub fn get_mesh(&self) -> &Handle<Mesh> {
+        &self.mesh
+    }
 
-        element_buffer.bind();
-        element_buffer.data(&indicies);
+    /// Draws another mesh, keeping the material, transform and draw settings.
+    pub fn set_mesh(&mut self, mesh: Handle<Mesh>) {
+        self.mesh = mesh;
+    }
 
-        // Setup vertex attribute pointer
-        let vertex_attrib_pointer = VertexAttribPointer::new(0, 3, DataType::Float, false, 3 * size_of::<f32>() as i32);
-        vertex_attrib_pointer.enable();
+    /// Returns how vertices are assembled into primitives.
+    pub fn get_topology(&self) -> Topology {
+        self.topology
+    }
 
-        // Unbind buffers and vertex array to clean up state
-        vertex_buffer.unbind();
-        vertex_array.unbind();
-        element_buffer.unbind();
+    /// Sets how vertices are assembled into primitives, e.g. `Topology::Lines` for
+    /// wireframes or `Topology::Points` for point clouds.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+    }
 
-        MeshRenderer {
-            shader_program: shaders,
-            element_buffer,
-            vertex_buffer,
-            vertex_array,
-            vertices,
-            indicies,
-        }
+    /// Returns the part of the mesh that is drawn, or `None` if all of it is.
+    pub fn get_draw_range(&self) -> Option<DrawRange> {
+        self.draw_range
     }
 
-    /// Updates the vertex data of the mesh.
+    /// Restricts drawing to part of the mesh, or draws all of it again with `None`.
+    ///
+    /// Lets several sub-meshes share one vertex and index buffer.
+    pub fn set_draw_range(&mut self, draw_range: Option<DrawRange>) {
+        self.draw_range = draw_range;
+    }
+
+    /// Checks whether the largest index value restarts line and triangle strips.
+    pub fn is_primitive_restart(&self) -> bool {
+        self.primitive_restart
+    }
+
+    /// Sets whether the largest value of the index type (`255`, `65535` or `u32::MAX`)
+    /// ends the current strip or fan and starts a new one.
+    pub fn set_primitive_restart(&mut self, primitive_restart: bool) {
+        self.primitive_restart = primitive_restart;
+    }
+
+    /// Returns the model-to-world matrix of the mesh.
+    pub fn get_transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    /// Sets the model-to-world matrix of the mesh.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.transform = transform;
+    }
+
+    /// Returns the material the mesh is drawn with. Tweaking it with `Handle::set`
+    /// changes every mesh sharing it.
+    pub fn get_material(&self) -> &Handle<Material> {
+        &self.material
+    }
+
+    /// Draws the mesh with another material. The pipeline state is kept.
+    pub fn set_material(&mut self, material: Handle<Material>) {
+        self.material = material;
+    }
+
+    /// Returns the fixed-function state the mesh is drawn with.
+    pub fn get_pipeline_state(&self) -> &PipelineState {
+        &self.pipeline_state
+    }
+
+    /// Sets the fixed-function state the mesh is drawn with.
+    ///
+    /// The state is applied on every draw, so it does not leak into other meshes.
+    pub fn set_pipeline_state(&mut self, pipeline_state: PipelineState) {
+        self.pipeline_state = pipeline_state;
+    }
+
+    /// Sets the joint matrices the mesh is skinned with, making it skinned. They are
+    /// uploaded on the next draw.
+    ///
+    /// Each matrix moves a vertex from the bind pose to where its joint is now, in the
+    /// mesh's own space; `Skin::joint_matrices` computes them. The mesh needs the
+    /// `VertexLayout::JOINTS` and `VertexLayout::WEIGHTS` attributes.
     ///
     /// # Arguments
     ///
-    /// * `vertices` - A vector of `f32` representing the new vertex data.
+    /// * `joint_matrices` - One matrix per joint. Joints beyond `MAX_JOINTS` are ignored.
+    pub fn set_joint_matrices(&mut self, joint_matrices: &[Mat4]) {
+        if joint_matrices.len() > MAX_JOINTS {
+            warn!("skin has {} joints, only the first {MAX_JOINTS} are used", joint_matrices.len());
+        }
+
+        // The block is always filled completely, reading past the buffer is undefined.
+        let mut palette = vec![Mat4::identity(); MAX_JOINTS];
+        let count = joint_matrices.len().min(MAX_JOINTS);
+        palette[..count].copy_from_slice(&joint_matrices[..count]);
+        self.joint_matrices = Some(palette);
+        self.joint_version += 1;
+    }
+
+    /// Stops skinning the mesh, drawing it in its bind pose again.
+    pub fn clear_joint_matrices(&mut self) {
+        self.joint_matrices = None;
+        self.joint_palette.clear();
+    }
+
+    /// Checks whether the mesh is deformed by joint matrices.
+    pub fn is_skinned(&self) -> bool {
+        self.joint_matrices.is_some()
+    }
+
+    /// Returns the number of morph targets the mesh can blend between, `0` while it
+    /// is loading.
+    pub fn get_morph_target_count(&self) -> usize {
+        self.mesh.try_get().map_or(0, |mesh| mesh.get_morph_target_count())
+    }
+
+    /// Returns the weight of each morph target, as last set.
+    pub fn get_morph_weights(&self) -> &[f32] {
+        &self.morph_weights
+    }
+
+    /// Sets how strongly each morph target offsets the mesh, usually from `0` to `1`.
+    ///
+    /// # Arguments
     ///
-    /// This method binds the vertex buffer, uploads the new vertex data to OpenGL,
-    /// and unbinds the buffer after the update.
-    pub fn set_vertices(&mut self, vertices: Vec<f32>) {
-        self.vertex_buffer.bind();
-        self.vertex_buffer.data(&vertices);
-        self.vertex_buffer.unbind();
-        self.vertices = vertices;
+    /// * `weights` - One weight per target. Missing weights are zero, extra ones ignored.
+    pub fn set_morph_weights(&mut self, weights: &[f32]) {
+        self.morph_weights = weights[..weights.len().min(MAX_MORPH_TARGETS)].to_vec();
     }
 
-    /// Updates the index data of the mesh.
+    /// Returns the screen-door fade of the mesh, see `set_dither_fade`.
+    pub fn get_dither_fade(&self) -> f32 {
+        self.dither_fade
+    }
+
+    /// Sets how much of the mesh is drawn with a screen-door dither, as `Lod` does while
+    /// crossfading between levels.
     ///
     /// # Arguments
     ///
-    /// * `indicies` - A vector of `u32` representing the new index data.
+    /// * `fade` - `0` draws every pixel. A positive fade draws that fraction of the
+    ///   pixels, a negative fade exactly the pixels a positive fade of the same size skips.
+    pub fn set_dither_fade(&mut self, fade: f32) {
+        self.dither_fade = fade.clamp(-1.0, 1.0);
+    }
+
+    /// Returns the model-space box around the mesh's positions, including every morph
+    /// target fully applied, or `None` if the mesh has no positions or is loading.
+    pub fn get_local_bounds(&self) -> Option<Aabb> {
+        self.mesh.try_get().and_then(|mesh| mesh.get_bounds().copied())
+    }
+
+    /// Returns the world-space box around the mesh, see `get_local_bounds`.
+    pub fn get_world_bounds(&self) -> Option<Aabb> {
+        self.get_local_bounds().map(|bounds| bounds.transformed(&self.transform))
+    }
+
+    /// Returns the world-space sphere around the mesh, or `None` if the mesh has no
+    /// positions or is loading.
+    pub fn get_bounding_sphere(&self) -> Option<BoundingSphere> {
+        let mesh = self.mesh.try_get()?;
+        mesh.get_bounding_sphere().map(|sphere| sphere.transformed(&self.transform))
+    }
+
+    /// Checks whether the mesh is skipped when it is outside the camera's view.
+    pub fn is_culling_enabled(&self) -> bool {
+        self.culling
+    }
+
+    /// Sets whether the mesh is skipped when it is outside the camera's view. Disable it
+    /// for meshes whose vertex shader moves vertices beyond their bounds.
+    pub fn set_culling_enabled(&mut self, culling: bool) {
+        self.culling = culling;
+    }
+
+    /// Checks whether any part of the mesh may be inside `frustum`.
     ///
-    /// This method binds the element buffer, uploads the new index data to OpenGL,
-    /// and unbinds the buffer after the update.
-    pub fn set_indices(&mut self, indicies: Vec<u32>) {
-        self.element_buffer.bind();
-        self.element_buffer.data(&indicies);
-        self.element_buffer.unbind();
-        self.indicies = indicies;
+    /// Meshes without bounds or with culling disabled are always visible, as are skinned
+    /// meshes, whose joints can move them anywhere, and meshes whose shader program
+    /// doesn't place them with `u_view` and `u_projection`.
+    pub fn is_visible(&self, frustum: &Frustum) -> bool {
+        let placed_by_camera = match self.material.try_get().as_deref() {
+            Some(Material::Custom(program)) => program.has_uniform("u_view") && program.has_uniform("u_projection"),
+            _ => true,
+        };
+        if !self.culling || self.is_skinned() || !placed_by_camera {
+            return true;
+        }
+        let (Some(sphere), Some(bounds)) = (self.get_bounding_sphere(), self.get_world_bounds()) else {
+            return true;
+        };
+        frustum.intersects_sphere(&sphere) && frustum.intersects_aabb(&bounds)
     }
 
-    /// Renders the mesh using the current shader program.
+    /// Renders the mesh using its material and pipeline state.
+    ///
+    /// The mesh is uploaded to `device` the first time it is drawn with it, then drawn
+    /// with its index data, or its vertices in order if it has none, in the mesh's
+    /// topology. Nothing is drawn while the mesh or material is still loading.
+    ///
+    /// # Arguments
     ///
-    /// This method binds the shader program and vertex array, and uses OpenGL's
-    /// `DrawElements` to render the mesh based on the index data.
+    /// * `device` - The device the draw is issued to.
+    /// * `context` - The camera, lights and environment of the frame.
+    pub fn render(&self, device: &mut dyn RenderDevice, context: &RenderContext) {
+        let (Some(mesh), Some(material)) = (self.mesh.try_get(), self.material.try_get()) else {
+            return;
+        };
+        let draw = match &*material {
+            Material::Custom(shader_program) => {
+                let shader = device.import_shader(shader_program);
+                let draw = self.draw_call(device, &mesh, shader);
+                self.with_engine_uniforms(device, context, draw)
+            }
+            Material::Pbr(material) => {
+                let shader = forward_shader(device);
+                let draw = self.draw_call(device, &mesh, shader);
+                material.with_forward(device, context, draw)
+            }
+        };
+
+        device.set_pipeline_state(&self.pipeline_state);
+        device.draw(&draw);
+    }
+
+    /// Builds the draw of `mesh`, which must be this renderer's, with `shader`.
     ///
-    /// OpenGL's context is used to draw the elements in the `TRIANGLES` mode.
-    pub fn render(&self) {
-        self.shader_program.bind();
-        self.vertex_array.bind();
+    /// The mesh and joint palette are uploaded to `device` as needed, and the model
+    /// matrix, skinning, morph target and dither uniforms are set. Camera and material
+    /// uniforms are left to the caller.
+    pub(crate) fn draw_call(&self, device: &mut dyn RenderDevice, mesh: &Mesh, shader: ShaderHandle) -> DrawCall {
+        let buffers = mesh.upload(device);
+        let mut draw = mesh
+            .draw_call(&buffers, shader, self.topology, self.draw_range, self.primitive_restart)
+            .with_uniform("u_model", UniformValue::Mat4(self.transform))
+            .with_uniform("u_dither_fade", UniformValue::Float(self.dither_fade))
+            .with_uniform("u_skinned", UniformValue::Int(self.joint_matrices.is_some() as i32));
+
+        if let Some(joint_matrices) = &self.joint_matrices {
+            let data = bytes_of(joint_matrices);
+            let mut palette = self.joint_palette.get_or_create(device, |device| JointPalette {
+                buffer: device.create_buffer(BufferTarget::UniformBuffer, BufferUsage::DynamicDraw, data),
+                version: self.joint_version,
+            });
+            if palette.version != self.joint_version {
+                device.update_buffer(palette.buffer, 0, data);
+                palette.version = self.joint_version;
+            }
+            draw = draw.with_uniform_block(JOINT_PALETTE_BLOCK, palette.buffer);
+        }
+
+        // Set even without deltas: a sampler left on unit 0 would clash with the
+        // material's 2D maps there and fail every draw.
+        draw = draw.with_uniform("u_morph_deltas", UniformValue::Int(MORPH_TEXTURE_UNIT as i32));
+        let target_count = if buffers.morph_deltas.is_some() { mesh.get_morph_target_count() } else { 0 };
+        draw = draw.with_uniform("u_morph_target_count", UniformValue::Int(target_count as i32));
+        if let Some(deltas) = buffers.morph_deltas {
+            let weights = (0..target_count).map(|target| self.morph_weights.get(target).copied().unwrap_or(0.0));
+            draw = draw
+                .with_texture(MORPH_TEXTURE_UNIT, deltas)
+                .with_uniform("u_morph_vertex_count", UniformValue::Int(mesh.vertex_count() as i32))
+                .with_uniform("u_morph_weights", UniformValue::FloatArray(weights.collect()));
+        }
+        draw
+    }
+
+    /// Checks whether the mesh is alpha blended and must be drawn after opaque geometry.
+    pub fn is_transparent(&self) -> bool {
+        self.material.try_get().is_some_and(|material| material.is_transparent())
+    }
 
-        // Ensure OpenGL context is set up correctly
-        unsafe {
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indicies.len() as i32,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
+    /// Adds the camera and environment uniforms a user shader program may declare.
+    fn with_engine_uniforms(&self, device: &mut dyn RenderDevice, context: &RenderContext, draw: DrawCall) -> DrawCall {
+        let camera = context.camera;
+        let draw = draw
+            .with_uniform("u_view", UniformValue::Mat4(camera.view_matrix()))
+            .with_uniform("u_projection", UniformValue::Mat4(camera.projection_matrix()))
+            .with_uniform("u_camera_position", UniformValue::Vec3(*camera.get_position()));
+        match context.reflection.and_then(Handle::try_get) {
+            Some(reflection) => draw
+                .with_texture(ENVIRONMENT_TEXTURE_UNIT, device.import_texture(ExternalTexture::Cubemap(&reflection)))
+                .with_uniform("u_environment", UniformValue::Int(ENVIRONMENT_TEXTURE_UNIT as i32)),
+            None => draw,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::assets::Assets;
+    use crate::core::components::Indices;
+    use crate::core::material::PbrMaterial;
+    use crate::core::mesh::MorphTarget;
+    use crate::core::Camera;
+    use crate::device::{DeviceCommand, RecordingDevice};
+    use crate::opengl::{IndexType, TextureFormat};
+
+    use nalgebra_glm::Vec3;
+
+    /// Returns a renderer of one triangle with the built-in lit material.
+    fn triangle(meshes: &mut Assets<Mesh>, materials: &mut Assets<Material>) -> MeshRenderer {
+        let mesh = Mesh::new(vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0], vec![0u16, 1, 2]);
+        MeshRenderer::new(meshes.add(mesh), materials.add(PbrMaterial::new().into()))
+    }
+
+    /// Renders once and returns the recorded calls.
+    fn render(renderer: &MeshRenderer, device: &mut RecordingDevice) -> Vec<DeviceCommand> {
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 3.0), Vec3::zeros());
+        let context = RenderContext {
+            camera: &camera,
+            lights: Vec::new(),
+            environment: None,
+            reflection: None,
+        };
+        renderer.render(device, &context);
+        device.take_commands()
+    }
+
+    fn created_buffers(commands: &[DeviceCommand]) -> Vec<(BufferTarget, usize)> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                DeviceCommand::CreateBuffer { target, size, .. } => Some((*target, *size)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn only_draw(commands: &[DeviceCommand]) -> &DrawCall {
+        let mut draws = commands.iter().filter_map(|command| match command {
+            DeviceCommand::Draw(draw) => Some(draw),
+            _ => None,
+        });
+        let draw = draws.next().expect("nothing was drawn");
+        assert!(draws.next().is_none(), "more than one draw");
+        draw
+    }
+
+    #[test]
+    fn mesh_is_uploaded_once_and_drawn_indexed() {
+        let (mut meshes, mut materials) = (Assets::new(), Assets::new());
+        let mut renderer = triangle(&mut meshes, &mut materials);
+        renderer.set_transform(Mat4::new_translation(&Vec3::new(1.0, 2.0, 3.0)));
+        let mut device = RecordingDevice::new();
+
+        let first = render(&renderer, &mut device);
+        assert!(first.iter().any(|command| matches!(command, DeviceCommand::CreateShader { .. })));
+        assert_eq!(
+            created_buffers(&first),
+            [(BufferTarget::ArrayBuffer, 9 * 4), (BufferTarget::ElementArrayBuffer, 3 * 2)]
+        );
+        assert!(matches!(first[first.len() - 2], DeviceCommand::SetPipelineState(_)));
+        let draw = only_draw(&first);
+        assert_eq!(draw.index_buffer.map(|(_, index_type)| index_type), Some(IndexType::UnsignedShort));
+        assert_eq!((draw.first, draw.count), (0, 3));
+        assert_eq!(draw.get_uniform("u_model"), Some(&UniformValue::Mat4(*renderer.get_transform())));
+        assert_eq!(draw.get_uniform("u_morph_deltas"), Some(&UniformValue::Int(MORPH_TEXTURE_UNIT as i32)));
+        assert_eq!(draw.get_uniform("u_morph_target_count"), Some(&UniformValue::Int(0)));
+
+        // A second renderer of the same mesh draws from the same buffers.
+        let other = MeshRenderer::new(renderer.get_mesh().clone(), renderer.get_material().clone());
+        let second = render(&other, &mut device);
+        assert!(created_buffers(&second).is_empty());
+        assert!(!second.iter().any(|command| matches!(command, DeviceCommand::CreateShader { .. })));
+        assert_eq!(only_draw(&second).vertex_buffer, draw.vertex_buffer);
+    }
+
+    #[test]
+    fn changed_and_dropped_meshes_are_released() {
+        let (mut meshes, mut materials) = (Assets::new(), Assets::new());
+        let renderer = triangle(&mut meshes, &mut materials);
+        let mut device = RecordingDevice::new();
+        let draw = only_draw(&render(&renderer, &mut device)).clone();
+        let (index_buffer, _) = draw.index_buffer.unwrap();
+
+        // Replacing the mesh behind the handle uploads the new one on the next draw.
+        renderer.get_mesh().set(Mesh::new(vec![0.0; 9], Indices::None));
+        let commands = render(&renderer, &mut device);
+        assert_eq!(created_buffers(&commands), [(BufferTarget::ArrayBuffer, 9 * 4)]);
+        assert_eq!(only_draw(&commands).index_buffer, None);
+        device.release_dropped();
+        let released = device.take_commands();
+        assert!(released.contains(&DeviceCommand::DestroyBuffer(draw.vertex_buffer)));
+        assert!(released.contains(&DeviceCommand::DestroyBuffer(index_buffer)));
+
+        drop(renderer);
+        device.release_dropped();
+        let released = device.take_commands();
+        assert_eq!(released.len(), 1);
+        assert!(matches!(released[0], DeviceCommand::DestroyBuffer(_)));
+    }
+
+    #[test]
+    fn joint_palette_is_updated_only_for_new_poses() {
+        let (mut meshes, mut materials) = (Assets::new(), Assets::new());
+        let mut renderer = triangle(&mut meshes, &mut materials);
+        let mut device = RecordingDevice::new();
+        render(&renderer, &mut device);
+
+        renderer.set_joint_matrices(&[Mat4::identity(); 2]);
+        let commands = render(&renderer, &mut device);
+        assert_eq!(created_buffers(&commands), [(BufferTarget::UniformBuffer, MAX_JOINTS * 64)]);
+        let draw = only_draw(&commands);
+        assert_eq!(draw.get_uniform("u_skinned"), Some(&UniformValue::Int(1)));
+        assert_eq!(draw.uniform_blocks.len(), 1);
+        assert_eq!(draw.uniform_blocks[0].0, JOINT_PALETTE_BLOCK);
+
+        let commands = render(&renderer, &mut device);
+        assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::UpdateBuffer { .. })));
+
+        renderer.set_joint_matrices(&[Mat4::new_scaling(2.0)]);
+        let commands = render(&renderer, &mut device);
+        let palette = draw.uniform_blocks[0].1;
+        assert!(commands.contains(&DeviceCommand::UpdateBuffer {
+            buffer: palette,
+            offset: 0,
+            size: MAX_JOINTS * 64,
+        }));
+        assert_eq!(device.get_buffer_data(palette).map(|data| data[..4].to_vec()), Some(2f32.to_ne_bytes().to_vec()));
+    }
+
+    #[test]
+    fn morph_deltas_are_bound_with_weights() {
+        let (mut meshes, mut materials) = (Assets::new(), Assets::new());
+        let mut renderer = triangle(&mut meshes, &mut materials);
+        let mut mesh = Mesh::new(vec![0.0; 9], vec![0u16, 1, 2]);
+        let mut target = MorphTarget::new("smile");
+        target.positions = vec![Vec3::y(); 3];
+        mesh.set_morph_targets(&[target.clone(), target]);
+        renderer.get_mesh().set(mesh);
+        renderer.set_morph_weights(&[0.5]);
+        let mut device = RecordingDevice::new();
+
+        let commands = render(&renderer, &mut device);
+        let deltas = commands.iter().find_map(|command| match command {
+            DeviceCommand::CreateBufferTexture { texture, format, size } => Some((*texture, *format, *size)),
+            _ => None,
+        });
+        let (texture, format, size) = deltas.expect("no deltas were uploaded");
+        assert_eq!((format, size), (TextureFormat::Rgba32F, 2 * 3 * 2 * 16));
+        let draw = only_draw(&commands);
+        assert_eq!(draw.get_texture(MORPH_TEXTURE_UNIT), Some(texture));
+        assert_eq!(draw.get_uniform("u_morph_target_count"), Some(&UniformValue::Int(2)));
+        assert_eq!(draw.get_uniform("u_morph_vertex_count"), Some(&UniformValue::Int(3)));
+        assert_eq!(draw.get_uniform("u_morph_weights"), Some(&UniformValue::FloatArray(vec![0.5, 0.0])));
+    }
+}
@@ -1,3 +1,13 @@
+mod indices;
+mod lod;
 mod mesh_renderer;
+mod morph_weights;
+mod skybox;
+mod transform;
 
-pub use mesh_renderer::MeshRenderer;
+pub use indices::Indices;
+pub use lod::{Lod, LodLevel, LodMetric};
+pub use mesh_renderer::{DrawRange, MeshRenderer, ENVIRONMENT_TEXTURE_UNIT, MORPH_TEXTURE_UNIT};
+pub use morph_weights::MorphWeights;
+pub use skybox::Skybox;
+pub use transform::{GlobalTransform, Transform};
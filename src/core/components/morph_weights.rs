@@ -0,0 +1,9 @@
+/// The weight of each morph target of an entity's meshes, see `MeshRenderer::set_morph_weights`.
+///
+/// Clips animate it through weight channels, and gameplay code can set it directly.
+/// `Scene::update_world` copies it to the `MeshRenderer` of the entity and of its
+/// children, so every primitive of an imported mesh follows its node.
+///
+/// [`Scene::update_world`]: crate::core::Scene::update_world
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphWeights(pub Vec<f32>);
@@ -0,0 +1,104 @@
+use crate::core::assets::Handle;
+use crate::core::material::OUTPUT_GLSL;
+use crate::core::primitives::unit_cube;
+use crate::core::Camera;
+use crate::device::{ExternalTexture, GpuCache, RenderDevice, ShaderHandle, UniformValue};
+use crate::opengl::{CompareFunction, Cubemap, PipelineState};
+
+const SKYBOX_VERTEX_SHADER: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_position;
+
+uniform mat4 u_view;
+uniform mat4 u_projection;
+
+out vec3 v_direction;
+
+void main() {
+    v_direction = a_position;
+    // Drop the translation so the sky stays centred on the camera, and force z = w so
+    // every fragment lands exactly on the far plane.
+    vec4 position = u_projection * mat4(mat3(u_view)) * vec4(a_position, 1.0);
+    gl_Position = position.xyww;
+}
+"#;
+
+const SKYBOX_FRAGMENT_SHADER: &str = r#"
+in vec3 v_direction;
+
+uniform samplerCube u_skybox;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(encode_output(textureLod(u_skybox, v_direction, 0.0).rgb), 1.0);
+}
+"#;
+
+thread_local! {
+    static SKYBOX_SHADER: GpuCache<ShaderHandle> = const { GpuCache::new() };
+}
+
+/// Draws a cube map as the scene background.
+///
+/// The skybox is rendered after opaque geometry and only fills the pixels that
+/// nothing else has been drawn to. Its cube map is an asset handle, so the same texture
+/// can be bound for reflections on meshes, and nothing is drawn while it is loading.
+pub struct Skybox {
+    cubemap: Handle<Cubemap>, // Environment drawn in the background
+}
+
+impl Skybox {
+    /// Creates a new `Skybox` drawing the given cube map.
+    ///
+    /// # Arguments
+    ///
+    /// * `cubemap` - The environment cube map, shared with any material reflecting it.
+    pub fn new(cubemap: Handle<Cubemap>) -> Skybox {
+        Skybox { cubemap }
+    }
+
+    /// Returns the cube map drawn by this skybox.
+    pub fn get_cubemap(&self) -> &Handle<Cubemap> {
+        &self.cubemap
+    }
+
+    /// Replaces the cube map drawn by this skybox.
+    pub fn set_cubemap(&mut self, cubemap: Handle<Cubemap>) {
+        self.cubemap = cubemap;
+    }
+
+    /// Renders the skybox at the far plane as seen from `camera`.
+    ///
+    /// The draw uses a `LessEqual` depth test without depth writes, so the sky never
+    /// covers geometry drawn earlier. Nothing is drawn while the cube map is loading.
+    pub fn render(&self, device: &mut dyn RenderDevice, camera: &Camera) {
+        let Some(cubemap) = self.cubemap.try_get() else {
+            return;
+        };
+        let shader = skybox_shader(device);
+        let cubemap = device.import_texture(ExternalTexture::Cubemap(&cubemap));
+        let draw = unit_cube(device, shader)
+            .with_uniform("u_view", UniformValue::Mat4(camera.view_matrix()))
+            .with_uniform("u_projection", UniformValue::Mat4(camera.projection_matrix()))
+            .with_uniform("u_skybox", UniformValue::Int(0))
+            .with_texture(0, cubemap);
+
+        device.set_pipeline_state(&PipelineState {
+            depth_write: false,
+            depth_compare: CompareFunction::LessEqual,
+            ..PipelineState::opaque()
+        });
+        device.draw(&draw);
+    }
+}
+
+/// Returns the program drawing skyboxes on `device`, compiling it the first time.
+fn skybox_shader(device: &mut dyn RenderDevice) -> ShaderHandle {
+    SKYBOX_SHADER.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let fragment_src = format!("#version 330 core\n{OUTPUT_GLSL}{SKYBOX_FRAGMENT_SHADER}");
+            device.create_shader(SKYBOX_VERTEX_SHADER, &fragment_src)
+        })
+    })
+}
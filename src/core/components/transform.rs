@@ -0,0 +1,67 @@
+use nalgebra_glm::{Mat4, Quat, Vec3};
+
+/// The placement of an entity relative to its parent, or to the world for roots.
+///
+/// Applied as scale, then rotation, then translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    /// Creates an identity transform.
+    pub fn new() -> Transform {
+        Transform {
+            translation: Vec3::zeros(),
+            rotation: Quat::identity(),
+            scale: Vec3::repeat(1.0),
+        }
+    }
+
+    /// Creates a transform that only translates.
+    pub fn from_translation(translation: Vec3) -> Transform {
+        Transform { translation, ..Transform::new() }
+    }
+
+    /// Returns a copy with a new rotation.
+    pub fn with_rotation(self, rotation: Quat) -> Transform {
+        Transform { rotation, ..self }
+    }
+
+    /// Returns a copy with a new scale.
+    pub fn with_scale(self, scale: Vec3) -> Transform {
+        Transform { scale, ..self }
+    }
+
+    /// Returns the local-to-parent matrix.
+    pub fn matrix(&self) -> Mat4 {
+        nalgebra_glm::translation(&self.translation)
+            * nalgebra_glm::quat_to_mat4(&self.rotation)
+            * nalgebra_glm::scaling(&self.scale)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::new()
+    }
+}
+
+/// The local-to-world matrix of an entity, written by `World::update_transforms`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlobalTransform(pub Mat4);
+
+impl GlobalTransform {
+    /// Returns the world-space position of the entity's origin.
+    pub fn get_translation(&self) -> Vec3 {
+        self.0.column(3).xyz()
+    }
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        GlobalTransform(Mat4::identity())
+    }
+}
@@ -0,0 +1,206 @@
+/// Decodes a BC1 block: two RGB565 colors and 2-bit indices, with an optional fully
+/// transparent texel.
+pub(super) fn bc1(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(block, texels, false);
+}
+
+/// Decodes a BC2 block: explicit 4-bit alphas followed by a BC1 color block.
+pub(super) fn bc2(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(&block[8..], texels, true);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        texel[3] = ((block[i / 2] >> (4 * (i % 2))) & 15) * 17;
+    }
+}
+
+/// Decodes a BC3 block: an interpolated alpha block followed by a BC1 color block.
+pub(super) fn bc3(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(&block[8..], texels, true);
+    for (texel, alpha) in texels.iter_mut().zip(unorm_block(block)) {
+        texel[3] = alpha;
+    }
+}
+
+/// Decodes a BC4 block into the red channel.
+pub(super) fn bc4(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    for (texel, red) in texels.iter_mut().zip(unorm_block(block)) {
+        *texel = [red, 0, 0, 255];
+    }
+}
+
+/// Decodes a BC5 block into the red and green channels.
+pub(super) fn bc5(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let (red, green) = (unorm_block(block), unorm_block(&block[8..]));
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = [red[i], green[i], 0, 255];
+    }
+}
+
+/// Decodes a signed BC4 block into the red channel, from -1 to 1.
+pub(super) fn bc4_snorm(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    for (texel, red) in texels.iter_mut().zip(snorm_block(block)) {
+        *texel = [red, 0.0, 0.0, 1.0];
+    }
+}
+
+/// Decodes a signed BC5 block into the red and green channels, from -1 to 1.
+pub(super) fn bc5_snorm(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    let (red, green) = (snorm_block(block), snorm_block(&block[8..]));
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = [red[i], green[i], 0.0, 1.0];
+    }
+}
+
+/// Decodes the 8 bytes of a BC1 color block. The colors of BC2 and BC3 blocks always
+/// use four opaque colors, whatever the order of the endpoints.
+fn color_block(block: &[u8], texels: &mut [[u8; 4]; 16], four_colors: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (a, b) = (rgb565(c0), rgb565(c1));
+    let mix = |wa: u32, wb: u32| {
+        let channel = |i: usize| ((wa * a[i] as u32 + wb * b[i] as u32) / (wa + wb)) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+
+    let palette = if c0 > c1 || four_colors {
+        [a, b, mix(2, 1), mix(1, 2)]
+    } else {
+        [a, b, mix(1, 1), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (2 * i) & 3) as usize];
+    }
+}
+
+/// Expands a 5:6:5 color to 8 bits per channel.
+fn rgb565(color: u16) -> [u8; 4] {
+    let (r, g, b) = ((color >> 11) as u8, (color >> 5 & 63) as u8, (color & 31) as u8);
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2, 255]
+}
+
+/// Decodes the 8 bytes of an interpolated single-channel block.
+fn unorm_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 255];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as u32) * a0 + i as u32 * a1 + 3) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as u32) * a0 + i as u32 * a1 + 2) / 5;
+        }
+    }
+    let indices = block_indices(block);
+    std::array::from_fn(|i| palette[indices[i] as usize] as u8)
+}
+
+/// Decodes the 8 bytes of an interpolated signed single-channel block.
+fn snorm_block(block: &[u8]) -> [f32; 16] {
+    // -128 and -127 both map to -1.
+    let (a0, a1) = ((block[0] as i8).max(-127) as f32 / 127.0, (block[1] as i8).max(-127) as f32 / 127.0);
+    let mut palette = [a0, a1, 0.0, 0.0, 0.0, 0.0, -1.0, 1.0];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i) as f32 * a0 + i as f32 * a1) / 7.0;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i) as f32 * a0 + i as f32 * a1) / 5.0;
+        }
+    }
+    let indices = block_indices(block);
+    std::array::from_fn(|i| palette[indices[i] as usize])
+}
+
+/// Reads the 3-bit indices stored in bytes 2 to 7 of a single-channel block.
+fn block_indices(block: &[u8]) -> [u8; 16] {
+    let bits = block[2..8].iter().rev().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+    std::array::from_fn(|i| (bits >> (3 * i) & 7) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Pure red and pure blue endpoints in RGB565, with texels cycling through indices 0 to 3.
+    const RED: [u8; 2] = [0x00, 0xF8];
+    const BLUE: [u8; 2] = [0x1F, 0x00];
+    const CYCLING_INDICES: [u8; 4] = [0xE4; 4];
+
+    fn color_block(c0: [u8; 2], c1: [u8; 2]) -> [u8; 8] {
+        let mut block = [0; 8];
+        block[..2].copy_from_slice(&c0);
+        block[2..4].copy_from_slice(&c1);
+        block[4..].copy_from_slice(&CYCLING_INDICES);
+        block
+    }
+
+    #[test]
+    fn bc1_interpolates_four_colors() {
+        let mut texels = [[0; 4]; 16];
+        bc1(&color_block(RED, BLUE), &mut texels);
+        let palette = [[255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 85, 255], [85, 0, 170, 255]];
+        for (i, texel) in texels.iter().enumerate() {
+            assert_eq!(*texel, palette[i % 4], "texel {i}");
+        }
+    }
+
+    #[test]
+    fn bc1_has_a_transparent_texel_when_the_endpoints_are_swapped() {
+        let mut texels = [[0; 4]; 16];
+        bc1(&color_block(BLUE, RED), &mut texels);
+        assert_eq!(texels[..4], [[0, 0, 255, 255], [255, 0, 0, 255], [127, 0, 127, 255], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn bc2_uses_four_colors_and_explicit_alpha() {
+        let mut block = [0xF0; 16];
+        block[8..].copy_from_slice(&color_block(BLUE, RED));
+        let mut texels = [[0; 4]; 16];
+        bc2(&block, &mut texels);
+        assert_eq!(texels[..4], [[0, 0, 255, 0], [255, 0, 0, 255], [85, 0, 170, 0], [170, 0, 85, 255]]);
+    }
+
+    #[test]
+    fn bc3_interpolates_eight_alphas() {
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&[255, 0, 0x02, 0, 0, 0, 0, 0]);
+        block[8..].copy_from_slice(&color_block(RED, BLUE));
+        let mut texels = [[0; 4]; 16];
+        bc3(&block, &mut texels);
+        assert_eq!(texels[0], [255, 0, 0, 219]);
+        assert_eq!(texels[1], [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn bc4_and_bc5_interpolate_six_values_between_the_extremes() {
+        // Texels 0, 1 and 2 use indices 2, 6 and 7, the others index 0.
+        let block = [0, 255, 0xF2, 0x01, 0, 0, 0, 0];
+        let mut texels = [[0; 4]; 16];
+        bc4(&block, &mut texels);
+        assert_eq!(texels[..4], [[51, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255], [0, 0, 0, 255]]);
+
+        let mut pair = [0; 16];
+        pair[8..].copy_from_slice(&block);
+        bc5(&pair, &mut texels);
+        assert_eq!(texels[..3], [[0, 51, 0, 255], [0, 0, 0, 255], [0, 255, 0, 255]]);
+    }
+
+    #[test]
+    fn signed_blocks_clamp_the_lowest_value_to_minus_one() {
+        let mut block = [0; 16];
+        // Red from 1 to -1 with eight values, green from -128 to 1 with six.
+        block[..8].copy_from_slice(&[0x7F, 0x81, 0x02, 0, 0, 0, 0, 0]);
+        block[8..].copy_from_slice(&[0x80, 0x7F, 0xF2, 0x01, 0, 0, 0, 0]);
+        let mut texels = [[0.0; 4]; 16];
+        bc4_snorm(&block, &mut texels);
+        assert_eq!(texels[..2], [[5.0 / 7.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]]);
+
+        bc5_snorm(&block, &mut texels);
+        assert_eq!(texels[0], [5.0 / 7.0, -3.0 / 5.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [1.0, -1.0, 0.0, 1.0]);
+        assert_eq!(texels[2], [1.0, 1.0, 0.0, 1.0]);
+        assert_eq!(texels[3], [1.0, -1.0, 0.0, 1.0]);
+    }
+}
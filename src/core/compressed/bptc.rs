@@ -0,0 +1,544 @@
+/// Subset of each texel in the two-subset partitions, one bit per texel.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00,
+    0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE, 0x088C, 0x3110, 0x6666, 0x366C,
+    0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8,
+    0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660, 0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subset of each texel in the three-subset partitions, two bits per texel.
+const PARTITIONS_3: [u32; 64] = [
+    0xAA685050, 0x6A5A5040, 0x5A5A4200, 0x5450A0A8, 0xA5A50000, 0xA0A05050, 0x5555A0A0, 0x5A5A5050,
+    0xAA550000, 0xAA555500, 0xAAAA5500, 0x90909090, 0x94949494, 0xA4A4A4A4, 0xA9A59450, 0x2A0A4250,
+    0xA5945040, 0x0A425054, 0xA5A5A500, 0x55A0A0A0, 0xA8A85454, 0x6A6A4040, 0xA4A45000, 0x1A1A0500,
+    0x0050A4A4, 0xAAA59090, 0x14696914, 0x69691400, 0xA08585A0, 0xAA821414, 0x50A4A450, 0x6A5A0200,
+    0xA9A58000, 0x5090A0A8, 0xA8A09050, 0x24242424, 0x00AA5500, 0x24924924, 0x24499224, 0x50A50A50,
+    0x500AA550, 0xAAAA4444, 0x66660000, 0xA5A0A5A0, 0x50A050A0, 0x69286928, 0x44AAAA44, 0x66666600,
+    0xAA444444, 0x54A854A8, 0x95809580, 0x96969600, 0xA85454A8, 0x80959580, 0xAA141414, 0x96960000,
+    0xAAAA1414, 0xA05050A0, 0xA0A5A5A0, 0x96000000, 0x40804080, 0xA9A8A9A8, 0xAAAAAA44, 0x2A4A5254,
+];
+
+/// Anchor texel of the second subset of the two-subset partitions.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texel of the second subset of the three-subset partitions.
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+
+/// Anchor texel of the third subset of the three-subset partitions.
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+/// Interpolation weights out of 64 for 2, 3 and 4-bit indices.
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// How the fields of a BC7 block are laid out in each mode.
+struct Bc7Mode {
+    subsets: usize,            // Subsets of the partition
+    partition_bits: u32,       // Bits of the partition index
+    rotation_bits: u32,        // Bits of the channel swapped with alpha
+    index_selection_bits: u32, // Bits choosing which index set drives alpha
+    color_bits: u32,           // Bits per color channel of each endpoint
+    alpha_bits: u32,           // Bits of alpha of each endpoint, 0 if opaque
+    endpoint_pbits: bool,      // One extra low bit per endpoint
+    shared_pbits: bool,        // One extra low bit per subset
+    index_bits: u32,           // Bits per texel of the first index set
+    index_bits_2: u32,         // Bits per texel of the second index set, 0 if there is none
+}
+
+#[rustfmt::skip]
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 3, index_bits_2: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0,
+        endpoint_pbits: false, shared_pbits: true, index_bits: 3, index_bits_2: 0 },
+    Bc7Mode { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 3 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8,
+        endpoint_pbits: false, shared_pbits: false, index_bits: 2, index_bits_2: 2 },
+    Bc7Mode { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 4, index_bits_2: 0 },
+    Bc7Mode { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5,
+        endpoint_pbits: true, shared_pbits: false, index_bits: 2, index_bits_2: 0 },
+];
+
+/// Decodes a BC7 block. Blocks of the reserved mode decode to transparent black.
+pub(super) fn bc7(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let mode_index = block[0].trailing_zeros() as usize;
+    let Some(mode) = BC7_MODES.get(mode_index) else {
+        *texels = [[0; 4]; 16];
+        return;
+    };
+    let mut bits = BitReader::new(block);
+    bits.read(mode_index as u32 + 1);
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    // Channels are stored one after the other, each with every endpoint.
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..4 {
+        let channel_bits = if channel < 3 { mode.color_bits } else { mode.alpha_bits };
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(channel_bits);
+        }
+    }
+    let mut pbits = [0; 6];
+    if mode.endpoint_pbits {
+        for pbit in &mut pbits[..endpoint_count] {
+            *pbit = bits.read(1);
+        }
+    } else if mode.shared_pbits {
+        for subset in 0..mode.subsets {
+            let pbit = bits.read(1);
+            (pbits[2 * subset], pbits[2 * subset + 1]) = (pbit, pbit);
+        }
+    }
+    let has_pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for (endpoint, pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let mut precision = if channel < 3 { mode.color_bits } else { mode.alpha_bits };
+            if precision == 0 {
+                *value = 255;
+                continue;
+            }
+            if has_pbits {
+                (*value, precision) = (*value << 1 | pbit, precision + 1);
+            }
+            // Repeat the high bits in the low ones, so the largest value maps to 255.
+            *value = *value << (8 - precision) | *value >> (2 * precision - 8);
+        }
+    }
+
+    let subset_of = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => (PARTITIONS_3[partition] >> (2 * texel) & 3) as usize,
+    };
+    let is_anchor = |texel: usize| match mode.subsets {
+        1 => texel == 0,
+        2 => texel == 0 || texel == ANCHORS_2[partition] as usize,
+        _ => {
+            texel == 0 || texel == ANCHORS_3_SECOND[partition] as usize || texel == ANCHORS_3_THIRD[partition] as usize
+        }
+    };
+    // Anchor texels drop the high bit of their index, which is always 0.
+    let indices: [u32; 16] = std::array::from_fn(|texel| bits.read(mode.index_bits - is_anchor(texel) as u32));
+    let indices_2: [u32; 16] = std::array::from_fn(|texel| match mode.index_bits_2 {
+        0 => 0,
+        index_bits => bits.read(index_bits - (texel == 0) as u32),
+    });
+
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let subset = subset_of(texel);
+        let (a, b) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+        let (color, alpha) = if mode.index_bits_2 == 0 {
+            ((indices[texel], mode.index_bits), (indices[texel], mode.index_bits))
+        } else if index_selection == 0 {
+            ((indices[texel], mode.index_bits), (indices_2[texel], mode.index_bits_2))
+        } else {
+            ((indices_2[texel], mode.index_bits_2), (indices[texel], mode.index_bits))
+        };
+        let (color_weight, alpha_weight) = (weight(color.0, color.1), weight(alpha.0, alpha.1));
+        let interpolate = |channel: usize, weight: u32| ((64 - weight) * a[channel] + weight * b[channel] + 32) >> 6;
+        *out = [
+            interpolate(0, color_weight) as u8,
+            interpolate(1, color_weight) as u8,
+            interpolate(2, color_weight) as u8,
+            interpolate(3, alpha_weight) as u8,
+        ];
+        match rotation {
+            1 => out.swap(0, 3),
+            2 => out.swap(1, 3),
+            3 => out.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+/// Fields of a BC6H block, read into the endpoints or the partition index.
+#[derive(Clone, Copy)]
+enum Field {
+    Rw,
+    Gw,
+    Bw,
+    Rx,
+    Gx,
+    Bx,
+    Ry,
+    Gy,
+    By,
+    Rz,
+    Gz,
+    Bz,
+    D,
+}
+
+/// How the fields of a BC6H block are laid out and transformed in each mode.
+struct Bc6hMode {
+    two_subsets: bool,                    // Whether the block has a partition and four endpoints
+    transformed: bool,                    // Whether endpoints past the first store a delta from it
+    base_bits: u32,                       // Bits per channel of the first endpoint
+    delta_bits: [u32; 3],                 // Bits per channel of the other endpoints
+    layout: &'static [(Field, u32, u32)], // Field, first bit and bit count of each run of bits
+}
+
+use Field::*;
+
+#[rustfmt::skip]
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 10, delta_bits: [5, 5, 5], layout: &[
+        (Gy, 4, 1), (By, 4, 1), (Bz, 4, 1), (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 5), (Gz, 4, 1),
+        (Gy, 0, 4), (Gx, 0, 5), (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 5), (Bz, 1, 1), (By, 0, 4), (Ry, 0, 5),
+        (Bz, 2, 1), (Rz, 0, 5), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 7, delta_bits: [6, 6, 6], layout: &[
+        (Gy, 5, 1), (Gz, 4, 1), (Gz, 5, 1), (Rw, 0, 7), (Bz, 0, 1), (Bz, 1, 1), (By, 4, 1), (Gw, 0, 7),
+        (By, 5, 1), (Bz, 2, 1), (Gy, 4, 1), (Bw, 0, 7), (Bz, 3, 1), (Bz, 5, 1), (Bz, 4, 1), (Rx, 0, 6),
+        (Gy, 0, 4), (Gx, 0, 6), (Gz, 0, 4), (Bx, 0, 6), (By, 0, 4), (Ry, 0, 6), (Rz, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 11, delta_bits: [5, 4, 4], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 5), (Rw, 10, 1), (Gy, 0, 4), (Gx, 0, 4), (Gw, 10, 1),
+        (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 4), (Bw, 10, 1), (Bz, 1, 1), (By, 0, 4), (Ry, 0, 5), (Bz, 2, 1),
+        (Rz, 0, 5), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 11, delta_bits: [4, 5, 4], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 4), (Rw, 10, 1), (Gz, 4, 1), (Gy, 0, 4), (Gx, 0, 5),
+        (Gw, 10, 1), (Gz, 0, 4), (Bx, 0, 4), (Bw, 10, 1), (Bz, 1, 1), (By, 0, 4), (Ry, 0, 4), (Bz, 0, 1),
+        (Bz, 2, 1), (Rz, 0, 4), (Gy, 4, 1), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 11, delta_bits: [4, 4, 5], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 4), (Rw, 10, 1), (By, 4, 1), (Gy, 0, 4), (Gx, 0, 4),
+        (Gw, 10, 1), (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 5), (Bw, 10, 1), (By, 0, 4), (Ry, 0, 4), (Bz, 1, 1),
+        (Bz, 2, 1), (Rz, 0, 4), (Bz, 4, 1), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 9, delta_bits: [5, 5, 5], layout: &[
+        (Rw, 0, 9), (By, 4, 1), (Gw, 0, 9), (Gy, 4, 1), (Bw, 0, 9), (Bz, 4, 1), (Rx, 0, 5), (Gz, 4, 1),
+        (Gy, 0, 4), (Gx, 0, 5), (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 5), (Bz, 1, 1), (By, 0, 4), (Ry, 0, 5),
+        (Bz, 2, 1), (Rz, 0, 5), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 8, delta_bits: [6, 5, 5], layout: &[
+        (Rw, 0, 8), (Gz, 4, 1), (By, 4, 1), (Gw, 0, 8), (Bz, 2, 1), (Gy, 4, 1), (Bw, 0, 8), (Bz, 3, 1),
+        (Bz, 4, 1), (Rx, 0, 6), (Gy, 0, 4), (Gx, 0, 5), (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 5), (Bz, 1, 1),
+        (By, 0, 4), (Ry, 0, 6), (Rz, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 8, delta_bits: [5, 6, 5], layout: &[
+        (Rw, 0, 8), (Bz, 0, 1), (By, 4, 1), (Gw, 0, 8), (Gy, 5, 1), (Gy, 4, 1), (Bw, 0, 8), (Gz, 5, 1),
+        (Bz, 4, 1), (Rx, 0, 5), (Gz, 4, 1), (Gy, 0, 4), (Gx, 0, 6), (Gz, 0, 4), (Bx, 0, 5), (Bz, 1, 1),
+        (By, 0, 4), (Ry, 0, 5), (Bz, 2, 1), (Rz, 0, 5), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: true, base_bits: 8, delta_bits: [5, 5, 6], layout: &[
+        (Rw, 0, 8), (Bz, 1, 1), (By, 4, 1), (Gw, 0, 8), (By, 5, 1), (Gy, 4, 1), (Bw, 0, 8), (Bz, 5, 1),
+        (Bz, 4, 1), (Rx, 0, 5), (Gz, 4, 1), (Gy, 0, 4), (Gx, 0, 5), (Bz, 0, 1), (Gz, 0, 4), (Bx, 0, 6),
+        (By, 0, 4), (Ry, 0, 5), (Bz, 2, 1), (Rz, 0, 5), (Bz, 3, 1), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: true, transformed: false, base_bits: 6, delta_bits: [6, 6, 6], layout: &[
+        (Rw, 0, 6), (Gz, 4, 1), (Bz, 0, 1), (Bz, 1, 1), (By, 4, 1), (Gw, 0, 6), (Gy, 5, 1), (By, 5, 1),
+        (Bz, 2, 1), (Gy, 4, 1), (Bw, 0, 6), (Gz, 5, 1), (Bz, 3, 1), (Bz, 5, 1), (Bz, 4, 1), (Rx, 0, 6),
+        (Gy, 0, 4), (Gx, 0, 6), (Gz, 0, 4), (Bx, 0, 6), (By, 0, 4), (Ry, 0, 6), (Rz, 0, 6), (D, 0, 5),
+    ] },
+    Bc6hMode { two_subsets: false, transformed: false, base_bits: 10, delta_bits: [10, 10, 10], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 10), (Gx, 0, 10), (Bx, 0, 10),
+    ] },
+    Bc6hMode { two_subsets: false, transformed: true, base_bits: 11, delta_bits: [9, 9, 9], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 9), (Rw, 10, 1), (Gx, 0, 9), (Gw, 10, 1), (Bx, 0, 9),
+        (Bw, 10, 1),
+    ] },
+    Bc6hMode { two_subsets: false, transformed: true, base_bits: 12, delta_bits: [8, 8, 8], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 8), (Rw, 11, 1), (Rw, 10, 1), (Gx, 0, 8), (Gw, 11, 1),
+        (Gw, 10, 1), (Bx, 0, 8), (Bw, 11, 1), (Bw, 10, 1),
+    ] },
+    Bc6hMode { two_subsets: false, transformed: true, base_bits: 16, delta_bits: [4, 4, 4], layout: &[
+        (Rw, 0, 10), (Gw, 0, 10), (Bw, 0, 10), (Rx, 0, 4), (Rw, 15, 1), (Rw, 14, 1), (Rw, 13, 1), (Rw, 12, 1),
+        (Rw, 11, 1), (Rw, 10, 1), (Gx, 0, 4), (Gw, 15, 1), (Gw, 14, 1), (Gw, 13, 1), (Gw, 12, 1), (Gw, 11, 1),
+        (Gw, 10, 1), (Bx, 0, 4), (Bw, 15, 1), (Bw, 14, 1), (Bw, 13, 1), (Bw, 12, 1), (Bw, 11, 1), (Bw, 10, 1),
+    ] },
+];
+
+/// Decodes an unsigned BC6H block.
+pub(super) fn bc6h_ufloat(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    bc6h(block, texels, false);
+}
+
+/// Decodes a signed BC6H block.
+pub(super) fn bc6h_sfloat(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    bc6h(block, texels, true);
+}
+
+/// Decodes a BC6H block to RGB half floats, converted to `f32`. Blocks of a reserved
+/// mode decode to black.
+fn bc6h(block: &[u8], texels: &mut [[f32; 4]; 16], signed: bool) {
+    let mut bits = BitReader::new(block);
+    let mode_index = match bits.read(2) {
+        low @ (0 | 1) => low as usize,
+        low => match bits.read(3) << 2 | low {
+            value if value & 3 == 2 => 2 + (value >> 2) as usize,
+            value if value >> 2 < 4 => 10 + (value >> 2) as usize,
+            _ => {
+                *texels = [[0.0, 0.0, 0.0, 1.0]; 16];
+                return;
+            }
+        },
+    };
+    let mode = &BC6H_MODES[mode_index];
+
+    let mut endpoints = [[0i32; 3]; 4];
+    let mut partition = 0;
+    for &(field, first_bit, count) in mode.layout {
+        let value = (bits.read(count) as i32) << first_bit;
+        match field {
+            D => partition |= value as usize,
+            field => {
+                let field = field as usize;
+                endpoints[field / 3][field % 3] |= value;
+            }
+        }
+    }
+
+    let endpoint_count = if mode.two_subsets { 4 } else { 2 };
+    for channel in 0..3 {
+        let (base_bits, delta_bits) = (mode.base_bits, mode.delta_bits[channel]);
+        if signed {
+            endpoints[0][channel] = sign_extend(endpoints[0][channel], base_bits);
+        }
+        for endpoint in &mut endpoints[1..endpoint_count] {
+            if signed || mode.transformed {
+                endpoint[channel] = sign_extend(endpoint[channel], delta_bits);
+            }
+        }
+        if mode.transformed {
+            let base = endpoints[0][channel];
+            for endpoint in &mut endpoints[1..endpoint_count] {
+                endpoint[channel] = (endpoint[channel] + base) & ((1 << base_bits) - 1);
+                if signed {
+                    endpoint[channel] = sign_extend(endpoint[channel], base_bits);
+                }
+            }
+        }
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = unquantize(endpoint[channel], base_bits, signed);
+        }
+    }
+
+    let index_bits = if mode.two_subsets { 3 } else { 4 };
+    let is_anchor = |texel: usize| texel == 0 || (mode.two_subsets && texel == ANCHORS_2[partition] as usize);
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let index = bits.read(index_bits - is_anchor(texel) as u32);
+        let subset = if mode.two_subsets { (PARTITIONS_2[partition] >> texel & 1) as usize } else { 0 };
+        let (a, b) = (endpoints[2 * subset], endpoints[2 * subset + 1]);
+        let weight = weight(index, index_bits) as i32;
+        let interpolate = |channel: usize| {
+            let value = ((64 - weight) * a[channel] + weight * b[channel] + 32) >> 6;
+            half_to_f32(finish_unquantize(value, signed))
+        };
+        *out = [interpolate(0), interpolate(1), interpolate(2), 1.0];
+    }
+}
+
+/// Reads the bits of a 16-byte block from the lowest bit of its first byte onwards.
+struct BitReader {
+    bits: u128, // The whole block, little-endian
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> BitReader {
+        let bytes: [u8; 16] = block[..16].try_into().expect("blocks are 16 bytes");
+        BitReader { bits: u128::from_le_bytes(bytes), position: 0 }
+    }
+
+    /// Reads the next `count` bits, which land in the low bits of the result.
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits >> self.position) & ((1 << count) - 1);
+        self.position += count;
+        value as u32
+    }
+}
+
+/// Returns the interpolation weight of an index with `bits` bits.
+fn weight(index: u32, bits: u32) -> u32 {
+    match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    }
+}
+
+/// Extends the sign bit of a `bits`-bit value.
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Scales a BC6H endpoint of `bits` bits to the 16-bit range interpolation works in.
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        return match value {
+            _ if bits >= 15 => value,
+            0 => 0,
+            _ if value == (1 << bits) - 1 => 0xFFFF,
+            _ => ((value << 15) + 0x4000) >> (bits - 1),
+        };
+    }
+    if bits >= 16 {
+        return value;
+    }
+    let magnitude = match value.abs() {
+        0 => 0,
+        magnitude if magnitude >= (1 << (bits - 1)) - 1 => 0x7FFF,
+        magnitude => ((magnitude << 15) + 0x4000) >> (bits - 1),
+    };
+    if value < 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Scales an interpolated BC6H value to the bits of a half float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | ((-value * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+/// Converts the bits of a half float to `f32`.
+fn half_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let (exponent, mantissa) = ((half >> 10 & 31) as i32, (half & 1023) as f32);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs fields of the given bit counts into a block, from its lowest bit onwards.
+    fn pack(fields: &[(u32, u32)]) -> [u8; 16] {
+        let (mut bits, mut position) = (0u128, 0);
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+        assert_eq!(position, 128, "the fields don't fill a block");
+        bits.to_le_bytes()
+    }
+
+    /// Returns the index fields of a block, given the index of some texels and the bit
+    /// count of each texel. The others use index 0.
+    fn indices(set: &[(usize, u32)], bits: impl Fn(usize) -> u32) -> Vec<(u32, u32)> {
+        let index = |texel| set.iter().find(|(i, _)| *i == texel).map_or(0, |(_, index)| *index);
+        (0..16).map(|texel| (index(texel), bits(texel))).collect()
+    }
+
+    #[test]
+    fn bc7_mode_6_interpolates_with_endpoint_pbits() {
+        let mut fields = vec![(1 << 6, 7), (0, 7), (127, 7), (127, 7), (0, 7), (0, 7), (0, 7), (127, 7), (127, 7)];
+        fields.extend([(0, 1), (1, 1)]);
+        fields.extend(indices(&[(5, 8), (15, 15)], |texel| if texel == 0 { 3 } else { 4 }));
+        let mut texels = [[0; 4]; 16];
+        bc7(&pack(&fields), &mut texels);
+        assert_eq!(texels[0], [0, 254, 0, 254]);
+        assert_eq!(texels[5], [135, 120, 1, 255]);
+        assert_eq!(texels[15], [255, 1, 1, 255]);
+    }
+
+    #[test]
+    fn bc7_mode_5_rotates_alpha_into_red() {
+        let mut fields = vec![(1 << 5, 6), (1, 2), (127, 7), (127, 7), (0, 7), (0, 7), (0, 7), (0, 7)];
+        fields.extend([(0, 8), (255, 8)]);
+        fields.extend(indices(&[], |texel| if texel == 0 { 1 } else { 2 }));
+        fields.extend(indices(&[(1, 1), (15, 3)], |texel| if texel == 0 { 1 } else { 2 }));
+        let mut texels = [[0; 4]; 16];
+        bc7(&pack(&fields), &mut texels);
+        assert_eq!(texels[0], [0, 0, 0, 255]);
+        assert_eq!(texels[1], [84, 0, 0, 255]);
+        assert_eq!(texels[15], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc7_mode_1_splits_the_texels_into_two_subsets() {
+        // Partition 13 puts the bottom two rows in the second subset, anchored at texel 15.
+        let mut fields = vec![(1 << 1, 2), (13, 6)];
+        fields.extend([0, 63, 63, 63, 0, 63, 0, 0, 0, 63, 0, 0].map(|value| (value, 6)));
+        fields.extend([(1, 1), (0, 1)]);
+        fields.extend(indices(&[(3, 4), (7, 7)], |texel| if texel == 0 || texel == 15 { 2 } else { 3 }));
+        let mut texels = [[0; 4]; 16];
+        bc7(&pack(&fields), &mut texels);
+        assert_eq!(texels[0], [2, 2, 2, 255]);
+        assert_eq!(texels[3], [148, 148, 148, 255]);
+        assert_eq!(texels[7], [255, 255, 255, 255]);
+        assert_eq!(texels[8], [253, 0, 0, 255]);
+        assert_eq!(texels[15], [253, 0, 0, 255]);
+    }
+
+    #[test]
+    fn bc7_reserved_mode_is_transparent_black() {
+        let mut texels = [[255; 4]; 16];
+        bc7(&[0; 16], &mut texels);
+        assert_eq!(texels, [[0; 4]; 16]);
+    }
+
+    #[test]
+    fn bc6h_mode_10_stores_two_endpoints() {
+        let mut fields = vec![(0b00011, 5), (0, 10), (512, 10), (0, 10), (1023, 10), (512, 10), (0, 10)];
+        fields.extend(indices(&[(1, 8), (15, 15)], |texel| if texel == 0 { 3 } else { 4 }));
+        let mut texels = [[0.0; 4]; 16];
+        bc6h_ufloat(&pack(&fields), &mut texels);
+        let green = 1.0 + 527.0 / 1024.0;
+        assert_eq!(texels[0], [0.0, green, 0.0, 1.0]);
+        assert_eq!(texels[1], [2.0 + 958.0 / 1024.0, green, 0.0, 1.0]);
+        assert_eq!(texels[15], [65504.0, green, 0.0, 1.0]);
+
+        // As signed values, the red endpoints are -512 and 511.
+        let mut fields = vec![(0b00011, 5), (512, 10), (0, 10), (0, 10), (511, 10), (0, 10), (0, 10)];
+        fields.extend(indices(&[(1, 8), (15, 15)], |texel| if texel == 0 { 3 } else { 4 }));
+        bc6h_sfloat(&pack(&fields), &mut texels);
+        assert_eq!(texels[0], [-65504.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [1.9375 / 16384.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[15], [65504.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn bc6h_mode_11_adds_deltas_to_the_first_endpoint() {
+        // Red goes from 1024 to 1024 - 1 in 11 bits, the high bit stored after the delta.
+        let mut fields = vec![(0b00111, 5), (0, 10), (0, 10), (0, 10), (0x1FF, 9), (1, 1)];
+        fields.extend([(0, 9), (0, 1), (0, 9), (0, 1)]);
+        fields.extend(indices(&[(15, 15)], |texel| if texel == 0 { 3 } else { 4 }));
+        let mut texels = [[0.0; 4]; 16];
+        bc6h_ufloat(&pack(&fields), &mut texels);
+        assert_eq!(texels[0], [1.0 + 519.0 / 1024.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[15], [1.0 + 504.0 / 1024.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn bc6h_reserved_modes_are_black() {
+        let mut texels = [[1.0; 4]; 16];
+        bc6h_ufloat(&pack(&[(0b10011, 5), (0, 123)]), &mut texels);
+        assert_eq!(texels, [[0.0, 0.0, 0.0, 1.0]; 16]);
+    }
+}
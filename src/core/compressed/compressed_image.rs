@@ -0,0 +1,254 @@
+use std::fs;
+use std::path::Path;
+
+use super::dds::read_dds;
+use super::ktx::read_ktx2;
+use super::{bc, bptc, etc};
+use crate::opengl::{CompressedFormat, Texture, TextureFormat};
+
+/// Decodes one block into its 16 texels, row by row.
+type BlockDecoder<T> = fn(&[u8], &mut [[T; 4]; 16]);
+
+/// Identifier at the start of KTX2 files.
+const KTX2_MAGIC: [u8; 12] = [0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n'];
+
+/// Identifier at the start of DDS files.
+const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+/// A 2D texture of GPU block-compressed texels and its mip levels, as stored in KTX2
+/// and DDS files.
+///
+/// `upload` keeps the blocks compressed in video memory when the driver supports the
+/// format, and decompresses them on the CPU otherwise. Mip levels stored in the file are
+/// uploaded as they are instead of being generated.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedImage {
+    pub format: CompressedFormat,
+    pub width: u32,  // Width of level 0 in texels
+    pub height: u32, // Height of level 0 in texels
+    /// Blocks of each mip level from level 0, row by row.
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Decompressed texels of a mip level, as RGBA row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Texels {
+    /// 8-bit channels of color formats, sRGB-encoded for sRGB formats.
+    Unorm8(Vec<u8>),
+    /// Channels of BC6H, signed BC4 and BC5, and EAC formats.
+    Float(Vec<f32>),
+}
+
+impl Texels {
+    /// Returns the texels if they have 8-bit channels.
+    pub fn as_unorm8(&self) -> Option<&[u8]> {
+        match self {
+            Texels::Unorm8(texels) => Some(texels),
+            Texels::Float(_) => None,
+        }
+    }
+
+    /// Returns the texels if they have floating-point channels.
+    pub fn as_float(&self) -> Option<&[f32]> {
+        match self {
+            Texels::Unorm8(_) => None,
+            Texels::Float(texels) => Some(texels),
+        }
+    }
+}
+
+impl CompressedImage {
+    /// Creates an image from its mip levels, checking they hold enough blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The block format.
+    /// * `width` - Width of level 0 in texels.
+    /// * `height` - Height of level 0 in texels.
+    /// * `levels` - Blocks of each mip level from level 0. Each level halves the size of the
+    ///   previous one, rounding down but never below one texel.
+    ///
+    /// # Returns
+    ///
+    /// The image, or an `Err(String)` if it is empty or a level is too short.
+    pub fn new(format: CompressedFormat, width: u32, height: u32, levels: Vec<Vec<u8>>) -> Result<Self, String> {
+        if width == 0 || height == 0 || levels.is_empty() {
+            return Err("Compressed image has no texels".to_string());
+        }
+        let image = CompressedImage { format, width, height, levels };
+        for (level, data) in image.levels.iter().enumerate() {
+            let (level_width, level_height) = image.get_level_size(level);
+            let expected = format.image_size(level_width, level_height);
+            if data.len() < expected {
+                return Err(format!(
+                    "Mip level {level} of a {width}x{height} {format:?} image holds {} bytes instead of {expected}",
+                    data.len()
+                ));
+            }
+        }
+        Ok(image)
+    }
+
+    /// Loads a KTX2 or DDS file, told apart by their contents rather than their extension.
+    ///
+    /// Legacy DDS files with DXT1, DXT3 or DXT5 blocks are read as sRGB, like most color
+    /// textures saved in them.
+    ///
+    /// # Returns
+    ///
+    /// The image, or an `Err(String)` if the file cannot be read, isn't a 2D texture, or
+    /// stores a format other than BC1 to BC7, ETC2 and EAC.
+    pub fn load(path: impl AsRef<Path>) -> Result<CompressedImage, String> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        let image = if bytes.starts_with(&KTX2_MAGIC) {
+            CompressedImage::from_ktx2(&bytes)
+        } else if bytes.starts_with(&DDS_MAGIC) {
+            CompressedImage::from_dds(&bytes)
+        } else {
+            Err("Not a KTX2 or DDS file".to_string())
+        };
+        image.map_err(|err| format!("Cannot load texture {}: {err}", path.display()))
+    }
+
+    /// Reads the bytes of a KTX2 file, see `load`. Supercompressed files aren't supported.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<CompressedImage, String> {
+        read_ktx2(bytes)
+    }
+
+    /// Reads the bytes of a DDS file, see `load`.
+    pub fn from_dds(bytes: &[u8]) -> Result<CompressedImage, String> {
+        read_dds(bytes)
+    }
+
+    /// Returns the size in texels of a mip level.
+    pub fn get_level_size(&self, level: usize) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    /// Decodes a mip level on the CPU. Missing blocks decode to zeros.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if there is no such level.
+    pub fn decompress(&self, level: usize) -> Texels {
+        use CompressedFormat::*;
+        let (width, height) = self.get_level_size(level);
+        let data = &self.levels[level];
+        let block_size = self.format.block_size();
+        let unorm8 = |decode: BlockDecoder<u8>| Texels::Unorm8(decode_blocks(data, width, height, block_size, decode));
+        let float = |decode: BlockDecoder<f32>| Texels::Float(decode_blocks(data, width, height, block_size, decode));
+        match self.format {
+            Bc1 | Bc1Srgb => unorm8(bc::bc1),
+            Bc2 | Bc2Srgb => unorm8(bc::bc2),
+            Bc3 | Bc3Srgb => unorm8(bc::bc3),
+            Bc4 => unorm8(bc::bc4),
+            Bc5 => unorm8(bc::bc5),
+            Bc4Snorm => float(bc::bc4_snorm),
+            Bc5Snorm => float(bc::bc5_snorm),
+            Bc6hUfloat => float(bptc::bc6h_ufloat),
+            Bc6hSfloat => float(bptc::bc6h_sfloat),
+            Bc7 | Bc7Srgb => unorm8(bptc::bc7),
+            Etc2Rgb8 | Etc2Rgb8Srgb => unorm8(etc::etc2_rgb),
+            Etc2Rgb8A1 | Etc2Rgb8A1Srgb => unorm8(etc::etc2_rgb_a1),
+            Etc2Rgba8 | Etc2Rgba8Srgb => unorm8(etc::etc2_rgba),
+            EacR11 => float(etc::eac_r11),
+            EacR11Snorm => float(etc::eac_r11_snorm),
+            EacRg11 => float(etc::eac_rg11),
+            EacRg11Snorm => float(etc::eac_rg11_snorm),
+        }
+    }
+
+    /// Uploads the image with all its mip levels.
+    ///
+    /// The blocks are uploaded as they are if the driver supports the format. Otherwise
+    /// every level is decompressed: 8-bit formats to `Srgb8Alpha8` or `Rgba8` following
+    /// the format's encoding, the others to `Rgba16F`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the image has no levels.
+    pub fn upload(&self) -> Texture {
+        let (width, height) = (self.width, self.height);
+        if self.format.is_supported() {
+            let levels: Vec<&[u8]> = self.levels.iter().map(Vec::as_slice).collect();
+            return Texture::from_compressed(width, height, self.format, &levels);
+        }
+
+        log::info!("{:?} textures aren't supported by the driver, decompressing {width}x{height} texels", self.format);
+        let levels: Vec<Texels> = (0..self.levels.len()).map(|level| self.decompress(level)).collect();
+        let unorm8: Vec<&[u8]> = levels.iter().filter_map(Texels::as_unorm8).collect();
+        let float: Vec<&[f32]> = levels.iter().filter_map(Texels::as_float).collect();
+        if !float.is_empty() {
+            Texture::from_mip_chain(width, height, TextureFormat::Rgba16F, &float)
+        } else if self.format.is_srgb() {
+            Texture::from_mip_chain(width, height, TextureFormat::Srgb8Alpha8, &unorm8)
+        } else {
+            Texture::from_mip_chain(width, height, TextureFormat::Rgba8, &unorm8)
+        }
+    }
+}
+
+/// Decodes every block of a level into RGBA texels, row by row, dropping the texels of
+/// edge blocks that lie outside the level.
+fn decode_blocks<T: Copy + Default>(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode: BlockDecoder<T>,
+) -> Vec<T> {
+    let (width, height) = (width as usize, height as usize);
+    let blocks_x = width.div_ceil(4);
+    let mut texels = vec![T::default(); width * height * 4];
+    let mut block_texels = [[T::default(); 4]; 16];
+    for (i, block) in data.chunks_exact(block_size).take(blocks_x * height.div_ceil(4)).enumerate() {
+        decode(block, &mut block_texels);
+        let (block_x, block_y) = (i % blocks_x * 4, i / blocks_x * 4);
+        for (j, texel) in block_texels.iter().enumerate() {
+            let (x, y) = (block_x + j % 4, block_y + j / 4);
+            if x < width && y < height {
+                texels[(y * width + x) * 4..][..4].copy_from_slice(texel);
+            }
+        }
+    }
+    texels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_must_hold_every_block() {
+        let err = CompressedImage::new(CompressedFormat::Bc1, 5, 5, vec![vec![0; 31]]).unwrap_err();
+        assert_eq!(err, "Mip level 0 of a 5x5 Bc1 image holds 31 bytes instead of 32");
+        let err = CompressedImage::new(CompressedFormat::Bc7, 8, 4, vec![vec![0; 32], vec![0; 15]]).unwrap_err();
+        assert_eq!(err, "Mip level 1 of a 8x4 Bc7 image holds 15 bytes instead of 16");
+        assert!(CompressedImage::new(CompressedFormat::Bc1, 0, 4, vec![vec![0; 8]]).is_err());
+        assert!(CompressedImage::new(CompressedFormat::Bc1, 4, 4, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        for bytes in [&[][..], b"DDS ", &KTX2_MAGIC, &[0xAB; 200]] {
+            assert!(CompressedImage::from_ktx2(bytes).is_err());
+            assert!(CompressedImage::from_dds(bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn decompression_drops_texels_outside_the_level() {
+        // A red block, then three blue ones.
+        let red = [0x00, 0xF8, 0x1F, 0x00, 0, 0, 0, 0];
+        let blue = [0x1F, 0x00, 0x00, 0x00, 0, 0, 0, 0];
+        let image = CompressedImage::new(CompressedFormat::Bc1, 5, 5, vec![[red, blue, blue, blue].concat()]).unwrap();
+        let texels = image.decompress(0);
+        let texels = texels.as_unorm8().unwrap();
+        assert_eq!(texels.len(), 5 * 5 * 4);
+        let texel = |x: usize, y: usize| &texels[(y * 5 + x) * 4..][..4];
+        assert_eq!(texel(3, 3), [255, 0, 0, 255]);
+        assert_eq!(texel(4, 0), [0, 0, 255, 255]);
+        assert_eq!(texel(0, 4), [0, 0, 255, 255]);
+    }
+}
@@ -0,0 +1,118 @@
+use ddsfile::{Caps2, Dds, DxgiFormat, FourCC};
+
+use super::CompressedImage;
+use crate::opengl::CompressedFormat;
+
+/// Reads a 2D texture and its mip levels from the bytes of a DDS file.
+pub(super) fn read_dds(bytes: &[u8]) -> Result<CompressedImage, String> {
+    let dds = Dds::read(bytes).map_err(|err| format!("Invalid DDS file: {err}"))?;
+    let is_array = dds.header10.as_ref().is_some_and(|header| header.array_size > 1);
+    if dds.header.caps2.intersects(Caps2::CUBEMAP | Caps2::VOLUME) || is_array || dds.get_depth() > 1 {
+        return Err("Only 2D DDS textures are supported, not cubemaps, arrays or 3D textures".to_string());
+    }
+    let format = compressed_format(&dds).ok_or_else(|| {
+        let format = dds.get_dxgi_format().map(|format| format!("{format:?}"));
+        format!("Unsupported DDS texture format {}", format.unwrap_or_else(|| "without a DXGI equivalent".to_string()))
+    })?;
+
+    // Levels are stored one after the other, each padded to whole blocks.
+    let (width, height) = (dds.get_width(), dds.get_height());
+    let mut levels = Vec::new();
+    let mut offset = 0;
+    for level in 0..dds.get_num_mipmap_levels().max(1) as usize {
+        let size = format.image_size((width >> level).max(1), (height >> level).max(1));
+        let Some(data) = dds.data.get(offset..offset + size) else {
+            return Err(format!("Truncated DDS file: mip level {level} is missing"));
+        };
+        levels.push(data.to_vec());
+        offset += size;
+    }
+    CompressedImage::new(format, width, height, levels)
+}
+
+/// Returns the block format of a DDS file, from its DXGI header or its legacy FourCC.
+fn compressed_format(dds: &Dds) -> Option<CompressedFormat> {
+    // FourCCs without a DXGI equivalent in `ddsfile`.
+    match dds.header.spf.fourcc.as_ref().map(|fourcc| fourcc.0) {
+        Some(FourCC::BC4_UNORM) => return Some(CompressedFormat::Bc4),
+        Some(FourCC::BC4_SNORM) => return Some(CompressedFormat::Bc4Snorm),
+        Some(FourCC::BC5_SNORM) => return Some(CompressedFormat::Bc5Snorm),
+        _ => {}
+    }
+    let format = match dds.get_dxgi_format()? {
+        DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => CompressedFormat::Bc1,
+        DxgiFormat::BC1_UNorm_sRGB => CompressedFormat::Bc1Srgb,
+        DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => CompressedFormat::Bc2,
+        DxgiFormat::BC2_UNorm_sRGB => CompressedFormat::Bc2Srgb,
+        DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => CompressedFormat::Bc3,
+        DxgiFormat::BC3_UNorm_sRGB => CompressedFormat::Bc3Srgb,
+        DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => CompressedFormat::Bc4,
+        DxgiFormat::BC4_SNorm => CompressedFormat::Bc4Snorm,
+        DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => CompressedFormat::Bc5,
+        DxgiFormat::BC5_SNorm => CompressedFormat::Bc5Snorm,
+        DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => CompressedFormat::Bc6hUfloat,
+        DxgiFormat::BC6H_SF16 => CompressedFormat::Bc6hSfloat,
+        DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => CompressedFormat::Bc7,
+        DxgiFormat::BC7_UNorm_sRGB => CompressedFormat::Bc7Srgb,
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, NewDxgiParams};
+
+    use super::*;
+
+    /// Returns the bytes of an 8x8 DDS file with its full mip chain.
+    fn dds_bytes(format: DxgiFormat, edit: impl FnOnce(&mut Dds)) -> Vec<u8> {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            height: 8,
+            width: 8,
+            depth: None,
+            format,
+            mipmap_levels: Some(4),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        edit(&mut dds);
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn reads_every_mip_level() {
+        let image = read_dds(&dds_bytes(DxgiFormat::BC1_UNorm, |_| {})).unwrap();
+        assert_eq!((image.format, image.width, image.height), (CompressedFormat::Bc1, 8, 8));
+        assert_eq!(image.levels.iter().map(Vec::len).collect::<Vec<_>>(), [32, 8, 8, 8]);
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let bytes = dds_bytes(DxgiFormat::BC7_UNorm, |_| {});
+        let err = read_dds(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err, "Truncated DDS file: mip level 3 is missing");
+        for length in [0, 3, 4, 100, 128, 140] {
+            let err = read_dds(&bytes[..length]).unwrap_err();
+            assert!(err.starts_with("Invalid DDS file"), "{length} bytes: {err}");
+        }
+    }
+
+    #[test]
+    fn malformed_headers_are_errors() {
+        let mut bytes = dds_bytes(DxgiFormat::BC1_UNorm, |_| {});
+        bytes[0] = b'X';
+        assert!(read_dds(&bytes).unwrap_err().starts_with("Invalid DDS file"));
+
+        let cubemap = dds_bytes(DxgiFormat::BC1_UNorm, |dds| dds.header.caps2 |= Caps2::CUBEMAP);
+        assert!(read_dds(&cubemap).unwrap_err().starts_with("Only 2D DDS textures"));
+        let err = read_dds(&dds_bytes(DxgiFormat::R8G8B8A8_UNorm, |_| {})).unwrap_err();
+        assert_eq!(err, "Unsupported DDS texture format R8G8B8A8_UNorm");
+    }
+}
@@ -0,0 +1,394 @@
+/// Intensity modifiers of ETC1 and ETC2 subblocks, indexed by codeword.
+const INTENSITY_MODIFIERS: [[i32; 2]; 8] =
+    [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+
+/// Distances between the paint colors of the T and H modes.
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+/// Modifiers of EAC blocks, indexed by table and texel index.
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Decodes an opaque ETC2 RGB block, which also decodes ETC1 blocks.
+pub(super) fn etc2_rgb(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(block, texels, false);
+}
+
+/// Decodes an ETC2 RGB block with punch-through alpha, where texels are either opaque
+/// or fully transparent.
+pub(super) fn etc2_rgb_a1(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(block, texels, true);
+}
+
+/// Decodes an ETC2 RGBA block: an EAC alpha block followed by an ETC2 RGB block.
+pub(super) fn etc2_rgba(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    color_block(&block[8..], texels, false);
+    let (base, multiplier, modifiers, indices) = eac_fields(block);
+    for (texel, index) in texels.iter_mut().zip(indices) {
+        texel[3] = (base as i32 + modifiers[index] * multiplier).clamp(0, 255) as u8;
+    }
+}
+
+/// Decodes an EAC R11 block into the red channel, from 0 to 1.
+pub(super) fn eac_r11(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    eac_red(block, texels, false);
+}
+
+/// Decodes a signed EAC R11 block into the red channel, from -1 to 1.
+pub(super) fn eac_r11_snorm(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    eac_red(block, texels, true);
+}
+
+/// Decodes an EAC RG11 block into the red and green channels, from 0 to 1.
+pub(super) fn eac_rg11(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    eac_red_green(block, texels, false);
+}
+
+/// Decodes a signed EAC RG11 block into the red and green channels, from -1 to 1.
+pub(super) fn eac_rg11_snorm(block: &[u8], texels: &mut [[f32; 4]; 16]) {
+    eac_red_green(block, texels, true);
+}
+
+fn eac_red(block: &[u8], texels: &mut [[f32; 4]; 16], signed: bool) {
+    for (texel, red) in texels.iter_mut().zip(eac_channel(block, signed)) {
+        *texel = [red, 0.0, 0.0, 1.0];
+    }
+}
+
+fn eac_red_green(block: &[u8], texels: &mut [[f32; 4]; 16], signed: bool) {
+    let (red, green) = (eac_channel(block, signed), eac_channel(&block[8..], signed));
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = [red[i], green[i], 0.0, 1.0];
+    }
+}
+
+/// Decodes the 8 bytes of an ETC2 color block.
+///
+/// Punch-through blocks reuse the differential bit as an opaque bit and are always
+/// differential. In non-opaque blocks, texel index 2 is fully transparent.
+fn color_block(block: &[u8], texels: &mut [[u8; 4]; 16], punch_through: bool) {
+    let bits = u64::from_be_bytes(block[..8].try_into().expect("blocks are 8 bytes"));
+    let flag = bits >> 33 & 1 == 1;
+    let opaque = !punch_through || flag;
+
+    if !punch_through && !flag {
+        let color = |shift: u32| (bits >> shift & 15) as i32 * 17;
+        let first = [color(60), color(52), color(44)];
+        let second = [color(56), color(48), color(40)];
+        return subblocks(bits, first, second, opaque, texels);
+    }
+
+    // The differential mode adds a 3-bit signed delta to each 5-bit channel; deltas
+    // overflowing a channel select the other modes instead.
+    let base = [bits >> 59 & 31, bits >> 51 & 31, bits >> 43 & 31].map(|channel| channel as i32);
+    let delta = [bits >> 56 & 7, bits >> 48 & 7, bits >> 40 & 7].map(|delta| ((delta as i32) << 29) >> 29);
+    let sum: [i32; 3] = std::array::from_fn(|i| base[i] + delta[i]);
+    let overflows = |i: usize| !(0..32).contains(&sum[i]);
+    if overflows(0) {
+        t_mode(bits, opaque, texels);
+    } else if overflows(1) {
+        h_mode(bits, opaque, texels);
+    } else if overflows(2) {
+        planar_mode(bits, texels);
+    } else {
+        subblocks(bits, base.map(expand_5), sum.map(expand_5), opaque, texels);
+    }
+}
+
+/// Decodes the two subblocks of the individual and differential modes, each with its
+/// base color and intensity codeword.
+fn subblocks(bits: u64, first: [i32; 3], second: [i32; 3], opaque: bool, texels: &mut [[u8; 4]; 16]) {
+    let codewords = [(bits >> 37 & 7) as usize, (bits >> 34 & 7) as usize];
+    let flip = bits >> 32 & 1 == 1;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let subblock = if flip { y / 2 } else { x / 2 };
+        let (base, modifiers) = ([first, second][subblock], INTENSITY_MODIFIERS[codewords[subblock]]);
+        let index = texel_index(bits, x, y);
+        if !opaque && index == 2 {
+            *texel = [0; 4];
+            continue;
+        }
+        let modifier = match index {
+            0 if !opaque => 0,
+            0 => modifiers[0],
+            1 => modifiers[1],
+            2 => -modifiers[0],
+            _ => -modifiers[1],
+        };
+        *texel = rgba(base.map(|channel| channel + modifier));
+    }
+}
+
+/// Decodes the T mode: one color, and three colors spread around a second one.
+fn t_mode(bits: u64, opaque: bool, texels: &mut [[u8; 4]; 16]) {
+    let channel = |shift: u32| (bits >> shift & 15) as i32 * 17;
+    let first = [((bits >> 59 & 3) << 2 | bits >> 56 & 3) as i32 * 17, channel(52), channel(48)];
+    let second = [channel(44), channel(40), channel(36)];
+    let distance = DISTANCES[((bits >> 34 & 3) << 1 | bits >> 32 & 1) as usize];
+    let paint = [first, second.map(|c| c + distance), second, second.map(|c| c - distance)];
+    paint_texels(bits, paint, opaque, texels);
+}
+
+/// Decodes the H mode: two pairs of colors spread around two colors.
+fn h_mode(bits: u64, opaque: bool, texels: &mut [[u8; 4]; 16]) {
+    let first = [bits >> 59 & 15, (bits >> 56 & 7) << 1 | bits >> 52 & 1, (bits >> 51 & 1) << 3 | bits >> 47 & 7];
+    let second = [bits >> 43 & 15, bits >> 39 & 15, bits >> 35 & 15];
+    // The lowest bit of the distance is implied by the order of the two colors.
+    let value = |color: [u64; 3]| color[0] << 8 | color[1] << 4 | color[2];
+    let order = (value(first) >= value(second)) as u64;
+    let distance = DISTANCES[((bits >> 34 & 1) << 2 | (bits >> 32 & 1) << 1 | order) as usize];
+    let (first, second) = (first.map(|c| c as i32 * 17), second.map(|c| c as i32 * 17));
+    let paint = [
+        first.map(|c| c + distance),
+        first.map(|c| c - distance),
+        second.map(|c| c + distance),
+        second.map(|c| c - distance),
+    ];
+    paint_texels(bits, paint, opaque, texels);
+}
+
+/// Picks the paint color of each texel of the T and H modes.
+fn paint_texels(bits: u64, paint: [[i32; 3]; 4], opaque: bool, texels: &mut [[u8; 4]; 16]) {
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let index = texel_index(bits, i % 4, i / 4);
+        *texel = if !opaque && index == 2 { [0; 4] } else { rgba(paint[index]) };
+    }
+}
+
+/// Decodes the planar mode: a gradient through the colors at the origin, the
+/// horizontal corner and the vertical corner.
+fn planar_mode(bits: u64, texels: &mut [[u8; 4]; 16]) {
+    let field = |shift: u32, count: u32| (bits >> shift & ((1 << count) - 1)) as i32;
+    let origin = [
+        expand_6(field(57, 6)),
+        expand_7(field(56, 1) << 6 | field(49, 6)),
+        expand_6(field(48, 1) << 5 | field(43, 2) << 3 | field(39, 3)),
+    ];
+    let horizontal = [expand_6(field(34, 5) << 1 | field(32, 1)), expand_7(field(25, 7)), expand_6(field(19, 6))];
+    let vertical = [expand_6(field(13, 6)), expand_7(field(6, 7)), expand_6(field(0, 6))];
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+        *texel = rgba(std::array::from_fn(|c| {
+            (x * (horizontal[c] - origin[c]) + y * (vertical[c] - origin[c]) + 4 * origin[c] + 2) >> 2
+        }));
+    }
+}
+
+/// Returns the 2-bit index of a texel, whose bits are stored column by column.
+fn texel_index(bits: u64, x: usize, y: usize) -> usize {
+    let k = x * 4 + y;
+    ((bits >> (16 + k) & 1) << 1 | bits >> k & 1) as usize
+}
+
+/// Clamps a color to 8 bits per channel and makes it opaque.
+fn rgba(color: [i32; 3]) -> [u8; 4] {
+    let [r, g, b] = color.map(|channel| channel.clamp(0, 255) as u8);
+    [r, g, b, 255]
+}
+
+/// Expands a 5, 6 or 7-bit channel to 8 bits.
+fn expand_5(value: i32) -> i32 {
+    value << 3 | value >> 2
+}
+
+fn expand_6(value: i32) -> i32 {
+    value << 2 | value >> 4
+}
+
+fn expand_7(value: i32) -> i32 {
+    value << 1 | value >> 6
+}
+
+/// Splits an EAC block into its base value, multiplier, modifier table and texel
+/// indices, row by row.
+fn eac_fields(block: &[u8]) -> (u8, i32, [i32; 8], [usize; 16]) {
+    let bits = block[2..8].iter().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+    // Indices are stored column by column from the highest bits.
+    let indices = std::array::from_fn(|i| (bits >> (45 - 3 * ((i % 4) * 4 + i / 4)) & 7) as usize);
+    (block[0], (block[1] >> 4) as i32, EAC_MODIFIERS[(block[1] & 15) as usize], indices)
+}
+
+/// Decodes the 11-bit values of an EAC R11 block, scaled to 0 to 1 or -1 to 1.
+fn eac_channel(block: &[u8], signed: bool) -> [f32; 16] {
+    let (base, multiplier, modifiers, indices) = eac_fields(block);
+    std::array::from_fn(|i| {
+        // A multiplier of 0 applies the modifiers unscaled, for fine gradients.
+        let modifier = match multiplier {
+            0 => modifiers[indices[i]],
+            _ => modifiers[indices[i]] * multiplier * 8,
+        };
+        if signed {
+            let base = (base as i8).max(-127) as i32;
+            (base * 8 + modifier).clamp(-1023, 1023) as f32 / 1023.0
+        } else {
+            (base as i32 * 8 + 4 + modifier).clamp(0, 2047) as f32 / 2047.0
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a color block from fields given as a value and the position of its lowest bit,
+    /// and the index of some texels. The others use index 0.
+    fn color(fields: &[(u64, u32)], indices: &[(usize, u64)]) -> [u8; 8] {
+        let bits = fields.iter().fold(0u64, |bits, &(value, shift)| bits | value << shift);
+        let bits = indices.iter().fold(bits, |bits, &(texel, index)| {
+            let k = texel % 4 * 4 + texel / 4;
+            bits | (index >> 1) << (16 + k) | (index & 1) << k
+        });
+        bits.to_be_bytes()
+    }
+
+    /// Builds an EAC block from its base, multiplier and table byte, and some texel indices.
+    fn eac(base: u8, multiplier_table: u8, set: &[(usize, u64)]) -> [u8; 8] {
+        let bits = set.iter().fold(0u64, |bits, &(i, index)| bits | index << (45 - 3 * (i % 4 * 4 + i / 4)));
+        let mut block = bits.to_be_bytes();
+        (block[0], block[1]) = (base, multiplier_table);
+        block
+    }
+
+    // Red and blue subblocks side by side with the smallest and largest codewords.
+    fn individual() -> [u8; 8] {
+        color(&[(15, 60), (15, 40), (7, 34)], &[(0, 3), (15, 1)])
+    }
+
+    // Subblocks on top of each other, the second one 3, -2 and -3 away from the first.
+    fn differential(opaque: u64) -> [u8; 8] {
+        let fields = [(16, 59), (3, 56), (8, 51), (6, 48), (31, 43), (5, 40), (1, 37), (1, 34), (opaque, 33), (1, 32)];
+        color(&fields, &[(14, 1), (15, 2)])
+    }
+
+    // The red delta overflows: one color, and three others at a distance of 16.
+    fn t_mode(opaque: u64) -> [u8; 8] {
+        let fields = [(7, 61), (2, 59), (3, 56), (4, 52), (8, 48), (8, 40), (15, 36), (1, 34), (opaque, 33), (1, 32)];
+        color(&fields, &[(1, 1), (4, 2), (5, 3)])
+    }
+
+    #[test]
+    fn individual_subblocks_have_4_bit_colors() {
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb(&individual(), &mut texels);
+        assert_eq!(texels[..3], [[247, 0, 0, 255], [255, 2, 2, 255], [47, 47, 255, 255]]);
+        assert_eq!(texels[15], [183, 183, 255, 255]);
+    }
+
+    #[test]
+    fn differential_subblocks_can_be_flipped() {
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb(&differential(1), &mut texels);
+        assert_eq!(texels[0], [137, 71, 255, 255]);
+        assert_eq!(texels[8], [161, 54, 236, 255]);
+        assert_eq!(texels[14..], [[173, 66, 248, 255], [151, 44, 226, 255]]);
+    }
+
+    #[test]
+    fn t_mode_spreads_three_colors_around_the_second() {
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb(&t_mode(1), &mut texels);
+        assert_eq!(texels[..2], [[187, 68, 136, 255], [16, 152, 255, 255]]);
+        assert_eq!(texels[4..6], [[0, 136, 255, 255], [0, 120, 239, 255]]);
+    }
+
+    #[test]
+    fn h_mode_orders_the_colors_to_pick_the_distance() {
+        // The green delta overflows. The first color is larger, so the distance is 16.
+        let fields = [
+            (8, 59), (2, 56), (7, 53), (1, 52), (1, 51), (4, 47), (2, 43), (3, 39), (4, 35), (1, 33), (1, 32),
+        ];
+        let block = color(&fields, &[(1, 1), (4, 2), (5, 3)]);
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb(&block, &mut texels);
+        assert_eq!(texels[..2], [[152, 101, 220, 255], [120, 69, 188, 255]]);
+        assert_eq!(texels[4..6], [[50, 67, 84, 255], [18, 35, 52, 255]]);
+    }
+
+    #[test]
+    fn planar_mode_is_a_gradient() {
+        // The blue delta overflows. Red grows to the right and green downwards.
+        let block = color(&[(7, 45), (2, 43), (6, 39), (31, 34), (1, 33), (1, 32), (127, 6)], &[]);
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb(&block, &mut texels);
+        assert_eq!(texels[0], [0, 0, 89, 255]);
+        assert_eq!(texels[3], [191, 0, 22, 255]);
+        assert_eq!(texels[12], [0, 191, 22, 255]);
+        assert_eq!(texels[15], [191, 191, 0, 255]);
+    }
+
+    #[test]
+    fn punch_through_blocks_have_transparent_texels_unless_opaque() {
+        let mut texels = [[0; 4]; 16];
+        etc2_rgb_a1(&differential(1), &mut texels);
+        assert_eq!(texels[15], [151, 44, 226, 255]);
+
+        // Index 0 loses its modifier and index 2 is transparent.
+        etc2_rgb_a1(&differential(0), &mut texels);
+        assert_eq!((texels[0], texels[8]), ([132, 66, 255, 255], [156, 49, 231, 255]));
+        assert_eq!(texels[14..], [[173, 66, 248, 255], [0; 4]]);
+
+        etc2_rgb_a1(&t_mode(0), &mut texels);
+        assert_eq!(texels[..2], [[187, 68, 136, 255], [16, 152, 255, 255]]);
+        assert_eq!(texels[4..6], [[0; 4], [0, 120, 239, 255]]);
+    }
+
+    #[test]
+    fn etc2_rgba_reads_alpha_from_an_eac_block() {
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&eac(100, 0x20, &[(0, 7), (1, 3)]));
+        block[8..].copy_from_slice(&individual());
+        let mut texels = [[0; 4]; 16];
+        etc2_rgba(&block, &mut texels);
+        assert_eq!(texels[..3], [[247, 0, 0, 128], [255, 2, 2, 70], [47, 47, 255, 94]]);
+    }
+
+    #[test]
+    fn eac_blocks_scale_the_modifiers_by_the_multiplier() {
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&eac(128, 0x1D, &[(0, 7), (15, 3)]));
+        // Without a multiplier, the modifiers apply to 11-bit values unscaled.
+        block[8..].copy_from_slice(&eac(255, 0x0D, &[(0, 7)]));
+        let mut texels = [[0.0; 4]; 16];
+        eac_r11(&block, &mut texels);
+        assert_eq!(texels[0], [1100.0 / 2047.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [1020.0 / 2047.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[15], [948.0 / 2047.0, 0.0, 0.0, 1.0]);
+
+        eac_rg11(&block, &mut texels);
+        assert_eq!(texels[0], [1100.0 / 2047.0, 1.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [1020.0 / 2047.0, 2043.0 / 2047.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn signed_eac_blocks_clamp_to_minus_one() {
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&eac(0x80, 0x1D, &[(0, 3), (15, 7)]));
+        block[8..].copy_from_slice(&eac(0x7F, 0x0D, &[(0, 7)]));
+        let mut texels = [[0.0; 4]; 16];
+        eac_r11_snorm(&block, &mut texels);
+        assert_eq!(texels[0], [-1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [-1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[15], [-944.0 / 1023.0, 0.0, 0.0, 1.0]);
+
+        eac_rg11_snorm(&block, &mut texels);
+        assert_eq!(texels[0], [-1.0, 1.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [-1.0, 1015.0 / 1023.0, 0.0, 1.0]);
+    }
+}
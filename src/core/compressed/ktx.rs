@@ -0,0 +1,120 @@
+use ktx2::{Format, Reader};
+
+use super::CompressedImage;
+use crate::opengl::CompressedFormat;
+
+/// Reads a 2D texture and its mip levels from the bytes of a KTX2 file.
+pub(super) fn read_ktx2(bytes: &[u8]) -> Result<CompressedImage, String> {
+    let reader = Reader::new(bytes).map_err(|err| format!("Invalid KTX2 file: {err}"))?;
+    let header = reader.header();
+    if let Some(scheme) = header.supercompression_scheme {
+        return Err(format!("Supercompressed KTX2 files aren't supported ({scheme:?})"));
+    }
+    if header.face_count > 1 || header.layer_count > 1 || header.pixel_depth > 1 {
+        return Err("Only 2D KTX2 textures are supported, not cubemaps, arrays or 3D textures".to_string());
+    }
+    let format = header
+        .format
+        .and_then(compressed_format)
+        .ok_or_else(|| format!("Unsupported KTX2 texture format {:?}", header.format))?;
+
+    let levels = reader.levels().map(|level| level.data.to_vec()).collect();
+    CompressedImage::new(format, header.pixel_width, header.pixel_height, levels)
+}
+
+/// Maps a Vulkan format to the matching block format.
+fn compressed_format(format: Format) -> Option<CompressedFormat> {
+    let format = match format {
+        // BC1 blocks decode the same with or without alpha.
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => CompressedFormat::Bc1,
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => CompressedFormat::Bc1Srgb,
+        Format::BC2_UNORM_BLOCK => CompressedFormat::Bc2,
+        Format::BC2_SRGB_BLOCK => CompressedFormat::Bc2Srgb,
+        Format::BC3_UNORM_BLOCK => CompressedFormat::Bc3,
+        Format::BC3_SRGB_BLOCK => CompressedFormat::Bc3Srgb,
+        Format::BC4_UNORM_BLOCK => CompressedFormat::Bc4,
+        Format::BC4_SNORM_BLOCK => CompressedFormat::Bc4Snorm,
+        Format::BC5_UNORM_BLOCK => CompressedFormat::Bc5,
+        Format::BC5_SNORM_BLOCK => CompressedFormat::Bc5Snorm,
+        Format::BC6H_UFLOAT_BLOCK => CompressedFormat::Bc6hUfloat,
+        Format::BC6H_SFLOAT_BLOCK => CompressedFormat::Bc6hSfloat,
+        Format::BC7_UNORM_BLOCK => CompressedFormat::Bc7,
+        Format::BC7_SRGB_BLOCK => CompressedFormat::Bc7Srgb,
+        Format::ETC2_R8G8B8_UNORM_BLOCK => CompressedFormat::Etc2Rgb8,
+        Format::ETC2_R8G8B8_SRGB_BLOCK => CompressedFormat::Etc2Rgb8Srgb,
+        Format::ETC2_R8G8B8A1_UNORM_BLOCK => CompressedFormat::Etc2Rgb8A1,
+        Format::ETC2_R8G8B8A1_SRGB_BLOCK => CompressedFormat::Etc2Rgb8A1Srgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => CompressedFormat::Etc2Rgba8,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => CompressedFormat::Etc2Rgba8Srgb,
+        Format::EAC_R11_UNORM_BLOCK => CompressedFormat::EacR11,
+        Format::EAC_R11_SNORM_BLOCK => CompressedFormat::EacR11Snorm,
+        Format::EAC_R11G11_UNORM_BLOCK => CompressedFormat::EacRg11,
+        Format::EAC_R11G11_SNORM_BLOCK => CompressedFormat::EacRg11Snorm,
+        _ => return None,
+    };
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the bytes of a 4x4 KTX2 file holding a single block of 8 bytes. `header`
+    /// edits its 80-byte header.
+    fn ktx2_bytes(format: Format, header: impl FnOnce(&mut [u8])) -> Vec<u8> {
+        let mut bytes = vec![0; 120];
+        bytes[..12].copy_from_slice(&[0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n']);
+        // Format, type size, width, height, depth, layers, faces and levels.
+        for (i, value) in [format.value(), 1, 4, 4, 0, 0, 1, 1].into_iter().enumerate() {
+            bytes[12 + 4 * i..][..4].copy_from_slice(&value.to_le_bytes());
+        }
+        // A data format descriptor holding only its size, then the level index.
+        bytes[48..56].copy_from_slice(&[104, 0, 0, 0, 4, 0, 0, 0]);
+        bytes[80..88].copy_from_slice(&112u64.to_le_bytes());
+        bytes[88..96].copy_from_slice(&8u64.to_le_bytes());
+        bytes[104..108].copy_from_slice(&4u32.to_le_bytes());
+        header(&mut bytes[..80]);
+        bytes
+    }
+
+    #[test]
+    fn reads_a_block_compressed_texture() {
+        let image = read_ktx2(&ktx2_bytes(Format::BC1_RGBA_UNORM_BLOCK, |_| {})).unwrap();
+        assert_eq!((image.format, image.width, image.height), (CompressedFormat::Bc1, 4, 4));
+        assert_eq!(image.levels, [vec![0; 8]]);
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let bytes = ktx2_bytes(Format::BC1_RGBA_UNORM_BLOCK, |_| {});
+        for length in [0, 12, 79, 80, 100, 108, 119] {
+            let err = read_ktx2(&bytes[..length]).unwrap_err();
+            assert!(err.starts_with("Invalid KTX2 file"), "{length} bytes: {err}");
+        }
+    }
+
+    #[test]
+    fn malformed_headers_are_errors() {
+        let format = Format::BC1_RGBA_UNORM_BLOCK;
+        let invalid = [
+            ktx2_bytes(format, |header| header[1] = b'X'),
+            // A zero width, then no faces.
+            ktx2_bytes(format, |header| header[20..24].fill(0)),
+            ktx2_bytes(format, |header| header[36..40].fill(0)),
+            // A level past the end of the file.
+            ktx2_bytes(format, |header| header[40] = 2),
+            ktx2_bytes(format, |header| header[52] = 255),
+        ];
+        for (i, bytes) in invalid.iter().enumerate() {
+            let err = read_ktx2(bytes).unwrap_err();
+            assert!(err.starts_with("Invalid KTX2 file"), "file {i}: {err}");
+        }
+
+        let supercompressed = ktx2_bytes(format, |header| header[44] = 2);
+        assert!(read_ktx2(&supercompressed).unwrap_err().starts_with("Supercompressed"));
+        let cubemap = ktx2_bytes(format, |header| header[36] = 6);
+        assert!(read_ktx2(&cubemap).unwrap_err().starts_with("Only 2D KTX2 textures"));
+        let err = read_ktx2(&ktx2_bytes(Format::R8G8B8A8_UNORM, |_| {})).unwrap_err();
+        assert_eq!(err, "Unsupported KTX2 texture format Some(R8G8B8A8_UNORM)");
+    }
+}
@@ -0,0 +1,8 @@
+mod bc;
+mod bptc;
+mod compressed_image;
+mod dds;
+mod etc;
+mod ktx;
+
+pub use compressed_image::{CompressedImage, Texels};
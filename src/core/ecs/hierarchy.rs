@@ -0,0 +1,101 @@
+use nalgebra_glm::Mat4;
+
+use super::{Entity, World};
+use crate::core::components::{GlobalTransform, Transform};
+
+/// The entity whose transform this entity's `Transform` is relative to.
+///
+/// Managed by `World::set_parent`; inserting it directly does not update `Children`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parent(pub Entity);
+
+/// The entities parented to this one, in the order they were attached.
+///
+/// Managed by `World::set_parent`; inserting it directly does not update `Parent`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<Entity>);
+
+/// A human-readable entity name, such as the node name of an imported model.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Name(pub String);
+
+impl World {
+    /// Attaches `child` to `parent`, detaching it from its previous parent first.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if either entity has been despawned, or if `parent` is
+    /// `child` or one of its descendants.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        assert!(self.is_alive(child) && self.is_alive(parent), "cannot parent despawned entities");
+        let mut ancestor = Some(parent);
+        while let Some(entity) = ancestor {
+            assert!(entity != child, "{child:?} cannot be parented to its own descendant {parent:?}");
+            ancestor = self.get_parent(entity);
+        }
+
+        self.remove_parent(child);
+        self.insert(child, Parent(parent));
+        match self.get_mut::<Children>(parent) {
+            Some(children) => children.0.push(child),
+            None => {
+                self.insert(parent, Children(vec![child]));
+            }
+        }
+    }
+
+    /// Detaches an entity from its parent, making it a root.
+    ///
+    /// # Returns
+    ///
+    /// The previous parent, if any.
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let Parent(parent) = self.remove::<Parent>(child)?;
+        if let Some(children) = self.get_mut::<Children>(parent) {
+            children.0.retain(|&entity| entity != child);
+        }
+        Some(parent)
+    }
+
+    /// Returns the parent of an entity, if any.
+    pub fn get_parent(&self, entity: Entity) -> Option<Entity> {
+        self.get::<Parent>(entity).map(|parent| parent.0)
+    }
+
+    /// Returns the children of an entity.
+    pub fn get_children(&self, entity: Entity) -> &[Entity] {
+        self.get::<Children>(entity).map_or(&[], |children| &children.0)
+    }
+
+    /// Finds the first entity with the given `Name`.
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.query::<Name>().find(|(_, entity_name)| entity_name.0 == name).map(|(entity, _)| entity)
+    }
+
+    /// Resolves every `Transform` into a `GlobalTransform`, walking the hierarchy from
+    /// its roots so children are placed relative to their parents.
+    ///
+    /// Entities without a `Transform` pass their parent's global transform on unchanged.
+    pub fn update_transforms(&mut self) {
+        let mut roots: Vec<Entity> = self
+            .query::<Transform>()
+            .map(|(entity, _)| entity)
+            .chain(self.query::<Children>().map(|(entity, _)| entity))
+            .filter(|&entity| !self.has::<Parent>(entity))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+
+        let mut pending: Vec<(Entity, Mat4)> = roots.into_iter().map(|root| (root, Mat4::identity())).collect();
+        while let Some((entity, parent)) = pending.pop() {
+            let global = match self.get::<Transform>(entity) {
+                Some(transform) => parent * transform.matrix(),
+                None => parent,
+            };
+            if self.has::<Transform>(entity) || self.has::<GlobalTransform>(entity) {
+                self.insert(entity, GlobalTransform(global));
+            }
+            pending.extend(self.get_children(entity).iter().map(|&child| (child, global)));
+        }
+    }
+}
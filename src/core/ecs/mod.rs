@@ -1,3 +1,7 @@
+mod hierarchy;
 mod system;
+mod world;
 
+pub use hierarchy::{Children, Name, Parent};
 pub use system::{SystemType, Scheduler};
+pub use world::{Entity, World};
@@ -1,4 +1,4 @@
-use crate::core::{components::MeshRenderer, Window};
+use crate::core::{Scene, Window};
 use std::collections::HashMap;
 
 /// Enum representing different system types for scheduling.
@@ -12,7 +12,7 @@ pub enum SystemType {
     Key,
 }
 
-pub type System = dyn FnMut(&mut Window, &mut Vec<MeshRenderer>);
+pub type System = dyn FnMut(&mut Window, &mut Scene);
 
 /// A struct that holds a collection of systems (functions) categorized by their type.
 #[derive(Default)]
@@ -42,7 +42,7 @@ impl Scheduler {
     /// let mut scheduler = Scheduler::new();
     /// scheduler.insert(SystemType::Startup, || println!("Starting up..."));
     /// ```
-    pub fn insert(&mut self, system_type: SystemType, system: impl FnMut(&mut Window, &mut Vec<MeshRenderer>) + 'static) {
+    pub fn insert(&mut self, system_type: SystemType, system: impl FnMut(&mut Window, &mut Scene) + 'static) {
         self.systems
             .entry(system_type)
             .or_default()
@@ -57,6 +57,7 @@ impl Scheduler {
     ///
     /// * `system_type` - The type of system whose functions are to be invoked.
     /// * `window` - The window instance passed to the systems.
+    /// * `scene` - The scene passed to the systems.
     ///
     /// # Example
     /// ```
@@ -64,11 +65,11 @@ impl Scheduler {
     /// scheduler.insert(SystemType::Startup, || println!("Starting up..."));
     /// scheduler.invoke(SystemType::Startup);
     /// ```
-    pub fn invoke(&mut self, system_type: SystemType, window: &mut Window, renders: &mut Vec<MeshRenderer>) {
+    pub fn invoke(&mut self, system_type: SystemType, window: &mut Window, scene: &mut Scene) {
         if let Some(systems) = self.systems.get_mut(&system_type) {
             for system in systems.iter_mut() {
                 // Dereference the Box to invoke the function
-                system(window, renders);
+                system(window, scene);
             }
         }
     }
@@ -0,0 +1,241 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Identifies an entity in a `World`.
+///
+/// Indices of despawned entities are reused, so each entity also carries the
+/// generation of its slot. A stale `Entity` never refers to a newer one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    /// Returns the slot index of the entity, unique among living entities.
+    pub fn get_index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns how many times the entity's slot was reused.
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity({}v{})", self.index, self.generation)
+    }
+}
+
+/// Type-erased access to a `Storage<T>`.
+trait ComponentStorage {
+    fn remove_index(&mut self, index: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The components of one type, indexed by entity slot.
+struct Storage<T> {
+    components: Vec<Option<T>>,
+}
+
+impl<T: 'static> ComponentStorage for Storage<T> {
+    fn remove_index(&mut self, index: usize) {
+        if let Some(component) = self.components.get_mut(index) {
+            *component = None;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Slot {
+    generation: u32,
+    alive: bool,
+}
+
+/// Holds entities and their components.
+///
+/// Any `'static` type can be a component, and an entity has at most one component of
+/// each type. Entities can be arranged in a hierarchy with `set_parent`, and
+/// `update_transforms` resolves each `Transform` into a `GlobalTransform`.
+#[derive(Default)]
+pub struct World {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn ComponentStorage>>,
+}
+
+impl World {
+    /// Creates an empty world.
+    pub fn new() -> World {
+        World::default()
+    }
+
+    /// Creates an entity without components.
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.alive = true;
+                Entity { index, generation: slot.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, alive: true });
+                Entity { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    /// Removes an entity, its components and all of its descendants.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the entity was already despawned.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.remove_parent(entity);
+
+        let mut pending = vec![entity];
+        while let Some(entity) = pending.pop() {
+            if let Some(children) = self.remove::<super::Children>(entity) {
+                pending.extend(children.0);
+            }
+            for storage in self.storages.values_mut() {
+                storage.remove_index(entity.index as usize);
+            }
+            let slot = &mut self.slots[entity.index as usize];
+            slot.alive = false;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free.push(entity.index);
+        }
+        true
+    }
+
+    /// Checks whether an entity has been spawned and not despawned since.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots
+            .get(entity.index as usize)
+            .is_some_and(|slot| slot.alive && slot.generation == entity.generation)
+    }
+
+    /// Returns the number of living entities.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Checks whether the world has no living entities.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over every living entity.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots.iter().enumerate().filter(|(_, slot)| slot.alive).map(|(index, slot)| Entity {
+            index: index as u32,
+            generation: slot.generation,
+        })
+    }
+
+    /// Adds a component to an entity, replacing any component of the same type.
+    ///
+    /// # Returns
+    ///
+    /// The replaced component, if any.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the entity has been despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        assert!(self.is_alive(entity), "cannot insert a component into despawned {entity:?}");
+
+        let index = entity.index as usize;
+        let components = &mut self.storage_mut::<T>().components;
+        if components.len() <= index {
+            components.resize_with(index + 1, || None);
+        }
+        components[index].replace(component)
+    }
+
+    /// Removes a component from an entity.
+    ///
+    /// # Returns
+    ///
+    /// The removed component, or `None` if the entity did not have one.
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let storage = self.storages.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut::<Storage<T>>()?;
+        storage.components.get_mut(entity.index as usize)?.take()
+    }
+
+    /// Returns an entity's component of type `T`.
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage::<T>()?.components.get(entity.index as usize)?.as_ref()
+    }
+
+    /// Returns an entity's component of type `T` mutably.
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let storage = self.storages.get_mut(&TypeId::of::<T>())?.as_any_mut().downcast_mut::<Storage<T>>()?;
+        storage.components.get_mut(entity.index as usize)?.as_mut()
+    }
+
+    /// Checks whether an entity has a component of type `T`.
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Iterates over every entity with a component of type `T`, in slot order.
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        let components = self.storage::<T>().map_or(&[][..], |storage| &storage.components);
+        components.iter().enumerate().filter_map(|(index, component)| {
+            let component = component.as_ref()?;
+            Some((Entity { index: index as u32, generation: self.slots[index].generation }, component))
+        })
+    }
+
+    /// Iterates mutably over every entity with a component of type `T`, in slot order.
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let slots = &self.slots;
+        let components = match self.storages.get_mut(&TypeId::of::<T>()) {
+            Some(storage) => storage.as_any_mut().downcast_mut::<Storage<T>>().map(|storage| &mut storage.components),
+            None => None,
+        };
+        components.into_iter().flatten().enumerate().filter_map(|(index, component)| {
+            let component = component.as_mut()?;
+            Some((Entity { index: index as u32, generation: slots[index].generation }, component))
+        })
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.storages.get(&TypeId::of::<T>())?.as_any().downcast_ref::<Storage<T>>()
+    }
+
+    fn storage_mut<T: 'static>(&mut self) -> &mut Storage<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T> { components: Vec::new() }))
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()
+            .expect("component storage registered under the wrong type")
+    }
+}
@@ -1,30 +1,121 @@
 use crate::core::ecs::{Scheduler, SystemType};
-use crate::core::Window;
+use crate::core::renderer::{RenderPath, Renderer};
+use crate::core::{HeadlessApi, Scene, Window};
+use crate::opengl::ClearState;
 
-use super::components::MeshRenderer;
+use image::RgbaImage;
+use std::time::Instant;
+
+/// Time step of frames rendered by `run_headless`, in seconds.
+const HEADLESS_TIMESTEP: f32 = 1.0 / 60.0;
 
-#[derive(Default)]
 pub struct Engine {
     scheduler: Scheduler,
-    renders: Vec<MeshRenderer>
+    scene: Scene,
+    render_path: RenderPath,
+    clear_state: ClearState,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            scheduler: Scheduler::default(),
+            scene: Scene::default(),
+            render_path: RenderPath::default(),
+            clear_state: ClearState::all(0.07, 0.17, 0.07, 1.0),
+        }
+    }
 }
 
 impl Engine {
-    pub fn add_system(&mut self, system_type: SystemType, system: impl FnMut(&mut Window, &mut Vec<MeshRenderer>) + 'static) {
+    pub fn add_system(&mut self, system_type: SystemType, system: impl FnMut(&mut Window, &mut Scene) + 'static) {
         self.scheduler.insert(system_type, system);
     }
 
+    /// Selects the forward or deferred renderer. Must be called before `run`.
+    pub fn set_render_path(&mut self, render_path: RenderPath) {
+        self.render_path = render_path;
+    }
+
+    /// Selects which buffers are cleared at the start of every frame, and to what.
+    pub fn set_clear_state(&mut self, clear_state: ClearState) {
+        self.clear_state = clear_state;
+    }
+
     pub fn run(&mut self) {
         let mut window = Window::new("Foux Engine", 800, 480);
-        self.scheduler.invoke(SystemType::Startup, &mut window, &mut self.renders);
+        let mut renderer = Renderer::new(self.render_path);
+        self.scheduler.invoke(SystemType::Startup, &mut window, &mut self.scene);
 
+        let mut last_frame = Instant::now();
         while !window.should_close() {
-            window.clear(0.07, 0.17, 0.07, 1.0);
-            self.scheduler.invoke(SystemType::Update, &mut window, &mut self.renders);
-            for render in self.renders.iter() {
-                render.render();
-            }
-            window.update();
+            let now = Instant::now();
+            let delta = now.duration_since(last_frame).as_secs_f32();
+            last_frame = now;
+
+            self.frame(&mut window, &mut renderer, delta);
         }
+        window.finish_captures();
+    }
+
+    /// Runs the engine without a display for a fixed number of frames.
+    ///
+    /// Startup systems run once, then `frames` frames of update systems and rendering,
+    /// each advancing `Scene::time` by `1/60` s so runs are reproducible. Meant for
+    /// automated image tests, see `foux::testing`.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the offscreen frame in pixels.
+    /// * `height` - Height of the offscreen frame in pixels.
+    /// * `frames` - How many frames to run; at least one is always rendered.
+    /// * `api` - The context API to render with.
+    ///
+    /// # Returns
+    ///
+    /// The last rendered frame, or an `Err(String)` if no headless context could be created.
+    pub fn run_headless(&mut self, width: u32, height: u32, frames: u32, api: HeadlessApi) -> Result<RgbaImage, String> {
+        let mut window = Window::headless(width, height, api)?;
+        let mut renderer = Renderer::new(self.render_path);
+        self.scheduler.invoke(SystemType::Startup, &mut window, &mut self.scene);
+
+        for _ in 0..frames.max(1) {
+            self.frame(&mut window, &mut renderer, HEADLESS_TIMESTEP);
+        }
+        let frame = window.read_pixels();
+        window.finish_captures();
+        Ok(frame)
+    }
+
+    fn frame(&mut self, window: &mut Window, renderer: &mut Renderer, delta: f32) {
+        let delta = window.get_recording().map_or(delta, |recording| recording.get_timestep());
+        self.scene.time.advance(delta);
+
+        let profiler = &self.scene.profiler;
+        profiler.begin("frame");
+        window.clear_with(&self.clear_state);
+
+        profiler.begin("assets");
+        self.scene.assets.update();
+        profiler.end();
+
+        profiler.begin("update");
+        self.scheduler.invoke(SystemType::Update, window, &mut self.scene);
+        self.scene.profiler.end();
+        self.scene.update_world();
+
+        let (width, height) = window.get_framebuffer_size();
+        if height > 0 {
+            self.scene.get_camera_mut().set_aspect(width as f32 / height as f32);
+        }
+
+        let render_scope = self.scene.profiler.scope("render");
+        let output = window.get_output_framebuffer();
+        renderer.render(&self.scene, output, width.max(0) as u32, height.max(0) as u32);
+        drop(render_scope);
+        self.scene.profiler.end();
+
+        window.update();
+        self.scene.profiler.end_frame();
     }
 }
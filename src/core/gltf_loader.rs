@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+
+use gltf::animation::util::ReadOutputs;
+use gltf::image::Format;
+use gltf::khr_lights_punctual::Kind;
+use gltf::mesh::Mode;
+use gltf::texture::{MagFilter, WrappingMode};
+use nalgebra_glm::{Mat4, Quat, Vec2, Vec3, Vec4};
+
+use crate::core::animation::{AnimationChannel, AnimationClip, ChannelValues, Interpolation, Skin};
+use crate::core::assets::{AssetServer, Handle};
+use crate::core::components::{MeshRenderer, MorphWeights, Transform};
+use crate::core::ecs::{Entity, Name, World};
+use crate::core::material::{AlphaMode, Material, PbrMaterial};
+use crate::core::mesh::{Mesh, MeshBuilder, MorphTarget};
+use crate::core::{Camera, Light};
+use crate::opengl::{CullMode, FrontFace, Texture, TextureFilter, TextureFormat, TextureWrap, Topology};
+
+/// Far plane used for glTF cameras with an infinite projection.
+const INFINITE_FAR_PLANE: f32 = 1000.0;
+
+/// The entities and animations spawned from a glTF 2.0 file.
+///
+/// Every node becomes an entity with a `Name` and a `Transform`, parented like in the
+/// file. Nodes carry their `Camera`, `Light` (`KHR_lights_punctual`) components, and
+/// each primitive of a node's mesh becomes a child entity with a `MeshRenderer`, plus
+/// the node's `Skin` when it has one. Nodes with morph targets get `MorphWeights`.
+///
+/// Nodes instancing the same glTF mesh share its uploaded `Mesh` handles, and
+/// primitives using the same glTF material share one `Handle<Material>`.
+pub struct GltfScene {
+    /// An entity parenting the scene's root nodes, named after the scene or the file.
+    pub root: Entity,
+    /// The entity of each node, indexed like the file's nodes. Nodes outside the spawned
+    /// scene are `None`.
+    pub nodes: Vec<Option<Entity>>,
+    /// The node entities with a `Camera`, in node order.
+    pub cameras: Vec<Entity>,
+    /// Every animation in the file, targeting the node entities, ready for an
+    /// `AnimationPlayer`.
+    pub animations: Vec<Rc<AnimationClip>>,
+}
+
+/// Converts the contents of a loaded glTF file into engine objects.
+struct Importer<'a> {
+    path: &'a Path,
+    origin: String,
+    document: &'a gltf::Document,
+    buffers: &'a [gltf::buffer::Data],
+    images: &'a [gltf::image::Data],
+    assets: &'a mut AssetServer,
+    /// Uploaded primitives, keyed by glTF mesh and primitive index.
+    meshes: HashMap<(usize, usize), (Handle<Mesh>, Topology)>,
+    /// Converted materials, keyed by glTF material index, `None` for the default material.
+    materials: HashMap<Option<usize>, Handle<Material>>,
+}
+
+impl GltfScene {
+    /// Loads a `.gltf` file with its buffers and images, or a binary `.glb`, and
+    /// spawns its default scene into `world`.
+    ///
+    /// Files without a default scene spawn their first scene, or every root node if
+    /// they have no scenes. Only the first texture coordinate and color sets are used,
+    /// and spot lights are imported as point lights.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file. External buffers and images are resolved next to it.
+    /// * `world` - The world to spawn the entities into.
+    /// * `assets` - The asset server that owns the uploaded meshes, materials and
+    ///   textures. Textures are labeled `texture{index}` after the file, with `-linear`
+    ///   appended for the linear variant, and shared by later loads of the same file.
+    ///
+    /// # Returns
+    ///
+    /// The spawned entities and animations, or an `Err(String)` if the file cannot be
+    /// read or is malformed. Nothing is spawned on error.
+    pub fn load<P: AsRef<Path>>(path: P, world: &mut World, assets: &mut AssetServer) -> Result<GltfScene, String> {
+        let path = path.as_ref();
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|err| format!("Cannot load {}: {err}", path.display()))?;
+
+        let mut importer = Importer {
+            path,
+            origin: path.display().to_string(),
+            document: &document,
+            buffers: &buffers,
+            images: &images,
+            assets,
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+        };
+        let name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        importer.spawn(world, &name)
+    }
+
+    /// Finds an animation by name.
+    pub fn get_animation(&self, name: &str) -> Option<&Rc<AnimationClip>> {
+        self.animations.iter().find(|animation| animation.name == name)
+    }
+}
+
+impl Importer<'_> {
+    fn spawn(&mut self, world: &mut World, name: &str) -> Result<GltfScene, String> {
+        let scene = self.document.default_scene().or_else(|| self.document.scenes().next());
+        let roots: Vec<gltf::Node> = match &scene {
+            Some(scene) => scene.nodes().collect(),
+            None => {
+                let children: Vec<usize> =
+                    self.document.nodes().flat_map(|node| node.children().map(|child| child.index())).collect();
+                self.document.nodes().filter(|node| !children.contains(&node.index())).collect()
+            }
+        };
+
+        let root = world.spawn();
+        let scene_name = scene.as_ref().and_then(|scene| scene.name()).unwrap_or(name);
+        world.insert(root, Name(scene_name.to_string()));
+        world.insert(root, Transform::new());
+
+        // Spawn the hierarchy first, so skins and animations can refer to any node.
+        let mut nodes = vec![None; self.document.nodes().len()];
+        let mut globals = vec![Mat4::identity(); nodes.len()];
+        let mut pending: Vec<(gltf::Node, Entity, Mat4)> =
+            roots.into_iter().rev().map(|node| (node, root, Mat4::identity())).collect();
+        while let Some((node, parent, parent_global)) = pending.pop() {
+            if nodes[node.index()].is_some() {
+                continue;
+            }
+            let entity = world.spawn();
+            let transform = node_transform(&node);
+            world.insert(entity, Name(node.name().map_or_else(|| format!("node{}", node.index()), str::to_string)));
+            world.insert(entity, transform);
+            world.set_parent(entity, parent);
+
+            let global = parent_global * transform.matrix();
+            nodes[node.index()] = Some(entity);
+            globals[node.index()] = global;
+            let children: Vec<gltf::Node> = node.children().collect();
+            pending.extend(children.into_iter().rev().map(|child| (child, entity, global)));
+        }
+
+        let mut cameras = Vec::new();
+        for node in self.document.nodes() {
+            let Some(entity) = nodes[node.index()] else { continue };
+            if let Err(err) = self.attach(world, &node, entity, &nodes, &globals, &mut cameras) {
+                world.despawn(root);
+                return Err(err);
+            }
+        }
+
+        let animations =
+            self.document.animations().map(|animation| Rc::new(self.animation(&animation, &nodes))).collect();
+        Ok(GltfScene { root, nodes, cameras, animations })
+    }
+
+    /// Adds a node's camera, light and mesh primitives to its entity.
+    fn attach(
+        &mut self,
+        world: &mut World,
+        node: &gltf::Node,
+        entity: Entity,
+        nodes: &[Option<Entity>],
+        globals: &[Mat4],
+        cameras: &mut Vec<Entity>,
+    ) -> Result<(), String> {
+        if let Some(camera) = node.camera() {
+            world.insert(entity, create_camera(&camera));
+            cameras.push(entity);
+        }
+        if let Some(light) = node.light() {
+            world.insert(entity, create_light(&light));
+        }
+
+        let Some(mesh) = node.mesh() else { return Ok(()) };
+        let skin = node.skin().and_then(|skin| self.skin(&skin, nodes));
+        // Mirroring transforms turn counter-clockwise triangles clockwise.
+        let mirrored = globals[node.index()].determinant() < 0.0;
+        let mesh_name = mesh.name().map_or_else(|| format!("mesh{}", mesh.index()), str::to_string);
+
+        for (index, primitive) in mesh.primitives().enumerate() {
+            let mut render = self.primitive(&mesh, &primitive)?;
+            if mirrored {
+                let mut pipeline_state = *render.get_pipeline_state();
+                pipeline_state.front_face = FrontFace::Clockwise;
+                render.set_pipeline_state(pipeline_state);
+            }
+
+            let child = world.spawn();
+            world.insert(child, Name(format!("{mesh_name}.{index}")));
+            world.insert(child, Transform::new());
+            world.insert(child, render);
+            if let Some(skin) = &skin {
+                world.insert(child, skin.clone());
+            }
+            world.set_parent(child, entity);
+        }
+
+        let target_count = mesh.primitives().map(|primitive| primitive.morph_targets().len()).max().unwrap_or(0);
+        if target_count > 0 {
+            let mut weights = node.weights().or(mesh.weights()).map_or_else(Vec::new, <[f32]>::to_vec);
+            weights.resize(target_count, 0.0);
+            world.insert(entity, MorphWeights(weights));
+        }
+        Ok(())
+    }
+
+    /// Creates a renderer for a mesh primitive, uploading the primitive and its material
+    /// the first time they are used.
+    fn primitive(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive) -> Result<MeshRenderer, String> {
+        let key = (mesh.index(), primitive.index());
+        let (geometry, topology) = match self.meshes.get(&key) {
+            Some(uploaded) => uploaded.clone(),
+            None => {
+                let uploaded = self.geometry(mesh, primitive)?;
+                self.meshes.insert(key, uploaded.clone());
+                uploaded
+            }
+        };
+        let material = primitive.material();
+        let handle = match self.materials.get(&material.index()) {
+            Some(handle) => handle.clone(),
+            None => {
+                let converted = self.material(&material)?;
+                let handle = self.assets.add(Material::from(converted));
+                self.materials.insert(material.index(), handle.clone());
+                handle
+            }
+        };
+
+        let mut render = MeshRenderer::new(geometry, handle);
+        render.set_topology(topology);
+        if !material.double_sided() {
+            let mut pipeline_state = *render.get_pipeline_state();
+            pipeline_state.cull_mode = CullMode::Back;
+            render.set_pipeline_state(pipeline_state);
+        }
+        Ok(render)
+    }
+
+    /// Uploads the vertices and indices of a mesh primitive.
+    fn geometry(&mut self, mesh: &gltf::Mesh, primitive: &gltf::Primitive) -> Result<(Handle<Mesh>, Topology), String> {
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let positions = reader.read_positions().ok_or_else(|| {
+            format!("{}: primitive {} of mesh {} has no positions", self.origin, primitive.index(), mesh.index())
+        })?;
+
+        let mut builder = MeshBuilder::new();
+        builder.positions = positions.map(Vec3::from).collect();
+        if let Some(normals) = reader.read_normals() {
+            builder.normals = normals.map(Vec3::from).collect();
+        }
+        if let Some(uvs) = reader.read_tex_coords(0) {
+            builder.uvs = uvs.into_f32().map(Vec2::from).collect();
+        }
+        if let Some(tangents) = reader.read_tangents() {
+            builder.tangents = tangents.map(Vec4::from).collect();
+        }
+        if let Some(colors) = reader.read_colors(0) {
+            builder.colors = colors.into_rgba_f32().map(Vec4::from).collect();
+        }
+        if let (Some(joints), Some(weights)) = (reader.read_joints(0), reader.read_weights(0)) {
+            builder.joints = joints.into_u16().map(|joints| Vec4::from(joints.map(f32::from))).collect();
+            builder.weights = weights.into_f32().map(Vec4::from).collect();
+        }
+        for (index, (positions, normals, _)) in reader.read_morph_targets().enumerate() {
+            let mut target = MorphTarget::new(&format!("target{index}"));
+            target.positions = match positions {
+                Some(positions) => positions.map(Vec3::from).collect(),
+                None => vec![Vec3::zeros(); builder.vertex_count()],
+            };
+            if let Some(normals) = normals {
+                target.normals = normals.map(Vec3::from).collect();
+            }
+            builder.morph_targets.push(target);
+        }
+
+        let count = builder.vertex_count();
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count as u32).collect(),
+        };
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= count) {
+            return Err(format!(
+                "{}: primitive {} of mesh {} indexes vertex {index} of {count}",
+                self.origin,
+                primitive.index(),
+                mesh.index()
+            ));
+        }
+
+        let topology = match primitive.mode() {
+            Mode::Points => Topology::Points,
+            Mode::Lines => Topology::Lines,
+            Mode::LineStrip => Topology::LineStrip,
+            Mode::LineLoop => Topology::LineLoop,
+            Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan => Topology::Triangles,
+        };
+        builder.indices = match primitive.mode() {
+            Mode::TriangleStrip => strip_to_list(&indices),
+            Mode::TriangleFan => fan_to_list(&indices),
+            _ => indices,
+        };
+
+        if topology == Topology::Triangles {
+            // glTF asks for flat normals when they are missing.
+            if builder.normals.is_empty() {
+                builder.compute_flat_normals();
+            }
+            if builder.tangents.is_empty() && primitive.material().normal_texture().is_some() {
+                builder.compute_tangents();
+            }
+        } else if builder.normals.is_empty() {
+            builder.normals = vec![Vec3::y(); count];
+        }
+
+        Ok((self.assets.add(builder.to_mesh()), topology))
+    }
+
+    fn material(&mut self, material: &gltf::Material) -> Result<PbrMaterial, String> {
+        let pbr = material.pbr_metallic_roughness();
+        let mut result = PbrMaterial::new();
+        result.albedo_factor = Vec4::from(pbr.base_color_factor());
+        result.metallic_factor = pbr.metallic_factor();
+        result.roughness_factor = pbr.roughness_factor();
+        result.emissive_factor = Vec3::from(material.emissive_factor()) * material.emissive_strength().unwrap_or(1.0);
+        result.alpha_mode = match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        };
+
+        if let Some(albedo) = pbr.base_color_texture() {
+            result.albedo_map = Some(self.texture(&albedo.texture(), true)?);
+        }
+        if let Some(metallic_roughness) = pbr.metallic_roughness_texture() {
+            result.metallic_roughness_map = Some(self.texture(&metallic_roughness.texture(), false)?);
+        }
+        if let Some(emissive) = material.emissive_texture() {
+            result.emissive_map = Some(self.texture(&emissive.texture(), true)?);
+        }
+        if let Some(normal) = material.normal_texture() {
+            result.normal_map = Some(self.texture(&normal.texture(), false)?);
+            result.normal_scale = normal.scale();
+        }
+        if let Some(occlusion) = material.occlusion_texture() {
+            result.occlusion_map = Some(self.texture(&occlusion.texture(), false)?);
+            result.occlusion_strength = occlusion.strength();
+        }
+        Ok(result)
+    }
+
+    /// Returns a texture with its sampler settings, uploading it the first time it is
+    /// used in each color space.
+    fn texture(&mut self, texture: &gltf::Texture, srgb: bool) -> Result<Handle<Texture>, String> {
+        let label = format!("texture{}{}", texture.index(), if srgb { "" } else { "-linear" });
+        let image = &self.images[texture.source().index()];
+        self.assets.load_labeled(self.path, &label, || Ok(upload_texture(texture, image, srgb)))
+    }
+
+    /// Returns the skin, or `None` if some of its joints are outside the spawned scene.
+    fn skin(&self, skin: &gltf::Skin, nodes: &[Option<Entity>]) -> Option<Skin> {
+        let Some(joints) = skin.joints().map(|joint| nodes[joint.index()]).collect::<Option<Vec<Entity>>>() else {
+            log::warn!("{}: skin {} has joints outside the scene and was skipped", self.origin, skin.index());
+            return None;
+        };
+
+        let buffers = self.buffers;
+        let reader = skin.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+        let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+            Some(matrices) => matrices.map(Mat4::from).collect(),
+            None => vec![Mat4::identity(); joints.len()],
+        };
+        Some(Skin { joints, inverse_bind_matrices })
+    }
+
+    fn animation(&self, animation: &gltf::Animation, nodes: &[Option<Entity>]) -> AnimationClip {
+        let mut clip = AnimationClip {
+            name: animation.name().map_or_else(|| format!("animation{}", animation.index()), str::to_string),
+            ..AnimationClip::default()
+        };
+
+        let buffers = self.buffers;
+        for channel in animation.channels() {
+            let Some(target) = nodes[channel.target().node().index()] else { continue };
+            let reader = channel.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+            let (Some(inputs), Some(outputs)) = (reader.read_inputs(), reader.read_outputs()) else { continue };
+
+            let times: Vec<f32> = inputs.collect();
+            let values = match outputs {
+                ReadOutputs::Translations(values) => ChannelValues::Translation(values.map(Vec3::from).collect()),
+                ReadOutputs::Rotations(values) => {
+                    ChannelValues::Rotation(values.into_f32().map(|[x, y, z, w]| Quat::new(w, x, y, z)).collect())
+                }
+                ReadOutputs::Scales(values) => ChannelValues::Scale(values.map(Vec3::from).collect()),
+                ReadOutputs::MorphTargetWeights(values) => ChannelValues::Weights(values.into_f32().collect()),
+            };
+            let interpolation = match channel.sampler().interpolation() {
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+
+            clip.duration = clip.duration.max(times.last().copied().unwrap_or(0.0));
+            clip.channels.push(AnimationChannel { target, interpolation, times, values });
+        }
+        clip
+    }
+}
+
+fn node_transform(node: &gltf::Node) -> Transform {
+    let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+    Transform {
+        translation: Vec3::from(translation),
+        rotation: Quat::new(w, x, y, z),
+        scale: Vec3::from(scale),
+    }
+}
+
+fn create_camera(camera: &gltf::Camera) -> Camera {
+    let mut result = Camera::default();
+    match camera.projection() {
+        gltf::camera::Projection::Perspective(perspective) => result.set_perspective(
+            perspective.yfov(),
+            perspective.znear(),
+            perspective.zfar().unwrap_or(INFINITE_FAR_PLANE),
+        ),
+        gltf::camera::Projection::Orthographic(orthographic) => {
+            result.set_orthographic(orthographic.ymag() * 2.0, orthographic.znear(), orthographic.zfar())
+        }
+    }
+    result
+}
+
+/// Creates a light shining down the node's `-Z` axis, or placed at its origin.
+fn create_light(light: &gltf::khr_lights_punctual::Light) -> Light {
+    let color = Vec3::from(light.color());
+    match light.kind() {
+        Kind::Directional => Light::directional(-Vec3::z(), color, light.intensity()),
+        Kind::Point | Kind::Spot { .. } => {
+            Light::point(Vec3::zeros(), light.range().unwrap_or(0.0), color, light.intensity())
+        }
+    }
+}
+
+/// Uploads the image of a texture with its sampler settings.
+fn upload_texture(texture: &gltf::Texture, image: &gltf::image::Data, srgb: bool) -> Texture {
+    let format = if srgb { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+    let uploaded = Texture::from_data(image.width, image.height, format, &to_rgba8(image));
+
+    let sampler = texture.sampler();
+    uploaded.bind(0);
+    uploaded.set_wrap(match sampler.wrap_s() {
+        WrappingMode::ClampToEdge => TextureWrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => TextureWrap::MirroredRepeat,
+        WrappingMode::Repeat => TextureWrap::Repeat,
+    });
+    if sampler.mag_filter() == Some(MagFilter::Nearest) {
+        uploaded.set_filter(TextureFilter::NearestMipmapNearest, TextureFilter::Nearest);
+    }
+    uploaded.unbind(0);
+    if let Some(name) = texture.name().or(texture.source().name()) {
+        uploaded.set_label(name);
+    }
+    uploaded
+}
+
+/// Converts any decoded image to 8-bit RGBA. Gray images are replicated into RGB.
+fn to_rgba8(image: &gltf::image::Data) -> Vec<u8> {
+    let (channels, size) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let component = |bytes: &[u8]| -> u8 {
+        match bytes.len() {
+            2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            4 => (f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).clamp(0.0, 1.0) * 255.0).round() as u8,
+            _ => bytes[0],
+        }
+    };
+
+    let mut rgba = Vec::with_capacity(image.width as usize * image.height as usize * 4);
+    for texel in image.pixels.chunks_exact(channels * size) {
+        let values: Vec<u8> = texel.chunks_exact(size).map(component).collect();
+        match values[..] {
+            [gray] => rgba.extend_from_slice(&[gray, gray, gray, 255]),
+            [gray, alpha] => rgba.extend_from_slice(&[gray, gray, gray, alpha]),
+            [red, green, blue] => rgba.extend_from_slice(&[red, green, blue, 255]),
+            _ => rgba.extend_from_slice(&values),
+        }
+    }
+    rgba
+}
+
+/// Converts triangle strip indices to a triangle list, keeping every triangle's winding.
+fn strip_to_list(indices: &[u32]) -> Vec<u32> {
+    let mut list = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+    for (index, window) in indices.windows(3).enumerate() {
+        if index % 2 == 0 {
+            list.extend_from_slice(&[window[0], window[1], window[2]]);
+        } else {
+            list.extend_from_slice(&[window[1], window[0], window[2]]);
+        }
+    }
+    list
+}
+
+/// Converts triangle fan indices to a triangle list.
+fn fan_to_list(indices: &[u32]) -> Vec<u32> {
+    let mut list = Vec::with_capacity(indices.len().saturating_sub(2) * 3);
+    for window in indices.get(1..).unwrap_or_default().windows(2) {
+        list.extend_from_slice(&[indices[0], window[0], window[1]]);
+    }
+    list
+}
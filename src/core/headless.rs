@@ -0,0 +1,194 @@
+use khronos_egl as egl;
+
+use std::ffi::{c_char, c_int, c_uint, c_void, CString};
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, a display with no window system behind it.
+const EGL_PLATFORM_SURFACELESS_MESA: egl::Enum = 0x31DD;
+
+/// OSMesa context attributes and values, see `GL/osmesa.h`.
+const OSMESA_FORMAT: c_int = 0x22;
+const OSMESA_RGBA: c_int = 0x1908;
+const OSMESA_DEPTH_BITS: c_int = 0x30;
+const OSMESA_STENCIL_BITS: c_int = 0x31;
+const OSMESA_PROFILE: c_int = 0x33;
+const OSMESA_CORE_PROFILE: c_int = 0x34;
+const OSMESA_CONTEXT_MAJOR_VERSION: c_int = 0x36;
+const OSMESA_CONTEXT_MINOR_VERSION: c_int = 0x37;
+
+type OsMesaContext = *mut c_void;
+type OsMesaCreateContextAttribs = unsafe extern "C" fn(*const c_int, OsMesaContext) -> OsMesaContext;
+type OsMesaMakeCurrent = unsafe extern "C" fn(OsMesaContext, *mut c_void, c_uint, c_int, c_int) -> u8;
+type OsMesaDestroyContext = unsafe extern "C" fn(OsMesaContext);
+type OsMesaGetProcAddress = unsafe extern "C" fn(*const c_char) -> *const c_void;
+
+/// Selects the context API a headless window is created with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeadlessApi {
+    /// EGL without a window surface, e.g. Mesa's surfaceless platform on llvmpipe.
+    #[default]
+    Egl,
+    /// Mesa's OSMesa, rendering in software into client memory.
+    OsMesa,
+}
+
+/// An OpenGL 3.3 core context created without a window system.
+///
+/// The context stays current on the thread that created it until it is dropped. Both
+/// APIs are loaded at runtime, so machines without them only fail when one is requested.
+pub(crate) enum HeadlessContext {
+    Egl {
+        egl: Box<egl::DynamicInstance<egl::EGL1_5>>,
+        display: egl::Display,
+        context: egl::Context,
+    },
+    OsMesa {
+        library: libloading::Library,
+        context: OsMesaContext,
+        // The default framebuffer OSMesa renders into; frames go to an offscreen one.
+        _buffer: Vec<u8>,
+    },
+}
+
+impl HeadlessContext {
+    /// Creates a context with the given API, makes it current and loads the OpenGL
+    /// functions from it.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the context's default framebuffer, where it has one.
+    /// * `height` - The height of the context's default framebuffer, where it has one.
+    /// * `api` - The context API to create the context with.
+    ///
+    /// # Returns
+    ///
+    /// The current context, or an `Err(String)` if the API's library is missing or
+    /// refuses to create an OpenGL 3.3 core context.
+    pub(crate) fn new(width: u32, height: u32, api: HeadlessApi) -> Result<HeadlessContext, String> {
+        match api {
+            HeadlessApi::Egl => HeadlessContext::egl(),
+            HeadlessApi::OsMesa => HeadlessContext::osmesa(width, height),
+        }
+    }
+
+    fn egl() -> Result<HeadlessContext, String> {
+        let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+            .map_err(|error| format!("Couldn't load EGL 1.5: {error}"))?;
+        let display = unsafe {
+            egl.get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, egl::DEFAULT_DISPLAY, &[egl::ATTRIB_NONE])
+        }
+        .map_err(|error| format!("Couldn't open a surfaceless EGL display: {error}"))?;
+        egl.initialize(display).map_err(|error| format!("Couldn't initialize EGL: {error}"))?;
+
+        let context = HeadlessContext::egl_context(&egl, display);
+        let context = match context {
+            Ok(context) => context,
+            Err(error) => {
+                let _ = egl.terminate(display);
+                return Err(error);
+            }
+        };
+        gl::load_with(|name| egl.get_proc_address(name).map_or(std::ptr::null(), |function| function as *const _));
+        Ok(HeadlessContext::Egl {
+            egl: Box::new(egl),
+            display,
+            context,
+        })
+    }
+
+    fn egl_context(egl: &egl::DynamicInstance<egl::EGL1_5>, display: egl::Display) -> Result<egl::Context, String> {
+        egl.bind_api(egl::OPENGL_API)
+            .map_err(|error| format!("EGL has no desktop OpenGL: {error}"))?;
+        let config = egl
+            .choose_first_config(display, &[egl::RENDERABLE_TYPE, egl::OPENGL_BIT, egl::SURFACE_TYPE, 0, egl::NONE])
+            .map_err(|error| format!("Couldn't choose an EGL config: {error}"))?
+            .ok_or("EGL has no surfaceless OpenGL config")?;
+
+        let debug = if cfg!(debug_assertions) { egl::TRUE } else { egl::FALSE } as egl::Int;
+        #[rustfmt::skip]
+        let attributes = [
+            egl::CONTEXT_MAJOR_VERSION, 3,
+            egl::CONTEXT_MINOR_VERSION, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::CONTEXT_OPENGL_DEBUG, debug,
+            egl::NONE,
+        ];
+        let context = egl
+            .create_context(display, config, None, &attributes)
+            .map_err(|error| format!("Couldn't create an OpenGL 3.3 core context with EGL: {error}"))?;
+        if let Err(error) = egl.make_current(display, None, None, Some(context)) {
+            let _ = egl.destroy_context(display, context);
+            return Err(format!("Couldn't make the EGL context current: {error}"));
+        }
+        Ok(context)
+    }
+
+    fn osmesa(width: u32, height: u32) -> Result<HeadlessContext, String> {
+        let library = ["libOSMesa.so.8", "libOSMesa.so", "osmesa.dll"]
+            .into_iter()
+            .find_map(|name| unsafe { libloading::Library::new(name) }.ok())
+            .ok_or("Couldn't load OSMesa")?;
+
+        let (create, make_current, destroy, get_proc_address) = unsafe {
+            let create = library.get::<OsMesaCreateContextAttribs>(b"OSMesaCreateContextAttribs\0");
+            let make_current = library.get::<OsMesaMakeCurrent>(b"OSMesaMakeCurrent\0");
+            let destroy = library.get::<OsMesaDestroyContext>(b"OSMesaDestroyContext\0");
+            let get_proc_address = library.get::<OsMesaGetProcAddress>(b"OSMesaGetProcAddress\0");
+            match (create, make_current, destroy, get_proc_address) {
+                (Ok(create), Ok(make_current), Ok(destroy), Ok(get_proc_address)) => {
+                    (*create, *make_current, *destroy, *get_proc_address)
+                }
+                _ => return Err(String::from("OSMesa is too old to create core profile contexts")),
+            }
+        };
+
+        #[rustfmt::skip]
+        let attributes = [
+            OSMESA_FORMAT, OSMESA_RGBA,
+            OSMESA_DEPTH_BITS, 24,
+            OSMESA_STENCIL_BITS, 8,
+            OSMESA_PROFILE, OSMESA_CORE_PROFILE,
+            OSMESA_CONTEXT_MAJOR_VERSION, 3,
+            OSMESA_CONTEXT_MINOR_VERSION, 3,
+            0,
+        ];
+        let context = unsafe { create(attributes.as_ptr(), std::ptr::null_mut()) };
+        if context.is_null() {
+            return Err(String::from("Couldn't create an OpenGL 3.3 core context with OSMesa"));
+        }
+
+        let mut buffer = vec![0; width as usize * height as usize * 4];
+        let pixels = buffer.as_mut_ptr() as *mut c_void;
+        let current = unsafe { make_current(context, pixels, gl::UNSIGNED_BYTE, width as c_int, height as c_int) };
+        if current == 0 {
+            unsafe { destroy(context) };
+            return Err(String::from("Couldn't make the OSMesa context current"));
+        }
+
+        gl::load_with(|name| match CString::new(name) {
+            Ok(name) => unsafe { get_proc_address(name.as_ptr()) },
+            Err(_) => std::ptr::null(),
+        });
+        Ok(HeadlessContext::OsMesa {
+            library,
+            context,
+            _buffer: buffer,
+        })
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        match self {
+            HeadlessContext::Egl { egl, display, context } => {
+                let _ = egl.make_current(*display, None, None, None);
+                let _ = egl.destroy_context(*display, *context);
+                let _ = egl.terminate(*display);
+            }
+            HeadlessContext::OsMesa { library, context, .. } => unsafe {
+                if let Ok(destroy) = library.get::<OsMesaDestroyContext>(b"OSMesaDestroyContext\0") {
+                    destroy(*context);
+                }
+            },
+        }
+    }
+}
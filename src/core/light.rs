@@ -0,0 +1,70 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+/// The shape of the light emitted by a `Light`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    /// Parallel rays travelling along `direction`, like sunlight.
+    Directional { direction: Vec3 },
+    /// Light radiating from `position`, fading out completely at `range`.
+    Point { position: Vec3, range: f32 },
+}
+
+/// A punctual light source used by the built-in lit materials.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    /// The kind of light and its placement.
+    pub kind: LightKind,
+    /// Linear RGB color of the light.
+    pub color: Vec3,
+    /// Brightness multiplier applied to `color`.
+    pub intensity: f32,
+}
+
+impl Light {
+    /// Creates a directional light shining along `direction`.
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Directional { direction: direction.normalize() },
+            color,
+            intensity,
+        }
+    }
+
+    /// Creates a point light at `position` that reaches up to `range` units.
+    pub fn point(position: Vec3, range: f32, color: Vec3, intensity: f32) -> Light {
+        Light {
+            kind: LightKind::Point { position, range },
+            color,
+            intensity,
+        }
+    }
+
+    /// Returns the light moved by `transform`.
+    ///
+    /// Lights attached to entities are placed this way: their direction and position
+    /// are relative to the entity's `GlobalTransform`.
+    pub fn transformed(&self, transform: &Mat4) -> Light {
+        let kind = match self.kind {
+            LightKind::Directional { direction } => LightKind::Directional {
+                direction: (transform * direction.push(0.0)).xyz().normalize(),
+            },
+            LightKind::Point { position, range } => LightKind::Point {
+                position: (transform * position.push(1.0)).xyz(),
+                range,
+            },
+        };
+        Light { kind, ..*self }
+    }
+
+    /// Packs the light into the two `vec4`s the built-in shaders read.
+    ///
+    /// The first holds the direction (`w = 0`) or position (`w = 1`); the second holds
+    /// the pre-multiplied color with the range in `w` (`0` for directional lights).
+    pub(crate) fn pack(&self) -> (Vec4, Vec4) {
+        let color = self.color * self.intensity;
+        match self.kind {
+            LightKind::Directional { direction } => (direction.push(0.0), color.push(0.0)),
+            LightKind::Point { position, range } => (position.push(1.0), color.push(range)),
+        }
+    }
+}
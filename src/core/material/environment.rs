@@ -0,0 +1,379 @@
+use std::cell::{Cell, RefCell};
+use std::f32::consts::FRAC_PI_2;
+use std::rc::{Rc, Weak};
+
+use nalgebra_glm::{Mat4, Vec3};
+
+use crate::core::assets::Handle;
+use crate::core::primitives::{fullscreen_triangle, unit_cube, FULLSCREEN_VERTEX_SHADER};
+use crate::device::{DrawCall, ExternalTexture, RenderDevice, RenderTarget, ShaderHandle, TextureHandle, UniformValue};
+use crate::opengl::{Attachment, Cubemap, PipelineState, Texture, TextureFormat};
+
+/// Edge length of each irradiance map face.
+const IRRADIANCE_SIZE: u32 = 32;
+/// Edge length of each prefiltered map face at mip level 0.
+const PREFILTERED_SIZE: u32 = 128;
+/// Number of roughness levels stored in the prefiltered map's mip chain.
+const PREFILTERED_LEVELS: u32 = 5;
+/// Edge length of the BRDF lookup table.
+const BRDF_LUT_SIZE: u32 = 512;
+
+const CAPTURE_VERTEX_SHADER: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_position;
+
+uniform mat4 u_view;
+uniform mat4 u_projection;
+
+out vec3 v_direction;
+
+void main() {
+    v_direction = a_position;
+    gl_Position = u_projection * u_view * vec4(a_position, 1.0);
+}
+"#;
+
+const IRRADIANCE_FRAGMENT_SHADER: &str = r#"
+#version 330 core
+const float PI = 3.14159265359;
+
+in vec3 v_direction;
+
+uniform samplerCube u_environment;
+
+out vec4 frag_color;
+
+void main() {
+    vec3 n = normalize(v_direction);
+    vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 right = normalize(cross(up, n));
+    up = normalize(cross(n, right));
+
+    vec3 irradiance = vec3(0.0);
+    float samples = 0.0;
+    for (float phi = 0.0; phi < 2.0 * PI; phi += 0.025) {
+        for (float theta = 0.0; theta < 0.5 * PI; theta += 0.025) {
+            vec3 tangent = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            vec3 direction = tangent.x * right + tangent.y * up + tangent.z * n;
+            irradiance += texture(u_environment, direction).rgb * cos(theta) * sin(theta);
+            samples += 1.0;
+        }
+    }
+
+    frag_color = vec4(PI * irradiance / samples, 1.0);
+}
+"#;
+
+const IMPORTANCE_SAMPLING_GLSL: &str = r#"
+const float PI = 3.14159265359;
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint i, uint count) {
+    return vec2(float(i) / float(count), radical_inverse(i));
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+    float a = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, n));
+    vec3 bitangent = cross(n, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+}
+"#;
+
+const PREFILTER_FRAGMENT_SHADER: &str = r#"
+const uint SAMPLE_COUNT = 512u;
+
+in vec3 v_direction;
+
+uniform samplerCube u_environment;
+uniform float u_roughness;
+uniform float u_resolution;
+
+out vec4 frag_color;
+
+void main() {
+    vec3 n = normalize(v_direction);
+    vec3 v = n;
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec3 h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), n, u_roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = max(dot(n, l), 0.0);
+        if (n_dot_l > 0.0) {
+            // Sample a blurrier mip for unlikely directions to avoid bright speckles.
+            float n_dot_h = max(dot(n, h), 0.0);
+            float a2 = pow(u_roughness, 4.0);
+            float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+            float pdf = a2 / (PI * d * d) / 4.0 + 1e-4;
+            float texel_angle = 4.0 * PI / (6.0 * u_resolution * u_resolution);
+            float sample_angle = 1.0 / (float(SAMPLE_COUNT) * pdf + 1e-4);
+            float lod = u_roughness == 0.0 ? 0.0 : 0.5 * log2(sample_angle / texel_angle);
+
+            color += textureLod(u_environment, l, lod).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    frag_color = vec4(color / weight, 1.0);
+}
+"#;
+
+const BRDF_FRAGMENT_SHADER: &str = r#"
+const uint SAMPLE_COUNT = 1024u;
+
+in vec2 v_uv;
+
+out vec2 frag_color;
+
+float geometry_schlick_ggx(float n_dot_x, float roughness) {
+    float k = roughness * roughness / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+void main() {
+    float n_dot_v = max(v_uv.x, 1e-3);
+    float roughness = v_uv.y;
+    vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    vec3 n = vec3(0.0, 0.0, 1.0);
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+        vec3 h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), n, roughness);
+        vec3 l = normalize(2.0 * dot(v, h) * h - v);
+        float n_dot_l = max(l.z, 0.0);
+        float n_dot_h = max(h.z, 0.0);
+        float v_dot_h = max(dot(v, h), 0.0);
+        if (n_dot_l > 0.0) {
+            float geometry = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            float visibility = geometry * v_dot_h / (n_dot_h * n_dot_v);
+            float fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * visibility;
+            bias += fresnel * visibility;
+        }
+    }
+
+    frag_color = vec2(scale, bias) / float(SAMPLE_COUNT);
+}
+"#;
+
+/// The split-sum BRDF lookup table shared by every environment.
+struct BrdfLut {
+    texture: Texture,  // Scale and bias by `(n·v, roughness)`
+    baked: Cell<bool>, // Whether `texture` was rendered
+}
+
+thread_local! {
+    static BRDF_LUT: RefCell<Weak<BrdfLut>> = const { RefCell::new(Weak::new()) };
+}
+
+/// Image-based lighting precomputed from an environment cube map.
+///
+/// The first `render` convolves the cube map into a diffuse irradiance map and a
+/// specular map prefiltered per roughness level, and generates the split-sum BRDF
+/// lookup table (shared by every environment). The maps are baked again when the source
+/// cube map is reloaded.
+pub struct Environment {
+    cubemap: Handle<Cubemap>, // Source of the lighting
+    irradiance: Cubemap,      // Diffuse irradiance
+    prefiltered: Cubemap,     // Specular radiance per roughness level
+    brdf_lut: Rc<BrdfLut>,    // Shared BRDF lookup table
+    baked: Cell<Option<u32>>, // Source cube map the maps were rendered from
+}
+
+impl Environment {
+    /// Creates an environment lit by `cubemap`. Its maps are allocated here and baked by
+    /// the first `render` after the cube map is loaded.
+    pub fn new(cubemap: Handle<Cubemap>) -> Environment {
+        let brdf_lut = match BRDF_LUT.with(|lut| lut.borrow().upgrade()) {
+            Some(brdf_lut) => brdf_lut,
+            None => {
+                let brdf_lut = Rc::new(BrdfLut {
+                    texture: Texture::new(BRDF_LUT_SIZE, BRDF_LUT_SIZE, TextureFormat::Rg16F),
+                    baked: Cell::new(false),
+                });
+                BRDF_LUT.with(|lut| *lut.borrow_mut() = Rc::downgrade(&brdf_lut));
+                brdf_lut
+            }
+        };
+
+        Environment {
+            cubemap,
+            irradiance: Cubemap::new(IRRADIANCE_SIZE, TextureFormat::Rgb16F, 1),
+            prefiltered: Cubemap::new(PREFILTERED_SIZE, TextureFormat::Rgb16F, PREFILTERED_LEVELS),
+            brdf_lut,
+            baked: Cell::new(None),
+        }
+    }
+
+    /// Bakes the lighting maps with `device` the first time it is called with the source
+    /// cube map loaded; later calls do nothing until the source is reloaded. Scenes call it
+    /// before drawing anything lit by the environment.
+    ///
+    /// Mipmaps are generated for the source cube map as part of the prefiltering. The
+    /// viewport and framebuffer binding are restored afterwards.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or an `Err(String)` if an offscreen framebuffer could not be completed.
+    /// The same source isn't baked again after an error.
+    pub fn render(&self, device: &mut dyn RenderDevice) -> Result<(), String> {
+        let Some(cubemap) = self.cubemap.try_get() else {
+            return Ok(());
+        };
+        if self.baked.replace(Some(cubemap.id())) == Some(cubemap.id()) {
+            return Ok(());
+        }
+        let framebuffer = device.get_framebuffer();
+        let (x, y, width, height) = device.get_viewport();
+        device.set_pipeline_state(&PipelineState::fullscreen());
+
+        let source = device.import_texture(ExternalTexture::Cubemap(&cubemap));
+        device.generate_mipmaps(source);
+        let result = self
+            .convolve_irradiance(device, source)
+            .and_then(|()| self.prefilter_specular(device, source, cubemap.get_size()));
+        let result = match result {
+            Ok(()) if !self.brdf_lut.baked.replace(true) => integrate_brdf(device, &self.brdf_lut.texture),
+            result => result,
+        };
+
+        device.bind_framebuffer(framebuffer);
+        device.set_viewport(x, y, width, height);
+        result
+    }
+
+    /// Returns the source cube map, also suitable for drawing as a skybox.
+    pub fn get_cubemap(&self) -> &Handle<Cubemap> {
+        &self.cubemap
+    }
+
+    /// Returns the cosine-weighted diffuse irradiance map.
+    pub fn get_irradiance(&self) -> &Cubemap {
+        &self.irradiance
+    }
+
+    /// Returns the specular map, with increasing roughness along its mip chain.
+    pub fn get_prefiltered(&self) -> &Cubemap {
+        &self.prefiltered
+    }
+
+    /// Returns the split-sum BRDF lookup table, indexed by `(n·v, roughness)`.
+    pub fn get_brdf_lut(&self) -> &Texture {
+        &self.brdf_lut.texture
+    }
+
+    /// Returns the mip level of the prefiltered map holding roughness `1.0`.
+    pub fn get_prefiltered_max_lod(&self) -> f32 {
+        (PREFILTERED_LEVELS - 1) as f32
+    }
+
+    fn convolve_irradiance(&self, device: &mut dyn RenderDevice, source: TextureHandle) -> Result<(), String> {
+        let shader = device.create_shader(CAPTURE_VERTEX_SHADER, IRRADIANCE_FRAGMENT_SHADER);
+        let draw = capture_draw(device, shader, source);
+        let target = device.import_texture(ExternalTexture::Cubemap(&self.irradiance));
+        let result = render_faces(device, &draw, target, 0, IRRADIANCE_SIZE);
+        device.destroy_shader(shader);
+        result
+    }
+
+    fn prefilter_specular(
+        &self,
+        device: &mut dyn RenderDevice,
+        source: TextureHandle,
+        source_size: u32,
+    ) -> Result<(), String> {
+        let fragment_src = format!("#version 330 core\n{IMPORTANCE_SAMPLING_GLSL}{PREFILTER_FRAGMENT_SHADER}");
+        let shader = device.create_shader(CAPTURE_VERTEX_SHADER, &fragment_src);
+        let draw = capture_draw(device, shader, source)
+            .with_uniform("u_resolution", UniformValue::Float(source_size as f32));
+        let target = device.import_texture(ExternalTexture::Cubemap(&self.prefiltered));
+        let result = (0..PREFILTERED_LEVELS).try_for_each(|level| {
+            let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+            let draw = draw.clone().with_uniform("u_roughness", UniformValue::Float(roughness));
+            render_faces(device, &draw, target, level, PREFILTERED_SIZE >> level)
+        });
+        device.destroy_shader(shader);
+        result
+    }
+}
+
+/// Returns the projection and the six view matrices looking through each cube face.
+fn capture_matrices() -> (Mat4, [Mat4; 6]) {
+    let projection = nalgebra_glm::perspective(1.0, FRAC_PI_2, 0.1, 10.0);
+    let eye = Vec3::zeros();
+    let look = |target: Vec3, up: Vec3| nalgebra_glm::look_at(&eye, &target, &up);
+    let views = [
+        look(Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+        look(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0)),
+        look(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+        look(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+        look(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, -1.0, 0.0)),
+        look(Vec3::new(0.0, 0.0, -1.0), Vec3::new(0.0, -1.0, 0.0)),
+    ];
+    (projection, views)
+}
+
+/// Returns a draw of the unit cube sampling `source` with a cube capture program.
+fn capture_draw(device: &mut dyn RenderDevice, shader: ShaderHandle, source: TextureHandle) -> DrawCall {
+    let (projection, _) = capture_matrices();
+    unit_cube(device, shader)
+        .with_uniform("u_projection", UniformValue::Mat4(projection))
+        .with_uniform("u_environment", UniformValue::Int(0))
+        .with_texture(0, source)
+}
+
+/// Renders `draw` into every face of a mip level of the cube map `target`.
+///
+/// # Arguments
+///
+/// * `size` - Edge length of the faces at `level`.
+fn render_faces(
+    device: &mut dyn RenderDevice,
+    draw: &DrawCall,
+    target: TextureHandle,
+    level: u32,
+    size: u32,
+) -> Result<(), String> {
+    let (_, views) = capture_matrices();
+    device.set_viewport(0, 0, size, size);
+    for (face, view) in views.iter().enumerate() {
+        let render_target = RenderTarget::new(Attachment::Color(0), target).with_face(face as u32).with_level(level);
+        let framebuffer = device.create_framebuffer(&[render_target])?;
+        device.bind_framebuffer(Some(framebuffer));
+        device.draw(&draw.clone().with_uniform("u_view", UniformValue::Mat4(*view)));
+        device.destroy_framebuffer(framebuffer);
+    }
+    Ok(())
+}
+
+fn integrate_brdf(device: &mut dyn RenderDevice, brdf_lut: &Texture) -> Result<(), String> {
+    let fragment_src = format!("#version 330 core\n{IMPORTANCE_SAMPLING_GLSL}{BRDF_FRAGMENT_SHADER}");
+    let shader = device.create_shader(FULLSCREEN_VERTEX_SHADER, &fragment_src);
+    let target = device.import_texture(ExternalTexture::Texture(brdf_lut));
+    let result = device.create_framebuffer(&[RenderTarget::new(Attachment::Color(0), target)]).map(|framebuffer| {
+        device.bind_framebuffer(Some(framebuffer));
+        device.set_viewport(0, 0, BRDF_LUT_SIZE, BRDF_LUT_SIZE);
+        let draw = fullscreen_triangle(device, shader);
+        device.draw(&draw);
+        device.destroy_framebuffer(framebuffer);
+    });
+    device.destroy_shader(shader);
+    result
+}
@@ -0,0 +1,86 @@
+mod environment;
+mod pbr;
+
+pub use environment::Environment;
+pub use pbr::{AlphaMode, PbrMaterial, MAX_JOINTS, MAX_MORPH_TARGETS};
+
+pub(crate) use pbr::{forward_shader, with_environment};
+pub(crate) use pbr::{DEFORMATION_UNIFORMS, JOINT_PALETTE_BINDING, JOINT_PALETTE_BLOCK};
+pub(crate) use pbr::{PBR_LIGHTING_GLSL, PBR_SURFACE_GLSL, PBR_VERTEX_SHADER, SURFACE_STRUCT_GLSL};
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::thread::LocalKey;
+
+use crate::opengl::ShaderProgram;
+
+/// Uniforms the renderer fills in when a custom material's program declares them.
+const ENGINE_UNIFORMS: [&str; 6] =
+    ["u_model", "u_view", "u_projection", "u_camera_position", "u_environment", "u_dither_fade"];
+
+/// Describes how a `MeshRenderer` shades its mesh.
+pub enum Material {
+    /// A user-supplied program. The engine only fills in the uniforms listed on
+    /// `MeshRenderer::new`, create it with `Material::from` so they are looked up.
+    Custom(ShaderProgram),
+    /// The built-in metallic/roughness material.
+    Pbr(PbrMaterial),
+}
+
+impl Material {
+    /// Checks whether meshes with this material are alpha blended and must be drawn
+    /// after opaque geometry.
+    pub fn is_transparent(&self) -> bool {
+        match self {
+            Material::Pbr(material) => material.is_transparent(),
+            Material::Custom(_) => false,
+        }
+    }
+}
+
+impl From<ShaderProgram> for Material {
+    fn from(mut shader_program: ShaderProgram) -> Material {
+        for uniform in ENGINE_UNIFORMS.iter().chain(&DEFORMATION_UNIFORMS) {
+            // Unused uniforms are optimized out of the program, which is fine here.
+            let _ = shader_program.create_uniform(uniform);
+        }
+        shader_program.bind_uniform_block(JOINT_PALETTE_BLOCK, JOINT_PALETTE_BINDING);
+        Material::Custom(shader_program)
+    }
+}
+
+impl From<PbrMaterial> for Material {
+    fn from(material: PbrMaterial) -> Material {
+        Material::Pbr(material)
+    }
+}
+
+/// GLSL helper mapping linear HDR radiance to the gamma-encoded window framebuffer.
+///
+/// Built-in shaders that write to the window call `encode_output` on their final color,
+/// so lit meshes and the skybox share one exposure curve.
+pub(crate) const OUTPUT_GLSL: &str = r#"
+vec3 encode_output(vec3 color) {
+    // ACES filmic approximation (Narkowicz 2015) followed by gamma encoding.
+    color = clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+    return pow(color, vec3(1.0 / 2.2));
+}
+"#;
+
+/// Returns the program cached in `cache`, building it if no material holds it anymore.
+///
+/// Only weak references are cached, so the program is deleted together with the last
+/// material using it instead of outliving the GL context.
+pub(crate) fn shared_program(
+    cache: &'static LocalKey<RefCell<Weak<ShaderProgram>>>,
+    build: impl FnOnce() -> ShaderProgram,
+) -> Rc<ShaderProgram> {
+    cache.with(|cache| {
+        if let Some(program) = cache.borrow().upgrade() {
+            return program;
+        }
+        let program = Rc::new(build());
+        *cache.borrow_mut() = Rc::downgrade(&program);
+        program
+    })
+}
@@ -0,0 +1,514 @@
+use nalgebra_glm::{Vec3, Vec4};
+
+use super::OUTPUT_GLSL;
+use crate::core::assets::Handle;
+use crate::core::RenderContext;
+use crate::device::{DrawCall, ExternalTexture, GpuCache, RenderDevice, ShaderHandle, UniformValue};
+use crate::opengl::Texture;
+
+/// Maximum number of lights the forward PBR shader accumulates per draw.
+pub const MAX_FORWARD_LIGHTS: usize = 8;
+
+/// Maximum number of joints a skinned mesh can be deformed by.
+pub const MAX_JOINTS: usize = 128;
+
+/// Maximum number of morph targets a mesh can blend between.
+pub const MAX_MORPH_TARGETS: usize = 64;
+
+/// Uniforms of `PBR_VERTEX_SHADER` that deform meshes, set by `MeshRenderer`.
+pub(crate) const DEFORMATION_UNIFORMS: [&str; 5] =
+    ["u_skinned", "u_morph_target_count", "u_morph_vertex_count", "u_morph_weights", "u_morph_deltas"];
+
+/// Uniform buffer binding point the joint palette of skinned meshes is bound to.
+pub(crate) const JOINT_PALETTE_BINDING: u32 = 0;
+
+/// Name of the uniform block holding the joint palette in `PBR_VERTEX_SHADER`.
+pub(crate) const JOINT_PALETTE_BLOCK: &str = "JointPalette";
+
+/// Vertex shader shared by every built-in pass drawing `VertexLayout::standard()` meshes.
+///
+/// Vertices are first offset by the weighted morph target deltas in `u_morph_deltas`, two
+/// texels (position, normal) per vertex and target. When `u_skinned` is set, they are
+/// then blended between the joint matrices of the `JointPalette` block by their
+/// `VertexLayout::JOINTS` and `VertexLayout::WEIGHTS`.
+pub(crate) const PBR_VERTEX_SHADER: &str = r#"
+#version 330 core
+#define MAX_JOINTS 128
+#define MAX_MORPH_TARGETS 64
+layout (location = 0) in vec3 a_position;
+layout (location = 1) in vec3 a_normal;
+layout (location = 2) in vec2 a_uv;
+layout (location = 3) in vec4 a_tangent;
+layout (location = 5) in vec4 a_joints;
+layout (location = 6) in vec4 a_weights;
+
+uniform mat4 u_model;
+uniform mat4 u_view;
+uniform mat4 u_projection;
+uniform bool u_skinned;
+uniform int u_morph_target_count;
+uniform int u_morph_vertex_count;
+uniform float u_morph_weights[MAX_MORPH_TARGETS];
+uniform samplerBuffer u_morph_deltas;
+
+layout (std140) uniform JointPalette {
+    mat4 u_joint_matrices[MAX_JOINTS];
+};
+
+out vec3 v_world_position;
+out vec3 v_normal;
+out vec4 v_tangent;
+out vec2 v_uv;
+
+void main() {
+    vec3 position = a_position;
+    vec3 normal = a_normal;
+    for (int target = 0; target < u_morph_target_count; target++) {
+        float weight = u_morph_weights[target];
+        if (weight != 0.0) {
+            int texel = (target * u_morph_vertex_count + gl_VertexID) * 2;
+            position += weight * texelFetch(u_morph_deltas, texel).xyz;
+            normal += weight * texelFetch(u_morph_deltas, texel + 1).xyz;
+        }
+    }
+
+    mat4 model = u_model;
+    if (u_skinned) {
+        ivec4 joints = ivec4(a_joints);
+        model *= a_weights.x * u_joint_matrices[joints.x]
+            + a_weights.y * u_joint_matrices[joints.y]
+            + a_weights.z * u_joint_matrices[joints.z]
+            + a_weights.w * u_joint_matrices[joints.w];
+    }
+
+    vec4 world_position = model * vec4(position, 1.0);
+    mat3 normal_matrix = transpose(inverse(mat3(model)));
+
+    v_world_position = world_position.xyz;
+    v_normal = normal_matrix * normal;
+    v_tangent = vec4(mat3(model) * a_tangent.xyz, a_tangent.w);
+    v_uv = a_uv;
+
+    gl_Position = u_projection * u_view * world_position;
+}
+"#;
+
+/// GLSL declaring the shading inputs of a single point on a surface.
+pub(crate) const SURFACE_STRUCT_GLSL: &str = r#"
+struct Surface {
+    vec3 albedo;
+    float alpha;
+    vec3 normal;
+    float metallic;
+    float roughness;
+    float occlusion;
+    vec3 emissive;
+};
+"#;
+
+/// GLSL sampling the glTF metallic/roughness material inputs into a `Surface`.
+///
+/// Expects `SURFACE_STRUCT_GLSL` to be included beforehand.
+pub(crate) const PBR_SURFACE_GLSL: &str = r#"
+const int HAS_ALBEDO_MAP = 1;
+const int HAS_NORMAL_MAP = 2;
+const int HAS_METALLIC_ROUGHNESS_MAP = 4;
+const int HAS_OCCLUSION_MAP = 8;
+const int HAS_EMISSIVE_MAP = 16;
+
+uniform vec4 u_albedo_factor;
+uniform float u_metallic_factor;
+uniform float u_roughness_factor;
+uniform vec3 u_emissive_factor;
+uniform float u_normal_scale;
+uniform float u_occlusion_strength;
+uniform float u_alpha_cutoff;
+uniform int u_texture_flags;
+
+uniform sampler2D u_albedo_map;
+uniform sampler2D u_normal_map;
+uniform sampler2D u_metallic_roughness_map;
+uniform sampler2D u_occlusion_map;
+uniform sampler2D u_emissive_map;
+
+uniform float u_dither_fade;
+
+const float BAYER[16] = float[16](0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+
+// Returns the 4x4 ordered dither threshold of the fragment's pixel, in (0, 1).
+float dither_threshold() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy) & 3;
+    return (BAYER[pixel.y * 4 + pixel.x] + 0.5) / 16.0;
+}
+
+Surface sample_surface(vec2 uv, vec3 normal, vec4 tangent) {
+    Surface surface;
+
+    // During a LOD crossfade both levels are drawn, each keeping the pixels the other
+    // discards: a positive fade keeps that fraction, a negative one the rest.
+    if (u_dither_fade != 0.0 && (u_dither_fade > 0.0) != (dither_threshold() < abs(u_dither_fade))) {
+        discard;
+    }
+
+    vec4 albedo = u_albedo_factor;
+    if ((u_texture_flags & HAS_ALBEDO_MAP) != 0) {
+        albedo *= texture(u_albedo_map, uv);
+    }
+    if (albedo.a < u_alpha_cutoff) {
+        discard;
+    }
+    surface.albedo = albedo.rgb;
+    surface.alpha = albedo.a;
+
+    surface.normal = normalize(normal);
+    if ((u_texture_flags & HAS_NORMAL_MAP) != 0 && dot(tangent.xyz, tangent.xyz) > 1e-6) {
+        vec3 t = normalize(tangent.xyz - surface.normal * dot(surface.normal, tangent.xyz));
+        vec3 b = cross(surface.normal, t) * tangent.w;
+        vec3 tangent_normal = texture(u_normal_map, uv).xyz * 2.0 - 1.0;
+        tangent_normal.xy *= u_normal_scale;
+        surface.normal = normalize(mat3(t, b, surface.normal) * tangent_normal);
+    }
+
+    surface.metallic = u_metallic_factor;
+    surface.roughness = u_roughness_factor;
+    if ((u_texture_flags & HAS_METALLIC_ROUGHNESS_MAP) != 0) {
+        vec4 metallic_roughness = texture(u_metallic_roughness_map, uv);
+        surface.roughness *= metallic_roughness.g;
+        surface.metallic *= metallic_roughness.b;
+    }
+    surface.roughness = clamp(surface.roughness, 0.04, 1.0);
+    surface.metallic = clamp(surface.metallic, 0.0, 1.0);
+
+    surface.occlusion = 1.0;
+    if ((u_texture_flags & HAS_OCCLUSION_MAP) != 0) {
+        surface.occlusion = 1.0 + u_occlusion_strength * (texture(u_occlusion_map, uv).r - 1.0);
+    }
+
+    surface.emissive = u_emissive_factor;
+    if ((u_texture_flags & HAS_EMISSIVE_MAP) != 0) {
+        surface.emissive *= texture(u_emissive_map, uv).rgb;
+    }
+
+    return surface;
+}
+"#;
+
+/// GLSL implementing the Cook-Torrance BRDF for punctual lights and image-based lighting.
+///
+/// Expects `SURFACE_STRUCT_GLSL` to be included beforehand.
+pub(crate) const PBR_LIGHTING_GLSL: &str = r#"
+const float PI = 3.14159265359;
+
+uniform samplerCube u_irradiance_map;
+uniform samplerCube u_prefiltered_map;
+uniform sampler2D u_brdf_lut;
+uniform float u_prefiltered_max_lod;
+uniform int u_has_environment;
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float ggx_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float ggx_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return ggx_v * ggx_l;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Returns the radiance reaching `world_position` from a packed light and writes the
+// direction towards the light into `l`.
+vec3 light_radiance(vec4 light_position, vec4 light_color, vec3 world_position, out vec3 l) {
+    if (light_position.w == 0.0) {
+        l = normalize(-light_position.xyz);
+        return light_color.rgb;
+    }
+
+    vec3 to_light = light_position.xyz - world_position;
+    float distance_squared = max(dot(to_light, to_light), 1e-4);
+    l = to_light * inversesqrt(distance_squared);
+
+    float range = light_color.a;
+    float falloff = 1.0;
+    if (range > 0.0) {
+        float ratio = distance_squared / (range * range);
+        falloff = clamp(1.0 - ratio * ratio, 0.0, 1.0);
+        falloff *= falloff;
+    }
+    return light_color.rgb * falloff / distance_squared;
+}
+
+vec3 direct_lighting(Surface surface, vec3 v, vec3 l, vec3 radiance) {
+    vec3 n = surface.normal;
+    vec3 h = normalize(v + l);
+    float n_dot_v = max(dot(n, v), 1e-4);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float n_dot_h = max(dot(n, h), 0.0);
+
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 fresnel = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    float distribution = distribution_ggx(n_dot_h, surface.roughness);
+    float geometry = geometry_smith(n_dot_v, n_dot_l, surface.roughness);
+
+    vec3 specular = distribution * geometry * fresnel / (4.0 * n_dot_v * n_dot_l + 1e-4);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - surface.metallic) * surface.albedo / PI;
+    return (diffuse + specular) * radiance * n_dot_l;
+}
+
+vec3 ambient_lighting(Surface surface, vec3 v) {
+    if (u_has_environment == 0) {
+        return vec3(0.03) * surface.albedo * surface.occlusion;
+    }
+
+    vec3 n = surface.normal;
+    float n_dot_v = max(dot(n, v), 1e-4);
+    vec3 f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+    vec3 fresnel = fresnel_schlick_roughness(n_dot_v, f0, surface.roughness);
+
+    vec3 irradiance = texture(u_irradiance_map, n).rgb;
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - surface.metallic) * irradiance * surface.albedo;
+
+    vec3 r = reflect(-v, n);
+    vec3 prefiltered = textureLod(u_prefiltered_map, r, surface.roughness * u_prefiltered_max_lod).rgb;
+    vec2 brdf = texture(u_brdf_lut, vec2(n_dot_v, surface.roughness)).rg;
+    vec3 specular = prefiltered * (fresnel * brdf.x + brdf.y);
+
+    return (diffuse + specular) * surface.occlusion;
+}
+"#;
+
+const PBR_FRAGMENT_SHADER: &str = r#"
+in vec3 v_world_position;
+in vec3 v_normal;
+in vec4 v_tangent;
+in vec2 v_uv;
+
+uniform vec3 u_camera_position;
+uniform vec4 u_light_positions[MAX_LIGHTS];
+uniform vec4 u_light_colors[MAX_LIGHTS];
+uniform int u_light_count;
+
+out vec4 frag_color;
+
+void main() {
+    Surface surface = sample_surface(v_uv, v_normal, v_tangent);
+    if (!gl_FrontFacing) {
+        surface.normal = -surface.normal;
+    }
+    vec3 v = normalize(u_camera_position - v_world_position);
+
+    vec3 color = ambient_lighting(surface, v) + surface.emissive;
+    for (int i = 0; i < u_light_count; i++) {
+        vec3 l;
+        vec3 radiance = light_radiance(u_light_positions[i], u_light_colors[i], v_world_position, l);
+        color += direct_lighting(surface, v, l, radiance);
+    }
+
+    frag_color = vec4(encode_output(color), surface.alpha);
+}
+"#;
+
+/// Texture units used by the material maps, in `HAS_*_MAP` bit order.
+const MAP_UNITS: [(&str, u32); 5] = [
+    ("u_albedo_map", 0),
+    ("u_normal_map", 1),
+    ("u_metallic_roughness_map", 2),
+    ("u_occlusion_map", 3),
+    ("u_emissive_map", 4),
+];
+
+/// Texture units used by the environment maps.
+pub(crate) const IRRADIANCE_UNIT: u32 = 5;
+pub(crate) const PREFILTERED_UNIT: u32 = 6;
+pub(crate) const BRDF_LUT_UNIT: u32 = 7;
+
+thread_local! {
+    static FORWARD_SHADER: GpuCache<ShaderHandle> = const { GpuCache::new() };
+}
+
+/// How the alpha channel of a `PbrMaterial` is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Alpha is ignored and the surface is fully opaque.
+    Opaque,
+    /// Fragments with alpha below the cutoff are discarded, the rest are opaque.
+    Mask(f32),
+    /// The surface is alpha blended over whatever is behind it.
+    Blend,
+}
+
+/// The built-in metallic/roughness material, following the glTF 2.0 model.
+///
+/// Every factor multiplies the matching texture when one is set. Maps are resolved when
+/// the material is drawn: ones still loading are left out, and reloaded ones are used
+/// from the next draw on. Channels follow glTF: roughness is read from green and
+/// metallic from blue of the `metallic_roughness_map`, occlusion from red of the
+/// `occlusion_map`.
+///
+/// Meshes using it need the `VertexLayout::standard()` attributes.
+pub struct PbrMaterial {
+    /// Base color and alpha, in linear space.
+    pub albedo_factor: Vec4,
+    /// Metalness, from `0` (dielectric) to `1` (metal).
+    pub metallic_factor: f32,
+    /// Perceptual roughness, from `0` (mirror) to `1` (fully rough).
+    pub roughness_factor: f32,
+    /// Emitted radiance added on top of the lighting.
+    pub emissive_factor: Vec3,
+    /// Scale applied to the X and Y of the tangent-space normal map.
+    pub normal_scale: f32,
+    /// How strongly the occlusion map darkens ambient lighting.
+    pub occlusion_strength: f32,
+    /// How alpha is interpreted.
+    pub alpha_mode: AlphaMode,
+    /// sRGB base color map.
+    pub albedo_map: Option<Handle<Texture>>,
+    /// Linear tangent-space normal map.
+    pub normal_map: Option<Handle<Texture>>,
+    /// Linear map with roughness in green and metallic in blue.
+    pub metallic_roughness_map: Option<Handle<Texture>>,
+    /// Linear map with ambient occlusion in red.
+    pub occlusion_map: Option<Handle<Texture>>,
+    /// sRGB emissive color map.
+    pub emissive_map: Option<Handle<Texture>>,
+}
+
+impl PbrMaterial {
+    /// Creates a white, fully rough dielectric material without any textures.
+    ///
+    /// The shader program is compiled the first time a `PbrMaterial` is drawn with a
+    /// device, and shared by every `PbrMaterial` drawn with it.
+    pub fn new() -> PbrMaterial {
+        PbrMaterial {
+            albedo_factor: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            emissive_factor: Vec3::zeros(),
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            albedo_map: None,
+            normal_map: None,
+            metallic_roughness_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+        }
+    }
+
+    /// Checks whether the material is alpha blended.
+    pub fn is_transparent(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend
+    }
+
+    /// Adds the surface uniforms and material maps to a draw whose program includes
+    /// `PBR_SURFACE_GLSL`.
+    pub(crate) fn with_surface(&self, device: &mut dyn RenderDevice, mut draw: DrawCall) -> DrawCall {
+        let maps = [
+            &self.albedo_map,
+            &self.normal_map,
+            &self.metallic_roughness_map,
+            &self.occlusion_map,
+            &self.emissive_map,
+        ];
+
+        let mut flags = 0;
+        for (bit, (map, (uniform, unit))) in maps.iter().zip(MAP_UNITS).enumerate() {
+            if let Some(texture) = map.as_ref().and_then(Handle::try_get) {
+                draw = draw.with_texture(unit, device.import_texture(ExternalTexture::Texture(&texture)));
+                flags |= 1 << bit;
+            }
+            draw = draw.with_uniform(uniform, UniformValue::Int(unit as i32));
+        }
+
+        let alpha_cutoff = match self.alpha_mode {
+            AlphaMode::Mask(cutoff) => cutoff,
+            _ => -1.0,
+        };
+
+        draw.with_uniform("u_texture_flags", UniformValue::Int(flags))
+            .with_uniform("u_albedo_factor", UniformValue::Vec4(self.albedo_factor))
+            .with_uniform("u_metallic_factor", UniformValue::Float(self.metallic_factor))
+            .with_uniform("u_roughness_factor", UniformValue::Float(self.roughness_factor))
+            .with_uniform("u_emissive_factor", UniformValue::Vec3(self.emissive_factor))
+            .with_uniform("u_normal_scale", UniformValue::Float(self.normal_scale))
+            .with_uniform("u_occlusion_strength", UniformValue::Float(self.occlusion_strength))
+            .with_uniform("u_alpha_cutoff", UniformValue::Float(alpha_cutoff))
+    }
+
+    /// Adds the camera, light, surface and environment uniforms of the forward program
+    /// to a draw.
+    pub(crate) fn with_forward(
+        &self,
+        device: &mut dyn RenderDevice,
+        context: &RenderContext,
+        draw: DrawCall,
+    ) -> DrawCall {
+        let camera = context.camera;
+        let (positions, colors): (Vec<Vec4>, Vec<Vec4>) =
+            context.lights.iter().take(MAX_FORWARD_LIGHTS).map(|light| light.pack()).unzip();
+
+        let mut draw = draw
+            .with_uniform("u_view", UniformValue::Mat4(camera.view_matrix()))
+            .with_uniform("u_projection", UniformValue::Mat4(camera.projection_matrix()))
+            .with_uniform("u_camera_position", UniformValue::Vec3(*camera.get_position()))
+            .with_uniform("u_light_count", UniformValue::Int(positions.len() as i32));
+        if !positions.is_empty() {
+            draw = draw
+                .with_uniform("u_light_positions", UniformValue::Vec4Array(positions))
+                .with_uniform("u_light_colors", UniformValue::Vec4Array(colors));
+        }
+
+        let draw = self.with_surface(device, draw);
+        with_environment(device, context, draw)
+    }
+}
+
+/// Returns the forward program of `PbrMaterial` on `device`, compiling it the first time.
+pub(crate) fn forward_shader(device: &mut dyn RenderDevice) -> ShaderHandle {
+    FORWARD_SHADER.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let fragment_src = format!(
+                "#version 330 core\n#define MAX_LIGHTS {MAX_FORWARD_LIGHTS}\n{OUTPUT_GLSL}{SURFACE_STRUCT_GLSL}{PBR_SURFACE_GLSL}{PBR_LIGHTING_GLSL}{PBR_FRAGMENT_SHADER}"
+            );
+            device.create_shader(PBR_VERTEX_SHADER, &fragment_src)
+        })
+    })
+}
+
+impl Default for PbrMaterial {
+    fn default() -> Self {
+        PbrMaterial::new()
+    }
+}
+
+/// Adds the scene environment maps to a draw whose program includes `PBR_LIGHTING_GLSL`.
+pub(crate) fn with_environment(device: &mut dyn RenderDevice, context: &RenderContext, draw: DrawCall) -> DrawCall {
+    let mut draw = draw
+        .with_uniform("u_irradiance_map", UniformValue::Int(IRRADIANCE_UNIT as i32))
+        .with_uniform("u_prefiltered_map", UniformValue::Int(PREFILTERED_UNIT as i32))
+        .with_uniform("u_brdf_lut", UniformValue::Int(BRDF_LUT_UNIT as i32))
+        .with_uniform("u_has_environment", UniformValue::Int(context.environment.is_some() as i32));
+
+    if let Some(environment) = context.environment {
+        let irradiance = device.import_texture(ExternalTexture::Cubemap(environment.get_irradiance()));
+        let prefiltered = device.import_texture(ExternalTexture::Cubemap(environment.get_prefiltered()));
+        let brdf_lut = device.import_texture(ExternalTexture::Texture(environment.get_brdf_lut()));
+        draw = draw
+            .with_texture(IRRADIANCE_UNIT, irradiance)
+            .with_texture(PREFILTERED_UNIT, prefiltered)
+            .with_texture(BRDF_LUT_UNIT, brdf_lut)
+            .with_uniform("u_prefiltered_max_lod", UniformValue::Float(environment.get_prefiltered_max_lod()));
+    }
+    draw
+}
@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+
+use nalgebra_glm::{Mat3, Mat4, Vec2, Vec3, Vec4};
+
+use super::{mikktspace, Mesh, MorphTarget};
+use crate::core::assets::AssetServer;
+use crate::core::components::{Indices, MeshRenderer};
+use crate::core::material::Material;
+use crate::opengl::VertexLayout;
+
+/// CPU-side mesh data that can be generated, combined and then uploaded as a `Mesh`.
+///
+/// Every attribute is either empty or holds one entry per position. Triangles are
+/// wound counter-clockwise when seen from the front, matching `FrontFace::CounterClockwise`.
+/// UVs follow the image convention, with `v = 0` at the top of a texture.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshBuilder {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    /// Tangents with the bitangent sign in `w`, as `VertexLayout::TANGENT` expects.
+    pub tangents: Vec<Vec4>,
+    pub colors: Vec<Vec4>,
+    /// Indices of up to four skin joints influencing each vertex, stored as floats.
+    pub joints: Vec<Vec4>,
+    /// How much each of the four `joints` influences the vertex, summing to one.
+    pub weights: Vec<Vec4>,
+    /// Triangle list indices into the attributes.
+    pub indices: Vec<u32>,
+    /// Blend shapes with one offset per position.
+    pub morph_targets: Vec<MorphTarget>,
+}
+
+impl MeshBuilder {
+    /// Creates an empty mesh.
+    pub fn new() -> MeshBuilder {
+        MeshBuilder::default()
+    }
+
+    /// Returns the number of vertices.
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns the number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Iterates over the triangles as vertex index triples.
+    pub fn triangles(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+    }
+
+    /// Gives every vertex the same color.
+    pub fn with_color(mut self, color: Vec4) -> MeshBuilder {
+        self.colors = vec![color; self.vertex_count()];
+        self
+    }
+
+    /// Recomputes normals by averaging the normals of the triangles around each vertex,
+    /// weighted by their area.
+    ///
+    /// Vertices are not welded, so UV seams and hard edges split by the generator stay hard.
+    pub fn compute_smooth_normals(&mut self) {
+        let mut normals = vec![Vec3::zeros(); self.vertex_count()];
+        for [a, b, c] in self.triangles() {
+            let face = (self.positions[b] - self.positions[a]).cross(&(self.positions[c] - self.positions[a]));
+            for vertex in [a, b, c] {
+                normals[vertex] += face;
+            }
+        }
+        self.normals = normals.into_iter().map(|normal| normalize_or(normal, Vec3::y())).collect();
+    }
+
+    /// Gives every triangle its own vertices, all sharing the triangle's normal.
+    ///
+    /// This triples the vertex count but produces hard edges everywhere. Tangents are
+    /// recomputed if the mesh had any.
+    pub fn compute_flat_normals(&mut self) {
+        let corners: Vec<usize> = self.indices.iter().map(|&index| index as usize).collect();
+        let had_tangents = !self.tangents.is_empty();
+
+        self.gather_vertices(&corners);
+        self.indices = (0..corners.len() as u32).collect();
+
+        self.normals = self
+            .positions
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                let face = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0]));
+                [normalize_or(face, Vec3::y()); 3]
+            })
+            .collect();
+
+        self.tangents.clear();
+        if had_tangents {
+            self.compute_tangents();
+        }
+    }
+
+    /// Computes per-vertex MikkTSpace tangents from the UV layout.
+    ///
+    /// The tangents are the ones the MikkTSpace reference implementation generates, which
+    /// is what normal map bakers expect, so baked maps shade without seams. A vertex whose
+    /// corners get different tangents, such as one on a UV mirror seam, is split. The
+    /// bitangent sign goes into `w` so shaders can rebuild it as
+    /// `cross(normal, tangent.xyz) * tangent.w`.
+    ///
+    /// Does nothing without normals and UVs; call `compute_smooth_normals` first if needed.
+    pub fn compute_tangents(&mut self) {
+        let count = self.vertex_count();
+        if self.normals.len() != count || self.uvs.len() != count {
+            return;
+        }
+
+        let corner_tangents = mikktspace::generate_tangents(&self.positions, &self.normals, &self.uvs, &self.indices);
+        let mut tangents = vec![None; count];
+        let mut sources: Vec<usize> = (0..count).collect();
+        let mut copies = HashMap::new();
+        for (index, tangent) in self.indices.iter_mut().zip(corner_tangents) {
+            let vertex = *index as usize;
+            if tangents[vertex].is_none() {
+                tangents[vertex] = Some(tangent);
+            } else if tangents[vertex] != Some(tangent) {
+                *index = *copies.entry((vertex, tangent.map(f32::to_bits))).or_insert_with(|| {
+                    sources.push(vertex);
+                    tangents.push(Some(tangent));
+                    sources.len() as u32 - 1
+                });
+            }
+        }
+        if sources.len() > count {
+            self.gather_vertices(&sources);
+        }
+
+        // Vertices without triangles still get a tangent perpendicular to their normal.
+        self.tangents = (tangents.into_iter().zip(&self.normals))
+            .map(|(tangent, normal)| tangent.unwrap_or_else(|| any_perpendicular(normal).push(1.0)))
+            .collect();
+    }
+
+    /// Replaces every attribute with the entries of the `sources` vertices, in order.
+    fn gather_vertices(&mut self, sources: &[usize]) {
+        self.positions = gather(&self.positions, sources);
+        self.normals = gather(&self.normals, sources);
+        self.uvs = gather(&self.uvs, sources);
+        self.tangents = gather(&self.tangents, sources);
+        self.colors = gather(&self.colors, sources);
+        self.joints = gather(&self.joints, sources);
+        self.weights = gather(&self.weights, sources);
+        for target in &mut self.morph_targets {
+            target.positions = gather(&target.positions, sources);
+            target.normals = gather(&target.normals, sources);
+        }
+    }
+
+    /// Transforms the mesh in place.
+    ///
+    /// Normals use the inverse transpose so non-uniform scales keep them perpendicular,
+    /// and mirroring transforms flip the winding so triangles keep facing outwards.
+    pub fn transform(&mut self, matrix: &Mat4) {
+        let linear: Mat3 = matrix.fixed_view::<3, 3>(0, 0).into();
+        let normal_matrix = linear.try_inverse().map(|inverse| inverse.transpose()).unwrap_or(linear);
+        let mirrored = linear.determinant() < 0.0;
+
+        for position in &mut self.positions {
+            *position = (matrix * position.push(1.0)).xyz();
+        }
+        for normal in &mut self.normals {
+            *normal = normalize_or(normal_matrix * *normal, *normal);
+        }
+        for target in &mut self.morph_targets {
+            for offset in &mut target.positions {
+                *offset = linear * *offset;
+            }
+            for offset in &mut target.normals {
+                *offset = normal_matrix * *offset;
+            }
+        }
+        for tangent in &mut self.tangents {
+            let direction = normalize_or(linear * tangent.xyz(), tangent.xyz());
+            let sign = if mirrored { -tangent.w } else { tangent.w };
+            *tangent = Vec4::new(direction.x, direction.y, direction.z, sign);
+        }
+        if mirrored {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+    }
+
+    /// Returns a transformed copy of the mesh, see `transform`.
+    pub fn transformed(mut self, matrix: &Mat4) -> MeshBuilder {
+        self.transform(matrix);
+        self
+    }
+
+    /// Appends another mesh's vertices and triangles.
+    ///
+    /// An attribute present on only one of the meshes is filled with a default for the
+    /// other: up normals, zero UVs, `+X` tangents, white colors and full weight on joint 0.
+    /// Morph targets are matched by position in `morph_targets`, with zero offsets for
+    /// vertices of a mesh without the target.
+    pub fn merge(&mut self, other: &MeshBuilder) {
+        let (count, other_count) = (self.vertex_count(), other.vertex_count());
+        merge_attribute(&mut self.normals, &other.normals, count, other_count, Vec3::y());
+        merge_attribute(&mut self.uvs, &other.uvs, count, other_count, Vec2::zeros());
+        merge_attribute(&mut self.tangents, &other.tangents, count, other_count, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        merge_attribute(&mut self.colors, &other.colors, count, other_count, Vec4::repeat(1.0));
+        merge_attribute(&mut self.joints, &other.joints, count, other_count, Vec4::zeros());
+        merge_attribute(&mut self.weights, &other.weights, count, other_count, Vec4::x());
+        while self.morph_targets.len() < other.morph_targets.len() {
+            let name = &other.morph_targets[self.morph_targets.len()].name;
+            self.morph_targets.push(MorphTarget::new(name));
+        }
+        for (index, target) in self.morph_targets.iter_mut().enumerate() {
+            let (positions, normals) = match other.morph_targets.get(index) {
+                Some(other_target) => (&other_target.positions[..], &other_target.normals[..]),
+                None => (&[][..], &[][..]),
+            };
+            merge_attribute(&mut target.positions, positions, count, other_count, Vec3::zeros());
+            merge_attribute(&mut target.normals, normals, count, other_count, Vec3::zeros());
+        }
+        self.positions.extend_from_slice(&other.positions);
+        self.indices.extend(other.indices.iter().map(|&index| index + count as u32));
+    }
+
+    /// Returns the layout of the attributes this mesh has, in `VertexLayout::standard()`
+    /// order followed by the color, joints and weights.
+    pub fn layout(&self) -> VertexLayout {
+        let mut layout = VertexLayout::position();
+        if !self.normals.is_empty() {
+            layout = layout.with(VertexLayout::NORMAL, 3);
+        }
+        if !self.uvs.is_empty() {
+            layout = layout.with(VertexLayout::UV, 2);
+        }
+        if !self.tangents.is_empty() {
+            layout = layout.with(VertexLayout::TANGENT, 4);
+        }
+        if !self.colors.is_empty() {
+            layout = layout.with(VertexLayout::COLOR, 4);
+        }
+        if !self.joints.is_empty() {
+            layout = layout.with(VertexLayout::JOINTS, 4).with(VertexLayout::WEIGHTS, 4);
+        }
+        layout
+    }
+
+    /// Interleaves the attributes into the vertex format described by `layout()`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a non-empty attribute does not have one entry per position.
+    pub fn interleave(&self) -> Vec<f32> {
+        let count = self.vertex_count();
+        for (name, len) in [
+            ("normals", self.normals.len()),
+            ("uvs", self.uvs.len()),
+            ("tangents", self.tangents.len()),
+            ("colors", self.colors.len()),
+            ("joints", self.joints.len()),
+            ("weights", self.weights.len()),
+        ] {
+            assert!(len == 0 || len == count, "MeshBuilder has {len} {name} for {count} positions");
+        }
+        assert!(self.joints.len() == self.weights.len(), "MeshBuilder has joints without weights or vice versa");
+
+        let mut vertices = Vec::with_capacity(count * self.layout().floats_per_vertex());
+        for vertex in 0..count {
+            vertices.extend_from_slice(self.positions[vertex].as_slice());
+            if let Some(normal) = self.normals.get(vertex) {
+                vertices.extend_from_slice(normal.as_slice());
+            }
+            if let Some(uv) = self.uvs.get(vertex) {
+                vertices.extend_from_slice(uv.as_slice());
+            }
+            if let Some(tangent) = self.tangents.get(vertex) {
+                vertices.extend_from_slice(tangent.as_slice());
+            }
+            if let Some(color) = self.colors.get(vertex) {
+                vertices.extend_from_slice(color.as_slice());
+            }
+            if let (Some(joints), Some(weights)) = (self.joints.get(vertex), self.weights.get(vertex)) {
+                vertices.extend_from_slice(joints.as_slice());
+                vertices.extend_from_slice(weights.as_slice());
+            }
+        }
+        vertices
+    }
+
+    /// Returns the indices in the smallest type that can address every vertex, keeping the
+    /// largest value of the type free for primitive restart.
+    pub fn compact_indices(&self) -> Indices {
+        if self.vertex_count() < u8::MAX as usize {
+            Indices::U8(self.indices.iter().map(|&index| index as u8).collect())
+        } else if self.vertex_count() < u16::MAX as usize {
+            Indices::U16(self.indices.iter().map(|&index| index as u16).collect())
+        } else {
+            Indices::U32(self.indices.clone())
+        }
+    }
+
+    /// Creates a `Mesh` from the data. Its buffers are uploaded to each device the first
+    /// time it is drawn there.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a non-empty attribute or morph target does not have
+    /// one entry per position.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::with_layout(self.interleave(), self.compact_indices(), self.layout());
+        if !self.morph_targets.is_empty() {
+            mesh.set_morph_targets(&self.morph_targets);
+        }
+        mesh
+    }
+
+    /// Creates the mesh and adds it and `material` to `assets`, for a mesh that is
+    /// drawn once. Share the handles with `MeshRenderer::new` to draw it more often.
+    ///
+    /// # Arguments
+    ///
+    /// * `assets` - The asset server that owns the mesh and the material.
+    /// * `material` - The `ShaderProgram` or built-in material to render the mesh with.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a non-empty attribute or morph target does not have
+    /// one entry per position.
+    pub fn build(&self, assets: &mut AssetServer, material: impl Into<Material>) -> MeshRenderer {
+        MeshRenderer::new(assets.add(self.to_mesh()), assets.add(material.into()))
+    }
+}
+
+pub(super) fn normalize_or(vector: Vec3, fallback: Vec3) -> Vec3 {
+    let length = vector.norm();
+    if length > f32::EPSILON && length.is_finite() {
+        vector / length
+    } else {
+        fallback
+    }
+}
+
+/// Returns a unit vector perpendicular to `normal`.
+fn any_perpendicular(normal: &Vec3) -> Vec3 {
+    let axis = if normal.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+    normalize_or(axis - normal * normal.dot(&axis), Vec3::z())
+}
+
+pub(super) fn gather<T: Copy>(attribute: &[T], corners: &[usize]) -> Vec<T> {
+    if attribute.is_empty() {
+        return Vec::new();
+    }
+    corners.iter().map(|&corner| attribute[corner]).collect()
+}
+
+fn merge_attribute<T: Copy>(attribute: &mut Vec<T>, other: &[T], count: usize, other_count: usize, default: T) {
+    if attribute.is_empty() && other.is_empty() {
+        return;
+    }
+    attribute.resize(count, default);
+    if other.is_empty() {
+        attribute.extend(std::iter::repeat_n(default, other_count));
+    } else {
+        attribute.extend_from_slice(other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the unnormalized normal of a triangle from its winding.
+    fn face_normal(mesh: &MeshBuilder, [a, b, c]: [usize; 3]) -> Vec3 {
+        (mesh.positions[b] - mesh.positions[a]).cross(&(mesh.positions[c] - mesh.positions[a]))
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_normals() {
+        let mut meshes = vec![
+            MeshBuilder::cube(1.0),
+            MeshBuilder::uv_sphere(1.0, 16, 8),
+            MeshBuilder::torus(1.0, 0.25, 16, 8),
+        ];
+        let mut flat = MeshBuilder::ico_sphere(1.0, 1);
+        flat.compute_flat_normals();
+        flat.compute_tangents();
+        meshes.push(flat);
+
+        for mesh in meshes {
+            assert_eq!(mesh.tangents.len(), mesh.vertex_count());
+            for (tangent, normal) in mesh.tangents.iter().zip(&mesh.normals) {
+                assert!(tangent.xyz().dot(normal).abs() < 1e-4, "{tangent:?} is not perpendicular to {normal:?}");
+                assert!((tangent.xyz().norm() - 1.0).abs() < 1e-4);
+                assert!(tangent.w == 1.0 || tangent.w == -1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_match_the_mikktspace_reference() {
+        // A bent strip whose right half mirrors the UVs of the left, with a degenerate
+        // triangle on the seam.
+        let normal = |x: f32, y: f32| Vec3::new(x, y, 1.0).normalize();
+        let mut mesh = MeshBuilder {
+            positions: vec![
+                Vec3::new(-1.0, 0.0, 0.0),
+                Vec3::new(0.0, 0.0, 0.25),
+                Vec3::new(1.0, 0.0, 0.1),
+                Vec3::new(-1.0, 1.0, 0.2),
+                Vec3::new(0.0, 1.0, 0.5),
+                Vec3::new(1.0, 1.0, 0.3),
+            ],
+            normals: vec![
+                normal(0.1, -0.2),
+                normal(0.0, -0.3),
+                normal(-0.2, -0.1),
+                normal(0.2, -0.1),
+                normal(0.0, -0.2),
+                normal(-0.1, 0.0),
+            ],
+            uvs: vec![
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.5, 1.0),
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.0),
+                Vec2::new(0.5, 0.0),
+                Vec2::new(0.0, 0.0),
+            ],
+            indices: vec![0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4, 1, 4, 4],
+            ..MeshBuilder::default()
+        };
+        let (positions, indices) = (mesh.positions.clone(), mesh.indices.clone());
+        // The tangent of each corner, from the reference implementation.
+        let expected = [
+            [0.99369895, 0.07342063, -0.08468582, -1.0],
+            [0.99742967, 0.06863047, 0.020589162, -1.0],
+            [0.9985459, 0.052859984, 0.010571983, -1.0],
+            [0.99369895, 0.07342063, -0.08468582, -1.0],
+            [0.9985459, 0.052859984, 0.010571983, -1.0],
+            [0.9802543, 0.051592343, -0.19089167, -1.0],
+            [-0.99874157, 0.048038688, 0.014411615, 1.0],
+            [-0.9806468, 0.035023104, -0.19262709, 1.0],
+            [-0.99503726, 0.0, -0.09950369, 1.0],
+            [-0.99874157, 0.048038688, 0.014411615, 1.0],
+            [-0.99503726, 0.0, -0.09950369, 1.0],
+            [-0.9992317, 0.03843199, 0.007686393, 1.0],
+            [0.99742967, 0.06863047, 0.020589162, -1.0],
+            [0.9985459, 0.052859984, 0.010571983, -1.0],
+            [0.9985459, 0.052859984, 0.010571983, -1.0],
+        ];
+
+        mesh.compute_tangents();
+        // The two seam vertices are split between the mirrored halves.
+        assert_eq!(mesh.vertex_count(), 8);
+        for (corner, expected) in expected.into_iter().enumerate() {
+            let vertex = mesh.indices[corner] as usize;
+            assert_eq!(mesh.positions[vertex], positions[indices[corner] as usize]);
+            let tangent = mesh.tangents[vertex];
+            assert!((tangent - Vec4::from(expected)).norm() < 1e-6, "corner {corner}: {tangent:?}");
+        }
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        let plane = MeshBuilder::plane(2.0, 2.0, 1);
+        for tangent in &plane.tangents {
+            assert!((tangent.xyz() - Vec3::x()).norm() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn merge_offsets_indices() {
+        let mut mesh = MeshBuilder::cube(1.0);
+        let plane = MeshBuilder::plane(1.0, 1.0, 2).with_color(Vec4::new(1.0, 0.0, 0.0, 1.0));
+        mesh.merge(&plane);
+
+        assert_eq!(mesh.vertex_count(), 24 + 9);
+        assert_eq!(mesh.triangle_count(), 12 + 8);
+        assert_eq!(&mesh.indices[36..], plane.indices.iter().map(|index| index + 24).collect::<Vec<_>>());
+        // The cube had no colors, so its vertices are filled with white.
+        assert_eq!(mesh.colors.len(), mesh.vertex_count());
+        assert_eq!(mesh.colors[0], Vec4::repeat(1.0));
+        assert_eq!(mesh.colors[24], Vec4::new(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn mirroring_flips_winding() {
+        let cube = MeshBuilder::cube(1.0);
+        let mirrored = cube.clone().transformed(&Mat4::new_nonuniform_scaling(&Vec3::new(-1.0, 1.0, 1.0)));
+
+        for (original, flipped) in cube.triangles().zip(mirrored.triangles()) {
+            assert_eq!([original[0], original[2], original[1]], flipped);
+        }
+        for triangle in mirrored.triangles() {
+            let face = face_normal(&mirrored, triangle);
+            assert!(face.dot(&mirrored.normals[triangle[0]]) > 0.0, "triangle {triangle:?} faces inwards");
+        }
+        for (original, flipped) in cube.tangents.iter().zip(&mirrored.tangents) {
+            assert_eq!(original.w, -flipped.w);
+        }
+    }
+
+    #[test]
+    fn uniform_transform_keeps_winding() {
+        let cube = MeshBuilder::cube(1.0);
+        let moved = cube.clone().transformed(&Mat4::new_translation(&Vec3::new(1.0, 2.0, 3.0)));
+        assert_eq!(cube.indices, moved.indices);
+        assert_eq!(cube.normals, moved.normals);
+    }
+
+    #[test]
+    fn compact_indices_picks_the_smallest_type() {
+        let mesh = |vertices: usize| MeshBuilder {
+            positions: vec![Vec3::zeros(); vertices],
+            indices: vec![0, 1, vertices as u32 - 1],
+            ..MeshBuilder::default()
+        };
+
+        assert_eq!(mesh(3).compact_indices(), Indices::U8(vec![0, 1, 2]));
+        assert_eq!(mesh(254).compact_indices(), Indices::U8(vec![0, 1, 253]));
+        // 255 is the primitive restart index of `u8` indices.
+        assert_eq!(mesh(256).compact_indices(), Indices::U16(vec![0, 1, 255]));
+        assert_eq!(mesh(65_534).compact_indices(), Indices::U16(vec![0, 1, 65_533]));
+        assert_eq!(mesh(70_000).compact_indices(), Indices::U32(vec![0, 1, 69_999]));
+    }
+}
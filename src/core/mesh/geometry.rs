@@ -0,0 +1,287 @@
+use crate::core::components::{DrawRange, Indices};
+use crate::core::material::MAX_MORPH_TARGETS;
+use crate::core::mesh::MorphTarget;
+use crate::core::{Aabb, BoundingSphere};
+use crate::device::{bytes_of, BufferHandle, DrawCall, GpuCache, GpuResources, RenderDevice, Resource};
+use crate::device::{ShaderHandle, TextureHandle};
+use crate::opengl::{BufferTarget, BufferUsage, IndexType, TextureFormat, Topology, VertexLayout};
+
+use log::warn;
+use nalgebra_glm::{Vec3, Vec4};
+
+/// The buffers a `Mesh` is uploaded to on one device.
+#[derive(Clone, Copy)]
+pub(crate) struct MeshBuffers {
+    vertex_buffer: BufferHandle,
+    index_buffer: Option<(BufferHandle, IndexType)>,
+    /// The morph target deltas, if the mesh has any.
+    pub(crate) morph_deltas: Option<TextureHandle>,
+}
+
+impl GpuResources for MeshBuffers {
+    fn resources(&self) -> Vec<Resource> {
+        let mut resources = vec![Resource::Buffer(self.vertex_buffer)];
+        resources.extend(self.index_buffer.map(|(buffer, _)| Resource::Buffer(buffer)));
+        resources.extend(self.morph_deltas.map(Resource::Texture));
+        resources
+    }
+}
+
+/// Vertex and index data, drawn by any number of `MeshRenderer`s.
+///
+/// Meshes are shared through a `Handle<Mesh>`, so every entity showing the same model
+/// draws from one set of buffers. Replacing the mesh behind the handle with
+/// `Handle::set` updates all of them.
+///
+/// The data is uploaded to a `RenderDevice` the first time the mesh is drawn with it,
+/// and again after it changes. Buffers of dropped meshes are destroyed by the device's
+/// `release_dropped`.
+pub struct Mesh {
+    vertices: Vec<f32>,                      // Vertex data (positions, normals, etc.)
+    indicies: Indices,                       // Index data for elements, if any
+    layout: VertexLayout,                    // How `vertices` maps to shader attributes
+    morph_targets: Vec<MorphTarget>,         // Offsets blended by the morph weights
+    bounds: Option<Aabb>,                    // Model-space box around every vertex
+    bounding_sphere: Option<BoundingSphere>, // Model-space sphere around every vertex
+    buffers: GpuCache<MeshBuffers>,          // The uploaded data, per device
+}
+
+impl Mesh {
+    /// Creates a mesh from tightly packed positions and their indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - A vector of `f32` representing the vertex positions.
+    /// * `indicies` - The index data as a `Vec` of `u8`, `u16` or `u32`, or `Indices::None`
+    ///   to draw the vertices in order.
+    ///
+    /// # Returns
+    ///
+    /// A new `Mesh` with the `VertexLayout::position()` layout.
+    pub fn new(vertices: Vec<f32>, indicies: impl Into<Indices>) -> Mesh {
+        Mesh::with_layout(vertices, indicies, VertexLayout::position())
+    }
+
+    /// Creates a mesh from interleaved vertex data and its indices.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - Interleaved vertex data, `layout.floats_per_vertex()` floats per vertex.
+    /// * `indicies` - The index data as a `Vec` of `u8`, `u16` or `u32`, or `Indices::None`
+    ///   to draw the vertices in order.
+    /// * `layout` - How each vertex maps to shader attributes.
+    ///
+    /// # Returns
+    ///
+    /// A new `Mesh` without morph targets.
+    pub fn with_layout(vertices: Vec<f32>, indicies: impl Into<Indices>, layout: VertexLayout) -> Mesh {
+        let (bounds, bounding_sphere) = compute_bounds(&vertices, &layout, &[]).unzip();
+
+        Mesh {
+            vertices,
+            indicies: indicies.into(),
+            layout,
+            morph_targets: Vec::new(),
+            bounds,
+            bounding_sphere,
+            buffers: GpuCache::new(),
+        }
+    }
+
+    /// Updates the vertex data of the mesh.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertices` - A vector of `f32` representing the new vertex data.
+    ///
+    /// The buffers on every device are released and uploaded again on the next draw.
+    pub fn set_vertices(&mut self, vertices: Vec<f32>) {
+        self.buffers.clear();
+        (self.bounds, self.bounding_sphere) = compute_bounds(&vertices, &self.layout, &self.morph_targets).unzip();
+        self.vertices = vertices;
+    }
+
+    /// Updates the index data of the mesh.
+    ///
+    /// # Arguments
+    ///
+    /// * `indicies` - The new index data as a `Vec` of `u8`, `u16` or `u32`, or
+    ///   `Indices::None` to draw the vertices in order.
+    ///
+    /// The buffers on every device are released and uploaded again on the next draw.
+    pub fn set_indices(&mut self, indicies: impl Into<Indices>) {
+        self.buffers.clear();
+        self.indicies = indicies.into();
+    }
+
+    /// Returns the vertex data of the mesh.
+    pub fn get_vertices(&self) -> &[f32] {
+        &self.vertices
+    }
+
+    /// Returns the index data of the mesh.
+    pub fn get_indices(&self) -> &Indices {
+        &self.indicies
+    }
+
+    /// Returns the vertex layout of the mesh.
+    pub fn get_layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    /// Returns the number of vertices in the vertex buffer.
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.layout.floats_per_vertex().max(1)
+    }
+
+    /// Uploads the morph targets of the mesh, replacing any previous ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `targets` - The targets, with one offset per vertex or no normal offsets. Targets
+    ///   beyond `MAX_MORPH_TARGETS` are ignored.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a target does not have one offset per vertex.
+    pub fn set_morph_targets(&mut self, targets: &[MorphTarget]) {
+        if targets.len() > MAX_MORPH_TARGETS {
+            warn!("mesh has {} morph targets, only the first {MAX_MORPH_TARGETS} are used", targets.len());
+        }
+        let targets = &targets[..targets.len().min(MAX_MORPH_TARGETS)];
+        let count = self.vertex_count();
+        for target in targets {
+            assert!(target.positions.len() == count, "morph target '{}' does not offset every vertex", target.name);
+            assert!(
+                target.normals.is_empty() || target.normals.len() == count,
+                "morph target '{}' does not offset every normal",
+                target.name
+            );
+        }
+
+        self.buffers.clear();
+        (self.bounds, self.bounding_sphere) = compute_bounds(&self.vertices, &self.layout, targets).unzip();
+        self.morph_targets = targets.to_vec();
+    }
+
+    /// Returns the morph targets of the mesh.
+    pub fn get_morph_targets(&self) -> &[MorphTarget] {
+        &self.morph_targets
+    }
+
+    /// Returns the number of morph targets the mesh can blend between.
+    pub fn get_morph_target_count(&self) -> usize {
+        self.morph_targets.len()
+    }
+
+    /// Returns the model-space box around the mesh's positions, including every morph
+    /// target fully applied, or `None` if the mesh has no positions.
+    pub fn get_bounds(&self) -> Option<&Aabb> {
+        self.bounds.as_ref()
+    }
+
+    /// Returns the model-space sphere around the mesh's positions, including every morph
+    /// target fully applied, or `None` if the mesh has no positions.
+    pub fn get_bounding_sphere(&self) -> Option<&BoundingSphere> {
+        self.bounding_sphere.as_ref()
+    }
+
+    /// Returns the buffers of the mesh on `device`, uploading them the first time.
+    pub(crate) fn upload(&self, device: &mut dyn RenderDevice) -> MeshBuffers {
+        *self.buffers.get_or_create(device, |device| {
+            let usage = BufferUsage::StaticDraw;
+            let vertex_buffer = device.create_buffer(BufferTarget::ArrayBuffer, usage, bytes_of(&self.vertices));
+            let index_buffer = self.indicies.index_type().map(|index_type| {
+                let buffer = device.create_buffer(BufferTarget::ElementArrayBuffer, usage, self.indicies.as_bytes());
+                (buffer, index_type)
+            });
+            let morph_deltas = (!self.morph_targets.is_empty()).then(|| {
+                let deltas = self.morph_deltas();
+                device.create_buffer_texture(TextureFormat::Rgba32F, bytes_of(&deltas))
+            });
+            MeshBuffers {
+                vertex_buffer,
+                index_buffer,
+                morph_deltas,
+            }
+        })
+    }
+
+    /// Returns the morph target offsets as the texels of `u_morph_deltas`: a position and
+    /// a normal offset per vertex, target after target.
+    fn morph_deltas(&self) -> Vec<Vec4> {
+        let mut deltas = Vec::with_capacity(self.morph_targets.len() * self.vertex_count() * 2);
+        for target in &self.morph_targets {
+            for (vertex, position) in target.positions.iter().enumerate() {
+                let normal = target.normals.get(vertex).copied().unwrap_or_default();
+                deltas.push(Vec4::new(position.x, position.y, position.z, 0.0));
+                deltas.push(Vec4::new(normal.x, normal.y, normal.z, 0.0));
+            }
+        }
+        deltas
+    }
+
+    /// Builds a draw of the mesh with `shader`, without any uniforms.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffers` - The buffers of the mesh on the device the call is issued to.
+    /// * `shader` - The program the draw runs.
+    /// * `topology` - How the vertices are assembled into primitives.
+    /// * `draw_range` - The part of the mesh to draw, or `None` for all of it.
+    /// * `primitive_restart` - Whether the largest index value restarts strips.
+    pub(crate) fn draw_call(
+        &self,
+        buffers: &MeshBuffers,
+        shader: ShaderHandle,
+        topology: Topology,
+        draw_range: Option<DrawRange>,
+        primitive_restart: bool,
+    ) -> DrawCall {
+        let count = match buffers.index_buffer {
+            Some(_) => self.indicies.len(),
+            None => self.vertex_count(),
+        };
+        let range = draw_range.unwrap_or(DrawRange {
+            count: count as u32,
+            ..DrawRange::default()
+        });
+
+        let draw = DrawCall::new(shader, buffers.vertex_buffer, self.layout.clone(), range.count)
+            .with_range(range.first, range.count)
+            .with_topology(topology);
+        match buffers.index_buffer {
+            Some((buffer, index_type)) => draw
+                .with_indices(buffer, index_type)
+                .with_base_vertex(range.base_vertex)
+                .with_primitive_restart(primitive_restart),
+            None => draw,
+        }
+    }
+}
+
+/// Computes the model-space bounds of the positions in `vertices`, extended by every
+/// morph target applied at full weight.
+///
+/// # Returns
+///
+/// `None` if the layout has no position attribute or there are no vertices.
+fn compute_bounds(vertices: &[f32], layout: &VertexLayout, targets: &[MorphTarget]) -> Option<(Aabb, BoundingSphere)> {
+    let offset = layout.offset_of(VertexLayout::POSITION)?;
+    let position = layout.attributes().iter().find(|attribute| attribute.location == VertexLayout::POSITION)?;
+    let components = position.components as usize;
+    let stride = layout.floats_per_vertex();
+
+    let positions: Vec<Vec3> = vertices
+        .chunks_exact(stride)
+        .map(|vertex| Vec3::from_fn(|axis, _| if axis < components { vertex[offset + axis] } else { 0.0 }))
+        .collect();
+    let mut points = positions.clone();
+    for target in targets {
+        points.extend(positions.iter().zip(&target.positions).map(|(position, delta)| position + delta));
+    }
+
+    let bounds = Aabb::from_points(points.iter().copied())?;
+    let sphere = BoundingSphere::from_points(&points)?;
+    Some((bounds, sphere))
+}
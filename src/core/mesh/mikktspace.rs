@@ -0,0 +1,483 @@
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+
+/// Largest angle, in degrees, between the tangents of triangles that are averaged at a
+/// shared vertex. The reference implementation's default, which bakers use.
+const ANGULAR_THRESHOLD: f32 = 180.0;
+
+/// Number of slices corners are bucketed into by position before welding.
+const WELD_CELLS: usize = 2048;
+
+/// Seed of the pivot choice when sorting edges.
+const SORT_SEED: u32 = 39871946;
+
+/// The attributes MikkTSpace reads, addressed by corner: `indices[corner]` is the vertex.
+struct Geometry<'a> {
+    positions: &'a [Vec3],
+    normals: &'a [Vec3],
+    uvs: &'a [Vec2],
+    indices: &'a [u32],
+}
+
+impl Geometry<'_> {
+    fn position(&self, corner: usize) -> Vec3 {
+        self.positions[self.indices[corner] as usize]
+    }
+
+    fn normal(&self, corner: usize) -> Vec3 {
+        self.normals[self.indices[corner] as usize]
+    }
+
+    fn uv(&self, corner: usize) -> Vec2 {
+        self.uvs[self.indices[corner] as usize]
+    }
+}
+
+/// A non-degenerate triangle and what MikkTSpace derives from it.
+struct Triangle {
+    face: usize,                   // Index of the triangle in the mesh
+    vertices: [usize; 3],          // Welded vertex of each corner
+    os: Vec3,                      // Unit direction of increasing u, or zero
+    ot: Vec3,                      // Unit direction of increasing v, or zero
+    group_with_any: bool,          // Whether its UVs are too degenerate to give a direction
+    orient_preserving: bool,       // Whether its UVs wind the same way as its positions
+    neighbors: [Option<usize>; 3], // Triangle across the edge starting at each corner
+    groups: [Option<usize>; 3],    // Group of each corner
+}
+
+/// Corners sharing a vertex that are joined by edges and agree on orientation.
+struct Group {
+    vertex: usize,           // The welded vertex
+    orient_preserving: bool, // Orientation of every triangle in the group
+    triangles: Vec<usize>,   // Triangles in the group, in the order they were reached
+}
+
+/// The tangent of a corner and whether its bitangent is `cross(normal, tangent)`.
+#[derive(Clone, Copy)]
+struct TangentSpace {
+    tangent: Vec3,
+    orient_preserving: bool,
+}
+
+/// Computes the MikkTSpace tangent of every corner of a triangle list, as baking tools
+/// that follow the standard do.
+///
+/// This is a port of the triangle path of Morten S. Mikkelsen's reference implementation,
+/// `mikktspace.c`, with its default angular threshold. Corners with the same position,
+/// normal and UV are welded first, so the result doesn't depend on how the vertices are
+/// indexed. Corners of triangles whose UVs give no direction get `(1, 0, 0, -1)` unless a
+/// neighbouring group takes them in, as in the reference.
+///
+/// # Returns
+///
+/// The tangent of each corner in `indices`, with the bitangent sign in `w`.
+pub(super) fn generate_tangents(positions: &[Vec3], normals: &[Vec3], uvs: &[Vec2], indices: &[u32]) -> Vec<Vec4> {
+    let geometry = Geometry { positions, normals, uvs, indices };
+    let corner_count = indices.len() / 3 * 3;
+    let welded = weld_corners(&geometry, corner_count);
+
+    // Degenerate triangles are left out and take the tangents of good corners afterwards.
+    let (mut triangles, degenerate): (Vec<Triangle>, Vec<Triangle>) = (0..corner_count / 3)
+        .map(|face| triangle(&geometry, face, [welded[face * 3], welded[face * 3 + 1], welded[face * 3 + 2]]))
+        .partition(|triangle| {
+            let [a, b, c] = triangle.vertices.map(|vertex| geometry.position(vertex));
+            a != b && a != c && b != c
+        });
+
+    let default = TangentSpace { tangent: Vec3::x(), orient_preserving: false };
+    let mut spaces = vec![default; corner_count];
+    if !triangles.is_empty() {
+        find_neighbors(&mut triangles);
+        let groups = build_groups(&mut triangles);
+        generate_spaces(&mut spaces, &triangles, &groups, &geometry);
+
+        for triangle in &degenerate {
+            for (corner, vertex) in triangle.vertices.iter().enumerate() {
+                let source = (triangles.iter())
+                    .flat_map(|good| (0..3).map(move |corner| (good.face * 3 + corner, good.vertices[corner])))
+                    .find(|(_, good)| good == vertex);
+                if let Some((source, _)) = source {
+                    spaces[triangle.face * 3 + corner] = spaces[source];
+                }
+            }
+        }
+    }
+
+    (spaces.iter())
+        .map(|space| {
+            let sign = if space.orient_preserving { 1.0 } else { -1.0 };
+            Vec4::new(space.tangent.x, space.tangent.y, space.tangent.z, sign)
+        })
+        .collect()
+}
+
+/// Returns, for each corner, a corner with exactly the same position, normal and UV that
+/// stands for all of them.
+fn weld_corners(geometry: &Geometry, corner_count: usize) -> Vec<usize> {
+    let mut welded: Vec<usize> = (0..corner_count).collect();
+    if corner_count == 0 {
+        return welded;
+    }
+
+    // Corners are bucketed along the longest side of the bounding box first.
+    let (mut min, mut max) = (geometry.position(0), geometry.position(0));
+    for corner in 1..corner_count {
+        let position = geometry.position(corner);
+        for axis in 0..3 {
+            if min[axis] > position[axis] {
+                min[axis] = position[axis];
+            } else if max[axis] < position[axis] {
+                max[axis] = position[axis];
+            }
+        }
+    }
+    let axis = longest_axis(max - min);
+    let mut cells = vec![Vec::new(); WELD_CELLS];
+    for corner in 0..corner_count {
+        cells[weld_cell(min[axis], max[axis], geometry.position(corner)[axis])].push(corner);
+    }
+    for cell in cells.iter_mut().filter(|cell| cell.len() > 1) {
+        weld_cell_corners(geometry, &mut welded, cell);
+    }
+    welded
+}
+
+fn longest_axis(size: Vec3) -> usize {
+    if size.y > size.x && size.y > size.z {
+        1
+    } else if size.z > size.x {
+        2
+    } else {
+        0
+    }
+}
+
+fn weld_cell(min: f32, max: f32, value: f32) -> usize {
+    let index = WELD_CELLS as f32 * ((value - min) / (max - min));
+    (index as isize).clamp(0, WELD_CELLS as isize - 1) as usize
+}
+
+/// Welds `corners` by splitting them in halves until each part lies on a plane, then
+/// welding every corner of a part to the first identical one before it.
+fn weld_cell_corners(geometry: &Geometry, welded: &mut [usize], corners: &mut [usize]) {
+    let (mut min, mut max) = (geometry.position(corners[0]), geometry.position(corners[0]));
+    for &corner in &corners[1..] {
+        let position = geometry.position(corner);
+        for axis in 0..3 {
+            if min[axis] > position[axis] {
+                min[axis] = position[axis];
+            } else if max[axis] < position[axis] {
+                max[axis] = position[axis];
+            }
+        }
+    }
+    let axis = longest_axis(max - min);
+    let separator = 0.5 * (max[axis] + min[axis]);
+
+    if separator >= max[axis] || separator <= min[axis] {
+        for (position, &corner) in corners.iter().enumerate() {
+            let identical = corners[..position].iter().find(|&&other| {
+                geometry.position(corner) == geometry.position(other)
+                    && geometry.normal(corner) == geometry.normal(other)
+                    && geometry.uv(corner) == geometry.uv(other)
+            });
+            if let Some(&other) = identical {
+                welded[corner] = welded[other];
+            }
+        }
+        return;
+    }
+
+    let below = |corner: usize| geometry.position(corner)[axis] < separator;
+    let (mut left, mut right) = (0, corners.len() as isize - 1);
+    while left < right {
+        while left < right && below(corners[left as usize]) {
+            left += 1;
+        }
+        while left < right && !below(corners[right as usize]) {
+            right -= 1;
+        }
+        if left < right {
+            corners.swap(left as usize, right as usize);
+            left += 1;
+            right -= 1;
+        }
+    }
+    if left == right {
+        if below(corners[right as usize]) {
+            left += 1;
+        } else {
+            right -= 1;
+        }
+    }
+    if right > 0 {
+        weld_cell_corners(geometry, welded, &mut corners[..=right as usize]);
+    }
+    if (left as usize) < corners.len() - 1 {
+        weld_cell_corners(geometry, welded, &mut corners[left as usize..]);
+    }
+}
+
+/// Evaluates the UV derivatives of a triangle.
+fn triangle(geometry: &Geometry, face: usize, vertices: [usize; 3]) -> Triangle {
+    let [v1, v2, v3] = vertices.map(|vertex| geometry.position(vertex));
+    let [t1, t2, t3] = vertices.map(|vertex| geometry.uv(vertex));
+    let (t21x, t21y, t31x, t31y) = (t2.x - t1.x, t2.y - t1.y, t3.x - t1.x, t3.y - t1.y);
+    let (d1, d2) = (v2 - v1, v3 - v1);
+
+    let signed_area = t21x * t31y - t21y * t31x;
+    let os = d1 * t31y - d2 * t21y;
+    let ot = d1 * -t31x + d2 * t21x;
+    let mut triangle = Triangle {
+        face,
+        vertices,
+        os: Vec3::zeros(),
+        ot: Vec3::zeros(),
+        group_with_any: true,
+        orient_preserving: signed_area > 0.0,
+        neighbors: [None; 3],
+        groups: [None; 3],
+    };
+    if not_zero(signed_area) {
+        let sign = if triangle.orient_preserving { 1.0 } else { -1.0 };
+        let (length_os, length_ot) = (length(os), length(ot));
+        if not_zero(length_os) {
+            triangle.os = os * (sign / length_os);
+        }
+        if not_zero(length_ot) {
+            triangle.ot = ot * (sign / length_ot);
+        }
+        let area = signed_area.abs();
+        triangle.group_with_any = !(not_zero(length_os / area) && not_zero(length_ot / area));
+    }
+    triangle
+}
+
+impl Triangle {
+    /// Returns which edge joins vertices `a` and `b`, numbered by its first corner, and
+    /// its ends in winding order.
+    fn edge(&self, a: usize, b: usize) -> (usize, usize, usize) {
+        let [v0, v1, v2] = self.vertices;
+        let on_edge = |vertex: usize| vertex == a || vertex == b;
+        if on_edge(v0) && on_edge(v1) {
+            (0, v0, v1)
+        } else if on_edge(v0) {
+            (2, v2, v0)
+        } else {
+            (1, v1, v2)
+        }
+    }
+}
+
+/// Pairs up triangles sharing an edge in opposite directions. Where more than two
+/// triangles share an edge, the pairs depend on the order the edges are sorted in.
+fn find_neighbors(triangles: &mut [Triangle]) {
+    let mut edges: Vec<[usize; 3]> = (triangles.iter().enumerate())
+        .flat_map(|(index, triangle)| {
+            (0..3).map(move |corner| {
+                let (a, b) = (triangle.vertices[corner], triangle.vertices[(corner + 1) % 3]);
+                [a.min(b), a.max(b), index]
+            })
+        })
+        .collect();
+    // Edges are sorted by first vertex, then each run by second vertex, then by triangle.
+    // A run is sorted when the next one starts, so the last run of each pass isn't.
+    sort_edges(&mut edges, 0, SORT_SEED);
+    for key in 1..3 {
+        let mut start = 0;
+        for index in 1..edges.len() {
+            if edges[start][..key] != edges[index][..key] {
+                sort_edges(&mut edges[start..index], key, SORT_SEED);
+                start = index;
+            }
+        }
+    }
+
+    for (position, &[low, high, a]) in edges.iter().enumerate() {
+        let (edge_a, start_a, end_a) = triangles[a].edge(low, high);
+        if triangles[a].neighbors[edge_a].is_some() {
+            continue;
+        }
+        let neighbor = (edges[position + 1..].iter())
+            .take_while(|[other_low, other_high, _]| (*other_low, *other_high) == (low, high))
+            .find_map(|&[_, _, b]| {
+                let (edge_b, start_b, end_b) = triangles[b].edge(low, high);
+                let opposite = start_a == end_b && end_a == start_b;
+                (opposite && triangles[b].neighbors[edge_b].is_none()).then_some((b, edge_b))
+            });
+        if let Some((b, edge_b)) = neighbor {
+            triangles[a].neighbors[edge_a] = Some(b);
+            triangles[b].neighbors[edge_b] = Some(a);
+        }
+    }
+}
+
+/// Quicksorts `edges` by `key`, choosing pivots from `seed`.
+fn sort_edges(edges: &mut [[usize; 3]], key: usize, mut seed: u32) {
+    if edges.len() < 2 {
+        return;
+    } else if edges.len() == 2 {
+        if edges[0][key] > edges[1][key] {
+            edges.swap(0, 1);
+        }
+        return;
+    }
+
+    let shift = seed & 31;
+    seed = seed.wrapping_add(seed.rotate_left(shift) | seed.rotate_right(32 - shift)).wrapping_add(3);
+    let pivot = edges[(seed % edges.len() as u32) as usize][key];
+    let (mut left, mut right) = (0, edges.len() as isize - 1);
+    while left <= right {
+        while edges[left as usize][key] < pivot {
+            left += 1;
+        }
+        while edges[right as usize][key] > pivot {
+            right -= 1;
+        }
+        if left <= right {
+            edges.swap(left as usize, right as usize);
+            left += 1;
+            right -= 1;
+        }
+    }
+    if right > 0 {
+        sort_edges(&mut edges[..=right as usize], key, seed);
+    }
+    if (left as usize) < edges.len() - 1 {
+        sort_edges(&mut edges[left as usize..], key, seed);
+    }
+}
+
+/// Splits the corners around each vertex into groups reachable from one another through
+/// shared edges without crossing a UV mirror.
+fn build_groups(triangles: &mut [Triangle]) -> Vec<Group> {
+    let mut groups = Vec::new();
+    for index in 0..triangles.len() {
+        for corner in 0..3 {
+            let triangle = &mut triangles[index];
+            if triangle.group_with_any || triangle.groups[corner].is_some() {
+                continue;
+            }
+            let mut group = Group {
+                vertex: triangle.vertices[corner],
+                orient_preserving: triangle.orient_preserving,
+                triangles: vec![index],
+            };
+            triangle.groups[corner] = Some(groups.len());
+            let neighbors = [triangle.neighbors[corner], triangle.neighbors[(corner + 2) % 3]];
+            for neighbor in neighbors.into_iter().flatten() {
+                assign_group(triangles, neighbor, groups.len(), &mut group);
+            }
+            groups.push(group);
+        }
+    }
+    groups
+}
+
+/// Adds the corner of `index` at the group's vertex to the group, then its neighbours
+/// around that vertex. A triangle without UV directions takes the orientation of the
+/// first group reaching it.
+fn assign_group(triangles: &mut [Triangle], index: usize, group_index: usize, group: &mut Group) {
+    let triangle = &mut triangles[index];
+    let corner = (triangle.vertices.iter())
+        .position(|vertex| *vertex == group.vertex)
+        .expect("neighbours share the group's vertex");
+    if triangle.groups[corner].is_some() {
+        return;
+    }
+    if triangle.group_with_any && triangle.groups.iter().all(Option::is_none) {
+        triangle.orient_preserving = group.orient_preserving;
+    }
+    if triangle.orient_preserving != group.orient_preserving {
+        return;
+    }
+
+    group.triangles.push(index);
+    triangle.groups[corner] = Some(group_index);
+    let neighbors = [triangle.neighbors[corner], triangle.neighbors[(corner + 2) % 3]];
+    for neighbor in neighbors.into_iter().flatten() {
+        assign_group(triangles, neighbor, group_index, group);
+    }
+}
+
+/// Averages the tangents of every group's corners, among the triangles whose tangents
+/// lie within `ANGULAR_THRESHOLD` of each other.
+fn generate_spaces(spaces: &mut [TangentSpace], triangles: &[Triangle], groups: &[Group], geometry: &Geometry) {
+    let threshold = ANGULAR_THRESHOLD.to_radians().cos();
+    for (group_index, group) in groups.iter().enumerate() {
+        let normal = geometry.normal(group.vertex);
+        let mut subgroups: Vec<(Vec<usize>, Vec3)> = Vec::new();
+        for &index in &group.triangles {
+            let triangle = &triangles[index];
+            let (os, ot) = (project(triangle.os, normal), project(triangle.ot, normal));
+            let mut members: Vec<usize> = (group.triangles.iter().copied())
+                .filter(|&other_index| {
+                    let other = &triangles[other_index];
+                    let (other_os, other_ot) = (project(other.os, normal), project(other.ot, normal));
+                    triangle.group_with_any
+                        || other.group_with_any
+                        || other_index == index
+                        || (dot(os, other_os) > threshold && dot(ot, other_ot) > threshold)
+                })
+                .collect();
+            members.sort_unstable();
+
+            let tangent = match subgroups.iter().find(|(subgroup, _)| *subgroup == members) {
+                Some((_, tangent)) => *tangent,
+                None => {
+                    let tangent = average_tangent(&members, triangles, group.vertex, geometry);
+                    subgroups.push((members, tangent));
+                    tangent
+                }
+            };
+            let corner = (triangle.groups.iter())
+                .position(|assigned| *assigned == Some(group_index))
+                .expect("group members have a corner in the group");
+            spaces[triangle.face * 3 + corner] = TangentSpace { tangent, orient_preserving: group.orient_preserving };
+        }
+    }
+}
+
+/// Averages the tangents of `members` at `vertex`, weighted by the corner angles.
+fn average_tangent(members: &[usize], triangles: &[Triangle], vertex: usize, geometry: &Geometry) -> Vec3 {
+    let normal = geometry.normal(vertex);
+    let mut tangent = Vec3::zeros();
+    for triangle in members.iter().map(|&index| &triangles[index]).filter(|triangle| !triangle.group_with_any) {
+        let corner = (triangle.vertices.iter())
+            .position(|other| *other == vertex)
+            .expect("group members have a corner at the group's vertex");
+        let [previous, current, next] =
+            [(corner + 2) % 3, corner, (corner + 1) % 3].map(|corner| geometry.position(triangle.vertices[corner]));
+        let (edge1, edge2) = (project(previous - current, normal), project(next - current, normal));
+        // The reference takes the arc cosine in double precision.
+        let angle = (dot(edge1, edge2).clamp(-1.0, 1.0) as f64).acos() as f32;
+        tangent += project(triangle.os, normal) * angle;
+    }
+    normalize_nonzero(tangent)
+}
+
+/// Removes the part of `vector` along `normal` and normalizes what is left.
+fn project(vector: Vec3, normal: Vec3) -> Vec3 {
+    normalize_nonzero(vector - normal * dot(normal, vector))
+}
+
+// The reference's vector helpers, spelled out so results round the same way.
+
+fn dot(a: Vec3, b: Vec3) -> f32 {
+    a.x * b.x + a.y * b.y + a.z * b.z
+}
+
+fn length(vector: Vec3) -> f32 {
+    dot(vector, vector).sqrt()
+}
+
+fn normalize_nonzero(vector: Vec3) -> Vec3 {
+    if not_zero(vector.x) || not_zero(vector.y) || not_zero(vector.z) {
+        vector * (1.0 / length(vector))
+    } else {
+        vector
+    }
+}
+
+fn not_zero(value: f32) -> bool {
+    value.abs() > f32::MIN_POSITIVE
+}
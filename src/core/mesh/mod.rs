@@ -0,0 +1,12 @@
+mod builder;
+mod geometry;
+mod mikktspace;
+mod morph;
+mod obj;
+mod shapes;
+mod simplify;
+
+pub use builder::MeshBuilder;
+pub use geometry::Mesh;
+pub use morph::MorphTarget;
+pub use obj::{ObjMaterial, ObjModel, ObjObject};
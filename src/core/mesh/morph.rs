@@ -0,0 +1,24 @@
+use nalgebra_glm::Vec3;
+
+/// Offsets added to every vertex of a mesh, scaled by a weight, to blend it towards
+/// another shape such as a facial expression.
+///
+/// Several targets can be blended at once; each contributes its offsets times its weight,
+/// see `MeshRenderer::set_morph_weights`.
+///
+/// [`MeshRenderer::set_morph_weights`]: crate::core::components::MeshRenderer::set_morph_weights
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget {
+    pub name: String,
+    /// Position offset of each vertex.
+    pub positions: Vec<Vec3>,
+    /// Normal offset of each vertex, or empty to leave normals unchanged.
+    pub normals: Vec<Vec3>,
+}
+
+impl MorphTarget {
+    /// Creates a target without offsets.
+    pub fn new(name: &str) -> MorphTarget {
+        MorphTarget { name: name.to_string(), ..MorphTarget::default() }
+    }
+}
@@ -0,0 +1,792 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+
+use super::builder::normalize_or;
+use super::MeshBuilder;
+use crate::core::assets::{AssetServer, Handle};
+use crate::core::components::MeshRenderer;
+use crate::core::material::{AlphaMode, Material, PbrMaterial};
+use crate::opengl::Texture;
+
+/// A model loaded from a Wavefront OBJ file.
+///
+/// Faces are triangulated and re-indexed so every vertex has a single index, and split
+/// into one `ObjObject` per group and material so each can be drawn with one call.
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    /// The meshes, in the order their group and material first appear in the file.
+    pub objects: Vec<ObjObject>,
+    /// The materials of every material library the file references.
+    pub materials: Vec<ObjMaterial>,
+    /// The `mtllib` file names, as written in the file.
+    pub material_libraries: Vec<String>,
+}
+
+/// The faces of one group that share a material.
+#[derive(Clone, Debug, Default)]
+pub struct ObjObject {
+    /// The `g` group name, or the `o` object name when the faces are not grouped.
+    pub name: String,
+    /// The `usemtl` material name, if any.
+    pub material: Option<String>,
+    /// The triangulated faces. Normals are always present, UVs and tangents when the
+    /// faces have texture coordinates, and colors when the positions have them.
+    pub mesh: MeshBuilder,
+}
+
+/// A material from a Wavefront MTL library.
+///
+/// Texture paths are resolved against the library's directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Kd`, the diffuse color.
+    pub diffuse: Vec3,
+    /// `Ks`, the specular color.
+    pub specular: Vec3,
+    /// `Ke`, the emitted color.
+    pub emissive: Vec3,
+    /// `Ns`, the Phong specular exponent.
+    pub shininess: Option<f32>,
+    /// `d`, or `1 - Tr`: the opacity.
+    pub dissolve: f32,
+    /// `Pr`, from the PBR extension.
+    pub roughness: Option<f32>,
+    /// `Pm`, from the PBR extension.
+    pub metallic: Option<f32>,
+    /// `map_Kd`
+    pub diffuse_map: Option<PathBuf>,
+    /// `map_Ke`
+    pub emissive_map: Option<PathBuf>,
+    /// `norm`, `map_Bump` or `bump`. Exporters commonly write tangent-space normal maps
+    /// under the bump keywords, so all three are read as normal maps.
+    pub normal_map: Option<PathBuf>,
+    /// The `-bm` option of the normal map.
+    pub normal_scale: f32,
+    /// `map_d`
+    pub alpha_map: Option<PathBuf>,
+}
+
+/// How a re-indexed vertex gets its normal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum VertexNormal {
+    /// The file's normal with this index.
+    Explicit(usize),
+    /// The average of the faces in this smoothing group around the position.
+    Smooth(u32),
+    /// The normal of the face with this index in `ObjParser::face_normals`.
+    Flat(usize),
+}
+
+/// Identifies a unique vertex: OBJ indexes each attribute independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: usize,
+    uv: Option<usize>,
+    normal: VertexNormal,
+}
+
+/// An `ObjObject` being filled.
+#[derive(Default)]
+struct Chunk {
+    object: ObjObject,
+    vertices: HashMap<VertexKey, u32>,
+    keys: Vec<VertexKey>,
+    has_uvs: bool,
+    has_colors: bool,
+}
+
+#[derive(Default)]
+struct ObjParser<'a> {
+    origin: Option<&'a str>,
+    positions: Vec<Vec3>,
+    colors: Vec<Option<Vec4>>,
+    uvs: Vec<Vec2>,
+    normals: Vec<Vec3>,
+    object: Option<String>,
+    group: Option<String>,
+    material: Option<String>,
+    smoothing_group: u32,
+    chunks: Vec<Chunk>,
+    chunk_lookup: HashMap<(String, Option<String>), usize>,
+    smooth_normals: HashMap<(usize, u32), Vec3>,
+    face_normals: Vec<Vec3>,
+    libraries: Vec<String>,
+}
+
+impl ObjModel {
+    /// Loads an OBJ file and the material libraries it references.
+    ///
+    /// A material library that cannot be read is logged and skipped, leaving its
+    /// materials undefined.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the OBJ file. Material libraries are looked up next to it.
+    ///
+    /// # Returns
+    ///
+    /// The model, or an `Err(String)` naming the file and line of the first malformed
+    /// statement.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ObjModel, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        let mut model = ObjParser::new(Some(&path.display().to_string())).parse(&source)?;
+
+        let directory = path.parent().unwrap_or(Path::new(""));
+        for library in &model.material_libraries {
+            let library_path = directory.join(library.replace('\\', "/"));
+            if !library_path.is_file() {
+                log::warn!("{}: material library {} not found", path.display(), library_path.display());
+                continue;
+            }
+            model.materials.extend(ObjMaterial::load_library(&library_path)?);
+        }
+        Ok(model)
+    }
+
+    /// Parses OBJ source without loading its material libraries.
+    ///
+    /// # Returns
+    ///
+    /// The model with no `materials`, or an `Err(String)` naming the line of the first
+    /// malformed statement.
+    pub fn parse(source: &str) -> Result<ObjModel, String> {
+        ObjParser::new(None).parse(source)
+    }
+
+    /// Finds a material by name.
+    pub fn get_material(&self, name: &str) -> Option<&ObjMaterial> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+
+    /// Uploads every object with a `PbrMaterial` converted from its OBJ material.
+    ///
+    /// Objects without a material, or whose material is not defined, get the default
+    /// `PbrMaterial`. Objects using the same material share one `Handle<Material>`, and
+    /// textures are loaded through `assets`, so ones used by several materials or models
+    /// are only loaded once.
+    ///
+    /// # Arguments
+    ///
+    /// * `assets` - The asset server that owns the uploaded meshes, materials and textures.
+    ///
+    /// # Returns
+    ///
+    /// One `MeshRenderer` per object, or an `Err(String)` if a texture cannot be loaded.
+    pub fn build(&self, assets: &mut AssetServer) -> Result<Vec<MeshRenderer>, String> {
+        let mut materials: HashMap<Option<&str>, Handle<Material>> = HashMap::new();
+        let mut renders = Vec::with_capacity(self.objects.len());
+        for object in &self.objects {
+            let name = object.material.as_deref().filter(|name| self.get_material(name).is_some());
+            let material = match materials.get(&name) {
+                Some(material) => material.clone(),
+                None => {
+                    let material = match name.and_then(|name| self.get_material(name)) {
+                        Some(material) => material.to_pbr_material(assets)?,
+                        None => PbrMaterial::new(),
+                    };
+                    let material = assets.add(Material::from(material));
+                    materials.insert(name, material.clone());
+                    material
+                }
+            };
+            renders.push(MeshRenderer::new(assets.add(object.mesh.to_mesh()), material));
+        }
+        Ok(renders)
+    }
+}
+
+impl ObjMaterial {
+    /// Creates a white, opaque material.
+    pub fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            diffuse: Vec3::repeat(1.0),
+            specular: Vec3::zeros(),
+            emissive: Vec3::zeros(),
+            shininess: None,
+            dissolve: 1.0,
+            roughness: None,
+            metallic: None,
+            diffuse_map: None,
+            emissive_map: None,
+            normal_map: None,
+            normal_scale: 1.0,
+            alpha_map: None,
+        }
+    }
+
+    /// Loads every material of an MTL library.
+    ///
+    /// # Returns
+    ///
+    /// The materials, or an `Err(String)` naming the file and line of the first
+    /// malformed statement.
+    pub fn load_library<P: AsRef<Path>>(path: P) -> Result<Vec<ObjMaterial>, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        parse_library(&source, Some(&path.display().to_string()), directory)
+    }
+
+    /// Parses MTL source.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The MTL statements.
+    /// * `directory` - The directory texture paths are relative to.
+    ///
+    /// # Returns
+    ///
+    /// The materials, or an `Err(String)` naming the line of the first malformed statement.
+    pub fn parse_library(source: &str, directory: &Path) -> Result<Vec<ObjMaterial>, String> {
+        parse_library(source, None, directory)
+    }
+
+    /// Converts the material to the built-in metallic/roughness model, loading its textures.
+    ///
+    /// `Pr` and `Pm` are used when present. Otherwise the roughness is derived from the
+    /// specular exponent and the surface is a dielectric. A `d` below one makes the
+    /// material alpha blended, and a `map_d` alpha tests the alpha of `map_Kd`.
+    ///
+    /// # Arguments
+    ///
+    /// * `assets` - The asset server the textures are loaded through.
+    ///
+    /// # Returns
+    ///
+    /// The material, or an `Err(String)` if a texture cannot be loaded.
+    pub fn to_pbr_material(&self, assets: &mut AssetServer) -> Result<PbrMaterial, String> {
+        let mut material = PbrMaterial::new();
+        material.albedo_factor = Vec4::new(self.diffuse.x, self.diffuse.y, self.diffuse.z, self.dissolve);
+        material.metallic_factor = self.metallic.unwrap_or(0.0);
+        material.roughness_factor = self.roughness.or(self.shininess.map(shininess_to_roughness)).unwrap_or(1.0);
+        material.emissive_factor = self.emissive;
+        material.normal_scale = self.normal_scale;
+        material.alpha_mode = if self.dissolve < 1.0 {
+            AlphaMode::Blend
+        } else if self.alpha_map.is_some() {
+            AlphaMode::Mask(0.5)
+        } else {
+            AlphaMode::Opaque
+        };
+
+        material.albedo_map = load_texture(assets, &self.diffuse_map, true)?;
+        material.normal_map = load_texture(assets, &self.normal_map, false)?;
+        material.emissive_map = load_texture(assets, &self.emissive_map, true)?;
+        if material.emissive_map.is_some() && self.emissive == Vec3::zeros() {
+            // The factor scales the map, so an emissive map without `Ke` would stay black.
+            material.emissive_factor = Vec3::repeat(1.0);
+        }
+        Ok(material)
+    }
+}
+
+impl<'a> ObjParser<'a> {
+    fn new(origin: Option<&'a str>) -> ObjParser<'a> {
+        ObjParser { origin, ..ObjParser::default() }
+    }
+
+    fn parse(mut self, source: &str) -> Result<ObjModel, String> {
+        for (number, statement) in source.lines().enumerate() {
+            let line = number + 1;
+            let tokens: Vec<&str> = without_comment(statement).split_whitespace().collect();
+            let Some((&keyword, arguments)) = tokens.split_first() else { continue };
+            self.statement(keyword, arguments, line)?;
+        }
+        Ok(self.finish())
+    }
+
+    fn statement(&mut self, keyword: &str, arguments: &[&str], line: usize) -> Result<(), String> {
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(arguments, 3, keyword, self.origin, line)?;
+                self.positions.push(Vec3::new(x, y, z));
+                // Vertex colors are a common extension: `v x y z r g b`.
+                let color = match arguments.len() {
+                    6.. => {
+                        let [r, g, b] = parse_floats(&arguments[3..], 3, keyword, self.origin, line)?;
+                        Some(Vec4::new(r, g, b, 1.0))
+                    }
+                    _ => None,
+                };
+                self.colors.push(color);
+            }
+            "vt" => {
+                let [u, v] = parse_floats(arguments, 1, keyword, self.origin, line)?;
+                // OBJ puts v = 0 at the bottom of the image, MeshBuilder at the top.
+                self.uvs.push(Vec2::new(u, 1.0 - v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(arguments, 3, keyword, self.origin, line)?;
+                self.normals.push(normalize_or(Vec3::new(x, y, z), Vec3::y()));
+            }
+            "f" => self.face(arguments, line)?,
+            "o" => {
+                self.object = Some(arguments.join(" "));
+                self.group = None;
+            }
+            "g" => self.group = (!arguments.is_empty()).then(|| arguments.join(" ")),
+            "usemtl" => {
+                if arguments.is_empty() {
+                    return Err(error(self.origin, line, "'usemtl' needs a material name"));
+                }
+                self.material = Some(arguments.join(" "));
+            }
+            "mtllib" => self.libraries.extend(arguments.iter().map(|library| library.to_string())),
+            "s" => {
+                self.smoothing_group = match arguments.first() {
+                    Some(&"off") => 0,
+                    Some(group) => group
+                        .parse()
+                        .map_err(|_| error(self.origin, line, format!("invalid smoothing group '{group}'")))?,
+                    None => return Err(error(self.origin, line, "'s' needs a group number or 'off'")),
+                };
+            }
+            // Points, lines, free-form geometry and rendering attributes are not supported.
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn face(&mut self, arguments: &[&str], line: usize) -> Result<(), String> {
+        if arguments.len() < 3 {
+            return Err(error(self.origin, line, format!("face has {} vertices, at least 3 are needed", arguments.len())));
+        }
+
+        let mut corners = Vec::with_capacity(arguments.len());
+        for corner in arguments {
+            let mut indices = corner.split('/');
+            let position = self.resolve(indices.next(), self.positions.len(), "position", line)?
+                .ok_or_else(|| error(self.origin, line, format!("face vertex '{corner}' has no position")))?;
+            let uv = self.resolve(indices.next(), self.uvs.len(), "texture coordinate", line)?;
+            let normal = self.resolve(indices.next(), self.normals.len(), "normal", line)?;
+            if indices.next().is_some() {
+                return Err(error(self.origin, line, format!("invalid face vertex '{corner}'")));
+            }
+            corners.push((position, uv, normal));
+        }
+
+        let points: Vec<Vec3> = corners.iter().map(|&(position, _, _)| self.positions[position]).collect();
+        let face_normal = newell_normal(&points);
+        let derived = match self.smoothing_group {
+            0 => {
+                self.face_normals.push(face_normal);
+                VertexNormal::Flat(self.face_normals.len() - 1)
+            }
+            group => VertexNormal::Smooth(group),
+        };
+
+        let chunk = self.chunk();
+        let mut vertices = Vec::with_capacity(corners.len());
+        for &(position, uv, normal) in &corners {
+            let normal = normal.map_or(derived, VertexNormal::Explicit);
+            if let VertexNormal::Smooth(group) = normal {
+                *self.smooth_normals.entry((position, group)).or_insert_with(Vec3::zeros) += face_normal;
+            }
+
+            let chunk = &mut self.chunks[chunk];
+            let key = VertexKey { position, uv, normal };
+            let index = *chunk.vertices.entry(key).or_insert_with(|| {
+                let mesh = &mut chunk.object.mesh;
+                mesh.positions.push(self.positions[position]);
+                mesh.uvs.push(uv.map_or(Vec2::zeros(), |uv| self.uvs[uv]));
+                mesh.colors.push(self.colors[position].unwrap_or(Vec4::repeat(1.0)));
+                chunk.keys.push(key);
+                chunk.has_uvs |= uv.is_some();
+                chunk.has_colors |= self.colors[position].is_some();
+                (mesh.positions.len() - 1) as u32
+            });
+            vertices.push(index);
+        }
+
+        let mesh = &mut self.chunks[chunk].object.mesh;
+        for [a, b, c] in triangulate(&points, &face_normal) {
+            mesh.indices.extend_from_slice(&[vertices[a], vertices[b], vertices[c]]);
+        }
+        Ok(())
+    }
+
+    /// Resolves a 1-based or negative, relative OBJ index. Empty indices resolve to `None`.
+    fn resolve(&self, index: Option<&str>, count: usize, kind: &str, line: usize) -> Result<Option<usize>, String> {
+        let Some(index) = index.filter(|index| !index.is_empty()) else { return Ok(None) };
+        let value: i64 = index
+            .parse()
+            .map_err(|_| error(self.origin, line, format!("invalid {kind} index '{index}'")))?;
+
+        let resolved = match value {
+            1.. => value - 1,
+            ..=-1 => count as i64 + value,
+            0 => -1,
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(error(
+                self.origin,
+                line,
+                format!("{kind} index {value} is out of range, {count} defined so far"),
+            ));
+        }
+        Ok(Some(resolved as usize))
+    }
+
+    /// Returns the chunk for the current group and material, creating it on first use.
+    fn chunk(&mut self) -> usize {
+        let name = self.group.clone().or_else(|| self.object.clone()).unwrap_or_else(|| "default".to_string());
+        let key = (name, self.material.clone());
+        if let Some(&chunk) = self.chunk_lookup.get(&key) {
+            return chunk;
+        }
+
+        self.chunks.push(Chunk {
+            object: ObjObject {
+                name: key.0.clone(),
+                material: key.1.clone(),
+                mesh: MeshBuilder::new(),
+            },
+            ..Chunk::default()
+        });
+        self.chunk_lookup.insert(key, self.chunks.len() - 1);
+        self.chunks.len() - 1
+    }
+
+    /// Resolves the normals of every vertex and drops the attributes no face used.
+    fn finish(self) -> ObjModel {
+        let objects = self
+            .chunks
+            .into_iter()
+            .filter(|chunk| !chunk.object.mesh.indices.is_empty())
+            .map(|mut chunk| {
+                let mesh = &mut chunk.object.mesh;
+                mesh.normals = chunk
+                    .keys
+                    .iter()
+                    .map(|key| match key.normal {
+                        VertexNormal::Explicit(normal) => self.normals[normal],
+                        VertexNormal::Smooth(group) => {
+                            normalize_or(self.smooth_normals[&(key.position, group)], Vec3::y())
+                        }
+                        VertexNormal::Flat(face) => normalize_or(self.face_normals[face], Vec3::y()),
+                    })
+                    .collect();
+                if !chunk.has_colors {
+                    mesh.colors.clear();
+                }
+                if chunk.has_uvs {
+                    mesh.compute_tangents();
+                } else {
+                    mesh.uvs.clear();
+                }
+                chunk.object
+            })
+            .collect();
+
+        ObjModel {
+            objects,
+            materials: Vec::new(),
+            material_libraries: self.libraries,
+        }
+    }
+}
+
+fn parse_library(source: &str, origin: Option<&str>, directory: &Path) -> Result<Vec<ObjMaterial>, String> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (number, statement) in source.lines().enumerate() {
+        let line = number + 1;
+        let tokens: Vec<&str> = without_comment(statement).split_whitespace().collect();
+        let Some((&keyword, arguments)) = tokens.split_first() else { continue };
+
+        if keyword == "newmtl" {
+            if arguments.is_empty() {
+                return Err(error(origin, line, "'newmtl' needs a material name"));
+            }
+            materials.push(ObjMaterial::new(&arguments.join(" ")));
+            continue;
+        }
+
+        let Some(material) = materials.last_mut() else {
+            return Err(error(origin, line, format!("'{keyword}' appears before any 'newmtl'")));
+        };
+        let color = |arguments: &[&str]| -> Result<Vec3, String> {
+            let [r, g, b] = parse_floats(arguments, 1, keyword, origin, line)?;
+            // A single value stands for a gray.
+            Ok(if arguments.len() == 1 { Vec3::repeat(r) } else { Vec3::new(r, g, b) })
+        };
+        let scalar = |arguments: &[&str]| -> Result<f32, String> {
+            let [value] = parse_floats(arguments, 1, keyword, origin, line)?;
+            Ok(value)
+        };
+        let texture = |arguments: &[&str]| -> Result<(PathBuf, Option<f32>), String> {
+            let (file, bump_scale) = parse_texture(arguments).map_err(|message| error(origin, line, message))?;
+            Ok((directory.join(file), bump_scale))
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = color(arguments)?,
+            "Ks" => material.specular = color(arguments)?,
+            "Ke" => material.emissive = color(arguments)?,
+            "Ns" => material.shininess = Some(scalar(arguments)?),
+            "d" => material.dissolve = scalar(arguments.strip_prefix(&["-halo"]).unwrap_or(arguments))?,
+            "Tr" => material.dissolve = 1.0 - scalar(arguments)?,
+            "Pr" => material.roughness = Some(scalar(arguments)?),
+            "Pm" => material.metallic = Some(scalar(arguments)?),
+            "map_Kd" => material.diffuse_map = Some(texture(arguments)?.0),
+            "map_Ke" => material.emissive_map = Some(texture(arguments)?.0),
+            "map_d" => material.alpha_map = Some(texture(arguments)?.0),
+            "norm" | "map_Bump" | "map_bump" | "bump" => {
+                let (file, scale) = texture(arguments)?;
+                material.normal_map = Some(file);
+                material.normal_scale = scale.unwrap_or(1.0);
+            }
+            // Ambient color, refraction, illumination models and other maps have no
+            // counterpart in the metallic/roughness model.
+            _ => {}
+        }
+    }
+    Ok(materials)
+}
+
+/// Splits a texture statement into its file name and `-bm` bump multiplier, skipping
+/// the other options.
+fn parse_texture(arguments: &[&str]) -> Result<(String, Option<f32>), String> {
+    let mut bump_scale = None;
+    let mut rest = arguments;
+    while let Some((&option, tail)) = rest.split_first().filter(|(first, _)| first.starts_with('-')) {
+        let values = match option {
+            "-bm" => {
+                let value = tail.first().and_then(|value| value.parse().ok()).filter(|value: &f32| value.is_finite());
+                bump_scale = Some(value.ok_or_else(|| "'-bm' needs a number".to_string())?);
+                1
+            }
+            // Up to three numbers, of which only the first is required.
+            "-o" | "-s" | "-t" => 1 + tail.iter().skip(1).take(2).take_while(|value| value.parse::<f32>().is_ok()).count(),
+            "-mm" => 2,
+            "-blendu" | "-blendv" | "-boost" | "-cc" | "-clamp" | "-imfchan" | "-texres" | "-type" => 1,
+            _ => return Err(format!("unknown texture option '{option}'")),
+        };
+        if tail.len() < values {
+            return Err(format!("'{option}' needs {values} values"));
+        }
+        rest = &tail[values..];
+    }
+
+    if rest.is_empty() {
+        return Err("missing texture file name".to_string());
+    }
+    Ok((rest.join(" ").replace('\\', "/"), bump_scale))
+}
+
+fn without_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or_default()
+}
+
+/// Parses up to `N` finite numbers, of which the first `required` must be present.
+/// Missing optional numbers are zero.
+fn parse_floats<const N: usize>(
+    arguments: &[&str],
+    required: usize,
+    keyword: &str,
+    origin: Option<&str>,
+    line: usize,
+) -> Result<[f32; N], String> {
+    if arguments.len() < required {
+        return Err(error(origin, line, format!("'{keyword}' needs at least {required} numbers")));
+    }
+
+    let mut values = [0.0; N];
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        // Values too large for an `f32` parse as infinity.
+        *value = (argument.parse().ok())
+            .filter(|value: &f32| value.is_finite())
+            .ok_or_else(|| error(origin, line, format!("invalid number '{argument}' in '{keyword}'")))?;
+    }
+    Ok(values)
+}
+
+fn error(origin: Option<&str>, line: usize, message: impl Display) -> String {
+    match origin {
+        Some(origin) => format!("{origin}:{line}: {message}"),
+        None => format!("line {line}: {message}"),
+    }
+}
+
+/// Returns the polygon's normal scaled by twice its area, robust to slightly non-planar
+/// polygons.
+fn newell_normal(points: &[Vec3]) -> Vec3 {
+    let mut normal = Vec3::zeros();
+    for (index, current) in points.iter().enumerate() {
+        let next = points[(index + 1) % points.len()];
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+    normal
+}
+
+/// Splits a polygon into triangles by ear clipping, keeping its winding.
+///
+/// Concave polygons are handled. Polygons too degenerate to clip are fanned.
+fn triangulate(points: &[Vec3], normal: &Vec3) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Project onto the axis plane most facing the normal, mirrored if needed so the
+    // polygon is counter-clockwise in 2D.
+    let axis = normal.iamax();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mirror = if normal[axis] < 0.0 { -1.0 } else { 1.0 };
+    let projected: Vec<Vec2> = points.iter().map(|point| Vec2::new(point[u] * mirror, point[v])).collect();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let corners = |index: usize| {
+            [remaining[(index + count - 1) % count], remaining[index], remaining[(index + 1) % count]]
+        };
+        let ear = (0..count).find(|&index| is_ear(&projected, &remaining, corners(index)));
+        let Some(ear) = ear else { break };
+        triangles.push(corners(ear));
+        remaining.remove(ear);
+    }
+
+    for index in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[index], remaining[index + 1]]);
+    }
+    triangles
+}
+
+fn is_ear(points: &[Vec2], remaining: &[usize], [a, b, c]: [usize; 3]) -> bool {
+    let (pa, pb, pc) = (points[a], points[b], points[c]);
+    if cross(pb - pa, pc - pb) <= f32::EPSILON {
+        return false;
+    }
+    remaining.iter().filter(|&&index| index != a && index != b && index != c).all(|&index| {
+        let point = points[index];
+        if point == pa || point == pb || point == pc {
+            return true;
+        }
+        cross(pb - pa, point - pa) < 0.0 || cross(pc - pb, point - pb) < 0.0 || cross(pa - pc, point - pc) < 0.0
+    })
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Converts a Phong specular exponent to perceptual roughness, matching the width of
+/// the Blinn-Phong lobe to the GGX one.
+fn shininess_to_roughness(shininess: f32) -> f32 {
+    (2.0 / (shininess.max(0.0) + 2.0)).sqrt().sqrt().clamp(0.0, 1.0)
+}
+
+fn load_texture(
+    assets: &mut AssetServer,
+    path: &Option<PathBuf>,
+    srgb: bool,
+) -> Result<Option<Handle<Texture>>, String> {
+    path.as_ref().map(|path| assets.load_texture(path, srgb)).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> String {
+        ObjModel::parse(source).expect_err(source)
+    }
+
+    fn library_error(source: &str) -> String {
+        ObjMaterial::parse_library(source, Path::new("")).expect_err(source)
+    }
+
+    #[test]
+    fn parses_a_quad_into_two_triangles() {
+        let model = ObjModel::parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nf 1/1 2/1 3/1 -1/1\n").unwrap();
+        assert_eq!(model.objects.len(), 1);
+        assert_eq!(model.objects[0].name, "default");
+        assert_eq!((model.objects[0].mesh.positions.len(), model.objects[0].mesh.indices.len()), (4, 6));
+    }
+
+    #[test]
+    fn malformed_statements_name_their_line() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\n";
+        let invalid = [
+            ("v 1 2".to_string(), "line 1: 'v' needs at least 3 numbers"),
+            ("\n# comment\nvn 0 x 1".to_string(), "line 3: invalid number 'x' in 'vn'"),
+            (format!("{vertices}f 1 2"), "line 4: face has 2 vertices, at least 3 are needed"),
+            (format!("{vertices}f 1 2 4"), "line 4: position index 4 is out of range, 3 defined so far"),
+            (format!("{vertices}f 0 1 2"), "line 4: position index 0 is out of range, 3 defined so far"),
+            (format!("{vertices}f 1 2 3/1"), "line 4: texture coordinate index 1 is out of range, 0 defined so far"),
+            (format!("{vertices}f 1 2 /1"), "line 4: face vertex '/1' has no position"),
+            (format!("{vertices}f 1 2 3//x"), "line 4: invalid normal index 'x'"),
+            (format!("{vertices}f 1 2 3///"), "line 4: invalid face vertex '3///'"),
+            ("usemtl\n".to_string(), "line 1: 'usemtl' needs a material name"),
+            ("s 1\ns".to_string(), "line 2: 's' needs a group number or 'off'"),
+            ("s on".to_string(), "line 1: invalid smoothing group 'on'"),
+        ];
+        for (source, expected) in invalid {
+            assert_eq!(error(&source), expected);
+        }
+    }
+
+    #[test]
+    fn malformed_material_statements_name_their_line() {
+        let invalid = [
+            ("Kd 1 1 1", "line 1: 'Kd' appears before any 'newmtl'"),
+            ("newmtl", "line 1: 'newmtl' needs a material name"),
+            ("newmtl red\nKd", "line 2: 'Kd' needs at least 1 numbers"),
+            ("newmtl red\n\nNs shiny", "line 3: invalid number 'shiny' in 'Ns'"),
+            ("newmtl red\nmap_Kd", "line 2: missing texture file name"),
+            ("newmtl red\nmap_Kd -o 1 2", "line 2: missing texture file name"),
+            ("newmtl red\nbump -bm high bump.png", "line 2: '-bm' needs a number"),
+            ("newmtl red\nmap_Kd -blur 1 red.png", "line 2: unknown texture option '-blur'"),
+            ("newmtl red\nmap_Kd -mm 0", "line 2: '-mm' needs 2 values"),
+        ];
+        for (source, expected) in invalid {
+            assert_eq!(library_error(source), expected);
+        }
+    }
+
+    #[test]
+    fn values_overflowing_their_type_are_errors() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\n";
+        let invalid = [
+            ("v 1e39 0 0".to_string(), "line 1: invalid number '1e39' in 'v'"),
+            ("v 0 0 0 1 inf 1".to_string(), "line 1: invalid number 'inf' in 'v'"),
+            ("vt NaN".to_string(), "line 1: invalid number 'NaN' in 'vt'"),
+            (format!("{vertices}f 1 2 9223372036854775808"), "line 4: invalid position index '9223372036854775808'"),
+            (
+                format!("{vertices}f 1 2 -9223372036854775808"),
+                "line 4: position index -9223372036854775808 is out of range, 3 defined so far",
+            ),
+            ("s 4294967296".to_string(), "line 1: invalid smoothing group '4294967296'"),
+        ];
+        for (source, expected) in invalid {
+            assert_eq!(error(&source), expected);
+        }
+        assert_eq!(library_error("newmtl red\nNs -1e39"), "line 2: invalid number '-1e39' in 'Ns'");
+        assert_eq!(library_error("newmtl red\nbump -bm 1e39 bump.png"), "line 2: '-bm' needs a number");
+    }
+
+    #[test]
+    fn errors_in_files_name_the_file() {
+        let dir = std::env::temp_dir().join(format!("foux-obj-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (model, library) = (dir.join("model.obj"), dir.join("model.mtl"));
+        fs::write(&model, "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\n").unwrap();
+        fs::write(&library, "newmtl red\nKd 1 0 x\n").unwrap();
+        let library_err = ObjModel::load(&model).expect_err("the library was read");
+        fs::write(&model, "v 0 0 0\nf 1 2 3\n").unwrap();
+        let model_err = ObjModel::load(&model).expect_err("the model was read");
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(library_err, format!("{}:2: invalid number 'x' in 'Kd'", library.display()));
+        assert_eq!(model_err, format!("{}:2: position index 2 is out of range, 1 defined so far", model.display()));
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use nalgebra_glm::{Vec2, Vec3};
+
+use super::MeshBuilder;
+
+/// One point of a profile revolved around the Y axis by `lathe`.
+#[derive(Clone, Copy)]
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    /// The normal in the profile plane: outward radial component and `y` component.
+    normal: Vec2,
+    /// Texture `v` coordinate of the ring.
+    v: f32,
+}
+
+impl MeshBuilder {
+    /// Creates a flat plane in the XZ plane, centered on the origin and facing `+Y`.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Size along X.
+    /// * `depth` - Size along Z.
+    /// * `subdivisions` - Number of quads along each side; at least one is used.
+    pub fn plane(width: f32, depth: f32, subdivisions: u32) -> MeshBuilder {
+        let cells = subdivisions.max(1);
+        let mut mesh = MeshBuilder::new();
+        for row in 0..=cells {
+            for column in 0..=cells {
+                let (u, v) = (column as f32 / cells as f32, row as f32 / cells as f32);
+                mesh.positions.push(Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth));
+                mesh.normals.push(Vec3::y());
+                mesh.uvs.push(Vec2::new(u, v));
+            }
+        }
+        // Rows run along +Z and columns along +X, so `grid_indices` winds towards +Y.
+        mesh.indices = grid_indices(cells, cells);
+        mesh.compute_tangents();
+        mesh
+    }
+
+    /// Creates an axis-aligned cube centered on the origin, with hard edges and each
+    /// face mapped to the whole texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Length of every edge.
+    pub fn cube(size: f32) -> MeshBuilder {
+        let half = size / 2.0;
+        // Normal, then the face's `u` and `v` axes with `u × v = normal`.
+        let faces = [
+            (Vec3::x(), -Vec3::z(), Vec3::y()),
+            (-Vec3::x(), Vec3::z(), Vec3::y()),
+            (Vec3::y(), Vec3::x(), -Vec3::z()),
+            (-Vec3::y(), Vec3::x(), Vec3::z()),
+            (Vec3::z(), Vec3::x(), Vec3::y()),
+            (-Vec3::z(), -Vec3::x(), Vec3::y()),
+        ];
+
+        let mut mesh = MeshBuilder::new();
+        for (normal, u, v) in faces {
+            let base = mesh.positions.len() as u32;
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+            for (su, sv) in corners {
+                mesh.positions.push((normal + u * su + v * sv) * half);
+                mesh.normals.push(normal);
+                mesh.uvs.push(Vec2::new((su + 1.0) / 2.0, (1.0 - sv) / 2.0));
+            }
+            mesh.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+        mesh.compute_tangents();
+        mesh
+    }
+
+    /// Creates a sphere from rings of latitude and segments of longitude.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius` - Radius of the sphere.
+    /// * `segments` - Number of segments around the Y axis; at least three are used.
+    /// * `rings` - Number of rings from pole to pole; at least two are used.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshBuilder {
+        let rings = rings.max(2);
+        let profile = (0..=rings).map(|ring| {
+            let v = ring as f32 / rings as f32;
+            // `sin(PI)` rounds below zero, which would spread the south pole into slivers.
+            let (sin, cos) = (v * PI).sin_cos();
+            let sin = sin.max(0.0);
+            ProfilePoint {
+                radius: radius * sin,
+                y: radius * cos,
+                normal: Vec2::new(sin, cos),
+                v,
+            }
+        });
+        lathe(&profile.collect::<Vec<_>>(), segments)
+    }
+
+    /// Creates a sphere by repeatedly subdividing an icosahedron, which spreads the
+    /// vertices more evenly than `uv_sphere`.
+    ///
+    /// UVs use a spherical projection, so the triangles crossing the `u = 0` seam stretch
+    /// across the texture, and their vertices are split by `compute_tangents`.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius` - Radius of the sphere.
+    /// * `subdivisions` - How many times every triangle is split in four.
+    pub fn ico_sphere(radius: f32, subdivisions: u32) -> MeshBuilder {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut directions: Vec<Vec3> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|&(x, y, z)| Vec3::new(x, y, z).normalize())
+        .collect();
+
+        #[rustfmt::skip]
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::new();
+            let mut midpoint = |a: u32, b: u32, directions: &mut Vec<Vec3>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let middle = (directions[a as usize] + directions[b as usize]).normalize();
+                    directions.push(middle);
+                    directions.len() as u32 - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b, &mut directions);
+                    let bc = midpoint(b, c, &mut directions);
+                    let ca = midpoint(c, a, &mut directions);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut mesh = MeshBuilder::new();
+        for direction in directions {
+            let u = (-direction.z).atan2(direction.x).rem_euclid(TAU) / TAU;
+            let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+            mesh.positions.push(direction * radius);
+            mesh.normals.push(direction);
+            mesh.uvs.push(Vec2::new(u, v));
+        }
+        mesh.indices = triangles.into_iter().flatten().collect();
+        mesh.compute_tangents();
+        mesh
+    }
+
+    /// Creates a capped cylinder around the Y axis, centered on the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius` - Radius of the cylinder.
+    /// * `height` - Height along Y.
+    /// * `segments` - Number of segments around the Y axis; at least three are used.
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> MeshBuilder {
+        let half = height / 2.0;
+        let side = [
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: Vec2::x(),
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: Vec2::x(),
+                v: 1.0,
+            },
+        ];
+
+        let mut mesh = lathe(&side, segments);
+        mesh.merge(&cap(radius, half, segments, true));
+        mesh.merge(&cap(radius, -half, segments, false));
+        mesh
+    }
+
+    /// Creates a cone around the Y axis with its apex at `+Y` and a capped base,
+    /// centered on the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius` - Radius of the base.
+    /// * `height` - Height along Y.
+    /// * `segments` - Number of segments around the Y axis; at least three are used.
+    pub fn cone(radius: f32, height: f32, segments: u32) -> MeshBuilder {
+        let half = height / 2.0;
+        let slope = Vec2::new(height, radius).normalize();
+        let side = [
+            ProfilePoint {
+                radius: 0.0,
+                y: half,
+                normal: slope,
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: slope,
+                v: 1.0,
+            },
+        ];
+
+        let mut mesh = lathe(&side, segments);
+        mesh.merge(&cap(radius, -half, segments, false));
+        mesh
+    }
+
+    /// Creates a capsule around the Y axis: a cylinder closed by two hemispheres,
+    /// centered on the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `radius` - Radius of the cylinder and hemispheres.
+    /// * `height` - Height of the cylindrical part; the total height is `height + 2 * radius`.
+    /// * `segments` - Number of segments around the Y axis; at least three are used.
+    /// * `rings` - Number of rings in each hemisphere; at least one is used.
+    pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshBuilder {
+        let rings = rings.max(1);
+        let half = height / 2.0;
+        let angles = (0..=rings).map(|ring| ring as f32 / rings as f32 * FRAC_PI_2);
+        let top = angles.clone().map(|angle| (angle, half));
+        let bottom = angles.map(|angle| (angle + FRAC_PI_2, -half));
+
+        // `v` follows the arc length along the profile, so the texture is not stretched.
+        let total = PI * radius + height;
+        let profile: Vec<ProfilePoint> = top
+            .chain(bottom)
+            .map(|(angle, center)| {
+                let (sin, cos) = angle.sin_cos();
+                let sin = sin.max(0.0);
+                let length = angle * radius + if center < 0.0 { height } else { 0.0 };
+                ProfilePoint {
+                    radius: radius * sin,
+                    y: center + radius * cos,
+                    normal: Vec2::new(sin, cos),
+                    v: if total > 0.0 { length / total } else { 0.0 },
+                }
+            })
+            .collect();
+        lathe(&profile, segments)
+    }
+
+    /// Creates a torus lying in the XZ plane, centered on the origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `major_radius` - Distance from the center to the middle of the tube.
+    /// * `minor_radius` - Radius of the tube.
+    /// * `major_segments` - Number of segments around the Y axis; at least three are used.
+    /// * `minor_segments` - Number of segments around the tube; at least three are used.
+    pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> MeshBuilder {
+        let (columns, rows) = (major_segments.max(3), minor_segments.max(3));
+        let mut mesh = MeshBuilder::new();
+        for row in 0..=rows {
+            let v = row as f32 / rows as f32;
+            let (sin_minor, cos_minor) = (v * TAU).sin_cos();
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let (sin_major, cos_major) = (u * TAU).sin_cos();
+                let radial = Vec3::new(cos_major, 0.0, -sin_major);
+                // The tube is walked downwards on its outside so the winding faces out.
+                let normal = radial * cos_minor - Vec3::y() * sin_minor;
+                mesh.positions.push(radial * major_radius + normal * minor_radius);
+                mesh.normals.push(normal);
+                mesh.uvs.push(Vec2::new(u, v));
+            }
+        }
+        mesh.indices = grid_indices(rows, columns);
+        mesh.compute_tangents();
+        mesh
+    }
+}
+
+/// Revolves a profile, listed from top to bottom, around the Y axis.
+///
+/// The seam at `u = 0` is duplicated so the texture wraps once around the shape.
+fn lathe(profile: &[ProfilePoint], segments: u32) -> MeshBuilder {
+    let columns = segments.max(3);
+    let mut mesh = MeshBuilder::new();
+    for point in profile {
+        for column in 0..=columns {
+            let u = column as f32 / columns as f32;
+            let (sin, cos) = (u * TAU).sin_cos();
+            let radial = Vec3::new(cos, 0.0, -sin);
+            mesh.positions.push(radial * point.radius + Vec3::y() * point.y);
+            mesh.normals.push(radial * point.normal.x + Vec3::y() * point.normal.y);
+            mesh.uvs.push(Vec2::new(u, point.v));
+        }
+    }
+    mesh.indices = grid_indices(profile.len().saturating_sub(1) as u32, columns);
+    mesh.compute_tangents();
+    mesh
+}
+
+/// Creates a disk at height `y` facing `+Y` when `up`, or `-Y` otherwise.
+fn cap(radius: f32, y: f32, segments: u32, up: bool) -> MeshBuilder {
+    let columns = segments.max(3);
+    let normal = if up { Vec3::y() } else { -Vec3::y() };
+    let mut mesh = MeshBuilder::new();
+
+    mesh.positions.push(Vec3::new(0.0, y, 0.0));
+    mesh.normals.push(normal);
+    mesh.uvs.push(Vec2::new(0.5, 0.5));
+    for column in 0..=columns {
+        let (sin, cos) = (column as f32 / columns as f32 * TAU).sin_cos();
+        mesh.positions.push(Vec3::new(cos * radius, y, -sin * radius));
+        mesh.normals.push(normal);
+        // Mirror the bottom cap's mapping so the texture reads correctly from below.
+        let v = if up { 0.5 + sin * 0.5 } else { 0.5 - sin * 0.5 };
+        mesh.uvs.push(Vec2::new(0.5 + cos * 0.5, 1.0 - v));
+    }
+    for column in 1..=columns {
+        let (current, next) = (column, column + 1);
+        if up {
+            mesh.indices.extend_from_slice(&[0, current, next]);
+        } else {
+            mesh.indices.extend_from_slice(&[0, next, current]);
+        }
+    }
+    mesh.compute_tangents();
+    mesh
+}
+
+/// Returns the triangles of a `rows` by `columns` grid of quads whose vertices are laid
+/// out row by row, `columns + 1` per row.
+///
+/// The triangles face the direction of `row_direction × column_direction`.
+fn grid_indices(rows: u32, columns: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    let mut indices = Vec::with_capacity((rows * columns * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let (b, c, d) = (a + stride, a + 1, a + stride + 1);
+            indices.extend_from_slice(&[a, b, c, b, d, c]);
+        }
+    }
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that every triangle winds counter-clockwise around its vertex normals, and
+    /// faces away from the origin when `convex`.
+    fn assert_faces_outwards(name: &str, mesh: &MeshBuilder, convex: bool) {
+        assert_eq!(mesh.normals.len(), mesh.vertex_count(), "{name}");
+        assert!(mesh.indices.iter().all(|&index| (index as usize) < mesh.vertex_count()), "{name}");
+        for triangle @ [a, b, c] in mesh.triangles() {
+            let (pa, pb, pc) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+            let face = (pb - pa).cross(&(pc - pa));
+            // Triangles collapsed onto a pole or apex have no direction.
+            if face.norm() < 1e-6 {
+                continue;
+            }
+            let normal = mesh.normals[a] + mesh.normals[b] + mesh.normals[c];
+            assert!(face.dot(&normal) > 0.0, "{name}: triangle {triangle:?} is wound clockwise");
+            if convex {
+                let center = (pa + pb + pc) / 3.0;
+                assert!(face.dot(&center) > 0.0, "{name}: triangle {triangle:?} faces inwards");
+            }
+        }
+        for normal in &mesh.normals {
+            assert!((normal.norm() - 1.0).abs() < 1e-4, "{name}: normal {normal:?} is not unit length");
+        }
+    }
+
+    #[test]
+    fn plane() {
+        let mesh = MeshBuilder::plane(2.0, 3.0, 4);
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (25, 96));
+        assert_faces_outwards("plane", &mesh, false);
+        assert!(mesh.triangles().all(|triangle| {
+            let [a, b, c] = triangle.map(|vertex| mesh.positions[vertex]);
+            (b - a).cross(&(c - a)).y > 0.0
+        }));
+        // At least one cell is always generated.
+        assert_eq!(MeshBuilder::plane(1.0, 1.0, 0).triangle_count(), 2);
+    }
+
+    #[test]
+    fn cube() {
+        let mesh = MeshBuilder::cube(2.0);
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (24, 36));
+        assert_faces_outwards("cube", &mesh, true);
+        assert!(mesh.positions.iter().all(|position| position.abs() == Vec3::repeat(1.0)));
+    }
+
+    #[test]
+    fn uv_sphere() {
+        let mesh = MeshBuilder::uv_sphere(2.0, 16, 8);
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (9 * 17, 8 * 16 * 6));
+        assert_faces_outwards("uv_sphere", &mesh, true);
+        assert!(mesh.positions.iter().all(|position| (position.norm() - 2.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn ico_sphere() {
+        for (subdivisions, vertices) in [(0, 12), (1, 42), (2, 162)] {
+            let mesh = MeshBuilder::ico_sphere(1.0, subdivisions);
+            // Vertices on the UV seam are split, as the mirrored triangles there give them
+            // a second tangent.
+            let mut positions: Vec<[u32; 3]> =
+                mesh.positions.iter().map(|position| [position.x, position.y, position.z].map(f32::to_bits)).collect();
+            positions.sort_unstable();
+            positions.dedup();
+            assert_eq!(positions.len(), vertices);
+            assert_eq!(mesh.triangle_count(), 20 * 4usize.pow(subdivisions));
+            assert_faces_outwards("ico_sphere", &mesh, true);
+        }
+    }
+
+    #[test]
+    fn cylinder() {
+        let mesh = MeshBuilder::cylinder(1.0, 2.0, 12);
+        // The side's two rings and each cap's center and ring, all with a seam vertex.
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (2 * 13 + 2 * 14, 12 * 6 + 2 * 12 * 3));
+        assert_faces_outwards("cylinder", &mesh, true);
+    }
+
+    #[test]
+    fn cone() {
+        let mesh = MeshBuilder::cone(1.0, 2.0, 12);
+        // Each side facet has its own apex vertex, so the base vertices between two facets
+        // get a tangent from each and are split.
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (2 * 13 + 11 + 14, 12 * 6 + 12 * 3));
+        assert_faces_outwards("cone", &mesh, true);
+    }
+
+    #[test]
+    fn capsule() {
+        let mesh = MeshBuilder::capsule(0.5, 1.0, 12, 4);
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (10 * 13, 9 * 12 * 6));
+        assert_faces_outwards("capsule", &mesh, true);
+        let top = mesh.positions.iter().map(|position| position.y).fold(f32::MIN, f32::max);
+        assert!((top - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn torus() {
+        let mesh = MeshBuilder::torus(1.0, 0.25, 16, 8);
+        assert_eq!((mesh.vertex_count(), mesh.indices.len()), (9 * 17, 8 * 16 * 6));
+        assert_faces_outwards("torus", &mesh, false);
+        for (position, normal) in mesh.positions.iter().zip(&mesh.normals) {
+            // Normals point away from the middle of the tube.
+            let ring = Vec3::new(position.x, 0.0, position.z).normalize();
+            assert!((position - ring - normal * 0.25).norm() < 1e-5);
+        }
+    }
+}
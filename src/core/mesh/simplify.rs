@@ -0,0 +1,224 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use nalgebra_glm::{DMat4, DVec3};
+
+use super::builder::gather;
+use super::MeshBuilder;
+
+/// Weight of the planes holding open borders and UV seams in place, relative to faces.
+const BORDER_WEIGHT: f64 = 100.0;
+/// Collapses that would turn a triangle's normal further than this, as a cosine, are rejected.
+const MIN_NORMAL_COSINE: f64 = 0.2;
+
+/// A candidate edge collapse, moving `from` onto `to`.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    /// The versions of `from` and `to` the cost was computed for.
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Orders cheaper collapses first in a max-heap, breaking ties by vertex so the result
+    /// doesn't depend on the order edges were found in.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| (other.from, other.to).cmp(&(self.from, self.to)))
+    }
+}
+
+impl MeshBuilder {
+    /// Returns a copy of the mesh with fewer triangles, for lower levels of a `Lod`.
+    ///
+    /// Edges are collapsed cheapest first by the quadric error metric (Garland and
+    /// Heckbert 1997), moving one vertex onto the other so every remaining vertex keeps
+    /// its original attributes. Open borders and UV seams, where vertices are split, are
+    /// preserved, and collapses that would fold triangles over are skipped, so the result
+    /// may keep more triangles than asked for.
+    ///
+    /// # Arguments
+    ///
+    /// * `ratio` - The fraction of the triangles to keep, from `0` to `1`.
+    pub fn simplify(&self, ratio: f32) -> MeshBuilder {
+        let target = (self.triangle_count() as f32 * ratio.clamp(0.0, 1.0)).round() as usize;
+        let positions: Vec<DVec3> = self.positions.iter().map(|position| position.cast()).collect();
+        let mut triangles: Vec<[usize; 3]> = self.triangles().collect();
+        let mut alive = vec![true; triangles.len()];
+        let mut live = triangles.len();
+
+        let mut quadrics = vec![DMat4::zeros(); positions.len()];
+        let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); positions.len()];
+        let mut edges: BTreeMap<(usize, usize), u32> = BTreeMap::new();
+        for (index, triangle) in triangles.iter().enumerate() {
+            let normal = face_normal(&positions, triangle);
+            let area = normal.norm() / 2.0;
+            for (corner, &vertex) in triangle.iter().enumerate() {
+                adjacency[vertex].push(index);
+                if area > 0.0 {
+                    quadrics[vertex] += plane_quadric(&(normal / (2.0 * area)), &positions[vertex], area);
+                }
+                let next = triangle[(corner + 1) % 3];
+                *edges.entry((vertex.min(next), vertex.max(next))).or_default() += 1;
+            }
+        }
+
+        // Edges used by a single triangle get a plane perpendicular to it, so collapses
+        // along the border are cheap and collapses away from it expensive.
+        for triangle in &triangles {
+            let normal = face_normal(&positions, triangle);
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                if edges[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+                let edge = positions[b] - positions[a];
+                let Some(border_normal) = edge.cross(&normal).try_normalize(f64::EPSILON) else { continue };
+                let quadric = plane_quadric(&border_normal, &positions[a], BORDER_WEIGHT * edge.norm_squared());
+                quadrics[a] += quadric;
+                quadrics[b] += quadric;
+            }
+        }
+
+        let mut versions = vec![0u32; positions.len()];
+        let mut removed = vec![false; positions.len()];
+        let candidate = |quadrics: &[DMat4], versions: &[u32], a: usize, b: usize| {
+            let quadric = quadrics[a] + quadrics[b];
+            let (onto_b, onto_a) = (quadric_error(&quadric, &positions[b]), quadric_error(&quadric, &positions[a]));
+            let (from, to, cost) = if onto_b <= onto_a { (a, b, onto_b) } else { (b, a, onto_a) };
+            Collapse {
+                cost,
+                from,
+                to,
+                versions: (versions[from], versions[to]),
+            }
+        };
+        let mut heap: BinaryHeap<Collapse> =
+            edges.keys().map(|&(a, b)| candidate(&quadrics, &versions, a, b)).collect();
+
+        while live > target {
+            let Some(collapse) = heap.pop() else { break };
+            let Collapse { from, to, .. } = collapse;
+            if removed[from] || removed[to] || collapse.versions != (versions[from], versions[to]) {
+                continue;
+            }
+            if folds_over(&positions, &triangles, &alive, &adjacency[from], from, to) {
+                continue;
+            }
+
+            for &index in &adjacency[from] {
+                if !alive[index] {
+                    continue;
+                }
+                if triangles[index].contains(&to) {
+                    alive[index] = false;
+                    live -= 1;
+                } else {
+                    triangles[index] = triangles[index].map(|vertex| if vertex == from { to } else { vertex });
+                }
+            }
+            let moved = std::mem::take(&mut adjacency[from]);
+            adjacency[to].extend(moved);
+            adjacency[to].retain(|&index| alive[index]);
+            adjacency[to].sort_unstable();
+            adjacency[to].dedup();
+
+            quadrics[to] = quadrics[to] + quadrics[from];
+            removed[from] = true;
+            versions[from] += 1;
+            versions[to] += 1;
+
+            let mut neighbors: Vec<usize> = adjacency[to].iter().flat_map(|&index| triangles[index]).collect();
+            neighbors.sort_unstable();
+            neighbors.dedup();
+            for neighbor in neighbors.into_iter().filter(|&neighbor| neighbor != to) {
+                heap.push(candidate(&quadrics, &versions, to, neighbor));
+            }
+        }
+
+        // Keep only the vertices the remaining triangles use, in order of first use.
+        let mut remap = vec![u32::MAX; positions.len()];
+        let mut corners = Vec::new();
+        let mut indices = Vec::with_capacity(live * 3);
+        for triangle in triangles.iter().zip(&alive).filter(|(_, alive)| **alive).map(|(triangle, _)| triangle) {
+            for &vertex in triangle {
+                if remap[vertex] == u32::MAX {
+                    remap[vertex] = corners.len() as u32;
+                    corners.push(vertex);
+                }
+                indices.push(remap[vertex]);
+            }
+        }
+
+        let mut mesh = MeshBuilder {
+            positions: gather(&self.positions, &corners),
+            normals: gather(&self.normals, &corners),
+            uvs: gather(&self.uvs, &corners),
+            tangents: gather(&self.tangents, &corners),
+            colors: gather(&self.colors, &corners),
+            joints: gather(&self.joints, &corners),
+            weights: gather(&self.weights, &corners),
+            indices,
+            morph_targets: self.morph_targets.clone(),
+        };
+        for target in &mut mesh.morph_targets {
+            target.positions = gather(&target.positions, &corners);
+            target.normals = gather(&target.normals, &corners);
+        }
+        mesh
+    }
+}
+
+/// Returns the unnormalized normal of a triangle, twice its area long.
+fn face_normal(positions: &[DVec3], triangle: &[usize; 3]) -> DVec3 {
+    let [a, b, c] = triangle.map(|vertex| positions[vertex]);
+    (b - a).cross(&(c - a))
+}
+
+/// Returns the quadric measuring the squared distance to a plane, times `weight`.
+fn plane_quadric(normal: &DVec3, point: &DVec3, weight: f64) -> DMat4 {
+    let plane = normal.push(-normal.dot(point));
+    plane * plane.transpose() * weight
+}
+
+/// Returns the error of moving a vertex with `quadric` to `position`.
+fn quadric_error(quadric: &DMat4, position: &DVec3) -> f64 {
+    let point = position.push(1.0);
+    (point.transpose() * quadric * point)[0].max(0.0)
+}
+
+/// Checks whether moving `from` onto `to` would flip or degenerate one of the triangles
+/// around `from` that survive the collapse.
+fn folds_over(
+    positions: &[DVec3],
+    triangles: &[[usize; 3]],
+    alive: &[bool],
+    around: &[usize],
+    from: usize,
+    to: usize,
+) -> bool {
+    around.iter().filter(|&&index| alive[index] && !triangles[index].contains(&to)).any(|&index| {
+        let before = face_normal(positions, &triangles[index]);
+        let moved = triangles[index].map(|vertex| if vertex == from { to } else { vertex });
+        let after = face_normal(positions, &moved);
+        match (before.try_normalize(f64::EPSILON), after.try_normalize(f64::EPSILON)) {
+            (Some(before), Some(after)) => before.dot(&after) < MIN_NORMAL_COSINE,
+            (_, None) => true,
+            (None, Some(_)) => false,
+        }
+    })
+}
@@ -1,8 +1,38 @@
+pub mod animation;
+pub mod assets;
+pub mod atlas;
 pub mod components;
+pub mod compressed;
 pub mod ecs;
+pub mod material;
+pub mod mesh;
+pub mod particles;
+pub mod renderer;
+pub mod tilemap;
 
+mod bounds;
+mod camera;
+mod capture;
 mod engine;
+mod gltf_loader;
+mod headless;
+mod light;
+mod primitives;
+mod profiler;
+mod render_context;
+mod scene;
+mod time;
 mod window;
 
+pub use bounds::{Aabb, BoundingSphere, CullingStats, Frustum};
+pub use camera::Camera;
+pub use capture::{FrameCapture, Recording};
 pub use engine::Engine;
+pub use gltf_loader::GltfScene;
+pub use light::{Light, LightKind};
+pub use profiler::{ProfileScope, Profiler, ScopeTiming};
+pub use render_context::RenderContext;
+pub use scene::Scene;
+pub use time::Time;
+pub use headless::HeadlessApi;
 pub use window::Window;
@@ -0,0 +1,60 @@
+use std::ops::{Add, Mul};
+
+/// A value that changes over a particle's life, given as keys over the normalized age
+/// `[0, 1]` and linearly interpolated between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>, // (normalized age, value), sorted by age
+}
+
+impl<T> Curve<T>
+where
+    T: Copy + Add<Output = T> + Mul<f32, Output = T>,
+{
+    /// Creates a curve that holds the same value over the whole life.
+    pub fn constant(value: T) -> Curve<T> {
+        Curve { keys: vec![(0.0, value)] }
+    }
+
+    /// Creates a curve from keys at normalized ages, which don't need to be sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - `(age, value)` pairs. Ages are clamped to `[0, 1]`; before the first
+    ///   and after the last key the curve holds that key's value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `keys` is empty.
+    pub fn new(keys: Vec<(f32, T)>) -> Curve<T> {
+        assert!(!keys.is_empty(), "A curve needs at least one key");
+        let mut keys: Vec<(f32, T)> = keys.into_iter().map(|(age, value)| (age.clamp(0.0, 1.0), value)).collect();
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Curve { keys }
+    }
+
+    /// Returns the keys of the curve, sorted by age.
+    pub fn get_keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    /// Returns the value of the curve at the normalized age `t`.
+    pub fn sample(&self, t: f32) -> T {
+        let next = self.keys.partition_point(|(age, _)| *age <= t);
+        if next == 0 {
+            return self.keys[0].1;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].1;
+        }
+        let (start, from) = self.keys[next - 1];
+        let (end, to) = self.keys[next];
+        let factor = if end > start { (t - start) / (end - start) } else { 0.0 };
+        from * (1.0 - factor) + to * factor
+    }
+
+    /// Samples the curve at `count` evenly spaced ages from `0` to `1` inclusive.
+    pub fn bake(&self, count: usize) -> Vec<T> {
+        (0..count).map(|i| self.sample(i as f32 / (count - 1).max(1) as f32)).collect()
+    }
+}
@@ -0,0 +1,767 @@
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::ops::Range;
+use std::rc::{Rc, Weak};
+
+use log::warn;
+use nalgebra_glm::{self as glm, Mat4, Vec3, Vec4};
+
+use super::Curve;
+use crate::core::assets::Handle;
+use crate::core::material::{shared_program, OUTPUT_GLSL};
+use crate::core::RenderContext;
+use crate::device::{bytes_of, BufferHandle, DrawCall, ExternalTexture, GpuCache, GpuResources, RenderDevice, Resource};
+use crate::device::{ShaderHandle, UniformValue};
+use crate::opengl::{BlendState, BufferObject, BufferTarget, BufferUsage, FeedbackCapture, PipelineState};
+use crate::opengl::{ShaderProgram, Texture, Topology, VertexArrayObject, VertexLayout};
+
+/// How many samples of the size and color curves are uploaded to the shader.
+const CURVE_SAMPLES: usize = 16;
+/// `f32`s per particle: `(position, age)` followed by `(velocity, lifetime)`.
+const PARTICLE_FLOATS: usize = 8;
+/// Texture unit the particle texture is bound to while drawing.
+const PARTICLE_TEXTURE_UNIT: u32 = 0;
+
+/// Corners of the billboard quad, drawn as a triangle strip.
+const QUAD_CORNERS: [f32; 8] = [-0.5, -0.5, 0.5, -0.5, -0.5, 0.5, 0.5, 0.5];
+
+const SIMULATE_VERTEX_SHADER: &str = r#"
+#version 330 core
+layout (location = 0) in vec4 a_position_age;
+layout (location = 1) in vec4 a_velocity_lifetime;
+
+uniform float u_delta;
+uniform vec3 u_gravity;
+uniform float u_drag;
+
+out vec4 v_position_age;
+out vec4 v_velocity_lifetime;
+
+void main() {
+    vec3 velocity = a_velocity_lifetime.xyz;
+    v_position_age = a_position_age;
+    if (a_position_age.w < a_velocity_lifetime.w) {
+        velocity = (velocity + u_gravity * u_delta) * exp(-u_drag * u_delta);
+        v_position_age = vec4(a_position_age.xyz + velocity * u_delta, a_position_age.w + u_delta);
+    }
+    v_velocity_lifetime = vec4(velocity, a_velocity_lifetime.w);
+}
+"#;
+
+const PARTICLE_VERTEX_SHADER: &str = r#"
+layout (location = 0) in vec4 a_position_age;
+layout (location = 1) in vec4 a_velocity_lifetime;
+layout (location = 2) in vec2 a_corner;
+
+uniform mat4 u_view;
+uniform mat4 u_projection;
+uniform float u_size_curve[CURVE_SAMPLES];
+uniform vec4 u_color_curve[CURVE_SAMPLES];
+
+out vec2 v_uv;
+out vec4 v_color;
+
+void main() {
+    float t = a_position_age.w / max(a_velocity_lifetime.w, 1e-6);
+    if (t >= 1.0) {
+        // Dead particles collapse to a single point outside the clip volume.
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        return;
+    }
+
+    float x = t * float(CURVE_SAMPLES - 1);
+    int i = int(x);
+    int j = min(i + 1, CURVE_SAMPLES - 1);
+    float size = mix(u_size_curve[i], u_size_curve[j], x - float(i));
+    v_color = mix(u_color_curve[i], u_color_curve[j], x - float(i));
+    v_uv = vec2(a_corner.x + 0.5, 0.5 - a_corner.y);
+
+    // The camera's right and up axes are the first two rows of the view rotation.
+    vec3 right = vec3(u_view[0][0], u_view[1][0], u_view[2][0]);
+    vec3 up = vec3(u_view[0][1], u_view[1][1], u_view[2][1]);
+    vec3 position = a_position_age.xyz + (right * a_corner.x + up * a_corner.y) * size;
+    gl_Position = u_projection * u_view * vec4(position, 1.0);
+}
+"#;
+
+const PARTICLE_FRAGMENT_SHADER: &str = r#"
+in vec2 v_uv;
+in vec4 v_color;
+
+uniform sampler2D u_texture;
+uniform bool u_textured;
+
+out vec4 frag_color;
+
+void main() {
+    vec4 color = v_color;
+    if (u_textured) {
+        color *= texture(u_texture, v_uv);
+    } else {
+        // Untextured particles are soft round dots.
+        color.a *= 1.0 - smoothstep(0.25, 0.5, length(v_uv - 0.5));
+    }
+    frag_color = vec4(encode_output(color.rgb), color.a);
+}
+"#;
+
+thread_local! {
+    static PARTICLE_SHADER: GpuCache<ShaderHandle> = const { GpuCache::new() };
+    static SIMULATE_PROGRAM: RefCell<Weak<ShaderProgram>> = const { RefCell::new(Weak::new()) };
+}
+
+/// Where new particles start, relative to the emitter's transform.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EmitterShape {
+    /// At the emitter's origin.
+    #[default]
+    Point,
+    /// Anywhere inside a sphere of the given radius.
+    Sphere(f32),
+    /// Anywhere inside a box of the given half extents.
+    Box(Vec3),
+}
+
+/// A number of particles spawned at once at a point of the emitter's cycle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Burst {
+    /// Seconds into the cycle at which the particles are spawned.
+    pub time: f32,
+    /// How many particles are spawned.
+    pub count: u32,
+}
+
+/// How particles are blended into the frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParticleBlend {
+    /// Particles add their light, for fire, sparks and magic. Order independent.
+    #[default]
+    Additive,
+    /// Particles cover what is behind them, for smoke and dust. CPU simulated particles
+    /// are sorted back to front.
+    Alpha,
+}
+
+/// Where an emitter's particles are simulated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParticleSimulation {
+    /// On the CPU, uploading the live particles every frame.
+    #[default]
+    Cpu,
+    /// On the GPU with transform feedback, so particles never leave video memory. New
+    /// particles overwrite the oldest ones once all `max_particles` are in use, and
+    /// alpha blended particles are drawn unsorted.
+    Gpu,
+}
+
+/// One simulated particle, in world space.
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vec3,
+    age: f32,
+    velocity: Vec3,
+    lifetime: f32,
+}
+
+impl Particle {
+    fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    fn pack(&self) -> [f32; PARTICLE_FLOATS] {
+        let (p, v) = (&self.position, &self.velocity);
+        [p.x, p.y, p.z, self.age, v.x, v.y, v.z, self.lifetime]
+    }
+
+    fn integrate(&mut self, gravity: &Vec3, damping: f32, delta: f32) {
+        self.velocity = (self.velocity + gravity * delta) * damping;
+        self.position += self.velocity * delta;
+        self.age += delta;
+    }
+}
+
+/// Particles simulated on the CPU, uploaded to a stream buffer when drawn.
+struct CpuParticles {
+    particles: Vec<Particle>, // Live particles, oldest first
+}
+
+/// Particles simulated with transform feedback, ping-ponging between two buffers.
+struct GpuParticles {
+    program: Rc<ShaderProgram>,              // Integrates one particle per point
+    buffers: [BufferObject; 2],              // Particle state, read from one and written to the other
+    simulate_arrays: [VertexArrayObject; 2], // Reads `buffers[i]` as points
+    current: usize,                          // Buffer holding the latest state
+    next_slot: usize,                        // Slot the next particle overwrites
+    expiry: Vec<f32>,                        // Clock time each slot's particle dies at
+    clock: f32,                              // Seconds simulated so far
+}
+
+impl GpuParticles {
+    /// Writes `particles` into the ring of slots of the current buffer.
+    fn push(&mut self, particles: &[Particle]) {
+        let capacity = self.expiry.len();
+        let particles = &particles[particles.len().saturating_sub(capacity)..];
+        if particles.is_empty() {
+            return;
+        }
+        let buffer = &self.buffers[self.current];
+        buffer.bind();
+        let (head, tail) = particles.split_at(particles.len().min(capacity - self.next_slot));
+        for (slot, chunk) in [(self.next_slot, head), (0, tail)] {
+            if chunk.is_empty() {
+                continue;
+            }
+            let data: Vec<f32> = chunk.iter().flat_map(Particle::pack).collect();
+            buffer.sub_data(slot * PARTICLE_FLOATS * size_of::<f32>(), &data);
+            for (expiry, particle) in self.expiry[slot..].iter_mut().zip(chunk) {
+                *expiry = self.clock + particle.lifetime;
+            }
+        }
+        buffer.unbind();
+        self.next_slot = (self.next_slot + particles.len()) % capacity;
+    }
+
+    /// Advances every slot by `delta` seconds into the other buffer.
+    fn advance(&mut self, delta: f32, gravity: &Vec3, drag: f32) {
+        let target = 1 - self.current;
+        self.program.bind();
+        self.program.set_1f_uniform("u_delta", delta);
+        self.program.set_3fv_uniform("u_gravity", gravity);
+        self.program.set_1f_uniform("u_drag", drag);
+
+        self.simulate_arrays[self.current].bind();
+        let capture = FeedbackCapture::begin(&self.buffers[target]);
+        unsafe { gl::DrawArrays(gl::POINTS, 0, self.expiry.len() as i32) };
+        drop(capture);
+        self.simulate_arrays[self.current].unbind();
+
+        self.current = target;
+        self.clock += delta;
+    }
+
+    /// Marks every slot as dead.
+    fn clear(&mut self) {
+        let dead = vec![0.0f32; self.expiry.len() * PARTICLE_FLOATS];
+        for buffer in &self.buffers {
+            buffer.bind();
+            buffer.data(&dead);
+            buffer.unbind();
+        }
+        self.expiry.fill(0.0);
+        self.clock = 0.0;
+        self.next_slot = 0;
+    }
+}
+
+/// The buffers an emitter draws from on one device.
+#[derive(Clone)]
+struct ParticleBuffers {
+    quad: BufferHandle,           // Billboard corners
+    instances: Vec<BufferHandle>, // The stream buffer of CPU particles, or both imported GPU buffers
+}
+
+impl GpuResources for ParticleBuffers {
+    fn resources(&self) -> Vec<Resource> {
+        std::iter::once(self.quad).chain(self.instances.iter().copied()).map(Resource::Buffer).collect()
+    }
+}
+
+/// The simulation state of an emitter.
+enum Backend {
+    Cpu(CpuParticles),
+    Gpu(GpuParticles),
+}
+
+/// A component spawning, simulating and drawing particles as camera-facing billboards.
+///
+/// Particles are spawned continuously at `rate` plus in `bursts` over a cycle of
+/// `duration` seconds, and live in world space: moving the emitter leaves the particles
+/// already spawned behind. Their size and color follow curves over their normalized age.
+///
+/// Emitters attached to entities of the scene's `world` follow their entity's
+/// `GlobalTransform`, are simulated by `Scene::update_world` and drawn after transparent
+/// meshes.
+pub struct ParticleEmitter {
+    /// Particles spawned per second while emitting.
+    pub rate: f32,
+    /// Particles spawned at once at set times of each cycle.
+    pub bursts: Vec<Burst>,
+    /// Length of a cycle in seconds.
+    pub duration: f32,
+    /// Whether a new cycle starts when one ends. Otherwise the emitter stops spawning.
+    pub looping: bool,
+    /// Range each particle's lifetime in seconds is picked from.
+    pub lifetime: Range<f32>,
+    /// Range each particle's initial speed is picked from.
+    pub speed: Range<f32>,
+    /// Direction particles are launched in, relative to the emitter.
+    pub direction: Vec3,
+    /// Half-angle in radians of the cone around `direction` particles are launched in.
+    pub spread: f32,
+    /// Where particles start, relative to the emitter.
+    pub shape: EmitterShape,
+    /// Acceleration applied to every particle, in world space.
+    pub gravity: Vec3,
+    /// How quickly particles slow down, as the fraction of velocity lost per second.
+    pub drag: f32,
+    /// World space size of the billboards over the particles' lives.
+    pub size: Curve<f32>,
+    /// Linear RGBA color over the particles' lives, multiplied with the texture.
+    pub color: Curve<Vec4>,
+    /// Texture of the billboards, or `None` for soft round dots. Particles are drawn as
+    /// dots while it is loading.
+    pub texture: Option<Handle<Texture>>,
+    /// How particles are blended into the frame.
+    pub blend: ParticleBlend,
+    /// Whether new particles are spawned. Live particles keep being simulated.
+    pub emitting: bool,
+    max_particles: usize,               // Most particles alive at once
+    buffers: GpuCache<ParticleBuffers>, // Buffers drawn from, per device
+    backend: Backend,                   // Where particles are simulated
+    transform: Mat4,                    // Emitter-to-world matrix
+    time: f32,                          // Seconds into the current cycle
+    spawn_accumulator: f32,             // Fraction of a particle owed by `rate`
+    random: Random,                     // Source of the per-particle variation
+}
+
+impl ParticleEmitter {
+    /// Creates a new `ParticleEmitter` launching small white particles upwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_particles` - The most particles alive at once.
+    /// * `simulation` - Where particles are simulated. `ParticleSimulation::Gpu` falls
+    ///   back to the CPU when the driver has no transform feedback support.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `ParticleEmitter`.
+    pub fn new(max_particles: usize, simulation: ParticleSimulation) -> ParticleEmitter {
+        let simulation = if simulation == ParticleSimulation::Gpu && !FeedbackCapture::is_supported() {
+            warn!("transform feedback is not supported, particles are simulated on the CPU");
+            ParticleSimulation::Cpu
+        } else {
+            simulation
+        };
+        let backend = match simulation {
+            ParticleSimulation::Cpu => Backend::Cpu(CpuParticles {
+                particles: Vec::with_capacity(max_particles),
+            }),
+            ParticleSimulation::Gpu => {
+                let program = shared_program(&SIMULATE_PROGRAM, || {
+                    let mut program = ShaderProgram::with_feedback(
+                        SIMULATE_VERTEX_SHADER,
+                        &["v_position_age", "v_velocity_lifetime"],
+                    );
+                    program.set_label("Particle simulation program");
+                    for uniform in ["u_delta", "u_gravity", "u_drag"] {
+                        program.create_uniform(uniform).unwrap();
+                    }
+                    program
+                });
+                let buffers = [(); 2].map(|_| BufferObject::new(BufferTarget::ArrayBuffer, BufferUsage::DynamicCopy));
+                let simulate_arrays = [0, 1].map(|i| {
+                    let vertex_array = VertexArrayObject::new();
+                    vertex_array.bind();
+                    buffers[i].bind();
+                    particle_layout().apply();
+                    buffers[i].unbind();
+                    vertex_array.unbind();
+                    vertex_array
+                });
+                let mut particles = GpuParticles {
+                    program,
+                    buffers,
+                    simulate_arrays,
+                    current: 0,
+                    next_slot: 0,
+                    expiry: vec![0.0; max_particles],
+                    clock: 0.0,
+                };
+                particles.clear();
+                Backend::Gpu(particles)
+            }
+        };
+
+        ParticleEmitter {
+            rate: 10.0,
+            bursts: Vec::new(),
+            duration: 5.0,
+            looping: true,
+            lifetime: 1.0..2.0,
+            speed: 1.0..2.0,
+            direction: Vec3::y(),
+            spread: 0.4,
+            shape: EmitterShape::Point,
+            gravity: Vec3::zeros(),
+            drag: 0.0,
+            size: Curve::constant(0.1),
+            color: Curve::constant(Vec4::repeat(1.0)),
+            texture: None,
+            blend: ParticleBlend::Additive,
+            emitting: true,
+            max_particles,
+            buffers: GpuCache::new(),
+            backend,
+            transform: Mat4::identity(),
+            time: 0.0,
+            spawn_accumulator: 0.0,
+            random: Random(0x9E37_79B9),
+        }
+    }
+
+    /// Returns where the particles are simulated, which is the CPU if the GPU was
+    /// requested but isn't supported.
+    pub fn get_simulation(&self) -> ParticleSimulation {
+        match self.backend {
+            Backend::Cpu(_) => ParticleSimulation::Cpu,
+            Backend::Gpu(_) => ParticleSimulation::Gpu,
+        }
+    }
+
+    /// Returns the most particles alive at once.
+    pub fn get_max_particles(&self) -> usize {
+        self.max_particles
+    }
+
+    /// Returns how many particles are alive.
+    pub fn get_particle_count(&self) -> usize {
+        match &self.backend {
+            Backend::Cpu(cpu) => cpu.particles.len(),
+            Backend::Gpu(gpu) => gpu.expiry.iter().filter(|expiry| **expiry > gpu.clock).count(),
+        }
+    }
+
+    /// Returns the emitter-to-world matrix new particles are spawned with.
+    pub fn get_transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    /// Sets the emitter-to-world matrix new particles are spawned with.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.transform = transform;
+    }
+
+    /// Checks whether a non-looping emitter has completed its cycle and all of its
+    /// particles have died.
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.duration && self.get_particle_count() == 0
+    }
+
+    /// Kills every particle and restarts the cycle.
+    pub fn reset(&mut self) {
+        match &mut self.backend {
+            Backend::Cpu(cpu) => cpu.particles.clear(),
+            Backend::Gpu(gpu) => gpu.clear(),
+        }
+        self.time = 0.0;
+        self.spawn_accumulator = 0.0;
+    }
+
+    /// Spawns `count` particles right away, whether or not the emitter is emitting.
+    pub fn burst(&mut self, count: u32) {
+        let particles: Vec<Particle> = (0..count).map(|_| self.spawn_particle()).collect();
+        match &mut self.backend {
+            Backend::Cpu(cpu) => {
+                let free = self.max_particles - cpu.particles.len();
+                cpu.particles.extend(particles.into_iter().take(free));
+            }
+            Backend::Gpu(gpu) => gpu.push(&particles),
+        }
+    }
+
+    /// Advances the particles by `delta` seconds, then spawns the ones due in that time.
+    pub fn simulate(&mut self, delta: f32) {
+        match &mut self.backend {
+            Backend::Cpu(cpu) => {
+                let damping = (-self.drag * delta).exp();
+                for particle in &mut cpu.particles {
+                    particle.integrate(&self.gravity, damping, delta);
+                }
+                cpu.particles.retain(Particle::is_alive);
+            }
+            Backend::Gpu(gpu) => {
+                if delta > 0.0 {
+                    gpu.advance(delta, &self.gravity, self.drag);
+                }
+            }
+        }
+
+        let count = self.advance_cycle(delta);
+        if count > 0 {
+            self.burst(count);
+        }
+    }
+
+    /// Draws the live particles with `device` as billboards facing the context's camera.
+    ///
+    /// The draw is depth tested without depth writes, so it belongs after opaque geometry.
+    pub fn render(&self, device: &mut dyn RenderDevice, context: &RenderContext) {
+        if self.get_particle_count() == 0 {
+            return;
+        }
+
+        let shader = particle_shader(device);
+        let buffers = self.device_buffers(device);
+        let (instance_buffer, instances) = match &self.backend {
+            Backend::Cpu(cpu) => {
+                let mut particles: Vec<&Particle> = cpu.particles.iter().collect();
+                if self.blend == ParticleBlend::Alpha {
+                    let eye = context.camera.get_position();
+                    particles.sort_by(|a, b| {
+                        glm::distance2(eye, &b.position).total_cmp(&glm::distance2(eye, &a.position))
+                    });
+                }
+                let data: Vec<f32> = particles.into_iter().flat_map(Particle::pack).collect();
+                device.update_buffer(buffers.instances[0], 0, bytes_of(&data));
+                (buffers.instances[0], cpu.particles.len())
+            }
+            Backend::Gpu(gpu) => (buffers.instances[gpu.current], gpu.expiry.len()),
+        };
+
+        let texture = self.texture.as_ref().and_then(Handle::try_get);
+        let mut draw = DrawCall::new(shader, buffers.quad, VertexLayout::new().with(2, 2), 4)
+            .with_instance_buffer(instance_buffer, particle_layout())
+            .with_topology(Topology::TriangleStrip)
+            .with_instances(instances as u32)
+            .with_uniform("u_view", UniformValue::Mat4(context.camera.view_matrix()))
+            .with_uniform("u_projection", UniformValue::Mat4(context.camera.projection_matrix()))
+            .with_uniform("u_size_curve", UniformValue::FloatArray(self.size.bake(CURVE_SAMPLES)))
+            .with_uniform("u_color_curve", UniformValue::Vec4Array(self.color.bake(CURVE_SAMPLES)))
+            .with_uniform("u_texture", UniformValue::Int(PARTICLE_TEXTURE_UNIT as i32))
+            .with_uniform("u_textured", UniformValue::Int(texture.is_some() as i32));
+        if let Some(texture) = texture {
+            draw = draw.with_texture(PARTICLE_TEXTURE_UNIT, device.import_texture(ExternalTexture::Texture(&texture)));
+        }
+
+        device.set_pipeline_state(&PipelineState {
+            blend: Some(match self.blend {
+                ParticleBlend::Additive => BlendState::additive(),
+                ParticleBlend::Alpha => BlendState::alpha(),
+            }),
+            ..PipelineState::transparent()
+        });
+        device.draw(&draw);
+    }
+
+    /// Returns the buffers the emitter draws from on `device`, creating the quad and CPU
+    /// stream buffer, or importing the GPU state buffers, the first time.
+    fn device_buffers(&self, device: &mut dyn RenderDevice) -> ParticleBuffers {
+        let buffers = self.buffers.get_or_create(device, |device| {
+            let target = BufferTarget::ArrayBuffer;
+            let quad = device.create_buffer(target, BufferUsage::StaticDraw, bytes_of(&QUAD_CORNERS));
+            let instances = match &self.backend {
+                Backend::Cpu(_) => {
+                    let size = self.max_particles * PARTICLE_FLOATS * size_of::<f32>();
+                    vec![device.create_buffer(target, BufferUsage::StreamDraw, &vec![0; size])]
+                }
+                Backend::Gpu(gpu) => gpu.buffers.iter().map(|buffer| device.import_buffer(buffer)).collect(),
+            };
+            ParticleBuffers { quad, instances }
+        });
+        buffers.clone()
+    }
+
+    /// Moves the cycle on by `delta` seconds.
+    ///
+    /// # Returns
+    ///
+    /// How many particles `rate` and `bursts` spawn in that time.
+    fn advance_cycle(&mut self, delta: f32) -> u32 {
+        if !self.emitting {
+            return 0;
+        }
+        let start = self.time;
+        let end = start + delta;
+        let bursts_between = |from: f32, to: f32| -> u32 {
+            self.bursts.iter().filter(|burst| burst.time >= from && burst.time < to).map(|burst| burst.count).sum()
+        };
+
+        let mut count = bursts_between(start, end.min(self.duration));
+        let emitting_time = if self.looping { delta } else { (self.duration - start).clamp(0.0, delta) };
+        self.spawn_accumulator += self.rate * emitting_time;
+        let spawned = self.spawn_accumulator.floor();
+        self.spawn_accumulator -= spawned;
+        count += spawned as u32;
+
+        if self.looping && self.duration > 0.0 && end >= self.duration {
+            self.time = (end - self.duration) % self.duration;
+            count += bursts_between(0.0, self.time);
+        } else {
+            self.time = end;
+        }
+        count
+    }
+
+    /// Picks the starting state of a new particle.
+    fn spawn_particle(&mut self) -> Particle {
+        let random = &mut self.random;
+        let local = match self.shape {
+            EmitterShape::Point => Vec3::zeros(),
+            EmitterShape::Sphere(radius) => random.in_unit_sphere() * radius,
+            EmitterShape::Box(half_extents) => {
+                let point = glm::vec3(random.signed(), random.signed(), random.signed());
+                point.component_mul(&half_extents)
+            }
+        };
+        let direction = random.in_cone(&self.direction, self.spread);
+        let direction = glm::mat4_to_mat3(&self.transform) * direction;
+        let speed = random.in_range(&self.speed);
+
+        Particle {
+            position: (self.transform * local.push(1.0)).xyz(),
+            age: 0.0,
+            velocity: direction.try_normalize(f32::EPSILON).unwrap_or(direction) * speed,
+            lifetime: random.in_range(&self.lifetime),
+        }
+    }
+}
+
+/// Returns the per-instance layout of a packed particle.
+fn particle_layout() -> VertexLayout {
+    VertexLayout::new().with(0, 4).with(1, 4)
+}
+
+/// Returns the program drawing particles on `device`, compiling it the first time.
+fn particle_shader(device: &mut dyn RenderDevice) -> ShaderHandle {
+    PARTICLE_SHADER.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let header = format!("#version 330 core\n#define CURVE_SAMPLES {CURVE_SAMPLES}\n");
+            let vertex_src = format!("{header}{PARTICLE_VERTEX_SHADER}");
+            let fragment_src = format!("{header}{OUTPUT_GLSL}{PARTICLE_FRAGMENT_SHADER}");
+            device.create_shader(&vertex_src, &fragment_src)
+        })
+    })
+}
+
+/// Small xorshift generator, so emitters vary their particles without extra dependencies.
+struct Random(u32);
+
+impl Random {
+    /// Returns a value in `[0, 1)`.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Returns a value in `[-1, 1)`.
+    fn signed(&mut self) -> f32 {
+        self.next() * 2.0 - 1.0
+    }
+
+    /// Returns a value in `range`.
+    fn in_range(&mut self, range: &Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next()
+    }
+
+    /// Returns a point uniformly distributed inside the unit sphere.
+    fn in_unit_sphere(&mut self) -> Vec3 {
+        loop {
+            let point = glm::vec3(self.signed(), self.signed(), self.signed());
+            if point.norm_squared() <= 1.0 {
+                return point;
+            }
+        }
+    }
+
+    /// Returns a unit vector uniformly distributed in the cone of half-angle `spread`
+    /// around `axis`.
+    fn in_cone(&mut self, axis: &Vec3, spread: f32) -> Vec3 {
+        let axis = axis.try_normalize(f32::EPSILON).unwrap_or_else(Vec3::y);
+        let cos_theta = 1.0 - self.next() * (1.0 - spread.cos());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * self.next();
+
+        let helper = if axis.x.abs() < 0.9 { Vec3::x() } else { Vec3::y() };
+        let tangent = axis.cross(&helper).normalize();
+        let bitangent = axis.cross(&tangent);
+        (tangent * phi.cos() + bitangent * phi.sin()) * sin_theta + axis * cos_theta
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Camera;
+    use crate::device::{DeviceCommand, RecordingDevice};
+
+    /// Renders once from a camera on the +Z axis and returns the recorded calls.
+    fn render(emitter: &ParticleEmitter, device: &mut RecordingDevice) -> Vec<DeviceCommand> {
+        let camera = Camera::new(Vec3::new(0.0, 0.0, 10.0), Vec3::zeros());
+        let context = RenderContext {
+            camera: &camera,
+            lights: Vec::new(),
+            environment: None,
+            reflection: None,
+        };
+        emitter.render(device, &context);
+        device.take_commands()
+    }
+
+    #[test]
+    fn cpu_particles_are_streamed_as_instances() {
+        let mut emitter = ParticleEmitter::new(16, ParticleSimulation::Cpu);
+        let mut device = RecordingDevice::new();
+        assert!(render(&emitter, &mut device).is_empty());
+
+        emitter.burst(3);
+        let commands = render(&emitter, &mut device);
+        let created: Vec<_> = (commands.iter())
+            .filter_map(|command| match command {
+                DeviceCommand::CreateBuffer { usage, size, .. } => Some((*usage, *size)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(created, [(BufferUsage::StaticDraw, 8 * 4), (BufferUsage::StreamDraw, 16 * PARTICLE_FLOATS * 4)]);
+        let Some(DeviceCommand::Draw(draw)) = commands.last() else {
+            panic!("the particles weren't drawn last");
+        };
+        let (instances, _) = draw.instance_buffer.clone().expect("no instance buffer");
+        assert_eq!((draw.topology, draw.count, draw.instances), (Topology::TriangleStrip, 4, 3));
+        assert!(commands.contains(&DeviceCommand::UpdateBuffer {
+            buffer: instances,
+            offset: 0,
+            size: 3 * PARTICLE_FLOATS * 4,
+        }));
+        assert_eq!(draw.get_uniform("u_textured"), Some(&UniformValue::Int(0)));
+
+        // Later frames only stream the particles into the same buffer.
+        emitter.burst(1);
+        let commands = render(&emitter, &mut device);
+        assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::CreateBuffer { .. })));
+        assert!(commands.contains(&DeviceCommand::UpdateBuffer {
+            buffer: instances,
+            offset: 0,
+            size: 4 * PARTICLE_FLOATS * 4,
+        }));
+    }
+
+    #[test]
+    fn alpha_blended_particles_are_sorted_back_to_front() {
+        let mut emitter = ParticleEmitter::new(8, ParticleSimulation::Cpu);
+        emitter.blend = ParticleBlend::Alpha;
+        emitter.shape = EmitterShape::Box(Vec3::repeat(4.0));
+        emitter.burst(8);
+        let mut device = RecordingDevice::new();
+        let commands = render(&emitter, &mut device);
+
+        let Some(DeviceCommand::Draw(draw)) = commands.last() else {
+            panic!("the particles weren't drawn last");
+        };
+        let (instances, _) = draw.instance_buffer.clone().unwrap();
+        let data = device.get_buffer_data(instances).unwrap();
+        let distances: Vec<f32> = (data.chunks(PARTICLE_FLOATS * 4).take(8))
+            .map(|particle| {
+                let position: Vec<f32> = (particle[..12].chunks(4))
+                    .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap()))
+                    .collect();
+                glm::distance(&Vec3::from_column_slice(&position), &Vec3::new(0.0, 0.0, 10.0))
+            })
+            .collect();
+        let sorted = distances.windows(2).all(|pair| pair[0] >= pair[1]);
+        assert!(sorted, "not sorted back to front: {distances:?}");
+        assert_eq!(draw.get_uniform("u_textured"), Some(&UniformValue::Int(0)));
+    }
+}
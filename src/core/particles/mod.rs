@@ -0,0 +1,14 @@
+mod curve;
+mod emitter;
+
+pub use curve::Curve;
+pub use emitter::{Burst, EmitterShape, ParticleBlend, ParticleEmitter, ParticleSimulation};
+
+use crate::core::ecs::World;
+
+/// Advances every `ParticleEmitter` in `world` by `delta` seconds.
+pub fn simulate(world: &mut World, delta: f32) {
+    for (_, emitter) in world.query_mut::<ParticleEmitter>() {
+        emitter.simulate(delta);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::device::{bytes_of, BufferHandle, DrawCall, GpuCache, GpuResources, RenderDevice, Resource, ShaderHandle};
+use crate::opengl::{BufferTarget, BufferUsage, IndexType, VertexLayout};
+
+use std::f32::consts::PI;
+
+#[rustfmt::skip]
+const CUBE_VERTICES: [f32; 108] = [
+    -1.0,  1.0, -1.0,  -1.0, -1.0, -1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,   1.0,  1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0, -1.0, -1.0,  -1.0,  1.0, -1.0,
+    -1.0,  1.0, -1.0,  -1.0,  1.0,  1.0,  -1.0, -1.0,  1.0,
+     1.0, -1.0, -1.0,   1.0, -1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0,  1.0, -1.0,   1.0, -1.0, -1.0,
+    -1.0, -1.0,  1.0,  -1.0,  1.0,  1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,   1.0, -1.0,  1.0,  -1.0, -1.0,  1.0,
+    -1.0,  1.0, -1.0,   1.0,  1.0, -1.0,   1.0,  1.0,  1.0,
+     1.0,  1.0,  1.0,  -1.0,  1.0,  1.0,  -1.0,  1.0, -1.0,
+    -1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0, -1.0,
+     1.0, -1.0, -1.0,  -1.0, -1.0,  1.0,   1.0, -1.0,  1.0,
+];
+
+const SPHERE_RINGS: u32 = 12;
+const SPHERE_SEGMENTS: u32 = 16;
+
+/// The buffers of a primitive on one device.
+#[derive(Clone, Copy)]
+struct PrimitiveBuffers {
+    vertices: BufferHandle,        // Positions at attribute location `0`
+    indices: Option<BufferHandle>, // `u32` indices, if the primitive is indexed
+    count: u32,                    // Number of vertices or indices drawn
+}
+
+impl GpuResources for PrimitiveBuffers {
+    fn resources(&self) -> Vec<Resource> {
+        let mut resources = vec![Resource::Buffer(self.vertices)];
+        resources.extend(self.indices.map(Resource::Buffer));
+        resources
+    }
+}
+
+thread_local! {
+    static UNIT_CUBE: GpuCache<PrimitiveBuffers> = const { GpuCache::new() };
+    static UNIT_SPHERE: GpuCache<PrimitiveBuffers> = const { GpuCache::new() };
+    static FULLSCREEN_TRIANGLE: GpuCache<PrimitiveBuffers> = const { GpuCache::new() };
+}
+
+impl PrimitiveBuffers {
+    fn draw_call(self, shader: ShaderHandle, layout: VertexLayout) -> DrawCall {
+        let draw = DrawCall::new(shader, self.vertices, layout, self.count);
+        match self.indices {
+            Some(indices) => draw.with_indices(indices, IndexType::UnsignedInt),
+            None => draw,
+        }
+    }
+}
+
+/// Returns a draw of a `[-1, 1]` cube of 36 unindexed positions at attribute location `0`,
+/// uploading the cube to `device` the first time.
+pub(crate) fn unit_cube(device: &mut dyn RenderDevice, shader: ShaderHandle) -> DrawCall {
+    let buffers = UNIT_CUBE.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let vertices = bytes_of(&CUBE_VERTICES);
+            PrimitiveBuffers {
+                vertices: device.create_buffer(BufferTarget::ArrayBuffer, BufferUsage::StaticDraw, vertices),
+                indices: None,
+                count: 36,
+            }
+        })
+    });
+    buffers.draw_call(shader, VertexLayout::position())
+}
+
+/// Returns a draw of a low-poly indexed sphere of radius `1` at attribute location `0`,
+/// uploading the sphere to `device` the first time.
+///
+/// The polygon faces are pushed outwards so the mesh fully encloses the true sphere,
+/// which makes it suitable as a light volume.
+pub(crate) fn unit_sphere(device: &mut dyn RenderDevice, shader: ShaderHandle) -> DrawCall {
+    let buffers = UNIT_SPHERE.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let (vertices, indices) = sphere_geometry();
+            let usage = BufferUsage::StaticDraw;
+            PrimitiveBuffers {
+                vertices: device.create_buffer(BufferTarget::ArrayBuffer, usage, bytes_of(&vertices)),
+                indices: Some(device.create_buffer(BufferTarget::ElementArrayBuffer, usage, bytes_of(&indices))),
+                count: indices.len() as u32,
+            }
+        })
+    });
+    buffers.draw_call(shader, VertexLayout::position())
+}
+
+/// Returns the positions and triangle indices of the sphere drawn by `unit_sphere`.
+fn sphere_geometry() -> (Vec<f32>, Vec<u32>) {
+    // Scale so the flat faces, not just the vertices, lie outside the unit sphere.
+    let scale = 1.0 / ((PI / SPHERE_RINGS as f32).cos() * (PI / SPHERE_SEGMENTS as f32).cos());
+
+    let mut vertices = Vec::new();
+    for ring in 0..=SPHERE_RINGS {
+        let theta = PI * ring as f32 / SPHERE_RINGS as f32;
+        for segment in 0..=SPHERE_SEGMENTS {
+            let phi = 2.0 * PI * segment as f32 / SPHERE_SEGMENTS as f32;
+            vertices.push(theta.sin() * phi.cos() * scale);
+            vertices.push(theta.cos() * scale);
+            vertices.push(theta.sin() * phi.sin() * scale);
+        }
+    }
+
+    let mut indices = Vec::new();
+    let stride = SPHERE_SEGMENTS + 1;
+    for ring in 0..SPHERE_RINGS {
+        for segment in 0..SPHERE_SEGMENTS {
+            let a = ring * stride + segment;
+            let b = a + stride;
+            indices.extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+/// Vertex shader for `fullscreen_triangle`, outputting `v_uv` in `[0, 1]`.
+pub(crate) const FULLSCREEN_VERTEX_SHADER: &str = r#"
+#version 330 core
+out vec2 v_uv;
+
+void main() {
+    v_uv = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+    gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+/// Returns a draw of a single triangle covering the viewport, generated from `gl_VertexID`.
+///
+/// The triangle has no vertex attributes; `shader` should use `FULLSCREEN_VERTEX_SHADER`
+/// as its vertex stage.
+pub(crate) fn fullscreen_triangle(device: &mut dyn RenderDevice, shader: ShaderHandle) -> DrawCall {
+    let buffers = FULLSCREEN_TRIANGLE.with(|cache| {
+        *cache.get_or_create(device, |device| PrimitiveBuffers {
+            vertices: device.create_buffer(BufferTarget::ArrayBuffer, BufferUsage::StaticDraw, &[]),
+            indices: None,
+            count: 3,
+        })
+    });
+    buffers.draw_call(shader, VertexLayout::new())
+}
@@ -0,0 +1,293 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::opengl::Query;
+
+/// How many frames of samples each scope's rolling timings average over.
+const ROLLING_FRAMES: usize = 60;
+
+/// How many frames may wait for query results before the oldest is dropped unread.
+const MAX_FRAMES_IN_FLIGHT: usize = 8;
+
+/// A scope whose GPU timestamps have been issued but not read back yet.
+struct ScopeQueries {
+    name: String,
+    depth: usize,
+    start: Query,
+    end: Query,
+    cpu: Duration,
+}
+
+/// A scope that has been entered but not left.
+struct OpenScope {
+    name: String,
+    /// Where the scope goes in the frame's list, so parents precede their children.
+    index: usize,
+    start: Option<Query>,
+    cpu_start: Instant,
+}
+
+/// Samples of one scope over the last `ROLLING_FRAMES` frames.
+#[derive(Default)]
+struct ScopeSamples {
+    name: String,
+    depth: usize,
+    gpu: VecDeque<f32>,
+    cpu: VecDeque<f32>,
+}
+
+impl ScopeSamples {
+    fn push(&mut self, gpu: f32, cpu: f32) {
+        if self.gpu.len() == ROLLING_FRAMES {
+            self.gpu.pop_front();
+            self.cpu.pop_front();
+        }
+        self.gpu.push_back(gpu);
+        self.cpu.push_back(cpu);
+    }
+}
+
+#[derive(Default)]
+struct ProfilerState {
+    open: Vec<OpenScope>,
+    current: Vec<ScopeQueries>,
+    in_flight: VecDeque<Vec<ScopeQueries>>,
+    free: Vec<Query>,
+    samples: Vec<ScopeSamples>,
+}
+
+impl ProfilerState {
+    fn query(&mut self) -> Query {
+        self.free.pop().unwrap_or_default()
+    }
+
+    fn recycle(&mut self, frame: Vec<ScopeQueries>) {
+        for scope in frame {
+            self.free.push(scope.start);
+            self.free.push(scope.end);
+        }
+    }
+
+    fn record(&mut self, scope: &ScopeQueries) {
+        let gpu = scope.end.get_result().saturating_sub(scope.start.get_result());
+        let (gpu, cpu) = (gpu as f32 / 1_000_000.0, scope.cpu.as_secs_f32() * 1000.0);
+
+        match self.samples.iter_mut().find(|samples| samples.name == scope.name) {
+            Some(samples) => samples.push(gpu, cpu),
+            None => {
+                let mut samples = ScopeSamples {
+                    name: scope.name.clone(),
+                    depth: scope.depth,
+                    ..ScopeSamples::default()
+                };
+                samples.push(gpu, cpu);
+                self.samples.push(samples);
+            }
+        }
+    }
+}
+
+/// Rolling timings of one named scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeTiming {
+    /// The scope's name, prefixed by its enclosing scopes, e.g. `frame/render/lighting`.
+    pub name: String,
+    /// How many scopes enclose this one.
+    pub depth: usize,
+    /// Average GPU time in milliseconds.
+    pub gpu_average: f32,
+    /// Longest GPU time in milliseconds.
+    pub gpu_max: f32,
+    /// Average CPU time in milliseconds.
+    pub cpu_average: f32,
+    /// Longest CPU time in milliseconds.
+    pub cpu_max: f32,
+    /// How many frames the timings cover.
+    pub samples: usize,
+}
+
+/// Measures CPU and GPU time spent in named scopes around render passes and draws.
+///
+/// GPU time comes from timestamp queries that are read a few frames after they were
+/// issued, once the GPU has caught up, so profiling never stalls the pipeline. Scopes
+/// take `&self`, so code holding a shared reference to the scene can profile itself.
+pub struct Profiler {
+    enabled: Cell<bool>,
+    state: RefCell<ProfilerState>,
+}
+
+/// Leaves its scope when dropped. Created by `Profiler::scope`.
+#[must_use = "the scope ends as soon as the guard is dropped"]
+pub struct ProfileScope<'a> {
+    profiler: Option<&'a Profiler>,
+}
+
+impl Profiler {
+    /// Creates an enabled profiler with no timings yet.
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Checks whether scopes are being measured.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Enables or disables measuring. Disabled scopes cost nothing.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.set(enabled);
+    }
+
+    /// Enters a named scope, which lasts until the returned guard is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the scope. Scopes entered while this one is open are
+    ///   reported as its children.
+    ///
+    /// # Returns
+    ///
+    /// A guard that leaves the scope when dropped.
+    pub fn scope(&self, name: &str) -> ProfileScope<'_> {
+        if !self.is_enabled() {
+            return ProfileScope { profiler: None };
+        }
+        self.begin(name);
+        ProfileScope { profiler: Some(self) }
+    }
+
+    /// Enters a named scope that lasts until the matching `end`.
+    ///
+    /// Prefer `scope`; this is for scopes spanning code that needs the profiler's owner
+    /// mutably, such as update systems receiving the scene.
+    pub fn begin(&self, name: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let name = match state.open.last() {
+            Some(parent) => format!("{}/{name}", parent.name),
+            None => name.to_string(),
+        };
+        let start = state.query();
+        start.record_timestamp();
+        let index = state.current.len();
+        state.open.push(OpenScope {
+            name,
+            index,
+            start: Some(start),
+            cpu_start: Instant::now(),
+        });
+    }
+
+    /// Closes the current frame and collects the timings of earlier frames whose
+    /// queries have finished. The engine calls this once per frame.
+    pub fn end_frame(&self) {
+        let mut state = self.state.borrow_mut();
+        let frame = std::mem::take(&mut state.current);
+        if !frame.is_empty() {
+            state.in_flight.push_back(frame);
+        }
+
+        while let Some(frame) = state.in_flight.front() {
+            let ready = frame.iter().all(|scope| scope.end.is_available());
+            if !ready && state.in_flight.len() <= MAX_FRAMES_IN_FLIGHT {
+                break;
+            }
+
+            let frame = state.in_flight.pop_front().unwrap_or_default();
+            if ready {
+                for scope in &frame {
+                    state.record(scope);
+                }
+            }
+            state.recycle(frame);
+        }
+    }
+
+    /// Returns the rolling timings of every scope, in the order they were first seen.
+    pub fn get_timings(&self) -> Vec<ScopeTiming> {
+        let state = self.state.borrow();
+        state
+            .samples
+            .iter()
+            .map(|samples| {
+                let count = samples.gpu.len().max(1) as f32;
+                ScopeTiming {
+                    name: samples.name.clone(),
+                    depth: samples.depth,
+                    gpu_average: samples.gpu.iter().sum::<f32>() / count,
+                    gpu_max: samples.gpu.iter().copied().fold(0.0, f32::max),
+                    cpu_average: samples.cpu.iter().sum::<f32>() / count,
+                    cpu_max: samples.cpu.iter().copied().fold(0.0, f32::max),
+                    samples: samples.gpu.len(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the rolling timings of one scope by its full name.
+    pub fn get_timing(&self, name: &str) -> Option<ScopeTiming> {
+        self.get_timings().into_iter().find(|timing| timing.name == name)
+    }
+
+    /// Forgets every collected timing.
+    pub fn reset(&self) {
+        self.state.borrow_mut().samples.clear();
+    }
+
+    /// Leaves the innermost scope entered with `begin`.
+    pub fn end(&self) {
+        let mut state = self.state.borrow_mut();
+        let Some(mut scope) = state.open.pop() else { return };
+
+        let end = state.query();
+        end.record_timestamp();
+        let depth = state.open.len();
+        let start = scope.start.take().unwrap_or_default();
+        state.current.insert(scope.index, ScopeQueries {
+            name: scope.name,
+            depth,
+            start,
+            end,
+            cpu: scope.cpu_start.elapsed(),
+        });
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler {
+            enabled: Cell::new(true),
+            state: RefCell::new(ProfilerState::default()),
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    /// Formats the timings as a table, with child scopes indented under their parents.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<32} {:>10} {:>10} {:>10} {:>10}", "scope", "gpu ms", "gpu max", "cpu ms", "cpu max")?;
+        for timing in self.get_timings() {
+            let label = timing.name.rsplit('/').next().unwrap_or_default();
+            let label = format!("{}{label}", "  ".repeat(timing.depth));
+            writeln!(
+                f,
+                "{label:<32} {:>10.3} {:>10.3} {:>10.3} {:>10.3}",
+                timing.gpu_average, timing.gpu_max, timing.cpu_average, timing.cpu_max
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ProfileScope<'_> {
+    fn drop(&mut self) {
+        if let Some(profiler) = self.profiler {
+            profiler.end();
+        }
+    }
+}
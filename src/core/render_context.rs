@@ -0,0 +1,17 @@
+use crate::core::assets::Handle;
+use crate::core::material::Environment;
+use crate::core::{Camera, Light};
+use crate::opengl::Cubemap;
+
+/// Per-frame scene state shared by every draw.
+pub struct RenderContext<'a> {
+    /// The camera the frame is rendered from.
+    pub camera: &'a Camera,
+    /// Lights affecting the built-in lit materials: the scene's own, followed by the
+    /// ones attached to entities, placed in world space.
+    pub lights: Vec<Light>,
+    /// Precomputed image-based lighting, if the scene has an environment.
+    pub environment: Option<&'a Environment>,
+    /// Cube map bound as `u_environment` for user shaders that reflect the sky.
+    pub reflection: Option<&'a Handle<Cubemap>>,
+}
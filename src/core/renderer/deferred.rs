@@ -0,0 +1,675 @@
+use log::warn;
+use nalgebra_glm::{Mat4, Vec4};
+
+use super::create_framebuffers;
+use crate::core::components::MeshRenderer;
+use crate::core::material::{with_environment, Material};
+use crate::core::material::{OUTPUT_GLSL, PBR_LIGHTING_GLSL, PBR_SURFACE_GLSL, PBR_VERTEX_SHADER, SURFACE_STRUCT_GLSL};
+use crate::core::primitives::{fullscreen_triangle, unit_sphere, FULLSCREEN_VERTEX_SHADER};
+use crate::core::renderer::ssao::{SsaoOutput, SsaoPass};
+use crate::core::{LightKind, RenderContext, Scene};
+use crate::device::{DrawCall, FramebufferHandle, GpuCache, GpuResources, RenderDevice, RenderTarget, Resource};
+use crate::device::{ShaderHandle, TextureHandle, UniformValue};
+use crate::opengl::{Attachment, ClearState, Sampler, TextureFormat, TextureWrap};
+use crate::opengl::{BlendEquation, BlendFactor, BlendState, CullMode, PipelineState};
+
+/// Maximum number of unbounded lights (directional, or point lights without a range)
+/// accumulated by the fullscreen ambient pass.
+const MAX_UNBOUNDED_LIGHTS: usize = 8;
+
+/// Texture units the G-buffer is bound to during the lighting passes. They start after
+/// the units used by the environment maps.
+const ALBEDO_UNIT: u32 = 8;
+const NORMAL_UNIT: u32 = 9;
+const EMISSIVE_UNIT: u32 = 10;
+const DEPTH_UNIT: u32 = 11;
+const SSAO_UNIT: u32 = 12;
+
+const GEOMETRY_FRAGMENT_SHADER: &str = r#"
+in vec3 v_world_position;
+in vec3 v_normal;
+in vec4 v_tangent;
+in vec2 v_uv;
+
+layout (location = 0) out vec4 g_albedo;
+layout (location = 1) out vec4 g_normal;
+layout (location = 2) out vec4 g_emissive;
+
+void main() {
+    Surface surface = sample_surface(v_uv, v_normal, v_tangent);
+    if (!gl_FrontFacing) {
+        surface.normal = -surface.normal;
+    }
+
+    g_albedo = vec4(surface.albedo, surface.occlusion);
+    g_normal = vec4(surface.normal, surface.roughness);
+    g_emissive = vec4(surface.emissive, surface.metallic);
+}
+"#;
+
+/// GLSL reading a `Surface` and its world position back from the G-buffer.
+const GBUFFER_GLSL: &str = r#"
+uniform sampler2D u_gbuffer_albedo;
+uniform sampler2D u_gbuffer_normal;
+uniform sampler2D u_gbuffer_emissive;
+uniform sampler2D u_gbuffer_depth;
+uniform mat4 u_inverse_view_projection;
+uniform vec3 u_camera_position;
+
+Surface read_surface(vec2 uv) {
+    vec4 albedo = texture(u_gbuffer_albedo, uv);
+    vec4 normal = texture(u_gbuffer_normal, uv);
+    vec4 emissive = texture(u_gbuffer_emissive, uv);
+
+    Surface surface;
+    surface.albedo = albedo.rgb;
+    surface.alpha = 1.0;
+    surface.occlusion = albedo.a;
+    surface.normal = normalize(normal.xyz);
+    surface.roughness = normal.w;
+    surface.emissive = emissive.rgb;
+    surface.metallic = emissive.a;
+    return surface;
+}
+
+vec3 reconstruct_position(vec2 uv, float depth) {
+    vec4 clip = vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    vec4 world = u_inverse_view_projection * clip;
+    return world.xyz / world.w;
+}
+"#;
+
+const AMBIENT_FRAGMENT_SHADER: &str = r#"
+in vec2 v_uv;
+
+uniform vec4 u_light_positions[MAX_LIGHTS];
+uniform vec4 u_light_colors[MAX_LIGHTS];
+uniform int u_light_count;
+uniform sampler2D u_ssao;
+uniform int u_has_ssao;
+
+out vec4 frag_color;
+
+void main() {
+    float depth = texture(u_gbuffer_depth, v_uv).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    Surface surface = read_surface(v_uv);
+    if (u_has_ssao != 0) {
+        surface.occlusion *= texture(u_ssao, v_uv).r;
+    }
+    vec3 world_position = reconstruct_position(v_uv, depth);
+    vec3 v = normalize(u_camera_position - world_position);
+
+    vec3 color = ambient_lighting(surface, v) + surface.emissive;
+    for (int i = 0; i < u_light_count; i++) {
+        vec3 l;
+        vec3 radiance = light_radiance(u_light_positions[i], u_light_colors[i], world_position, l);
+        color += direct_lighting(surface, v, l, radiance);
+    }
+
+    frag_color = vec4(color, 1.0);
+}
+"#;
+
+const LIGHT_VOLUME_VERTEX_SHADER: &str = r#"
+#version 330 core
+layout (location = 0) in vec3 a_position;
+
+uniform mat4 u_view_projection;
+uniform vec4 u_light_position;
+uniform vec4 u_light_color;
+
+void main() {
+    gl_Position = u_view_projection * vec4(u_light_position.xyz + a_position * u_light_color.a, 1.0);
+}
+"#;
+
+const LIGHT_VOLUME_FRAGMENT_SHADER: &str = r#"
+uniform vec4 u_light_position;
+uniform vec4 u_light_color;
+uniform vec2 u_screen_size;
+
+out vec4 frag_color;
+
+void main() {
+    vec2 uv = gl_FragCoord.xy / u_screen_size;
+    float depth = texture(u_gbuffer_depth, uv).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+
+    Surface surface = read_surface(uv);
+    vec3 world_position = reconstruct_position(uv, depth);
+    vec3 v = normalize(u_camera_position - world_position);
+
+    vec3 l;
+    vec3 radiance = light_radiance(u_light_position, u_light_color, world_position, l);
+    frag_color = vec4(direct_lighting(surface, v, l, radiance), 1.0);
+}
+"#;
+
+const RESOLVE_FRAGMENT_SHADER: &str = r#"
+in vec2 v_uv;
+
+uniform sampler2D u_hdr;
+uniform sampler2D u_gbuffer_depth;
+
+out vec4 frag_color;
+
+void main() {
+    // Leave the background untouched so the clear color or skybox shows through.
+    if (texture(u_gbuffer_depth, v_uv).r >= 1.0) {
+        discard;
+    }
+    frag_color = vec4(encode_output(texture(u_hdr, v_uv).rgb), 1.0);
+}
+"#;
+
+/// The render targets written by the geometry pass and read by the lighting passes.
+struct GBuffer {
+    framebuffer: FramebufferHandle,     // Renders into the four textures below
+    albedo: TextureHandle,              // RGB albedo, A occlusion
+    normal: TextureHandle,              // RGB world-space normal, A roughness
+    emissive: TextureHandle,            // RGB emissive, A metallic
+    depth: TextureHandle,               // Depth and stencil, blitted to the output afterwards
+    hdr_framebuffer: FramebufferHandle, // Renders into `hdr`
+    hdr: TextureHandle,                 // Linear lighting accumulated before tone mapping
+    width: u32,
+    height: u32,
+}
+
+impl GBuffer {
+    fn new(device: &mut dyn RenderDevice, width: u32, height: u32) -> Result<GBuffer, String> {
+        let albedo = device.create_texture(width, height, TextureFormat::Rgba8, None);
+        let normal = device.create_texture(width, height, TextureFormat::Rgba16F, None);
+        let emissive = device.create_texture(width, height, TextureFormat::Rgba16F, None);
+        let depth = device.create_texture(width, height, TextureFormat::Depth24Stencil8, None);
+        let hdr = device.create_texture(width, height, TextureFormat::Rgba16F, None);
+
+        let geometry_targets = [
+            RenderTarget::new(Attachment::Color(0), albedo),
+            RenderTarget::new(Attachment::Color(1), normal),
+            RenderTarget::new(Attachment::Color(2), emissive),
+            RenderTarget::new(Attachment::DepthStencil, depth),
+        ];
+        let hdr_targets = [RenderTarget::new(Attachment::Color(0), hdr)];
+        let framebuffers = create_framebuffers(device, &[&geometry_targets, &hdr_targets]).inspect_err(|_| {
+            for texture in [albedo, normal, emissive, depth, hdr] {
+                device.get_release_queue().push(Resource::Texture(texture));
+            }
+        })?;
+
+        Ok(GBuffer {
+            framebuffer: framebuffers[0],
+            albedo,
+            normal,
+            emissive,
+            depth,
+            hdr_framebuffer: framebuffers[1],
+            hdr,
+            width,
+            height,
+        })
+    }
+
+    /// Adds the G-buffer textures and the uniforms reconstructing surfaces from them to a
+    /// lighting pass draw.
+    fn with_textures(&self, draw: DrawCall, context: &RenderContext, inverse_view_projection: &Mat4) -> DrawCall {
+        let sampler = Sampler::nearest(TextureWrap::ClampToEdge);
+        let mut draw = draw
+            .with_uniform("u_inverse_view_projection", UniformValue::Mat4(*inverse_view_projection))
+            .with_uniform("u_camera_position", UniformValue::Vec3(*context.camera.get_position()));
+        let textures = [
+            ("u_gbuffer_albedo", ALBEDO_UNIT, self.albedo),
+            ("u_gbuffer_normal", NORMAL_UNIT, self.normal),
+            ("u_gbuffer_emissive", EMISSIVE_UNIT, self.emissive),
+            ("u_gbuffer_depth", DEPTH_UNIT, self.depth),
+        ];
+        for (name, unit, texture) in textures {
+            draw = draw
+                .with_uniform(name, UniformValue::Int(unit as i32))
+                .with_texture(unit, texture)
+                .with_sampler(unit, sampler);
+        }
+        draw
+    }
+}
+
+impl GpuResources for GBuffer {
+    fn resources(&self) -> Vec<Resource> {
+        vec![
+            Resource::Framebuffer(self.framebuffer),
+            Resource::Framebuffer(self.hdr_framebuffer),
+            Resource::Texture(self.albedo),
+            Resource::Texture(self.normal),
+            Resource::Texture(self.emissive),
+            Resource::Texture(self.depth),
+            Resource::Texture(self.hdr),
+        ]
+    }
+}
+
+/// Shades opaque PBR meshes through a G-buffer, then draws the rest of the scene forward.
+pub(crate) struct DeferredRenderer {
+    gbuffer: Option<GBuffer>,                    // Targets of the last frame
+    geometry_shader: GpuCache<ShaderHandle>,     // Writes surfaces into the G-buffer
+    ambient_shader: GpuCache<ShaderHandle>,      // Ambient light and unbounded lights
+    light_volume_shader: GpuCache<ShaderHandle>, // Point lights with a range
+    resolve_shader: GpuCache<ShaderHandle>,      // Tone maps the lighting into the output
+    ssao: Option<SsaoPass>,                      // Created once a scene enables SSAO
+}
+
+impl DeferredRenderer {
+    pub(crate) fn new() -> DeferredRenderer {
+        DeferredRenderer {
+            gbuffer: None,
+            geometry_shader: GpuCache::new(),
+            ambient_shader: GpuCache::new(),
+            light_volume_shader: GpuCache::new(),
+            resolve_shader: GpuCache::new(),
+            ssao: None,
+        }
+    }
+
+    /// Draws `scene` with `device` into `output`, or into the window when `output` is `None`.
+    ///
+    /// Opaque PBR meshes go through the G-buffer; everything else, including the skybox,
+    /// is drawn forward afterwards against the G-buffer's depth. Frames the G-buffer can't
+    /// be created for are drawn with the forward path instead.
+    pub(crate) fn render(
+        &mut self,
+        device: &mut dyn RenderDevice,
+        scene: &Scene,
+        output: Option<FramebufferHandle>,
+        width: u32,
+        height: u32,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        if let Err(err) = self.resize_gbuffer(device, width, height) {
+            warn!("Couldn't create the G-buffer, drawing the frame forward: {err}");
+            scene.render(device);
+            return;
+        }
+        let Some(gbuffer) = &self.gbuffer else { return };
+        if let Some(Err(err)) = scene.environment.as_ref().map(|environment| environment.render(device)) {
+            warn!("Couldn't bake the environment lighting: {err}");
+        }
+
+        let context = scene.render_context();
+        let (opaque, transparent) = scene.partition_renders();
+        let (deferred, forward): (Vec<&MeshRenderer>, Vec<&MeshRenderer>) = opaque
+            .into_iter()
+            .partition(|render| matches!(render.get_material().try_get().as_deref(), Some(Material::Pbr(_))));
+
+        let profiler = &scene.profiler;
+        let scope = profiler.scope("geometry");
+        self.geometry_pass(device, gbuffer, &context, &deferred);
+        drop(scope);
+
+        // The SSAO pass is only compiled once a scene first enables it, and skipped for
+        // frames its buffers can't be created for.
+        let ssao_output = scene.ssao.and_then(|settings| {
+            let _scope = profiler.scope("ssao");
+            let ssao = self.ssao.get_or_insert_with(SsaoPass::new);
+            match ssao.render(device, &settings, context.camera, gbuffer.depth, gbuffer.normal, (width, height)) {
+                Ok(()) => Some(settings.output),
+                Err(err) => {
+                    warn!("Couldn't create the SSAO buffers, skipping ambient occlusion: {err}");
+                    None
+                }
+            }
+        });
+        let occlusion = self.ssao.as_ref().and_then(|ssao| ssao.occlusion());
+
+        let lighting_occlusion = occlusion.filter(|_| ssao_output == Some(SsaoOutput::Lighting));
+        let scope = profiler.scope("lighting");
+        self.lighting_pass(device, gbuffer, &context, lighting_occlusion);
+        drop(scope);
+
+        if let (Some(ssao), Some(occlusion)) = (&self.ssao, occlusion) {
+            if ssao_output == Some(SsaoOutput::PostProcess) {
+                ssao.apply(device, occlusion);
+            }
+        }
+        let scope = profiler.scope("resolve");
+        self.resolve_pass(device, gbuffer, output);
+        drop(scope);
+
+        // Forward geometry is depth tested against what the G-buffer saw.
+        let _scope = profiler.scope("forward");
+        device.blit_framebuffer(gbuffer.framebuffer, output, width, height, false, true);
+        for render in forward {
+            render.render(device, &context);
+        }
+        if let Some(skybox) = &scene.skybox {
+            skybox.render(device, context.camera);
+        }
+        for tilemap in scene.tilemaps() {
+            tilemap.render(device, &context);
+        }
+        for render in transparent {
+            render.render(device, &context);
+        }
+        for emitter in scene.particle_emitters() {
+            emitter.render(device, &context);
+        }
+    }
+
+    /// Recreates the G-buffer if it doesn't match the output size, queueing the old one
+    /// for destruction.
+    ///
+    /// # Returns
+    ///
+    /// An error if the G-buffer's framebuffers are incomplete, leaving no G-buffer.
+    fn resize_gbuffer(&mut self, device: &mut dyn RenderDevice, width: u32, height: u32) -> Result<(), String> {
+        let resized = self
+            .gbuffer
+            .as_ref()
+            .is_none_or(|gbuffer| gbuffer.width != width || gbuffer.height != height);
+        if resized {
+            for resource in self.gbuffer.take().iter().flat_map(GpuResources::resources) {
+                device.get_release_queue().push(resource);
+            }
+            self.gbuffer = Some(GBuffer::new(device, width, height)?);
+        }
+        Ok(())
+    }
+
+    fn geometry_pass(
+        &self,
+        device: &mut dyn RenderDevice,
+        gbuffer: &GBuffer,
+        context: &RenderContext,
+        renders: &[&MeshRenderer],
+    ) {
+        device.bind_framebuffer(Some(gbuffer.framebuffer));
+        device.clear(&ClearState::all(0.0, 0.0, 0.0, 0.0));
+
+        let shader = *self.geometry_shader.get_or_create(device, |device| {
+            let geometry_src = format!("#version 330 core\n{SURFACE_STRUCT_GLSL}{PBR_SURFACE_GLSL}{GEOMETRY_FRAGMENT_SHADER}");
+            device.create_shader(PBR_VERTEX_SHADER, &geometry_src)
+        });
+        let view = UniformValue::Mat4(context.camera.view_matrix());
+        let projection = UniformValue::Mat4(context.camera.projection_matrix());
+
+        for render in renders {
+            let (Some(mesh), Some(material)) = (render.get_mesh().try_get(), render.get_material().try_get()) else {
+                continue;
+            };
+            if let Material::Pbr(material) = &*material {
+                let draw = render
+                    .draw_call(device, &mesh, shader)
+                    .with_uniform("u_view", view.clone())
+                    .with_uniform("u_projection", projection.clone());
+                let draw = material.with_surface(device, draw);
+                // The G-buffer stores surface data rather than colors, so it is never blended.
+                device.set_pipeline_state(&PipelineState {
+                    blend: None,
+                    ..*render.get_pipeline_state()
+                });
+                device.draw(&draw);
+            }
+        }
+    }
+
+    fn lighting_pass(
+        &self,
+        device: &mut dyn RenderDevice,
+        gbuffer: &GBuffer,
+        context: &RenderContext,
+        occlusion: Option<TextureHandle>,
+    ) {
+        device.bind_framebuffer(Some(gbuffer.hdr_framebuffer));
+        device.clear(&ClearState {
+            color: Some([0.0; 4]),
+            depth: None,
+            stencil: None,
+        });
+
+        let view_projection = context.camera.projection_matrix() * context.camera.view_matrix();
+        let inverse_view_projection = view_projection.try_inverse().unwrap_or_else(Mat4::identity);
+        let lighting_header = format!("#version 330 core\n{SURFACE_STRUCT_GLSL}{PBR_LIGHTING_GLSL}{GBUFFER_GLSL}");
+
+        // Ambient, emissive and every light without a bounded volume in one fullscreen pass.
+        let (positions, colors): (Vec<Vec4>, Vec<Vec4>) = context
+            .lights
+            .iter()
+            .filter(|light| match light.kind {
+                LightKind::Directional { .. } => true,
+                LightKind::Point { range, .. } => range <= 0.0,
+            })
+            .take(MAX_UNBOUNDED_LIGHTS)
+            .map(|light| light.pack())
+            .unzip();
+
+        let shader = *self.ambient_shader.get_or_create(device, |device| {
+            let ambient_src = format!("#define MAX_LIGHTS {MAX_UNBOUNDED_LIGHTS}\n{AMBIENT_FRAGMENT_SHADER}");
+            device.create_shader(FULLSCREEN_VERTEX_SHADER, &format!("{lighting_header}{ambient_src}"))
+        });
+        let mut draw = fullscreen_triangle(device, shader)
+            .with_uniform("u_light_count", UniformValue::Int(positions.len() as i32))
+            .with_uniform("u_has_ssao", UniformValue::Int(occlusion.is_some() as i32));
+        if !positions.is_empty() {
+            draw = draw
+                .with_uniform("u_light_positions", UniformValue::Vec4Array(positions))
+                .with_uniform("u_light_colors", UniformValue::Vec4Array(colors));
+        }
+        if let Some(occlusion) = occlusion {
+            draw = draw
+                .with_texture(SSAO_UNIT, occlusion)
+                .with_uniform("u_ssao", UniformValue::Int(SSAO_UNIT as i32));
+        }
+        let draw = gbuffer.with_textures(with_environment(device, context, draw), context, &inverse_view_projection);
+        device.set_pipeline_state(&PipelineState::fullscreen());
+        device.draw(&draw);
+
+        // Point lights with a range only shade the pixels their sphere covers. Culling
+        // front faces keeps the volume visible while the camera is inside it.
+        let shader = *self.light_volume_shader.get_or_create(device, |device| {
+            let fragment_src = format!("{lighting_header}{LIGHT_VOLUME_FRAGMENT_SHADER}");
+            device.create_shader(LIGHT_VOLUME_VERTEX_SHADER, &fragment_src)
+        });
+        let screen_size = nalgebra_glm::vec2(gbuffer.width as f32, gbuffer.height as f32);
+        let volume = unit_sphere(device, shader)
+            .with_uniform("u_view_projection", UniformValue::Mat4(view_projection))
+            .with_uniform("u_screen_size", UniformValue::Vec2(screen_size));
+        let volume = gbuffer.with_textures(volume, context, &inverse_view_projection);
+
+        device.set_pipeline_state(&PipelineState {
+            cull_mode: CullMode::Front,
+            blend: Some(BlendState::new(BlendEquation::Add, BlendFactor::One, BlendFactor::One)),
+            ..PipelineState::fullscreen()
+        });
+        for light in context.lights.iter() {
+            if let LightKind::Point { range, .. } = light.kind {
+                if range > 0.0 {
+                    let (position, color) = light.pack();
+                    let draw = volume
+                        .clone()
+                        .with_uniform("u_light_position", UniformValue::Vec4(position))
+                        .with_uniform("u_light_color", UniformValue::Vec4(color));
+                    device.draw(&draw);
+                }
+            }
+        }
+    }
+
+    fn resolve_pass(&self, device: &mut dyn RenderDevice, gbuffer: &GBuffer, output: Option<FramebufferHandle>) {
+        device.bind_framebuffer(output);
+
+        let shader = *self.resolve_shader.get_or_create(device, |device| {
+            let resolve_src = format!("#version 330 core\n{OUTPUT_GLSL}{RESOLVE_FRAGMENT_SHADER}");
+            device.create_shader(FULLSCREEN_VERTEX_SHADER, &resolve_src)
+        });
+        let sampler = Sampler::nearest(TextureWrap::ClampToEdge);
+        let draw = fullscreen_triangle(device, shader)
+            .with_texture(0, gbuffer.hdr)
+            .with_texture(DEPTH_UNIT, gbuffer.depth)
+            .with_sampler(0, sampler)
+            .with_sampler(DEPTH_UNIT, sampler)
+            .with_uniform("u_hdr", UniformValue::Int(0))
+            .with_uniform("u_gbuffer_depth", UniformValue::Int(DEPTH_UNIT as i32));
+        device.set_pipeline_state(&PipelineState::fullscreen());
+        device.draw(&draw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::material::PbrMaterial;
+    use crate::core::mesh::MeshBuilder;
+    use crate::core::renderer::SsaoSettings;
+    use crate::core::{Camera, Light};
+    use crate::device::{DeviceCommand, RecordingDevice};
+
+    use nalgebra_glm::Vec3;
+
+    /// A lit cube with a directional light and a ranged point light.
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.profiler.set_enabled(false);
+        scene.camera = Camera::new(Vec3::new(0.0, 1.0, 4.0), Vec3::zeros());
+        scene.lights.push(Light::directional(Vec3::new(0.0, -1.0, 0.0), Vec3::repeat(1.0), 1.0));
+        scene.lights.push(Light::point(Vec3::new(1.0, 1.0, 1.0), 5.0, Vec3::repeat(1.0), 1.0));
+        let cube = MeshBuilder::cube(1.0).build(&mut scene.assets, PbrMaterial::new());
+        scene.renders.push(cube);
+        scene
+    }
+
+    fn created_framebuffers(commands: &[DeviceCommand]) -> Vec<Vec<RenderTarget>> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                DeviceCommand::CreateFramebuffer { targets, .. } => Some(targets.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the framebuffer bound for each draw, in order.
+    fn draw_targets(commands: &[DeviceCommand]) -> Vec<Option<FramebufferHandle>> {
+        let mut framebuffer = None;
+        let mut targets = Vec::new();
+        for command in commands {
+            match command {
+                DeviceCommand::BindFramebuffer(bound) => framebuffer = *bound,
+                DeviceCommand::Draw(_) => targets.push(framebuffer),
+                _ => (),
+            }
+        }
+        targets
+    }
+
+    fn draws(commands: &[DeviceCommand]) -> Vec<&DrawCall> {
+        commands
+            .iter()
+            .filter_map(|command| match command {
+                DeviceCommand::Draw(draw) => Some(draw),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_goes_through_the_gbuffer() {
+        let scene = scene();
+        let mut device = RecordingDevice::new();
+        let mut renderer = DeferredRenderer::new();
+        renderer.render(&mut device, &scene, None, 64, 32);
+        let commands = device.take_commands();
+
+        let framebuffers = created_framebuffers(&commands);
+        assert_eq!(framebuffers.len(), 2);
+        assert_eq!(framebuffers[0].len(), 4);
+        assert_eq!(framebuffers[0][3].attachment, Attachment::DepthStencil);
+        let gbuffer = renderer.gbuffer.as_ref().unwrap();
+        assert_eq!(framebuffers[1], [RenderTarget::new(Attachment::Color(0), gbuffer.hdr)]);
+
+        // Geometry, ambient, one light volume and the resolve, each into its own target.
+        let draws = draws(&commands);
+        assert_eq!(draws.len(), 4);
+        assert_eq!(
+            draw_targets(&commands),
+            [Some(gbuffer.framebuffer), Some(gbuffer.hdr_framebuffer), Some(gbuffer.hdr_framebuffer), None]
+        );
+        assert_eq!(draws[1].get_uniform("u_light_count"), Some(&UniformValue::Int(1)));
+        assert_eq!(draws[1].get_texture(DEPTH_UNIT), Some(gbuffer.depth));
+        assert_eq!(draws[1].get_sampler(DEPTH_UNIT), Some(Sampler::nearest(TextureWrap::ClampToEdge)));
+        assert!(draws[2].index_buffer.is_some());
+        assert!(draws[2].get_uniform("u_light_position").is_some());
+        assert_eq!(draws[3].get_texture(0), Some(gbuffer.hdr));
+        assert!(commands.contains(&DeviceCommand::BlitFramebuffer {
+            source: gbuffer.framebuffer,
+            target: None,
+            width: 64,
+            height: 32,
+            color: false,
+            depth: true,
+        }));
+
+        // The next frame of the same size reuses everything.
+        renderer.render(&mut device, &scene, None, 64, 32);
+        let commands = device.take_commands();
+        assert!(created_framebuffers(&commands).is_empty());
+        assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::CreateShader { .. })));
+    }
+
+    #[test]
+    fn ssao_occlusion_feeds_the_ambient_pass() {
+        let mut scene = scene();
+        scene.ssao = Some(SsaoSettings::default());
+        let mut device = RecordingDevice::new();
+        let mut renderer = DeferredRenderer::new();
+        renderer.render(&mut device, &scene, None, 64, 32);
+        let commands = device.take_commands();
+
+        assert_eq!(created_framebuffers(&commands).len(), 4);
+        let occlusion = renderer.ssao.as_ref().and_then(SsaoPass::occlusion).unwrap();
+        let gbuffer = renderer.gbuffer.as_ref().unwrap();
+        let (draws, targets) = (draws(&commands), draw_targets(&commands));
+        // Geometry, occlusion, two blur passes, ambient, light volume and resolve.
+        assert_eq!(draws.len(), 7);
+        assert_eq!(targets[4..], [Some(gbuffer.hdr_framebuffer), Some(gbuffer.hdr_framebuffer), None]);
+        assert_eq!(draws[1].get_texture(0), Some(gbuffer.depth));
+        assert_eq!(draws[1].get_texture(1), Some(gbuffer.normal));
+        assert_eq!(draws[1].get_sampler(2), Some(Sampler::nearest(TextureWrap::Repeat)));
+        // The horizontal blur reads the occlusion and the vertical one writes it back.
+        assert_eq!(draws[2].get_texture(0), Some(occlusion));
+        assert_ne!(targets[2], targets[1]);
+        assert_eq!(targets[3], targets[1]);
+        assert_eq!(draws[4].get_uniform("u_has_ssao"), Some(&UniformValue::Int(1)));
+        assert_eq!(draws[4].get_texture(SSAO_UNIT), Some(occlusion));
+
+        // Occlusion is drawn at half resolution, then the full viewport is restored.
+        assert!(commands.contains(&DeviceCommand::SetViewport {
+            x: 0,
+            y: 0,
+            width: 32,
+            height: 16,
+        }));
+        assert_eq!(device.get_viewport(), (0, 0, 64, 32));
+    }
+
+    #[test]
+    fn resizing_releases_the_old_gbuffer() {
+        let scene = scene();
+        let mut device = RecordingDevice::new();
+        let mut renderer = DeferredRenderer::new();
+        renderer.render(&mut device, &scene, None, 64, 32);
+        let old = renderer.gbuffer.as_ref().unwrap().resources();
+        let old_framebuffer = renderer.gbuffer.as_ref().unwrap().framebuffer;
+
+        renderer.render(&mut device, &scene, None, 32, 32);
+        device.take_commands();
+        device.release_dropped();
+        let released = device.take_commands();
+        assert_eq!(released.len(), old.len());
+        assert!(released.contains(&DeviceCommand::DestroyFramebuffer(old_framebuffer)));
+    }
+}
@@ -0,0 +1,87 @@
+mod deferred;
+mod ssao;
+
+pub use ssao::{SsaoOutput, SsaoQuality, SsaoSettings};
+
+use deferred::DeferredRenderer;
+
+use crate::core::Scene;
+use crate::device::{FramebufferHandle, GlDevice, RenderDevice, RenderTarget, Resource};
+use crate::opengl::Framebuffer;
+
+/// Selects how the engine shades opaque geometry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderPath {
+    /// Every mesh is shaded in a single pass, looping over all lights per fragment.
+    #[default]
+    Forward,
+    /// Opaque PBR meshes are written to a G-buffer and lit afterwards, with point lights
+    /// drawn as light volumes. Transparent meshes and meshes with a custom
+    /// `ShaderProgram` are still drawn forward on top.
+    Deferred,
+}
+
+/// Draws a `Scene` with the selected `RenderPath`.
+pub(crate) struct Renderer {
+    device: GlDevice,
+    deferred: Option<DeferredRenderer>,
+}
+
+impl Renderer {
+    /// Creates the renderer, compiling the programs the path needs up front.
+    pub(crate) fn new(path: RenderPath) -> Renderer {
+        let deferred = match path {
+            RenderPath::Forward => None,
+            RenderPath::Deferred => Some(DeferredRenderer::new()),
+        };
+        Renderer {
+            device: GlDevice::new(),
+            deferred,
+        }
+    }
+
+    /// Draws `scene` into the window, after destroying the GPU resources of meshes
+    /// dropped since the last frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `scene` - The scene to draw.
+    /// * `output` - The framebuffer to draw into, or `None` for the window's own.
+    /// * `width` - Width of the output in pixels.
+    /// * `height` - Height of the output in pixels.
+    pub(crate) fn render(&mut self, scene: &Scene, output: Option<&Framebuffer>, width: u32, height: u32) {
+        self.device.release_dropped();
+        let output = output.map(|framebuffer| self.device.import_framebuffer(framebuffer));
+        self.device.bind_framebuffer(output);
+        self.device.set_viewport(0, 0, width, height);
+        match &mut self.deferred {
+            Some(deferred) => deferred.render(&mut self.device, scene, output, width, height),
+            None => scene.render(&mut self.device),
+        }
+    }
+}
+
+/// Creates a framebuffer for each set of targets, or none of them.
+///
+/// # Returns
+///
+/// The framebuffers in order, or the first error after queueing the framebuffers already
+/// created for destruction.
+fn create_framebuffers(
+    device: &mut dyn RenderDevice,
+    targets: &[&[RenderTarget]],
+) -> Result<Vec<FramebufferHandle>, String> {
+    let mut framebuffers = Vec::with_capacity(targets.len());
+    for targets in targets {
+        match device.create_framebuffer(targets) {
+            Ok(framebuffer) => framebuffers.push(framebuffer),
+            Err(err) => {
+                for framebuffer in framebuffers {
+                    device.get_release_queue().push(Resource::Framebuffer(framebuffer));
+                }
+                return Err(err);
+            }
+        }
+    }
+    Ok(framebuffers)
+}
@@ -0,0 +1,449 @@
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+use super::create_framebuffers;
+use crate::core::primitives::{fullscreen_triangle, FULLSCREEN_VERTEX_SHADER};
+use crate::core::Camera;
+use crate::device::{bytes_of, FramebufferHandle, GpuCache, GpuResources, RenderDevice, RenderTarget, Resource};
+use crate::device::{ShaderHandle, TextureHandle, UniformValue};
+use crate::opengl::{Attachment, BlendState, PipelineState, Sampler, TextureFormat, TextureWrap};
+
+/// Largest kernel used by any preset.
+const MAX_KERNEL_SIZE: usize = 64;
+/// Edge length of the tiled rotation noise texture.
+const NOISE_SIZE: u32 = 4;
+
+const SSAO_FRAGMENT_SHADER: &str = r#"
+#version 330 core
+in vec2 v_uv;
+
+uniform sampler2D u_depth;
+uniform sampler2D u_normal;
+uniform sampler2D u_noise;
+uniform vec4 u_kernel[MAX_KERNEL_SIZE];
+uniform int u_kernel_size;
+uniform mat4 u_view;
+uniform mat4 u_projection;
+uniform mat4 u_inverse_projection;
+uniform vec2 u_noise_scale;
+uniform float u_radius;
+uniform float u_bias;
+uniform float u_intensity;
+
+out float frag_occlusion;
+
+vec3 view_position(vec2 uv) {
+    float depth = texture(u_depth, uv).r;
+    vec4 view = u_inverse_projection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return view.xyz / view.w;
+}
+
+void main() {
+    if (texture(u_depth, v_uv).r >= 1.0) {
+        frag_occlusion = 1.0;
+        return;
+    }
+
+    vec3 position = view_position(v_uv);
+    vec3 normal = normalize(mat3(u_view) * texture(u_normal, v_uv).xyz);
+
+    // Orient the hemisphere kernel around the normal, rotated by the tiled noise.
+    vec3 random = vec3(texture(u_noise, v_uv * u_noise_scale).xy, 0.0);
+    vec3 tangent = normalize(random - normal * dot(random, normal));
+    vec3 bitangent = cross(normal, tangent);
+    mat3 tbn = mat3(tangent, bitangent, normal);
+
+    float occlusion = 0.0;
+    for (int i = 0; i < u_kernel_size; i++) {
+        vec3 sample_position = position + tbn * u_kernel[i].xyz * u_radius;
+
+        vec4 offset = u_projection * vec4(sample_position, 1.0);
+        offset.xy = offset.xy / offset.w * 0.5 + 0.5;
+
+        float scene_depth = view_position(offset.xy).z;
+        float range_check = smoothstep(0.0, 1.0, u_radius / abs(position.z - scene_depth));
+        occlusion += (scene_depth >= sample_position.z + u_bias ? 1.0 : 0.0) * range_check;
+    }
+
+    frag_occlusion = pow(1.0 - occlusion / float(u_kernel_size), u_intensity);
+}
+"#;
+
+const BLUR_FRAGMENT_SHADER: &str = r#"
+#version 330 core
+in vec2 v_uv;
+
+uniform sampler2D u_occlusion;
+uniform sampler2D u_depth;
+uniform mat4 u_inverse_projection;
+uniform vec2 u_direction;
+uniform int u_radius;
+
+out float frag_occlusion;
+
+float view_depth(vec2 uv) {
+    float depth = texture(u_depth, uv).r;
+    vec4 view = u_inverse_projection * vec4(vec3(uv, depth) * 2.0 - 1.0, 1.0);
+    return view.z / view.w;
+}
+
+void main() {
+    vec2 texel = u_direction / vec2(textureSize(u_occlusion, 0));
+    float center_depth = view_depth(v_uv);
+    float sigma = float(u_radius) * 0.5 + 0.5;
+
+    float total = 0.0;
+    float weight_sum = 0.0;
+    for (int i = -u_radius; i <= u_radius; i++) {
+        vec2 uv = v_uv + texel * float(i);
+        // Gaussian falloff in screen space, rejecting samples across depth edges.
+        float spatial = exp(-float(i * i) / (2.0 * sigma * sigma));
+        float range = exp(-abs(view_depth(uv) - center_depth) * 4.0);
+        float weight = spatial * range;
+        total += texture(u_occlusion, uv).r * weight;
+        weight_sum += weight;
+    }
+
+    frag_occlusion = total / max(weight_sum, 1e-4);
+}
+"#;
+
+const APPLY_FRAGMENT_SHADER: &str = r#"
+#version 330 core
+in vec2 v_uv;
+
+uniform sampler2D u_occlusion;
+
+out vec4 frag_color;
+
+void main() {
+    frag_color = vec4(vec3(texture(u_occlusion, v_uv).r), 1.0);
+}
+"#;
+
+/// Sample count, resolution and blur presets for `SsaoSettings`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SsaoQuality {
+    /// 8 samples at half resolution.
+    Low,
+    /// 16 samples at half resolution.
+    #[default]
+    Medium,
+    /// 32 samples at full resolution.
+    High,
+    /// 64 samples at full resolution with a wider blur.
+    Ultra,
+}
+
+impl SsaoQuality {
+    /// Returns the number of hemisphere samples taken per pixel.
+    pub fn kernel_size(self) -> usize {
+        match self {
+            SsaoQuality::Low => 8,
+            SsaoQuality::Medium => 16,
+            SsaoQuality::High => 32,
+            SsaoQuality::Ultra => MAX_KERNEL_SIZE,
+        }
+    }
+
+    /// Returns the divisor applied to the screen size for the occlusion buffer.
+    pub fn resolution_divisor(self) -> u32 {
+        match self {
+            SsaoQuality::Low | SsaoQuality::Medium => 2,
+            SsaoQuality::High | SsaoQuality::Ultra => 1,
+        }
+    }
+
+    /// Returns the radius in texels of each bilateral blur pass.
+    pub fn blur_radius(self) -> i32 {
+        match self {
+            SsaoQuality::Low => 2,
+            SsaoQuality::Medium => 3,
+            SsaoQuality::High => 4,
+            SsaoQuality::Ultra => 6,
+        }
+    }
+}
+
+/// Where the ambient occlusion term is applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SsaoOutput {
+    /// Multiplied into the material occlusion, so only ambient lighting is darkened.
+    #[default]
+    Lighting,
+    /// Multiplied over the lit image before tone mapping, darkening direct light too.
+    PostProcess,
+}
+
+/// Screen-space ambient occlusion settings.
+///
+/// SSAO reads the G-buffer, so it only runs on the `RenderPath::Deferred` path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SsaoSettings {
+    /// Sample count, resolution and blur preset.
+    pub quality: SsaoQuality,
+    /// Where the result is applied.
+    pub output: SsaoOutput,
+    /// World-space radius of the sampling hemisphere.
+    pub radius: f32,
+    /// Depth offset preventing flat surfaces from occluding themselves.
+    pub bias: f32,
+    /// Exponent applied to the result; higher values darken creases further.
+    pub intensity: f32,
+}
+
+impl SsaoSettings {
+    /// Creates settings for the given preset with a half-unit radius.
+    pub fn new(quality: SsaoQuality) -> SsaoSettings {
+        SsaoSettings {
+            quality,
+            output: SsaoOutput::Lighting,
+            radius: 0.5,
+            bias: 0.025,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        SsaoSettings::new(SsaoQuality::default())
+    }
+}
+
+/// The ping-pong occlusion targets, sized for one screen size and preset.
+struct SsaoTargets {
+    occlusion_framebuffer: FramebufferHandle, // Renders into `occlusion`
+    occlusion: TextureHandle,                 // Occlusion, blurred once `render` returns
+    blur_framebuffer: FramebufferHandle,      // Renders into `blur`
+    blur: TextureHandle,                      // Horizontally blurred occlusion
+    size: (u32, u32),                         // Size of both textures
+    screen_size: (u32, u32),                  // Screen size the targets were created for
+    divisor: u32,                             // Resolution divisor of the preset
+}
+
+impl SsaoTargets {
+    fn new(device: &mut dyn RenderDevice, screen_size: (u32, u32), divisor: u32) -> Result<SsaoTargets, String> {
+        let size = ((screen_size.0 / divisor).max(1), (screen_size.1 / divisor).max(1));
+        let occlusion = device.create_texture(size.0, size.1, TextureFormat::R16F, None);
+        let blur = device.create_texture(size.0, size.1, TextureFormat::R16F, None);
+        let target = |texture| RenderTarget::new(Attachment::Color(0), texture);
+        let framebuffers = create_framebuffers(device, &[&[target(occlusion)], &[target(blur)]]).inspect_err(|_| {
+            device.get_release_queue().push(Resource::Texture(occlusion));
+            device.get_release_queue().push(Resource::Texture(blur));
+        })?;
+        Ok(SsaoTargets {
+            occlusion_framebuffer: framebuffers[0],
+            occlusion,
+            blur_framebuffer: framebuffers[1],
+            blur,
+            size,
+            screen_size,
+            divisor,
+        })
+    }
+}
+
+impl GpuResources for SsaoTargets {
+    fn resources(&self) -> Vec<Resource> {
+        vec![
+            Resource::Framebuffer(self.occlusion_framebuffer),
+            Resource::Texture(self.occlusion),
+            Resource::Framebuffer(self.blur_framebuffer),
+            Resource::Texture(self.blur),
+        ]
+    }
+}
+
+/// The programs of the SSAO pass on one device.
+#[derive(Clone, Copy)]
+struct SsaoShaders {
+    ssao: ShaderHandle,  // Samples the hemisphere kernel
+    blur: ShaderHandle,  // Blurs along one axis
+    apply: ShaderHandle, // Multiplies the occlusion over the frame
+}
+
+impl GpuResources for SsaoShaders {
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::Shader(self.ssao), Resource::Shader(self.blur), Resource::Shader(self.apply)]
+    }
+}
+
+/// Computes blurred ambient occlusion from a depth and world-space normal buffer.
+pub(crate) struct SsaoPass {
+    shaders: GpuCache<SsaoShaders>, // Programs, compiled on first use
+    noise: GpuCache<TextureHandle>, // Tiled rotation noise
+    noise_texels: Vec<f32>,         // Contents of `noise`
+    kernel: Vec<Vec4>,              // Hemisphere samples
+    targets: Option<SsaoTargets>,   // Occlusion buffers of the last frame
+}
+
+impl SsaoPass {
+    pub(crate) fn new() -> SsaoPass {
+        let mut random = Random(0x2545_F491);
+        let noise_texels = noise_texels(&mut random);
+        let kernel = hemisphere_kernel(&mut random);
+
+        SsaoPass {
+            shaders: GpuCache::new(),
+            noise: GpuCache::new(),
+            noise_texels,
+            kernel,
+            targets: None,
+        }
+    }
+
+    /// Renders the occlusion buffer with `device`, afterwards available through `occlusion`.
+    ///
+    /// Leaves the viewport covering the full `screen_size` and the framebuffer that was
+    /// bound before bound again.
+    ///
+    /// # Returns
+    ///
+    /// An error if the occlusion buffers can't be created, leaving `occlusion` empty.
+    pub(crate) fn render(
+        &mut self,
+        device: &mut dyn RenderDevice,
+        settings: &SsaoSettings,
+        camera: &Camera,
+        depth: TextureHandle,
+        normal: TextureHandle,
+        screen_size: (u32, u32),
+    ) -> Result<(), String> {
+        let divisor = settings.quality.resolution_divisor();
+        let targets = match self.targets.take() {
+            Some(targets) if targets.screen_size == screen_size && targets.divisor == divisor => targets,
+            previous => {
+                for resource in previous.iter().flat_map(GpuResources::resources) {
+                    device.get_release_queue().push(resource);
+                }
+                SsaoTargets::new(device, screen_size, divisor)?
+            }
+        };
+        let shaders = self.shaders(device);
+        let targets = &*self.targets.insert(targets);
+
+        let noise = *self.noise.get_or_create(device, |device| {
+            let texels = bytes_of(&self.noise_texels);
+            device.create_texture(NOISE_SIZE, NOISE_SIZE, TextureFormat::Rgb16F, Some(texels))
+        });
+        let projection = camera.projection_matrix();
+        let inverse_projection = projection.try_inverse().unwrap_or_else(Mat4::identity);
+        let (width, height) = targets.size;
+        let kernel_size = settings.quality.kernel_size();
+        let output = device.get_framebuffer();
+        let gbuffer_sampler = Sampler::nearest(TextureWrap::ClampToEdge);
+
+        device.set_pipeline_state(&PipelineState::fullscreen());
+        device.set_viewport(0, 0, width, height);
+
+        // Occlusion from the hemisphere kernel.
+        device.bind_framebuffer(Some(targets.occlusion_framebuffer));
+        let noise_scale = Vec2::new(width as f32, height as f32) / NOISE_SIZE as f32;
+        let draw = fullscreen_triangle(device, shaders.ssao)
+            .with_texture(0, depth)
+            .with_texture(1, normal)
+            .with_texture(2, noise)
+            .with_sampler(0, gbuffer_sampler)
+            .with_sampler(1, gbuffer_sampler)
+            .with_sampler(2, Sampler::nearest(TextureWrap::Repeat))
+            .with_uniform("u_depth", UniformValue::Int(0))
+            .with_uniform("u_normal", UniformValue::Int(1))
+            .with_uniform("u_noise", UniformValue::Int(2))
+            .with_uniform("u_kernel", UniformValue::Vec4Array(self.kernel[..kernel_size].to_vec()))
+            .with_uniform("u_kernel_size", UniformValue::Int(kernel_size as i32))
+            .with_uniform("u_view", UniformValue::Mat4(camera.view_matrix()))
+            .with_uniform("u_projection", UniformValue::Mat4(projection))
+            .with_uniform("u_inverse_projection", UniformValue::Mat4(inverse_projection))
+            .with_uniform("u_noise_scale", UniformValue::Vec2(noise_scale))
+            .with_uniform("u_radius", UniformValue::Float(settings.radius))
+            .with_uniform("u_bias", UniformValue::Float(settings.bias))
+            .with_uniform("u_intensity", UniformValue::Float(settings.intensity));
+        device.draw(&draw);
+
+        // Separable bilateral blur: horizontally into `blur`, then vertically back.
+        let blur = fullscreen_triangle(device, shaders.blur)
+            .with_texture(1, depth)
+            .with_sampler(1, gbuffer_sampler)
+            .with_uniform("u_occlusion", UniformValue::Int(0))
+            .with_uniform("u_depth", UniformValue::Int(1))
+            .with_uniform("u_inverse_projection", UniformValue::Mat4(inverse_projection))
+            .with_uniform("u_radius", UniformValue::Int(settings.quality.blur_radius()));
+        let passes = [
+            (targets.blur_framebuffer, targets.occlusion, Vec2::new(1.0, 0.0)),
+            (targets.occlusion_framebuffer, targets.blur, Vec2::new(0.0, 1.0)),
+        ];
+        for (framebuffer, source, direction) in passes {
+            device.bind_framebuffer(Some(framebuffer));
+            let direction = UniformValue::Vec2(direction);
+            device.draw(&blur.clone().with_texture(0, source).with_uniform("u_direction", direction));
+        }
+
+        device.bind_framebuffer(output);
+        device.set_viewport(0, 0, screen_size.0, screen_size.1);
+
+        Ok(())
+    }
+
+    /// Returns the blurred occlusion buffer from the last `render`.
+    pub(crate) fn occlusion(&self) -> Option<TextureHandle> {
+        self.targets.as_ref().map(|targets| targets.occlusion)
+    }
+
+    /// Multiplies the occlusion buffer over the color of the bound framebuffer.
+    pub(crate) fn apply(&self, device: &mut dyn RenderDevice, occlusion: TextureHandle) {
+        let shader = self.shaders(device).apply;
+        let draw = fullscreen_triangle(device, shader)
+            .with_texture(0, occlusion)
+            .with_uniform("u_occlusion", UniformValue::Int(0));
+        device.set_pipeline_state(&PipelineState {
+            blend: Some(BlendState::multiply()),
+            ..PipelineState::fullscreen()
+        });
+        device.draw(&draw);
+    }
+
+    /// Returns the programs on `device`, compiling them the first time.
+    fn shaders(&self, device: &mut dyn RenderDevice) -> SsaoShaders {
+        *self.shaders.get_or_create(device, |device| {
+            let ssao_src = SSAO_FRAGMENT_SHADER.replace("MAX_KERNEL_SIZE", &MAX_KERNEL_SIZE.to_string());
+            SsaoShaders {
+                ssao: device.create_shader(FULLSCREEN_VERTEX_SHADER, &ssao_src),
+                blur: device.create_shader(FULLSCREEN_VERTEX_SHADER, BLUR_FRAGMENT_SHADER),
+                apply: device.create_shader(FULLSCREEN_VERTEX_SHADER, APPLY_FRAGMENT_SHADER),
+            }
+        })
+    }
+}
+
+/// A small xorshift generator, so the kernel is identical on every run.
+struct Random(u32);
+
+impl Random {
+    /// Returns a value in `[0, 1)`.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
+/// Returns sample offsets inside the unit `+Z` hemisphere, denser near the origin.
+fn hemisphere_kernel(random: &mut Random) -> Vec<Vec4> {
+    (0..MAX_KERNEL_SIZE)
+        .map(|i| {
+            let direction = Vec3::new(random.next() * 2.0 - 1.0, random.next() * 2.0 - 1.0, random.next());
+            let direction = direction.try_normalize(1e-6).unwrap_or_else(Vec3::z) * random.next();
+            let t = i as f32 / MAX_KERNEL_SIZE as f32;
+            let scale = 0.1 + 0.9 * t * t;
+            (direction * scale).push(0.0)
+        })
+        .collect()
+}
+
+/// Returns the texels of a tiling texture of random rotation vectors around `+Z`.
+fn noise_texels(random: &mut Random) -> Vec<f32> {
+    (0..NOISE_SIZE * NOISE_SIZE)
+        .flat_map(|_| [random.next() * 2.0 - 1.0, random.next() * 2.0 - 1.0, 0.0])
+        .collect()
+}
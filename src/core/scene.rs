@@ -0,0 +1,246 @@
+use std::cell::Cell;
+
+use log::warn;
+
+use crate::core::animation;
+use crate::core::assets::AssetServer;
+use crate::core::components::{GlobalTransform, Lod, MeshRenderer, MorphWeights, Skybox};
+use crate::core::ecs::{Entity, World};
+use crate::core::material::Environment;
+use crate::core::particles::{self, ParticleEmitter};
+use crate::core::renderer::SsaoSettings;
+use crate::core::tilemap::{self, Tilemap};
+use crate::core::{Camera, CullingStats, Light, Profiler, RenderContext, Time};
+use crate::device::RenderDevice;
+
+/// Everything the engine draws each frame.
+///
+/// Systems receive the scene mutably, so they can spawn meshes, move the camera
+/// or swap the skybox between frames.
+///
+/// Meshes, lights and cameras can either be owned by the scene directly or attached
+/// as components to entities of its `world`, where they follow the entity's
+/// `GlobalTransform`.
+#[derive(Default)]
+pub struct Scene {
+    /// The camera the scene is rendered from when there is no `active_camera`.
+    pub camera: Camera,
+    /// Meshes drawn every frame, in order, before the `world`'s meshes.
+    pub renders: Vec<MeshRenderer>,
+    /// Lights affecting the built-in lit materials, in addition to the `world`'s lights.
+    pub lights: Vec<Light>,
+    /// Entities with their components, such as spawned models.
+    pub world: World,
+    /// An entity with a `Camera` component to render from instead of `camera`.
+    pub active_camera: Option<Entity>,
+    /// Image-based lighting for the built-in PBR material.
+    pub environment: Option<Environment>,
+    /// Optional background drawn after opaque geometry. Its cube map is also bound
+    /// as the environment for reflections when the scene has no `environment`.
+    pub skybox: Option<Skybox>,
+    /// Screen-space ambient occlusion, applied on the deferred render path only.
+    pub ssao: Option<SsaoSettings>,
+    /// Frame timing, advanced by the engine every frame.
+    pub time: Time,
+    /// Meshes, textures, shaders and other resources shared by handle.
+    pub assets: AssetServer,
+    /// CPU and GPU timings of the engine's frame, update and render passes, plus any
+    /// scopes systems open themselves.
+    pub profiler: Profiler,
+    /// Result of the last frustum culling pass.
+    culling_stats: Cell<CullingStats>,
+}
+
+impl Scene {
+    /// Creates a new, empty `Scene` with a default camera and no skybox.
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    /// Returns the camera the scene is rendered from: the `active_camera` entity's, or
+    /// `camera` if there is none.
+    pub fn get_camera(&self) -> &Camera {
+        self.active_camera
+            .and_then(|entity| self.world.get::<Camera>(entity))
+            .unwrap_or(&self.camera)
+    }
+
+    /// Returns the camera the scene is rendered from mutably, see `get_camera`.
+    pub fn get_camera_mut(&mut self) -> &mut Camera {
+        match self.active_camera.and_then(|entity| self.world.get_mut::<Camera>(entity)) {
+            Some(camera) => camera,
+            None => &mut self.camera,
+        }
+    }
+
+    /// Advances every `AnimationPlayer` and propagates transforms through the `world`
+    /// hierarchy, then moves every `MeshRenderer`, `Camera`, `ParticleEmitter`, `Tilemap`
+    /// and `Lod` component to its entity's `GlobalTransform`, picks LOD levels for the
+    /// camera, poses skinned and morphed meshes, simulates particles and animates tilemaps.
+    ///
+    /// The engine calls this every frame after the update systems.
+    pub fn update_world(&mut self) {
+        animation::animate(&mut self.world, self.time.get_delta());
+        self.world.update_transforms();
+
+        let globals: Vec<(Entity, GlobalTransform)> =
+            self.world.query::<GlobalTransform>().map(|(entity, global)| (entity, *global)).collect();
+        for (entity, GlobalTransform(matrix)) in globals {
+            if let Some(render) = self.world.get_mut::<MeshRenderer>(entity) {
+                render.set_transform(matrix);
+            }
+            if let Some(camera) = self.world.get_mut::<Camera>(entity) {
+                camera.set_from_transform(&matrix);
+            }
+            if let Some(emitter) = self.world.get_mut::<ParticleEmitter>(entity) {
+                emitter.set_transform(matrix);
+            }
+            if let Some(tilemap) = self.world.get_mut::<Tilemap>(entity) {
+                tilemap.set_transform(matrix);
+            }
+            if let Some(lod) = self.world.get_mut::<Lod>(entity) {
+                lod.set_transform(matrix);
+            }
+        }
+        let camera = self.get_camera().clone();
+        for (_, lod) in self.world.query_mut::<Lod>() {
+            lod.update(&camera, self.time.get_delta());
+        }
+        animation::update_skins(&mut self.world);
+
+        let morphs: Vec<(Entity, MorphWeights)> =
+            self.world.query::<MorphWeights>().map(|(entity, weights)| (entity, weights.clone())).collect();
+        for (entity, MorphWeights(weights)) in morphs {
+            let children = self.world.get_children(entity).to_vec();
+            for target in std::iter::once(entity).chain(children) {
+                if let Some(render) = self.world.get_mut::<MeshRenderer>(target) {
+                    render.set_morph_weights(&weights);
+                }
+            }
+        }
+        particles::simulate(&mut self.world, self.time.get_delta());
+        tilemap::update(&mut self.world, self.time.get_delta());
+    }
+
+    /// Returns every mesh to draw: the scene's own, then the `world`'s, then the selected
+    /// levels of the `world`'s `Lod`s.
+    pub fn all_renders(&self) -> impl Iterator<Item = &MeshRenderer> {
+        self.renders
+            .iter()
+            .chain(self.world.query::<MeshRenderer>().map(|(_, render)| render))
+            .chain(self.world.query::<Lod>().flat_map(|(_, lod)| lod.renders()))
+    }
+
+    /// Returns the per-frame state passed to every draw.
+    pub fn render_context(&self) -> RenderContext<'_> {
+        let reflection = match (&self.environment, &self.skybox) {
+            (Some(environment), _) => Some(environment.get_cubemap()),
+            (None, Some(skybox)) => Some(skybox.get_cubemap()),
+            (None, None) => None,
+        };
+
+        let world_lights = self.world.query::<Light>().map(|(entity, light)| match self.world.get::<GlobalTransform>(entity) {
+            Some(GlobalTransform(matrix)) => light.transformed(matrix),
+            None => *light,
+        });
+
+        RenderContext {
+            camera: self.get_camera(),
+            lights: self.lights.iter().copied().chain(world_lights).collect(),
+            environment: self.environment.as_ref(),
+            reflection,
+        }
+    }
+
+    /// Returns the meshes inside the camera's frustum, in submission order, and records
+    /// how many were culled for `get_culling_stats`.
+    pub fn visible_renders(&self) -> Vec<&MeshRenderer> {
+        let frustum = self.get_camera().frustum();
+        let (visible, culled): (Vec<&MeshRenderer>, Vec<&MeshRenderer>) =
+            self.all_renders().partition(|render| render.is_visible(&frustum));
+        self.culling_stats.set(CullingStats {
+            visible: visible.len(),
+            culled: culled.len(),
+        });
+        visible
+    }
+
+    /// Returns how many meshes the last frame drew and skipped as off-screen.
+    pub fn get_culling_stats(&self) -> CullingStats {
+        self.culling_stats.get()
+    }
+
+    /// Culls the meshes once with `visible_renders` and splits them by how they are drawn.
+    ///
+    /// # Returns
+    ///
+    /// The visible meshes that are not alpha blended, in submission order, and the visible
+    /// alpha blended meshes sorted back to front from the camera.
+    pub fn partition_renders(&self) -> (Vec<&MeshRenderer>, Vec<&MeshRenderer>) {
+        let eye = self.get_camera().get_position();
+        let distance = |render: &MeshRenderer| {
+            let transform = render.get_transform();
+            let position = nalgebra_glm::vec3(transform[(0, 3)], transform[(1, 3)], transform[(2, 3)]);
+            nalgebra_glm::distance2(eye, &position)
+        };
+
+        let (mut transparent, opaque): (Vec<&MeshRenderer>, Vec<&MeshRenderer>) =
+            self.visible_renders().into_iter().partition(|render| render.is_transparent());
+        transparent.sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+        (opaque, transparent)
+    }
+
+    /// Returns the `world`'s tilemaps, drawn after the skybox and before transparent meshes.
+    pub fn tilemaps(&self) -> impl Iterator<Item = &Tilemap> {
+        self.world.query::<Tilemap>().map(|(_, tilemap)| tilemap)
+    }
+
+    /// Returns the `world`'s particle emitters, drawn after transparent meshes.
+    pub fn particle_emitters(&self) -> impl Iterator<Item = &ParticleEmitter> {
+        self.world.query::<ParticleEmitter>().map(|(_, emitter)| emitter)
+    }
+
+    /// Renders every opaque mesh, then the skybox behind them, then tilemaps, transparent
+    /// meshes and particles on top, all in a single forward pass. The environment's
+    /// lighting is baked first if it hasn't been yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the scene is drawn with.
+    pub fn render(&self, device: &mut dyn RenderDevice) {
+        if let Some(Err(err)) = self.environment.as_ref().map(|environment| environment.render(device)) {
+            warn!("Couldn't bake the environment lighting: {err}");
+        }
+
+        let context = self.render_context();
+        let (opaque, transparent) = self.partition_renders();
+
+        let scope = self.profiler.scope("opaque");
+        for render in opaque {
+            render.render(device, &context);
+        }
+        drop(scope);
+
+        if let Some(skybox) = &self.skybox {
+            let _scope = self.profiler.scope("skybox");
+            skybox.render(device, context.camera);
+        }
+
+        let scope = self.profiler.scope("tilemaps");
+        for tilemap in self.tilemaps() {
+            tilemap.render(device, &context);
+        }
+        drop(scope);
+
+        let scope = self.profiler.scope("transparent");
+        for render in transparent {
+            render.render(device, &context);
+        }
+        drop(scope);
+
+        let _scope = self.profiler.scope("particles");
+        for emitter in self.particle_emitters() {
+            emitter.render(device, &context);
+        }
+    }
+}
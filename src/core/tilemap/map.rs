@@ -0,0 +1,362 @@
+use nalgebra_glm::{self as glm, Mat4, Vec2, Vec3};
+
+use super::tile_layer::tile_layout;
+use super::{TileLayer, Tileset, MAX_TILE_ANIMATIONS};
+use crate::core::material::OUTPUT_GLSL;
+use crate::core::RenderContext;
+use crate::device::{DrawCall, ExternalTexture, GpuCache, RenderDevice, ShaderHandle, UniformValue};
+use crate::opengl::{PipelineState, Sampler, TextureWrap};
+
+/// Texture unit the tileset texture is bound to while drawing.
+const TILE_TEXTURE_UNIT: u32 = 0;
+
+const TILE_VERTEX_SHADER: &str = r#"
+layout (location = 0) in vec2 a_position;
+layout (location = 1) in vec2 a_corner;
+layout (location = 2) in vec4 a_rect;
+layout (location = 3) in float a_animation;
+
+uniform mat4 u_model;
+uniform mat4 u_view;
+uniform mat4 u_projection;
+uniform vec4 u_animation_rects[MAX_TILE_ANIMATIONS];
+
+out vec2 v_uv;
+
+void main() {
+    // Animated tiles read their current frame instead of the rectangle they were built with.
+    vec4 rect = a_animation < 0.0 ? a_rect : u_animation_rects[int(a_animation)];
+    v_uv = rect.xy + a_corner * rect.zw;
+    gl_Position = u_projection * u_view * u_model * vec4(a_position, 0.0, 1.0);
+}
+"#;
+
+const TILE_FRAGMENT_SHADER: &str = r#"
+in vec2 v_uv;
+
+uniform sampler2D u_texture;
+uniform float u_opacity;
+
+out vec4 frag_color;
+
+void main() {
+    vec4 color = texture(u_texture, v_uv);
+    frag_color = vec4(encode_output(color.rgb), color.a * u_opacity);
+}
+"#;
+
+thread_local! {
+    static TILE_SHADER: GpuCache<ShaderHandle> = const { GpuCache::new() };
+}
+
+/// Which axis of a staggered or hexagonal map is staggered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StaggerAxis {
+    /// Every other column is shifted down by half a cell.
+    X,
+    /// Every other row is shifted right by half a cell.
+    #[default]
+    Y,
+}
+
+/// Which columns or rows of a staggered or hexagonal map are shifted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StaggerIndex {
+    /// Columns or rows 1, 3, 5 and so on.
+    #[default]
+    Odd,
+    /// Columns or rows 0, 2, 4 and so on.
+    Even,
+}
+
+/// How the cells of a tilemap are laid out, following Tiled's map orientations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Orientation {
+    /// Square or rectangular cells in rows and columns.
+    #[default]
+    Orthogonal,
+    /// Diamond cells, with the map's x axis running down to the right and its y axis down
+    /// to the left.
+    Isometric,
+    /// Diamond cells in rows and columns, every other one shifted by half a cell.
+    Staggered { axis: StaggerAxis, index: StaggerIndex },
+    /// Hexagonal cells in rows and columns, every other one shifted by half a cell.
+    Hexagonal {
+        axis: StaggerAxis,
+        index: StaggerIndex,
+        /// Length in pixels of the hexagon's flat sides along the stagger axis.
+        side_length: u32,
+    },
+}
+
+/// Size and layout of a tilemap's cells, shared by its layers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct Grid {
+    pub orientation: Orientation,
+    pub width: u32,       // Columns of cells
+    pub height: u32,      // Rows of cells
+    pub tile_width: u32,  // Width of a cell in pixels
+    pub tile_height: u32, // Height of a cell in pixels
+}
+
+impl Grid {
+    /// Returns the top left corner of a cell's bounding box, in pixels from the top left
+    /// of the map with y growing down, as Tiled lays them out.
+    pub fn cell_origin(&self, x: u32, y: u32) -> Vec2 {
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        let (column, row) = (x as f32, y as f32);
+        match self.orientation {
+            Orientation::Orthogonal => Vec2::new(column * tile_width, row * tile_height),
+            Orientation::Isometric => {
+                // The top corner of cell (0, 0) is centered above the map's left edge.
+                let left = (column - row + self.height as f32 - 1.0) * tile_width / 2.0;
+                Vec2::new(left, (column + row) * tile_height / 2.0)
+            }
+            Orientation::Staggered { axis, index } => self.staggered_origin(x, y, axis, index, 0),
+            Orientation::Hexagonal { axis, index, side_length } => {
+                self.staggered_origin(x, y, axis, index, side_length)
+            }
+        }
+    }
+
+    /// Lays out staggered and hexagonal cells, staggered ones being hexagons without sides.
+    fn staggered_origin(&self, x: u32, y: u32, axis: StaggerAxis, index: StaggerIndex, side_length: u32) -> Vec2 {
+        let (tile_width, tile_height) = (self.tile_width as f32, self.tile_height as f32);
+        let side_length = side_length as f32;
+        let staggered = |position: u32| (position % 2 == 1) == (index == StaggerIndex::Odd);
+        match axis {
+            StaggerAxis::X => {
+                let column_width = (tile_width + side_length) / 2.0;
+                let shift = if staggered(x) { tile_height / 2.0 } else { 0.0 };
+                Vec2::new(x as f32 * column_width, y as f32 * tile_height + shift)
+            }
+            StaggerAxis::Y => {
+                let row_height = (tile_height + side_length) / 2.0;
+                let shift = if staggered(y) { tile_width / 2.0 } else { 0.0 };
+                Vec2::new(x as f32 * tile_width + shift, y as f32 * row_height)
+            }
+        }
+    }
+}
+
+/// A 2D grid of tiles in one or more layers, drawn from tilesets.
+///
+/// Each layer is split into chunks of `CHUNK_SIZE` by `CHUNK_SIZE` cells, uploaded to a
+/// static vertex buffer when first drawn and only rebuilt by `update` after one of their
+/// tiles changes. Chunks outside the camera's view are skipped, so maps can be much larger
+/// than the screen. Animated tiles switch frames in the shader without touching the
+/// buffers. Tileset textures are sampled without filtering, so tiles stay crisp and don't
+/// bleed into each other.
+///
+/// The map is laid out in pixels, one world unit each, with its top left corner at the
+/// origin, x growing right and y growing up, so rows run down the negative y axis.
+/// Scale the entity's transform to draw it at another size. Layers are alpha blended
+/// in order after opaque geometry, without writing depth, and tiles within a layer
+/// are drawn from the top of the screen down. Tiles taller than their cell may overlap
+/// the wrong way along the edges of isometric chunks.
+pub struct Tilemap {
+    grid: Grid,             // Layout of the cells
+    tilesets: Vec<Tileset>, // Tilesets referenced by the tiles
+    layers: Vec<TileLayer>, // Layers, drawn in order
+    transform: Mat4,        // Map-to-world matrix
+    time: f32,              // Seconds animated tiles have played for
+}
+
+impl Tilemap {
+    /// Creates a tilemap without tilesets or layers.
+    ///
+    /// # Arguments
+    ///
+    /// * `orientation` - How the cells are laid out.
+    /// * `width` - Columns of cells in every layer.
+    /// * `height` - Rows of cells in every layer.
+    /// * `tile_width` - Width of a cell in pixels. Isometric cells are this wide from one
+    ///   corner to the other.
+    /// * `tile_height` - Height of a cell in pixels.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `Tilemap`.
+    pub fn new(orientation: Orientation, width: u32, height: u32, tile_width: u32, tile_height: u32) -> Tilemap {
+        Tilemap {
+            grid: Grid { orientation, width, height, tile_width, tile_height },
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            transform: Mat4::identity(),
+            time: 0.0,
+        }
+    }
+
+    /// Returns how the cells are laid out.
+    pub fn get_orientation(&self) -> Orientation {
+        self.grid.orientation
+    }
+
+    /// Changes how the cells are laid out, rebuilding every chunk on the next `update`.
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.grid.orientation = orientation;
+        self.invalidate();
+    }
+
+    /// Returns the number of columns of cells.
+    pub fn get_width(&self) -> u32 {
+        self.grid.width
+    }
+
+    /// Returns the number of rows of cells.
+    pub fn get_height(&self) -> u32 {
+        self.grid.height
+    }
+
+    /// Returns the width of a cell in pixels.
+    pub fn get_tile_width(&self) -> u32 {
+        self.grid.tile_width
+    }
+
+    /// Returns the height of a cell in pixels.
+    pub fn get_tile_height(&self) -> u32 {
+        self.grid.tile_height
+    }
+
+    /// Adds a tileset and returns its index, referred to by `Tile::tileset`.
+    pub fn add_tileset(&mut self, tileset: Tileset) -> usize {
+        self.tilesets.push(tileset);
+        // Chunks may hold tiles of the tileset that were skipped while it was missing.
+        self.invalidate();
+        self.tilesets.len() - 1
+    }
+
+    /// Returns the tilesets, by index.
+    pub fn get_tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    /// Returns a tileset mutably, rebuilding every chunk on the next `update`.
+    pub fn get_tileset_mut(&mut self, index: usize) -> Option<&mut Tileset> {
+        self.invalidate();
+        self.tilesets.get_mut(index)
+    }
+
+    /// Adds a layer on top of the others and returns its index.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the layer's size differs from the map's.
+    pub fn add_layer(&mut self, layer: TileLayer) -> usize {
+        assert!(
+            layer.get_width() == self.grid.width && layer.get_height() == self.grid.height,
+            "layer {:?} is {}x{} cells, but the map is {}x{}",
+            layer.name,
+            layer.get_width(),
+            layer.get_height(),
+            self.grid.width,
+            self.grid.height
+        );
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
+
+    /// Returns the layers, from the bottom one up.
+    pub fn get_layers(&self) -> &[TileLayer] {
+        &self.layers
+    }
+
+    /// Returns a layer mutably, to change its tiles or how it is drawn.
+    pub fn get_layer_mut(&mut self, index: usize) -> Option<&mut TileLayer> {
+        self.layers.get_mut(index)
+    }
+
+    /// Returns the index of the first layer with the given name.
+    pub fn get_layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    /// Returns the center of a cell in map space, see `Tilemap`.
+    pub fn get_cell_center(&self, x: u32, y: u32) -> Vec2 {
+        let center = self.grid.cell_origin(x, y)
+            + Vec2::new(self.grid.tile_width as f32, self.grid.tile_height as f32) / 2.0;
+        Vec2::new(center.x, -center.y)
+    }
+
+    /// Returns how many seconds animated tiles have played for.
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// Sets how many seconds animated tiles have played for, e.g. to restart them.
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Returns the map-to-world matrix.
+    pub fn get_transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    /// Sets the map-to-world matrix.
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.transform = transform;
+    }
+
+    /// Advances animated tiles by `delta` seconds and rebuilds the chunks whose tiles
+    /// changed since the last update.
+    pub fn update(&mut self, delta: f32) {
+        self.time += delta;
+        for layer in &mut self.layers {
+            layer.rebuild(&self.grid, &self.tilesets);
+        }
+    }
+
+    /// Draws the visible layers with `device`, skipping chunks outside the context camera's
+    /// view. Chunks are uploaded the first time they are drawn.
+    ///
+    /// The draw is depth tested without depth writes, so it belongs after opaque geometry.
+    pub fn render(&self, device: &mut dyn RenderDevice, context: &RenderContext) {
+        let shader = tile_shader(device);
+        device.set_pipeline_state(&PipelineState::transparent());
+
+        let frustum = context.camera.frustum();
+        let textures: Vec<_> = (self.tilesets.iter())
+            .map(|tileset| device.import_texture(ExternalTexture::Texture(&tileset.texture.get())))
+            .collect();
+        let animation_rects: Vec<_> = self.tilesets.iter().map(|tileset| tileset.animation_rects(self.time)).collect();
+        for layer in self.layers.iter().filter(|layer| layer.visible && layer.opacity > 0.0) {
+            let model = self.transform * glm::translation(&Vec3::new(layer.offset.x, -layer.offset.y, 0.0));
+            for (buffer, batch) in layer.visible_batches(device, &frustum, &model) {
+                let mut draw = DrawCall::new(shader, buffer, tile_layout(), batch.count)
+                    .with_range(batch.first, batch.count)
+                    .with_uniform("u_view", UniformValue::Mat4(context.camera.view_matrix()))
+                    .with_uniform("u_projection", UniformValue::Mat4(context.camera.projection_matrix()))
+                    .with_uniform("u_model", UniformValue::Mat4(model))
+                    .with_uniform("u_opacity", UniformValue::Float(layer.opacity))
+                    .with_uniform("u_texture", UniformValue::Int(TILE_TEXTURE_UNIT as i32))
+                    .with_texture(TILE_TEXTURE_UNIT, textures[batch.tileset])
+                    .with_sampler(TILE_TEXTURE_UNIT, Sampler::nearest(TextureWrap::ClampToEdge));
+                let rects = &animation_rects[batch.tileset];
+                if !rects.is_empty() {
+                    draw = draw.with_uniform("u_animation_rects", UniformValue::Vec4Array(rects.clone()));
+                }
+                device.draw(&draw);
+            }
+        }
+    }
+
+    /// Rebuilds every chunk on the next `update`.
+    fn invalidate(&mut self) {
+        for layer in &mut self.layers {
+            layer.invalidate();
+        }
+    }
+}
+
+/// Returns the program drawing tilemaps on `device`, compiling it the first time.
+fn tile_shader(device: &mut dyn RenderDevice) -> ShaderHandle {
+    TILE_SHADER.with(|cache| {
+        *cache.get_or_create(device, |device| {
+            let header = format!("#version 330 core\n#define MAX_TILE_ANIMATIONS {MAX_TILE_ANIMATIONS}\n");
+            let vertex_src = format!("{header}{TILE_VERTEX_SHADER}");
+            let fragment_src = format!("{header}{OUTPUT_GLSL}{TILE_FRAGMENT_SHADER}");
+            device.create_shader(&vertex_src, &fragment_src)
+        })
+    })
+}
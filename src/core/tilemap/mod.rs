@@ -0,0 +1,22 @@
+mod map;
+mod tile;
+mod tile_layer;
+mod tiled;
+mod tileset;
+mod tmj;
+mod tmx;
+
+pub use map::{Orientation, StaggerAxis, StaggerIndex, Tilemap};
+pub use tile::Tile;
+pub use tile_layer::{TileLayer, CHUNK_SIZE};
+pub use tiled::{ObjectShape, TiledMap, TiledObject, TiledProperties, TiledProperty};
+pub use tileset::{TileFrame, Tileset, MAX_TILE_ANIMATIONS};
+
+use crate::core::ecs::World;
+
+/// Advances every `Tilemap` in `world` by `delta` seconds and rebuilds its changed chunks.
+pub fn update(world: &mut World, delta: f32) {
+    for (_, tilemap) in world.query_mut::<Tilemap>() {
+        tilemap.update(delta);
+    }
+}
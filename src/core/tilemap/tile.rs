@@ -0,0 +1,54 @@
+use nalgebra_glm::Vec2;
+
+/// A tile placed in a layer cell: a tile of one of the map's tilesets, optionally flipped.
+///
+/// Flips follow Tiled: the diagonal flip swaps the tile's axes first, then the horizontal
+/// and vertical flips mirror the result. Combining them rotates the tile, e.g. a diagonal
+/// and horizontal flip turns it 90 degrees clockwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tile {
+    pub tileset: usize,        // Index of the tileset in the map
+    pub id: u32,               // Index of the tile in its tileset, row by row
+    pub flip_horizontal: bool, // Mirrored left to right
+    pub flip_vertical: bool,   // Mirrored top to bottom
+    pub flip_diagonal: bool,   // Mirrored along the top-left to bottom-right diagonal
+}
+
+impl Tile {
+    /// Creates an unflipped tile.
+    ///
+    /// # Arguments
+    ///
+    /// * `tileset` - Index of the tileset in the map.
+    /// * `id` - Index of the tile in its tileset, counting row by row from the top left.
+    pub fn new(tileset: usize, id: u32) -> Tile {
+        Tile { tileset, id, ..Tile::default() }
+    }
+
+    /// Returns a copy with new flip flags.
+    pub fn with_flip(self, horizontal: bool, vertical: bool, diagonal: bool) -> Tile {
+        Tile {
+            flip_horizontal: horizontal,
+            flip_vertical: vertical,
+            flip_diagonal: diagonal,
+            ..self
+        }
+    }
+
+    /// Maps a corner of the drawn quad, from `(0, 0)` at its top left to `(1, 1)` at its
+    /// bottom right, to the corner of the tile image it shows.
+    pub(super) fn image_corner(&self, corner: Vec2) -> Vec2 {
+        // Undo the flips in reverse order: vertical, horizontal, then diagonal.
+        let mut corner = corner;
+        if self.flip_vertical {
+            corner.y = 1.0 - corner.y;
+        }
+        if self.flip_horizontal {
+            corner.x = 1.0 - corner.x;
+        }
+        if self.flip_diagonal {
+            corner = Vec2::new(corner.y, corner.x);
+        }
+        corner
+    }
+}
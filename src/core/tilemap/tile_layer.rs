@@ -0,0 +1,242 @@
+use nalgebra_glm::{Mat4, Vec2, Vec3};
+
+use super::map::Grid;
+use super::{Tile, Tileset};
+use crate::core::{Aabb, Frustum};
+use crate::device::{bytes_of, BufferHandle, GpuCache, RenderDevice};
+use crate::opengl::{BufferTarget, BufferUsage, VertexLayout};
+
+/// Cells along each side of a chunk.
+pub const CHUNK_SIZE: u32 = 32;
+
+/// `f32`s per vertex: position, image corner, tile rectangle and animation slot.
+const TILE_VERTEX_FLOATS: usize = 9;
+
+/// Corners of a tile's quad from its top left, as two counter-clockwise triangles.
+const QUAD_CORNERS: [(f32, f32); 6] = [(0.0, 0.0), (0.0, 1.0), (1.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)];
+
+/// A run of tiles in a chunk drawn from the same tileset.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct TileBatch {
+    pub tileset: usize, // Index of the tileset
+    pub first: u32,     // First vertex
+    pub count: u32,     // Number of vertices
+}
+
+/// The built tiles of one chunk of a layer.
+struct TileChunk {
+    vertices: Vec<f32>,             // Six vertices per tile
+    buffer: GpuCache<BufferHandle>, // `vertices`, uploaded to each device drawing them
+    batches: Vec<TileBatch>,        // Runs of tiles, in draw order
+    bounds: Aabb,                   // Bounds of the quads in layer space
+}
+
+/// Returns how the vertices of a chunk map to the attributes of the tilemap program.
+pub(super) fn tile_layout() -> VertexLayout {
+    VertexLayout::new().with(0, 2).with(1, 2).with(2, 4).with(3, 1)
+}
+
+/// One layer of a `Tilemap`: a tile or nothing in each cell.
+///
+/// Changing tiles only marks their chunk for rebuilding, so many tiles can be edited
+/// in a frame for the cost of one rebuild per chunk on the map's next `update`, and one
+/// upload on its next draw.
+pub struct TileLayer {
+    /// Name shown in editors, such as the Tiled layer name.
+    pub name: String,
+    /// Whether the layer is drawn.
+    pub visible: bool,
+    /// Opacity the layer is blended with, from `0.0` to `1.0`.
+    pub opacity: f32,
+    /// Offset of the whole layer, in pixels to the right and down.
+    pub offset: Vec2,
+    width: u32,                     // Columns of cells
+    height: u32,                    // Rows of cells
+    tiles: Vec<Option<Tile>>,       // Cells, row by row
+    chunks: Vec<Option<TileChunk>>, // Built chunks, row by row, `None` while empty
+    dirty: Vec<bool>,               // Chunks to rebuild on the next update
+    draw_order: Vec<usize>,         // Non-empty chunks, from the top of the screen down
+}
+
+impl TileLayer {
+    /// Creates a visible, opaque layer with empty cells.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the layer.
+    /// * `width` - Columns of cells, matching the map's.
+    /// * `height` - Rows of cells, matching the map's.
+    pub fn new(name: impl Into<String>, width: u32, height: u32) -> TileLayer {
+        let chunk_count = (width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE)) as usize;
+        TileLayer {
+            name: name.into(),
+            visible: true,
+            opacity: 1.0,
+            offset: Vec2::zeros(),
+            width,
+            height,
+            tiles: vec![None; width as usize * height as usize],
+            chunks: (0..chunk_count).map(|_| None).collect(),
+            dirty: vec![true; chunk_count],
+            draw_order: Vec::new(),
+        }
+    }
+
+    /// Returns the number of columns of cells.
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the number of rows of cells.
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the tile in a cell, or `None` if the cell is empty or outside the layer.
+    pub fn get_tile(&self, x: u32, y: u32) -> Option<Tile> {
+        if x < self.width && y < self.height {
+            self.tiles[(y * self.width + x) as usize]
+        } else {
+            None
+        }
+    }
+
+    /// Places a tile in a cell, or empties it with `None`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the cell is outside the layer.
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<Tile>) {
+        let (width, height) = (self.width, self.height);
+        assert!(x < width && y < height, "cell ({x}, {y}) is outside the {width}x{height} layer");
+        let cell = &mut self.tiles[(y * self.width + x) as usize];
+        if *cell != tile {
+            *cell = tile;
+            let chunks_x = self.width.div_ceil(CHUNK_SIZE);
+            self.dirty[(y / CHUNK_SIZE * chunks_x + x / CHUNK_SIZE) as usize] = true;
+        }
+    }
+
+    /// Empties every cell.
+    pub fn clear(&mut self) {
+        self.tiles.fill(None);
+        self.invalidate();
+    }
+
+    /// Marks every chunk for rebuilding.
+    pub(super) fn invalidate(&mut self) {
+        self.dirty.fill(true);
+    }
+
+    /// Rebuilds the chunks changed since the last rebuild. Their old buffers are released
+    /// and the new ones uploaded when next drawn.
+    pub(super) fn rebuild(&mut self, grid: &Grid, tilesets: &[Tileset]) {
+        if !self.dirty.contains(&true) {
+            return;
+        }
+        let chunks_x = self.width.div_ceil(CHUNK_SIZE);
+        for index in 0..self.chunks.len() {
+            if std::mem::take(&mut self.dirty[index]) {
+                let (chunk_x, chunk_y) = (index as u32 % chunks_x, index as u32 / chunks_x);
+                self.chunks[index] = self.build_chunk(chunk_x, chunk_y, grid, tilesets);
+            }
+        }
+
+        let origin = |index: &usize| {
+            let (chunk_x, chunk_y) = (*index as u32 % chunks_x, *index as u32 / chunks_x);
+            grid.cell_origin(chunk_x * CHUNK_SIZE, chunk_y * CHUNK_SIZE)
+        };
+        self.draw_order = (0..self.chunks.len()).filter(|index| self.chunks[*index].is_some()).collect();
+        self.draw_order.sort_by(|a, b| {
+            let (a, b) = (origin(a), origin(b));
+            a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+        });
+    }
+
+    /// Returns the batches of the chunks inside the frustum, in draw order, uploading
+    /// chunks to `device` the first time they are drawn with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the layer is drawn with.
+    /// * `frustum` - The camera's view.
+    /// * `model` - The layer-to-world matrix.
+    ///
+    /// # Returns
+    ///
+    /// Each batch with the vertex buffer it reads, laid out by `tile_layout`.
+    pub(super) fn visible_batches(
+        &self,
+        device: &mut dyn RenderDevice,
+        frustum: &Frustum,
+        model: &Mat4,
+    ) -> Vec<(BufferHandle, TileBatch)> {
+        let mut batches = Vec::new();
+        for chunk in self.draw_order.iter().filter_map(|index| self.chunks[*index].as_ref()) {
+            if !frustum.intersects_aabb(&chunk.bounds.transformed(model)) {
+                continue;
+            }
+            let buffer = *chunk.buffer.get_or_create(device, |device| {
+                device.create_buffer(BufferTarget::ArrayBuffer, BufferUsage::StaticDraw, bytes_of(&chunk.vertices))
+            });
+            batches.extend(chunk.batches.iter().map(|batch| (buffer, *batch)));
+        }
+        batches
+    }
+
+    /// Builds the vertices of a chunk's tiles.
+    ///
+    /// # Returns
+    ///
+    /// The chunk, or `None` if it holds no tile of an existing tileset.
+    fn build_chunk(&self, chunk_x: u32, chunk_y: u32, grid: &Grid, tilesets: &[Tileset]) -> Option<TileChunk> {
+        let mut cells = Vec::new();
+        for y in chunk_y * CHUNK_SIZE..((chunk_y + 1) * CHUNK_SIZE).min(self.height) {
+            for x in chunk_x * CHUNK_SIZE..((chunk_x + 1) * CHUNK_SIZE).min(self.width) {
+                let Some(tile) = self.get_tile(x, y) else { continue };
+                let Some(tileset) = tilesets.get(tile.tileset) else { continue };
+                cells.push((grid.cell_origin(x, y), tile, tileset));
+            }
+        }
+        // Tiles lower on the screen cover the ones above them.
+        cells.sort_by(|(a, ..), (b, ..)| a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x)));
+
+        let mut vertices = Vec::with_capacity(cells.len() * QUAD_CORNERS.len() * TILE_VERTEX_FLOATS);
+        let mut batches: Vec<TileBatch> = Vec::new();
+        let mut corners = Vec::with_capacity(cells.len() * 2);
+        for (origin, tile, tileset) in cells {
+            // Tile images sit on the bottom left corner of their cell.
+            let size = Vec2::new(tileset.tile_width as f32, tileset.tile_height as f32);
+            let bottom_left = origin + tileset.offset + Vec2::new(0.0, grid.tile_height as f32);
+            let top_left = bottom_left - Vec2::new(0.0, size.y);
+            corners.push(Vec3::new(top_left.x, -top_left.y, 0.0));
+            corners.push(Vec3::new(top_left.x + size.x, -bottom_left.y, 0.0));
+
+            let rect = tileset.get_tile_rect(tile.id);
+            let animation = tileset.animation_slot(tile.id).map_or(-1.0, |slot| slot as f32);
+            for (x, y) in QUAD_CORNERS {
+                let position = top_left + Vec2::new(x * size.x, y * size.y);
+                let corner = tile.image_corner(Vec2::new(x, y));
+                vertices.extend_from_slice(&[position.x, -position.y, corner.x, corner.y]);
+                vertices.extend_from_slice(&[rect.x, rect.y, rect.z, rect.w, animation]);
+            }
+
+            match batches.last_mut() {
+                Some(batch) if batch.tileset == tile.tileset => batch.count += QUAD_CORNERS.len() as u32,
+                _ => batches.push(TileBatch {
+                    tileset: tile.tileset,
+                    first: batches.last().map_or(0, |batch| batch.first + batch.count),
+                    count: QUAD_CORNERS.len() as u32,
+                }),
+            }
+        }
+        let bounds = Aabb::from_points(corners)?;
+
+        Some(TileChunk {
+            vertices,
+            buffer: GpuCache::new(),
+            batches,
+            bounds,
+        })
+    }
+}
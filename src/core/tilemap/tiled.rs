@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use nalgebra_glm::{self as glm, Vec2, Vec3};
+
+use super::{tmj, tmx};
+use super::{Orientation, StaggerAxis, StaggerIndex, Tile, TileFrame, TileLayer, Tilemap, Tileset};
+use crate::core::assets::AssetServer;
+use crate::core::components::Transform;
+use crate::core::ecs::{Entity, Name, World};
+
+/// Global tile id bits flagging a tile as mirrored left to right.
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+/// Global tile id bits flagging a tile as mirrored top to bottom.
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// Global tile id bits flagging a tile's axes as swapped.
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// Global tile id bits flagging a hexagonal tile as rotated by 120 degrees, which is ignored.
+const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+
+/// A custom property of a Tiled map, layer or object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TiledProperty {
+    Bool(bool),
+    /// Integers, and references to objects by id.
+    Int(i64),
+    Float(f64),
+    /// Strings, and colors and file paths as Tiled writes them.
+    String(String),
+}
+
+/// The custom properties of a Tiled map, layer or object, by name.
+///
+/// Inserted on the entities spawned by `TiledMap::load` that have any.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TiledProperties(pub HashMap<String, TiledProperty>);
+
+/// The outline of a Tiled object, in pixels from its origin with y growing up.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectShape {
+    /// A rectangle of the object's size, or the tile of a tile object.
+    Rectangle,
+    /// An ellipse fitting in the object's size.
+    Ellipse,
+    /// A single point at the object's origin.
+    Point,
+    /// A closed outline through the points.
+    Polygon(Vec<Vec2>),
+    /// An open line through the points.
+    Polyline(Vec<Vec2>),
+    /// A text box of the object's size.
+    Text(String),
+}
+
+/// An object of a Tiled object layer, such as a spawn point, trigger area or collider.
+///
+/// The entity's `Transform` places the object's origin and applies its rotation. The
+/// origin is the top left corner of rectangles, ellipses and text, and the bottom left
+/// corner of tile objects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TiledObject {
+    /// Unique id of the object in the map.
+    pub id: u32,
+    /// The object's class, called its type before Tiled 1.9.
+    pub class: String,
+    /// The outline of the object.
+    pub shape: ObjectShape,
+    /// Width and height in pixels, extending right and down from the origin, or right
+    /// and up for tile objects.
+    pub size: Vec2,
+    /// The tile drawn by tile objects.
+    pub tile: Option<Tile>,
+    /// Whether the object and its layer are shown in Tiled.
+    pub visible: bool,
+}
+
+/// A Tiled map spawned into a `World`.
+///
+/// The root entity holds the `Tilemap` with every tile layer. Object layers become
+/// child entities of the root, with one child per object holding a `TiledObject`.
+pub struct TiledMap {
+    /// Entity holding the `Tilemap`, named after the file.
+    pub root: Entity,
+    /// One entity per object layer, in the order of the file.
+    pub object_layers: Vec<Entity>,
+    /// One entity per object, in the order of the file.
+    pub objects: Vec<Entity>,
+}
+
+impl TiledMap {
+    /// Loads a Tiled map saved as `.tmx` or `.tmj` and spawns it into `world`.
+    ///
+    /// Tile layer data may be stored as CSV, as XML elements, or in base64 optionally
+    /// compressed with zlib or gzip. Tilesets may be embedded or external `.tsx` and
+    /// `.tsj` files, and must be cut from a single image. Group layers are flattened,
+    /// combining their offset, opacity and visibility with their children's. Image
+    /// layers are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the map file. Tilesets and images are resolved relative to the
+    ///   file referring to them.
+    /// * `world` - The world to spawn the map into.
+    /// * `assets` - The asset server tileset images are loaded through, so maps sharing
+    ///   an image share its texture.
+    ///
+    /// # Returns
+    ///
+    /// The spawned entities, or an `Err(String)` if the map cannot be read, is infinite,
+    /// uses zstd compression or image collection tilesets, or an image cannot be loaded.
+    pub fn load(path: impl AsRef<Path>, world: &mut World, assets: &mut AssetServer) -> Result<TiledMap, String> {
+        let path = path.as_ref();
+        let name = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        read_map(path)
+            .and_then(|map| spawn(map, &name, world, assets))
+            .map_err(|err| format!("Cannot load Tiled map {}: {err}", path.display()))
+    }
+}
+
+/// A map as read from a file, before its tilesets are uploaded.
+pub(super) struct MapData {
+    pub orientation: Orientation,
+    pub width: u32,       // Columns of cells
+    pub height: u32,      // Rows of cells
+    pub tile_width: u32,  // Width of a cell in pixels
+    pub tile_height: u32, // Height of a cell in pixels
+    pub infinite: bool,   // Whether layers are stored in chunks without fixed bounds
+    pub tilesets: Vec<TilesetData>,
+    pub layers: Vec<LayerData>,
+    pub properties: TiledProperties,
+}
+
+/// A tileset as read from a file, before its image is uploaded.
+pub(super) struct TilesetData {
+    pub first_gid: u32,         // Global id of the first tile
+    pub name: String,           // Tileset name
+    pub tile_width: u32,        // Width of a tile in pixels
+    pub tile_height: u32,       // Height of a tile in pixels
+    pub spacing: u32,           // Pixels between adjacent tiles
+    pub margin: u32,            // Pixels around the edge of the image
+    pub columns: u32,           // Tiles per row, `0` when unknown
+    pub tile_count: u32,        // Tiles in the image, `0` when unknown
+    pub image: Option<PathBuf>, // The image, or `None` for image collections
+    pub offset: Vec2,           // Drawing offset, right and down
+    pub animations: Vec<(u32, Vec<TileFrame>)>,
+}
+
+/// What a layer holds.
+pub(super) enum LayerKind {
+    /// Global tile ids with flip flags, row by row.
+    Tiles(Vec<u32>),
+    Objects(Vec<ObjectData>),
+}
+
+/// A tile or object layer, with the attributes of its groups already applied.
+pub(super) struct LayerData {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: Vec2, // Pixels, right and down
+    pub properties: TiledProperties,
+    pub kind: LayerKind,
+}
+
+/// The visibility, opacity and offset of the group layers around a layer, combined.
+#[derive(Clone, Copy)]
+pub(super) struct LayerGroup {
+    pub visible: bool,
+    pub opacity: f32,
+    pub offset: Vec2, // Pixels, right and down
+}
+
+impl LayerGroup {
+    /// Returns the attributes of layers outside any group.
+    pub fn root() -> LayerGroup {
+        LayerGroup { visible: true, opacity: 1.0, offset: Vec2::zeros() }
+    }
+
+    /// Combines a layer's own attributes with its groups'.
+    pub fn nest(&self, visible: bool, opacity: f32, offset: Vec2) -> LayerGroup {
+        LayerGroup {
+            visible: self.visible && visible,
+            opacity: self.opacity * opacity,
+            offset: self.offset + offset,
+        }
+    }
+}
+
+/// An object as read from a file, in Tiled's pixel coordinates.
+pub(super) struct ObjectData {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub position: Vec2, // Origin, right and down from the top left of the map
+    pub size: Vec2,     // Width and height
+    pub rotation: f32,  // Degrees clockwise
+    pub gid: u32,       // Global tile id with flip flags, `0` unless a tile object
+    pub visible: bool,
+    pub shape: ObjectShape, // Points right and down from the origin
+    pub properties: TiledProperties,
+}
+
+/// Reads a map file, telling TMX and TMJ apart by their contents.
+fn read_map(path: &Path) -> Result<MapData, String> {
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    if text.trim_start().starts_with('<') {
+        tmx::parse_map(&text, directory)
+    } else {
+        tmj::parse_map(&text, directory)
+    }
+}
+
+/// Reads an external `.tsx` or `.tsj` tileset, telling them apart by their contents.
+pub(super) fn read_tileset(path: &Path, first_gid: u32) -> Result<TilesetData, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("Cannot read tileset {}: {err}", path.display()))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    let tileset = if text.trim_start().starts_with('<') {
+        tmx::parse_tileset_file(&text, directory, first_gid)
+    } else {
+        tmj::parse_tileset_file(&text, directory, first_gid)
+    };
+    tileset.map_err(|err| format!("Invalid tileset {}: {err}", path.display()))
+}
+
+/// Reads the orientation attributes shared by TMX and TMJ maps.
+pub(super) fn parse_orientation(
+    orientation: &str,
+    stagger_axis: Option<&str>,
+    stagger_index: Option<&str>,
+    side_length: u32,
+) -> Result<Orientation, String> {
+    let axis = match stagger_axis {
+        Some("x") => StaggerAxis::X,
+        Some("y") | None => StaggerAxis::Y,
+        Some(axis) => return Err(format!("Unknown stagger axis {axis:?}")),
+    };
+    let index = match stagger_index {
+        Some("odd") | None => StaggerIndex::Odd,
+        Some("even") => StaggerIndex::Even,
+        Some(index) => return Err(format!("Unknown stagger index {index:?}")),
+    };
+    match orientation {
+        "orthogonal" => Ok(Orientation::Orthogonal),
+        "isometric" => Ok(Orientation::Isometric),
+        "staggered" => Ok(Orientation::Staggered { axis, index }),
+        "hexagonal" => Ok(Orientation::Hexagonal { axis, index, side_length }),
+        _ => Err(format!("Unknown map orientation {orientation:?}")),
+    }
+}
+
+/// Decodes the global tile ids of a tile layer stored as CSV or base64 text.
+///
+/// # Arguments
+///
+/// * `encoding` - `"csv"` or `"base64"`.
+/// * `compression` - `None`, `"zlib"` or `"gzip"` for base64 data.
+/// * `text` - The encoded data.
+pub(super) fn decode_tile_data(encoding: &str, compression: Option<&str>, text: &str) -> Result<Vec<u32>, String> {
+    match encoding {
+        "csv" => text
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse().map_err(|err| format!("Invalid tile id {value:?}: {err}")))
+            .collect(),
+        "base64" => {
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .map_err(|err| format!("Invalid base64 tile data: {err}"))?;
+            let mut data = Vec::new();
+            let inflated = match compression {
+                None | Some("") => {
+                    data = bytes;
+                    Ok(0)
+                }
+                Some("zlib") => ZlibDecoder::new(&bytes[..]).read_to_end(&mut data),
+                Some("gzip") => GzDecoder::new(&bytes[..]).read_to_end(&mut data),
+                Some(compression) => return Err(format!("Unsupported tile data compression {compression:?}")),
+            };
+            inflated.map_err(|err| format!("Invalid compressed tile data: {err}"))?;
+            if data.len() % 4 != 0 {
+                return Err(format!("Tile data of {} bytes isn't made of 4-byte tile ids", data.len()));
+            }
+            Ok(data.chunks_exact(4).map(|id| u32::from_le_bytes([id[0], id[1], id[2], id[3]])).collect())
+        }
+        _ => Err(format!("Unknown tile data encoding {encoding:?}")),
+    }
+}
+
+/// Converts a property value written as text, following its Tiled type. Class
+/// properties, which nest other properties, return `None`.
+pub(super) fn parse_property(kind: &str, value: &str) -> Result<Option<TiledProperty>, String> {
+    let invalid = |err: &dyn std::fmt::Display| format!("Invalid {kind} property {value:?}: {err}");
+    let property = match kind {
+        "bool" => TiledProperty::Bool(value.parse().map_err(|err| invalid(&err))?),
+        "int" | "object" => TiledProperty::Int(value.parse().map_err(|err| invalid(&err))?),
+        "float" => TiledProperty::Float(value.parse().map_err(|err| invalid(&err))?),
+        "class" => return Ok(None),
+        _ => TiledProperty::String(value.to_string()),
+    };
+    Ok(Some(property))
+}
+
+/// Loads the tilesets and tile layers into a `Tilemap` and spawns the map's entities.
+fn spawn(mut map: MapData, name: &str, world: &mut World, assets: &mut AssetServer) -> Result<TiledMap, String> {
+    if map.infinite {
+        return Err("Infinite maps aren't supported".to_string());
+    }
+    let cell_count = (map.width as usize)
+        .checked_mul(map.height as usize)
+        .ok_or_else(|| format!("The map is too large ({}x{} cells)", map.width, map.height))?;
+    let mut tilemap = Tilemap::new(map.orientation, map.width, map.height, map.tile_width, map.tile_height);
+    for tileset in &map.tilesets {
+        tilemap.add_tileset(load_tileset(tileset, assets)?);
+    }
+    let first_gids: Vec<u32> = map.tilesets.iter().map(|tileset| tileset.first_gid).collect();
+    let tile = |gid: u32| resolve_gid(&first_gids, gid);
+
+    let root = world.spawn();
+    world.insert(root, Name(name.to_string()));
+    world.insert(root, Transform::new());
+    insert_properties(world, root, std::mem::take(&mut map.properties));
+
+    let (mut object_layers, mut objects) = (Vec::new(), Vec::new());
+    for layer in std::mem::take(&mut map.layers) {
+        match layer.kind {
+            LayerKind::Tiles(gids) => {
+                if gids.len() != cell_count {
+                    world.despawn(root);
+                    return Err(format!(
+                        "Layer {:?} has {} tiles instead of {}x{}",
+                        layer.name,
+                        gids.len(),
+                        map.width,
+                        map.height
+                    ));
+                }
+                let mut tile_layer = TileLayer::new(layer.name, map.width, map.height);
+                tile_layer.visible = layer.visible;
+                tile_layer.opacity = layer.opacity;
+                tile_layer.offset = layer.offset;
+                for (index, gid) in gids.into_iter().enumerate() {
+                    let (x, y) = (index as u32 % map.width, index as u32 / map.width);
+                    tile_layer.set_tile(x, y, tile(gid));
+                }
+                tilemap.add_layer(tile_layer);
+            }
+            LayerKind::Objects(layer_objects) => {
+                let entity = world.spawn();
+                world.insert(entity, Name(layer.name));
+                world.insert(entity, Transform::from_translation(Vec3::new(layer.offset.x, -layer.offset.y, 0.0)));
+                insert_properties(world, entity, layer.properties);
+                world.set_parent(entity, root);
+                object_layers.push(entity);
+
+                for object in layer_objects {
+                    let child = spawn_object(world, object, &map, layer.visible, tile);
+                    world.set_parent(child, entity);
+                    objects.push(child);
+                }
+            }
+        }
+    }
+    world.insert(root, tilemap);
+    Ok(TiledMap { root, object_layers, objects })
+}
+
+/// Spawns an object with its name, transform, `TiledObject` and properties.
+fn spawn_object(
+    world: &mut World,
+    object: ObjectData,
+    map: &MapData,
+    layer_visible: bool,
+    tile: impl Fn(u32) -> Option<Tile>,
+) -> Entity {
+    // Isometric maps place objects along the diagonal axes of the cells.
+    let to_map = |point: Vec2, origin: Vec2| match map.orientation {
+        Orientation::Isometric => {
+            let (tile_width, tile_height) = (map.tile_width as f32, map.tile_height as f32);
+            let x = (point.x - point.y) / tile_height * tile_width / 2.0 + origin.x;
+            Vec2::new(x, -(point.x + point.y) / 2.0)
+        }
+        _ => Vec2::new(point.x, -point.y),
+    };
+    let map_origin = match map.orientation {
+        Orientation::Isometric => Vec2::new(map.height as f32 * map.tile_width as f32 / 2.0, 0.0),
+        _ => Vec2::zeros(),
+    };
+    let points = |points: Vec<Vec2>| points.into_iter().map(|point| to_map(point, Vec2::zeros())).collect();
+    let shape = match object.shape {
+        ObjectShape::Polygon(outline) => ObjectShape::Polygon(points(outline)),
+        ObjectShape::Polyline(outline) => ObjectShape::Polyline(points(outline)),
+        shape => shape,
+    };
+
+    let position = to_map(object.position, map_origin);
+    let rotation = glm::quat_angle_axis(-object.rotation.to_radians(), &Vec3::z());
+    let entity = world.spawn();
+    let name = if object.name.is_empty() { format!("object{}", object.id) } else { object.name };
+    world.insert(entity, Name(name));
+    world.insert(entity, Transform::from_translation(Vec3::new(position.x, position.y, 0.0)).with_rotation(rotation));
+    world.insert(
+        entity,
+        TiledObject {
+            id: object.id,
+            class: object.class,
+            shape,
+            size: object.size,
+            tile: tile(object.gid),
+            visible: object.visible && layer_visible,
+        },
+    );
+    insert_properties(world, entity, object.properties);
+    entity
+}
+
+/// Inserts `properties` on `entity` unless there are none.
+fn insert_properties(world: &mut World, entity: Entity, properties: TiledProperties) {
+    if !properties.0.is_empty() {
+        world.insert(entity, properties);
+    }
+}
+
+/// Loads a tileset's image through `assets`. `Tilemap` samples it without filtering
+/// itself, so the texture can be shared with other users.
+fn load_tileset(data: &TilesetData, assets: &mut AssetServer) -> Result<Tileset, String> {
+    let Some(image) = &data.image else {
+        return Err(format!("Tileset {:?} is an image collection, which isn't supported", data.name));
+    };
+    let texture = assets.load_texture(image, true)?;
+
+    let mut tileset = Tileset::new(texture, data.tile_width.max(1), data.tile_height.max(1))
+        .with_spacing(data.spacing, data.margin)
+        .with_name(data.name.clone())
+        .with_offset(data.offset);
+    if data.columns > 0 {
+        tileset.columns = data.columns;
+    }
+    if data.tile_count > 0 {
+        tileset.tile_count = data.tile_count;
+    }
+    tileset.animations.extend(data.animations.iter().cloned());
+    Ok(tileset)
+}
+
+/// Maps a global tile id with flip flags to a tile of the tileset it falls in.
+///
+/// # Arguments
+///
+/// * `first_gids` - Global id of the first tile of each tileset, in increasing order.
+/// * `gid` - The global tile id, `0` for no tile.
+fn resolve_gid(first_gids: &[u32], gid: u32) -> Option<Tile> {
+    let id = gid & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | ROTATED_HEXAGONAL_120);
+    if id == 0 {
+        return None;
+    }
+    let tileset = first_gids.iter().rposition(|first_gid| *first_gid <= id)?;
+    let tile = Tile::new(tileset, id - first_gids[tileset]);
+    Some(tile.with_flip(
+        gid & FLIPPED_HORIZONTALLY != 0,
+        gid & FLIPPED_VERTICALLY != 0,
+        gid & FLIPPED_DIAGONALLY != 0,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a TMX map and spawns it into `world`.
+    fn spawn_tmx(text: &str, world: &mut World) -> Result<TiledMap, String> {
+        spawn(tmx::parse_map(text, Path::new(""))?, "map", world, &mut AssetServer::new())
+    }
+
+    fn error(text: &str, world: &mut World) -> String {
+        match spawn_tmx(text, world) {
+            Ok(_) => panic!("{text} was spawned"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn spawns_tile_and_object_layers() {
+        let mut world = World::new();
+        let text = r#"<map width="2" height="1" tilewidth="16" tileheight="16">
+            <objectgroup name="spawns"><object id="3" x="8" y="4"><point/></object></objectgroup>
+            <layer name="ground"><data encoding="csv">0,0</data></layer>
+        </map>"#;
+        let map = spawn_tmx(text, &mut world).unwrap();
+        assert_eq!(map.root, world.find_by_name("map").unwrap());
+        assert_eq!(world.get::<Tilemap>(map.root).unwrap().get_layers().len(), 1);
+        assert_eq!(world.get_children(map.root), map.object_layers);
+        assert_eq!(world.get::<TiledObject>(map.objects[0]).unwrap().shape, ObjectShape::Point);
+    }
+
+    #[test]
+    fn layers_must_fill_the_map() {
+        let mut world = World::new();
+        // The object layer spawned before the short tile layer is removed with the root.
+        let text = r#"<map width="2" height="2" tilewidth="16" tileheight="16">
+            <objectgroup name="spawns"><object id="1"/></objectgroup>
+            <layer name="ground"><data encoding="csv">1,2,3</data></layer>
+        </map>"#;
+        assert_eq!(error(text, &mut world), r#"Layer "ground" has 3 tiles instead of 2x2"#);
+        assert!(world.is_empty());
+    }
+
+    #[test]
+    fn huge_maps_are_errors_rather_than_allocations() {
+        let mut world = World::new();
+        let text = r#"<map width="4294967295" height="4294967295" tilewidth="16" tileheight="16">
+            <layer name="ground"><data encoding="csv">1</data></layer>
+        </map>"#;
+        assert_eq!(error(text, &mut world), r#"Layer "ground" has 1 tiles instead of 4294967295x4294967295"#);
+        assert!(world.is_empty());
+
+        let infinite = r#"<map width="2" height="2" tilewidth="16" tileheight="16" infinite="1"/>"#;
+        assert_eq!(error(infinite, &mut world), "Infinite maps aren't supported");
+    }
+
+    #[test]
+    fn tilesets_without_an_image_are_errors() {
+        let text = r#"<map width="1" height="1" tilewidth="16" tileheight="16">
+            <tileset firstgid="1" name="props" tilewidth="16" tileheight="16"/>
+        </map>"#;
+        let err = error(text, &mut World::new());
+        assert_eq!(err, r#"Tileset "props" is an image collection, which isn't supported"#);
+    }
+
+    #[test]
+    fn global_ids_resolve_to_the_last_tileset_starting_before_them() {
+        let first_gids = [1, 5, 9];
+        assert_eq!(resolve_gid(&first_gids, 0), None);
+        assert_eq!(resolve_gid(&first_gids, 4), Some(Tile::new(0, 3)));
+        assert_eq!(resolve_gid(&first_gids, 5), Some(Tile::new(1, 0)));
+        let flipped = resolve_gid(&first_gids, 12 | FLIPPED_HORIZONTALLY | FLIPPED_DIAGONALLY);
+        assert_eq!(flipped, Some(Tile::new(2, 3).with_flip(true, false, true)));
+        assert_eq!(resolve_gid(&[], 3), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use nalgebra_glm::{Vec2, Vec4};
+
+use crate::core::assets::Handle;
+use crate::opengl::Texture;
+
+/// How many animated tiles of a tileset can be drawn animated. Further animations show
+/// their first frame.
+pub const MAX_TILE_ANIMATIONS: usize = 64;
+
+/// One frame of an animated tile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileFrame {
+    /// Index of the tile shown, in the same tileset.
+    pub tile: u32,
+    /// How long the frame is shown, in seconds.
+    pub duration: f32,
+}
+
+/// A texture cut into a grid of equally sized tiles, numbered row by row from the top left.
+///
+/// Tiles larger than the map's cells stick out of the top and right of their cell, as
+/// they do in Tiled.
+#[derive(Clone)]
+pub struct Tileset {
+    /// Name shown in editors, such as the Tiled tileset name.
+    pub name: String,
+    /// The image the tiles are cut from.
+    pub texture: Handle<Texture>,
+    pub tile_width: u32,  // Width of a tile in texels
+    pub tile_height: u32, // Height of a tile in texels
+    pub spacing: u32,     // Texels between adjacent tiles
+    pub margin: u32,      // Texels around the edge of the texture
+    pub columns: u32,     // Tiles per row of the texture
+    pub tile_count: u32,  // Tiles in the texture
+    /// Offset of every tile from its cell, in texels to the right and down.
+    pub offset: Vec2,
+    /// Frames of each animated tile, by tile index.
+    pub animations: BTreeMap<u32, Vec<TileFrame>>,
+}
+
+impl Tileset {
+    /// Creates a tileset of tiles packed edge to edge, filling the whole texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `texture` - The image the tiles are cut from, already loaded.
+    /// * `tile_width` - Width of a tile in texels.
+    /// * `tile_height` - Height of a tile in texels.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `tile_width` or `tile_height` is zero, or if `texture`
+    /// hasn't finished loading.
+    pub fn new(texture: Handle<Texture>, tile_width: u32, tile_height: u32) -> Tileset {
+        assert!(tile_width > 0 && tile_height > 0, "tiles must be at least one texel wide and high");
+        let mut tileset = Tileset {
+            name: String::new(),
+            texture,
+            tile_width,
+            tile_height,
+            spacing: 0,
+            margin: 0,
+            columns: 0,
+            tile_count: 0,
+            offset: Vec2::zeros(),
+            animations: BTreeMap::new(),
+        };
+        tileset.fit_grid();
+        tileset
+    }
+
+    /// Returns a copy with space between and around the tiles, recounting the tiles that
+    /// fit in the texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `spacing` - Texels between adjacent tiles.
+    /// * `margin` - Texels around the edge of the texture.
+    pub fn with_spacing(mut self, spacing: u32, margin: u32) -> Tileset {
+        self.spacing = spacing;
+        self.margin = margin;
+        self.fit_grid();
+        self
+    }
+
+    /// Returns a copy with a new name.
+    pub fn with_name(self, name: impl Into<String>) -> Tileset {
+        Tileset { name: name.into(), ..self }
+    }
+
+    /// Returns a copy whose tiles are drawn offset from their cells, in texels to the
+    /// right and down.
+    pub fn with_offset(self, offset: Vec2) -> Tileset {
+        Tileset { offset, ..self }
+    }
+
+    /// Returns a copy where a tile cycles through `frames`, looping forever.
+    pub fn with_animation(mut self, tile: u32, frames: Vec<TileFrame>) -> Tileset {
+        self.animations.insert(tile, frames);
+        self
+    }
+
+    /// Returns the texture coordinates of a tile as `(u, v, width, height)`, with `v`
+    /// growing down the image.
+    pub fn get_tile_rect(&self, tile: u32) -> Vec4 {
+        let columns = self.columns.max(1);
+        let (column, row) = (tile % columns, tile / columns);
+        let x = self.margin + column * (self.tile_width + self.spacing);
+        let y = self.margin + row * (self.tile_height + self.spacing);
+        let texture = self.texture.get();
+        let (width, height) = (texture.get_width() as f32, texture.get_height() as f32);
+        Vec4::new(
+            x as f32 / width,
+            y as f32 / height,
+            self.tile_width as f32 / width,
+            self.tile_height as f32 / height,
+        )
+    }
+
+    /// Returns the tile an animated tile shows `time` seconds into its animation, or the
+    /// tile itself if it isn't animated.
+    pub fn get_animation_frame(&self, tile: u32, time: f32) -> u32 {
+        let Some(frames) = self.animations.get(&tile) else { return tile };
+        let total: f32 = frames.iter().map(|frame| frame.duration).sum();
+        if total <= 0.0 {
+            return frames.first().map_or(tile, |frame| frame.tile);
+        }
+        let mut time = time.rem_euclid(total);
+        for frame in frames {
+            if time < frame.duration {
+                return frame.tile;
+            }
+            time -= frame.duration;
+        }
+        frames.last().map_or(tile, |frame| frame.tile)
+    }
+
+    /// Returns the slot of an animated tile in `animation_rects`, or `None` if it isn't
+    /// animated or doesn't fit in `MAX_TILE_ANIMATIONS`.
+    pub(super) fn animation_slot(&self, tile: u32) -> Option<usize> {
+        self.animations.keys().take(MAX_TILE_ANIMATIONS).position(|id| *id == tile)
+    }
+
+    /// Returns the texture coordinates of the current frame of each animated tile, by slot.
+    pub(super) fn animation_rects(&self, time: f32) -> Vec<Vec4> {
+        self.animations
+            .keys()
+            .take(MAX_TILE_ANIMATIONS)
+            .map(|tile| self.get_tile_rect(self.get_animation_frame(*tile, time)))
+            .collect()
+    }
+
+    /// Counts the columns and tiles that fit in the texture.
+    fn fit_grid(&mut self) {
+        let fit = |size: u32, tile: u32| (size.saturating_sub(2 * self.margin) + self.spacing) / (tile + self.spacing);
+        let (width, height) = {
+            let texture = self.texture.get();
+            (texture.get_width(), texture.get_height())
+        };
+        self.columns = fit(width, self.tile_width);
+        self.tile_count = self.columns * fit(height, self.tile_height);
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use nalgebra_glm::Vec2;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::tiled::{decode_tile_data, parse_orientation, parse_property, read_tileset};
+use super::tiled::{LayerData, LayerGroup, LayerKind, MapData, ObjectData, TilesetData};
+use super::{ObjectShape, TileFrame, TiledProperties};
+
+/// Reads a map from the text of a `.tmj` file.
+///
+/// # Arguments
+///
+/// * `text` - The JSON document.
+/// * `directory` - Directory of the file, which external tilesets are relative to.
+pub(super) fn parse_map(text: &str, directory: &Path) -> Result<MapData, String> {
+    let map: JsonMap = serde_json::from_str(text).map_err(|err| format!("Invalid TMJ file: {err}"))?;
+    let orientation = parse_orientation(
+        &map.orientation,
+        map.staggeraxis.as_deref(),
+        map.staggerindex.as_deref(),
+        map.hexsidelength,
+    )?;
+    let mut tilesets = Vec::new();
+    for tileset in map.tilesets {
+        let first_gid = tileset.firstgid;
+        let tileset = match tileset.source.clone() {
+            Some(source) => read_tileset(&directory.join(source), first_gid)?,
+            None => convert_tileset(tileset, directory, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+    let mut layers = Vec::new();
+    convert_layers(map.layers, &LayerGroup::root(), &mut layers)?;
+
+    Ok(MapData {
+        orientation,
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        infinite: map.infinite,
+        tilesets,
+        layers,
+        properties: convert_properties(map.properties)?,
+    })
+}
+
+/// Reads a tileset from the text of a `.tsj` file, see `parse_map`.
+pub(super) fn parse_tileset_file(text: &str, directory: &Path, first_gid: u32) -> Result<TilesetData, String> {
+    let tileset: JsonTileset = serde_json::from_str(text).map_err(|err| format!("Invalid TSJ file: {err}"))?;
+    convert_tileset(tileset, directory, first_gid)
+}
+
+/// Converts a tileset, resolving its image relative to `directory`.
+fn convert_tileset(tileset: JsonTileset, directory: &Path, first_gid: u32) -> Result<TilesetData, String> {
+    let animations = tileset
+        .tiles
+        .into_iter()
+        .filter(|tile| !tile.animation.is_empty())
+        .map(|tile| {
+            let frames = tile
+                .animation
+                .iter()
+                .map(|frame| TileFrame { tile: frame.tileid, duration: frame.duration as f32 / 1000.0 })
+                .collect();
+            (tile.id, frames)
+        })
+        .collect();
+    Ok(TilesetData {
+        first_gid,
+        name: tileset.name,
+        tile_width: tileset.tilewidth,
+        tile_height: tileset.tileheight,
+        spacing: tileset.spacing,
+        margin: tileset.margin,
+        columns: tileset.columns,
+        tile_count: tileset.tilecount,
+        image: tileset.image.map(|image| directory.join(image)),
+        offset: tileset.tileoffset.map_or_else(Vec2::zeros, |offset| Vec2::new(offset.x, offset.y)),
+        animations,
+    })
+}
+
+/// Converts the tile, object and group layers of a map or group, in order.
+fn convert_layers(
+    json_layers: Vec<JsonLayer>,
+    group: &LayerGroup,
+    layers: &mut Vec<LayerData>,
+) -> Result<(), String> {
+    for layer in json_layers {
+        let nested = group.nest(layer.visible, layer.opacity, Vec2::new(layer.offsetx, layer.offsety));
+        let kind = match layer.kind.as_str() {
+            "tilelayer" => LayerKind::Tiles(convert_tile_data(&layer)?),
+            "objectgroup" => {
+                LayerKind::Objects(layer.objects.into_iter().map(convert_object).collect::<Result<_, String>>()?)
+            }
+            "group" => {
+                convert_layers(layer.layers, &nested, layers)?;
+                continue;
+            }
+            _ => continue,
+        };
+        layers.push(LayerData {
+            name: layer.name,
+            visible: nested.visible,
+            opacity: nested.opacity,
+            offset: nested.offset,
+            properties: convert_properties(layer.properties)?,
+            kind,
+        });
+    }
+    Ok(())
+}
+
+/// Reads the global tile ids of a tile layer, stored as an array or as base64 text.
+fn convert_tile_data(layer: &JsonLayer) -> Result<Vec<u32>, String> {
+    match &layer.data {
+        Some(Value::String(text)) => {
+            let encoding = layer.encoding.as_deref().unwrap_or("base64");
+            decode_tile_data(encoding, layer.compression.as_deref(), text)
+        }
+        Some(Value::Array(ids)) => ids
+            .iter()
+            .map(|id| {
+                let gid = id.as_u64().and_then(|id| u32::try_from(id).ok());
+                gid.ok_or_else(|| format!("Invalid tile id {id}"))
+            })
+            .collect(),
+        Some(data) => Err(format!("Invalid tile data in layer {:?}: {data}", layer.name)),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Converts an object.
+fn convert_object(object: JsonObject) -> Result<ObjectData, String> {
+    let points = |points: Vec<JsonPoint>| points.into_iter().map(|point| Vec2::new(point.x, point.y)).collect();
+    let shape = if object.ellipse {
+        ObjectShape::Ellipse
+    } else if object.point {
+        ObjectShape::Point
+    } else if let Some(polygon) = object.polygon {
+        ObjectShape::Polygon(points(polygon))
+    } else if let Some(polyline) = object.polyline {
+        ObjectShape::Polyline(points(polyline))
+    } else if let Some(text) = object.text {
+        ObjectShape::Text(text.text)
+    } else {
+        ObjectShape::Rectangle
+    };
+
+    // Tiled 1.9 wrote the object's `type` as `class`, later versions went back to `type`.
+    let class = object.class.or(object.kind).unwrap_or_default();
+    Ok(ObjectData {
+        id: object.id,
+        name: object.name,
+        class,
+        position: Vec2::new(object.x, object.y),
+        size: Vec2::new(object.width, object.height),
+        rotation: object.rotation,
+        gid: object.gid,
+        visible: object.visible,
+        shape,
+        properties: convert_properties(object.properties)?,
+    })
+}
+
+/// Converts custom properties, whose values are JSON values of their Tiled type.
+fn convert_properties(json_properties: Vec<JsonProperty>) -> Result<TiledProperties, String> {
+    let mut properties = HashMap::new();
+    for property in json_properties {
+        let value = match &property.value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        if let Some(value) = parse_property(&property.kind, &value)? {
+            properties.insert(property.name, value);
+        }
+    }
+    Ok(TiledProperties(properties))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_string_type() -> String {
+    "string".to_string()
+}
+
+#[derive(Deserialize)]
+struct JsonMap {
+    #[serde(default)]
+    orientation: String,
+    width: u32,
+    height: u32,
+    tilewidth: u32,
+    tileheight: u32,
+    #[serde(default)]
+    hexsidelength: u32,
+    staggeraxis: Option<String>,
+    staggerindex: Option<String>,
+    #[serde(default)]
+    infinite: bool,
+    #[serde(default)]
+    tilesets: Vec<JsonTileset>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct JsonTileset {
+    firstgid: u32,
+    source: Option<String>, // External tileset, relative to the map
+    name: String,
+    tilewidth: u32,
+    tileheight: u32,
+    spacing: u32,
+    margin: u32,
+    columns: u32,
+    tilecount: u32,
+    image: Option<String>, // Relative to the file holding the tileset
+    tileoffset: Option<JsonPoint>,
+    tiles: Vec<JsonTile>,
+}
+
+#[derive(Deserialize)]
+struct JsonTile {
+    id: u32,
+    #[serde(default)]
+    animation: Vec<JsonFrame>,
+}
+
+#[derive(Deserialize)]
+struct JsonFrame {
+    tileid: u32,
+    duration: u32, // Milliseconds
+}
+
+#[derive(Deserialize)]
+struct JsonLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default = "default_opacity")]
+    opacity: f32,
+    #[serde(default)]
+    offsetx: f32,
+    #[serde(default)]
+    offsety: f32,
+    data: Option<Value>, // Array of ids, or base64 text
+    encoding: Option<String>,
+    compression: Option<String>,
+    #[serde(default)]
+    objects: Vec<JsonObject>,
+    #[serde(default)]
+    layers: Vec<JsonLayer>, // Children of group layers
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonObject {
+    #[serde(default)]
+    id: u32,
+    #[serde(default)]
+    name: String,
+    class: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    x: f32,
+    #[serde(default)]
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default)]
+    gid: u32,
+    #[serde(default = "default_true")]
+    visible: bool,
+    #[serde(default)]
+    ellipse: bool,
+    #[serde(default)]
+    point: bool,
+    polygon: Option<Vec<JsonPoint>>,
+    polyline: Option<Vec<JsonPoint>>,
+    text: Option<JsonText>,
+    #[serde(default)]
+    properties: Vec<JsonProperty>,
+}
+
+#[derive(Deserialize)]
+struct JsonPoint {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct JsonText {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct JsonProperty {
+    name: String,
+    #[serde(rename = "type", default = "default_string_type")]
+    kind: String,
+    #[serde(default)]
+    value: Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a 2x2 orthogonal map with extra members, such as its layers.
+    fn map(members: &str) -> String {
+        let separator = if members.is_empty() { "" } else { ", " };
+        let size = r#""width": 2, "height": 2, "tilewidth": 16, "tileheight": 16"#;
+        format!(r#"{{"orientation": "orthogonal", {size}{separator}{members}}}"#)
+    }
+
+    /// Returns the `layers` member of a map with one tile layer holding `data`.
+    fn layer(data: &str) -> String {
+        format!(r#""layers": [{{"type": "tilelayer", "name": "ground", "data": {data}}}]"#)
+    }
+
+    fn error(text: &str) -> String {
+        match parse_map(text, Path::new("")) {
+            Ok(_) => panic!("{text} was read"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn reads_tile_data_as_arrays_and_base64() {
+        for data in ["[1, 2, 3, 2147483652]", r#""AQAAAAIAAAADAAAABAAAgA==""#] {
+            let map = parse_map(&map(&layer(data)), Path::new("")).unwrap();
+            let LayerKind::Tiles(gids) = &map.layers[0].kind else { panic!("not a tile layer") };
+            assert_eq!(*gids, [1, 2, 3, 0x8000_0004]);
+        }
+    }
+
+    #[test]
+    fn malformed_maps_are_errors() {
+        let invalid = [
+            ("{".to_string(), "Invalid TMJ file: "),
+            (r#"{"height": 2, "tilewidth": 16, "tileheight": 16}"#.to_string(), "Invalid TMJ file: missing field"),
+            (map(r#""layers": {}"#), "Invalid TMJ file: "),
+            (map(r#""staggeraxis": "z""#), r#"Unknown stagger axis "z""#),
+            (map(r#""layers": [{"name": "untyped"}]"#), "Invalid TMJ file: missing field `type`"),
+            (map(&layer(r#"{"gids": []}"#)), r#"Invalid tile data in layer "ground": "#),
+            (map(&layer(r#"[1, "2", 3, 4]"#)), r#"Invalid tile id "2""#),
+            (map(&layer("[1, 2, 3.5, 4]")), "Invalid tile id 3.5"),
+            (map(&layer(r#""AQAA""#)), "Tile data of 3 bytes isn't made of 4-byte tile ids"),
+            (map(&layer(r#""not base64""#)), "Invalid base64 tile data: "),
+            (map(r#""properties": [{"name": "n", "type": "bool", "value": "yes"}]"#), r#"Invalid bool property "yes""#),
+        ];
+        for (text, expected) in invalid {
+            let err = error(&text);
+            assert!(err.starts_with(expected), "{text}: {err}");
+        }
+    }
+
+    #[test]
+    fn values_overflowing_their_type_are_errors() {
+        let size = |width: &str| format!(r#"{{"width": {width}, "height": 2, "tilewidth": 16, "tileheight": 16}}"#);
+        let invalid = [
+            (size("4294967296"), "Invalid TMJ file"),
+            (size("-2"), "Invalid TMJ file"),
+            (map(&layer("[1, 2, 3, 4294967296]")), "Invalid tile id 4294967296"),
+            (map(&layer("[1, 2, 3, -1]")), "Invalid tile id -1"),
+            (map(r#""tilesets": [{"firstgid": 4294967296}]"#), "Invalid TMJ file"),
+            (map(r#""properties": [{"name": "n", "type": "int", "value": 9223372036854775808}]"#), "Invalid int"),
+        ];
+        for (text, expected) in invalid {
+            let err = error(&text);
+            assert!(err.starts_with(expected), "{text}: {err}");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use nalgebra_glm::Vec2;
+use roxmltree::{Document, Node};
+
+use super::tiled::{decode_tile_data, parse_orientation, parse_property, read_tileset};
+use super::tiled::{LayerData, LayerGroup, LayerKind, MapData, ObjectData, TilesetData};
+use super::{ObjectShape, TileFrame, TiledProperties};
+
+/// Reads a map from the text of a `.tmx` file.
+///
+/// # Arguments
+///
+/// * `text` - The XML document.
+/// * `directory` - Directory of the file, which external tilesets are relative to.
+pub(super) fn parse_map(text: &str, directory: &Path) -> Result<MapData, String> {
+    let document = Document::parse(text).map_err(|err| format!("Invalid TMX file: {err}"))?;
+    let map = document.root_element();
+    if map.tag_name().name() != "map" {
+        return Err(format!("Expected a <map> element, found <{}>", map.tag_name().name()));
+    }
+
+    let orientation = parse_orientation(
+        map.attribute("orientation").unwrap_or("orthogonal"),
+        map.attribute("staggeraxis"),
+        map.attribute("staggerindex"),
+        attribute(map, "hexsidelength", 0)?,
+    )?;
+    let mut tilesets = Vec::new();
+    for tileset in elements(map, "tileset") {
+        let first_gid = attribute(tileset, "firstgid", 1)?;
+        let tileset = match tileset.attribute("source") {
+            Some(source) => read_tileset(&directory.join(source), first_gid)?,
+            None => parse_tileset(tileset, directory, first_gid)?,
+        };
+        tilesets.push(tileset);
+    }
+    let mut layers = Vec::new();
+    parse_layers(map, &LayerGroup::root(), &mut layers)?;
+
+    Ok(MapData {
+        orientation,
+        width: attribute(map, "width", 0)?,
+        height: attribute(map, "height", 0)?,
+        tile_width: attribute(map, "tilewidth", 0)?,
+        tile_height: attribute(map, "tileheight", 0)?,
+        infinite: attribute(map, "infinite", 0)? != 0,
+        tilesets,
+        layers,
+        properties: parse_properties(map)?,
+    })
+}
+
+/// Reads a tileset from the text of a `.tsx` file, see `parse_map`.
+pub(super) fn parse_tileset_file(text: &str, directory: &Path, first_gid: u32) -> Result<TilesetData, String> {
+    let document = Document::parse(text).map_err(|err| format!("Invalid TSX file: {err}"))?;
+    parse_tileset(document.root_element(), directory, first_gid)
+}
+
+/// Reads a `<tileset>` element, resolving its image relative to `directory`.
+fn parse_tileset(tileset: Node, directory: &Path, first_gid: u32) -> Result<TilesetData, String> {
+    let offset = match element(tileset, "tileoffset") {
+        Some(offset) => Vec2::new(attribute(offset, "x", 0.0)?, attribute(offset, "y", 0.0)?),
+        None => Vec2::zeros(),
+    };
+    let image = element(tileset, "image").and_then(|image| image.attribute("source"));
+
+    let mut animations = Vec::new();
+    for tile in elements(tileset, "tile") {
+        let Some(animation) = element(tile, "animation") else { continue };
+        let frames = elements(animation, "frame")
+            .map(|frame| {
+                Ok(TileFrame {
+                    tile: attribute(frame, "tileid", 0)?,
+                    duration: attribute::<f32>(frame, "duration", 0.0)? / 1000.0,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        animations.push((attribute(tile, "id", 0)?, frames));
+    }
+
+    Ok(TilesetData {
+        first_gid,
+        name: tileset.attribute("name").unwrap_or_default().to_string(),
+        tile_width: attribute(tileset, "tilewidth", 0)?,
+        tile_height: attribute(tileset, "tileheight", 0)?,
+        spacing: attribute(tileset, "spacing", 0)?,
+        margin: attribute(tileset, "margin", 0)?,
+        columns: attribute(tileset, "columns", 0)?,
+        tile_count: attribute(tileset, "tilecount", 0)?,
+        image: image.map(|source| directory.join(source)),
+        offset,
+        animations,
+    })
+}
+
+/// Reads the tile, object and group layers of a map or group, in order.
+fn parse_layers(parent: Node, group: &LayerGroup, layers: &mut Vec<LayerData>) -> Result<(), String> {
+    for node in parent.children().filter(Node::is_element) {
+        let kind = match node.tag_name().name() {
+            "layer" => LayerKind::Tiles(parse_tile_data(node)?),
+            "objectgroup" => {
+                LayerKind::Objects(elements(node, "object").map(parse_object).collect::<Result<_, String>>()?)
+            }
+            "group" => {
+                parse_layers(node, &nest(group, node)?, layers)?;
+                continue;
+            }
+            _ => continue,
+        };
+        let group = nest(group, node)?;
+        layers.push(LayerData {
+            name: node.attribute("name").unwrap_or_default().to_string(),
+            visible: group.visible,
+            opacity: group.opacity,
+            offset: group.offset,
+            properties: parse_properties(node)?,
+            kind,
+        });
+    }
+    Ok(())
+}
+
+/// Combines a layer element's visibility, opacity and offset with its groups'.
+fn nest(group: &LayerGroup, layer: Node) -> Result<LayerGroup, String> {
+    let offset = Vec2::new(attribute(layer, "offsetx", 0.0)?, attribute(layer, "offsety", 0.0)?);
+    Ok(group.nest(attribute(layer, "visible", 1)? != 0, attribute(layer, "opacity", 1.0)?, offset))
+}
+
+/// Reads the global tile ids of a `<layer>`, stored as text or as `<tile>` elements.
+fn parse_tile_data(layer: Node) -> Result<Vec<u32>, String> {
+    let Some(data) = element(layer, "data") else { return Ok(Vec::new()) };
+    match data.attribute("encoding") {
+        Some(encoding) => decode_tile_data(encoding, data.attribute("compression"), data.text().unwrap_or_default()),
+        None => elements(data, "tile").map(|tile| attribute(tile, "gid", 0)).collect(),
+    }
+}
+
+/// Reads an `<object>` element.
+fn parse_object(object: Node) -> Result<ObjectData, String> {
+    let shape = if element(object, "ellipse").is_some() {
+        ObjectShape::Ellipse
+    } else if element(object, "point").is_some() {
+        ObjectShape::Point
+    } else if let Some(polygon) = element(object, "polygon") {
+        ObjectShape::Polygon(parse_points(polygon)?)
+    } else if let Some(polyline) = element(object, "polyline") {
+        ObjectShape::Polyline(parse_points(polyline)?)
+    } else if let Some(text) = element(object, "text") {
+        ObjectShape::Text(text.text().unwrap_or_default().to_string())
+    } else {
+        ObjectShape::Rectangle
+    };
+
+    // Tiled 1.9 renamed the `type` attribute to `class`.
+    let class = object.attribute("class").or(object.attribute("type")).unwrap_or_default();
+    Ok(ObjectData {
+        id: attribute(object, "id", 0)?,
+        name: object.attribute("name").unwrap_or_default().to_string(),
+        class: class.to_string(),
+        position: Vec2::new(attribute(object, "x", 0.0)?, attribute(object, "y", 0.0)?),
+        size: Vec2::new(attribute(object, "width", 0.0)?, attribute(object, "height", 0.0)?),
+        rotation: attribute(object, "rotation", 0.0)?,
+        gid: attribute(object, "gid", 0)?,
+        visible: attribute(object, "visible", 1)? != 0,
+        shape,
+        properties: parse_properties(object)?,
+    })
+}
+
+/// Reads the `points` attribute of a polygon or polyline, as `x,y` pairs separated by
+/// spaces.
+fn parse_points(node: Node) -> Result<Vec<Vec2>, String> {
+    let points = node.attribute("points").unwrap_or_default();
+    points
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point.split_once(',').ok_or_else(|| format!("Invalid point {point:?}"))?;
+            let coordinate = |value: &str| value.parse().map_err(|err| format!("Invalid point {point:?}: {err}"));
+            Ok(Vec2::new(coordinate(x)?, coordinate(y)?))
+        })
+        .collect()
+}
+
+/// Reads the `<properties>` child of an element.
+fn parse_properties(node: Node) -> Result<TiledProperties, String> {
+    let mut properties = HashMap::new();
+    for property in element(node, "properties").into_iter().flat_map(|properties| elements(properties, "property")) {
+        let name = property.attribute("name").unwrap_or_default();
+        // Multi-line strings are stored as the element's text instead of its value.
+        let value = property.attribute("value").or(property.text()).unwrap_or_default();
+        if let Some(value) = parse_property(property.attribute("type").unwrap_or("string"), value)? {
+            properties.insert(name.to_string(), value);
+        }
+    }
+    Ok(TiledProperties(properties))
+}
+
+/// Returns the child elements with the given tag name.
+fn elements<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |child| child.has_tag_name(name))
+}
+
+/// Returns the first child element with the given tag name.
+fn element<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+/// Parses an attribute, or returns `default` if the element doesn't have it.
+fn attribute<T: FromStr>(node: Node, name: &str, default: T) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    match node.attribute(name) {
+        Some(value) => value.trim().parse().map_err(|err| {
+            format!("Invalid {name} attribute {value:?} on <{}>: {err}", node.tag_name().name())
+        }),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a 2x2 orthogonal map with the given attributes and children.
+    fn map(attributes: &str, children: &str) -> String {
+        format!(r#"<map width="2" height="2" tilewidth="16" tileheight="16" {attributes}>{children}</map>"#)
+    }
+
+    /// Returns a layer whose data has the given attributes and text.
+    fn layer(attributes: &str, data: &str) -> String {
+        format!(r#"<layer name="ground" width="2" height="2"><data {attributes}>{data}</data></layer>"#)
+    }
+
+    fn error(text: &str) -> String {
+        match parse_map(text, Path::new("")) {
+            Ok(_) => panic!("{text} was read"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn reads_tile_data_in_every_encoding() {
+        let layers = [
+            layer(r#"encoding="csv""#, "1,2,\n3,2147483652"),
+            layer("", r#"<tile gid="1"/><tile gid="2"/><tile gid="3"/><tile gid="2147483652"/>"#),
+            layer(r#"encoding="base64""#, "AQAAAAIAAAADAAAABAAAgA=="),
+            layer(r#"encoding="base64" compression="zlib""#, "eJxjZGBgYAJiZiBmYWBoAAAA4ACL"),
+        ];
+        let map = parse_map(&map("", &layers.concat()), Path::new("")).unwrap();
+        assert_eq!(map.layers.len(), 4);
+        for layer in map.layers {
+            let LayerKind::Tiles(gids) = layer.kind else { panic!("not a tile layer") };
+            assert_eq!(gids, [1, 2, 3, 0x8000_0004]);
+        }
+    }
+
+    #[test]
+    fn malformed_maps_are_errors() {
+        let invalid = [
+            ("<map", "Invalid TMX file: "),
+            ("<tileset/>", "Expected a <map> element, found <tileset>"),
+            (&map(r#"orientation="diagonal""#, ""), r#"Unknown map orientation "diagonal""#),
+            (&map(r#"orientation="staggered" staggeraxis="z""#, ""), r#"Unknown stagger axis "z""#),
+            (&map(r#"hexsidelength="wide""#, ""), r#"Invalid hexsidelength attribute "wide" on <map>: "#),
+            (&map("", &layer(r#"encoding="hex""#, "01")), r#"Unknown tile data encoding "hex""#),
+            (&map("", &layer(r#"encoding="csv""#, "1,2,x,4")), r#"Invalid tile id "x": "#),
+            (&map("", &layer(r#"encoding="base64""#, "!!!!")), "Invalid base64 tile data: "),
+            (&map("", &layer(r#"encoding="base64""#, "AQAA")), "Tile data of 3 bytes isn't made of 4-byte tile ids"),
+            (&map("", &layer(r#"encoding="base64" compression="zlib""#, "AQAA")), "Invalid compressed tile data: "),
+            (&map("", &layer(r#"encoding="base64" compression="zstd""#, "AQAA")), r#"compression "zstd""#),
+            (&map("", r#"<objectgroup><object x="left"/></objectgroup>"#), r#"Invalid x attribute "left" on <object>"#),
+            (&map("", r#"<objectgroup><object><polygon points="0,0 1"/></object></objectgroup>"#), "Invalid point"),
+            (&map("", r#"<properties><property name="n" type="int" value="ten"/></properties>"#), "Invalid int"),
+        ];
+        for (text, expected) in invalid {
+            let err = error(text);
+            assert!(err.contains(expected), "{text}: {err}");
+        }
+    }
+
+    #[test]
+    fn values_overflowing_their_type_are_errors() {
+        let property = r#"<properties><property name="n" type="int" value="9223372036854775808"/></properties>"#;
+        let invalid = [
+            (map(r#"hexsidelength="4294967296""#, ""), "Invalid hexsidelength attribute"),
+            (map("", &layer(r#"encoding="csv""#, "1,2,3,4294967296")), "Invalid tile id"),
+            (map("", &layer("", r#"<tile gid="-1"/>"#)), "Invalid gid attribute"),
+            (map("", r#"<tileset firstgid="4294967296"/>"#), "Invalid firstgid attribute"),
+            (map("", property), "Invalid int property"),
+        ];
+        for (text, expected) in invalid {
+            let err = error(&text);
+            assert!(err.starts_with(expected), "{text}: {err}");
+        }
+    }
+}
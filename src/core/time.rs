@@ -0,0 +1,33 @@
+/// Frame timing, advanced by the engine before update systems run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Time {
+    delta: f32,
+    elapsed: f64,
+    frame: u64,
+}
+
+impl Time {
+    /// Returns the time the last frame took, in seconds.
+    ///
+    /// While the window is recording, this is the recording's fixed timestep instead.
+    pub fn get_delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Returns the time elapsed since the first frame, in seconds.
+    pub fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Returns how many frames have started, `1` during the first frame.
+    pub fn get_frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Starts the next frame, `delta` seconds after the current one.
+    pub(crate) fn advance(&mut self, delta: f32) {
+        self.frame += 1;
+        self.delta = delta;
+        self.elapsed += delta as f64;
+    }
+}
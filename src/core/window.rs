@@ -1,9 +1,68 @@
 use glfw::Context;
+use image::RgbaImage;
+
+use std::path::{Path, PathBuf};
+
+use crate::core::headless::{HeadlessApi, HeadlessContext};
+use crate::core::{FrameCapture, Recording};
+use crate::opengl::{Attachment, ClearState, Framebuffer, Renderbuffer, TextureFormat};
+use crate::opengl::enable_debug_output;
+
+/// The framebuffer a headless window renders into instead of a visible surface.
+struct Offscreen {
+    framebuffer: Framebuffer,
+    // Owned so the attachments live as long as the framebuffer.
+    _color: Renderbuffer,
+    _depth: Renderbuffer,
+    width: u32,
+    height: u32,
+}
+
+impl Offscreen {
+    fn new(width: u32, height: u32) -> Result<Offscreen, String> {
+        let framebuffer = Framebuffer::new();
+        let color = Renderbuffer::new(width, height, TextureFormat::Rgba8);
+        let depth = Renderbuffer::new(width, height, TextureFormat::Depth24Stencil8);
+
+        framebuffer.bind();
+        framebuffer.attach_renderbuffer(Attachment::Color(0), &color);
+        framebuffer.attach_renderbuffer(Attachment::DepthStencil, &depth);
+        framebuffer.check_status()?;
+        framebuffer.set_label("Headless output framebuffer");
+
+        Ok(Offscreen {
+            framebuffer,
+            _color: color,
+            _depth: depth,
+            width,
+            height,
+        })
+    }
+}
+
+/// What a window presents to: a GLFW window, or a context with no window system at all.
+enum Surface {
+    Glfw {
+        pwindow: glfw::PWindow,
+        glfw: glfw::Glfw,
+    },
+    Headless {
+        offscreen: Offscreen,
+        context: HeadlessContext,
+        should_close: bool,
+    },
+}
 
 /// Represents a window in a GLFW context.
+///
+/// Fields holding OpenGL objects come first, so they are dropped while the context
+/// still exists.
 pub struct Window {
-    pwindow: glfw::PWindow,
-    glfw: glfw::Glfw,
+    capture: FrameCapture,
+    screenshots: Vec<PathBuf>,
+    recording: Option<Recording>,
+    capture_results: Vec<Result<PathBuf, String>>,
+    surface: Surface,
     title: String,
 }
 
@@ -31,6 +90,9 @@ impl Window {
         glfw.window_hint(glfw::WindowHint::X11ClassName(Some(title.clone())));
         glfw.window_hint(glfw::WindowHint::ContextVersion(3, 3));
         glfw.window_hint(glfw::WindowHint::Floating(true));
+        glfw.window_hint(glfw::WindowHint::DepthBits(Some(24)));
+        glfw.window_hint(glfw::WindowHint::StencilBits(Some(8)));
+        glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(cfg!(debug_assertions)));
 
         // Create the window and capture events
         let (mut pwindow, events) = glfw
@@ -43,23 +105,170 @@ impl Window {
         // Enable key polling and make the window current
         pwindow.set_key_polling(true);
         pwindow.make_current();
+        enable_debug_output();
+
+        // Seamless filtering hides the edges between cube map faces. Depth, blend and
+        // cull state is set per draw through `PipelineState`.
+        unsafe { gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
 
         Window {
-            pwindow,
+            surface: Surface::Glfw { pwindow, glfw },
             title,
-            glfw,
+            capture: FrameCapture::new(),
+            screenshots: Vec::new(),
+            recording: None,
+            capture_results: Vec::new(),
         }
     }
 
+    /// Creates a window without a display, rendering into an offscreen framebuffer.
+    ///
+    /// The context is created through EGL or OSMesa directly, without GLFW, so no display
+    /// server or GPU is needed when the chosen API is backed by a software rasterizer
+    /// such as Mesa's llvmpipe.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the offscreen framebuffer.
+    /// * `height` - The height of the offscreen framebuffer.
+    /// * `api` - The context API to create the OpenGL context with.
+    ///
+    /// # Returns
+    ///
+    /// The headless `Window`, or an `Err(String)` if the context could not be created,
+    /// e.g. because the API is not available on this machine.
+    pub fn headless(width: u32, height: u32, api: HeadlessApi) -> Result<Window, String> {
+        let context = HeadlessContext::new(width, height, api)?;
+        enable_debug_output();
+        unsafe { gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
+
+        let offscreen = Offscreen::new(width, height)?;
+        Ok(Window {
+            surface: Surface::Headless {
+                offscreen,
+                context,
+                should_close: false,
+            },
+            title: String::from("Foux Engine (headless)"),
+            capture: FrameCapture::new(),
+            screenshots: Vec::new(),
+            recording: None,
+            capture_results: Vec::new(),
+        })
+    }
+
+    /// Saves the frame currently being drawn as a PNG once it is finished.
+    ///
+    /// The readback is asynchronous; the outcome is reported by `take_capture_results`
+    /// a few frames later.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Where the PNG is written.
+    pub fn capture_screenshot<P: AsRef<Path>>(&mut self, path: P) {
+        self.screenshots.push(path.as_ref().to_path_buf());
+    }
+
+    /// Starts saving every finished frame as a numbered PNG.
+    ///
+    /// Until `stop_recording`, the engine advances `Scene::time` by the recording's
+    /// fixed timestep rather than by wall-clock time.
+    pub fn start_recording(&mut self, recording: Recording) {
+        self.recording = Some(recording);
+    }
+
+    /// Stops recording and waits for every recorded frame to be written.
+    ///
+    /// # Returns
+    ///
+    /// The finished recording, or `None` if the window was not recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        let recording = self.recording.take();
+        self.finish_captures();
+        recording
+    }
+
+    /// Returns the active recording, if any.
+    pub fn get_recording(&self) -> Option<&Recording> {
+        self.recording.as_ref()
+    }
+
+    /// Returns the outcome of every screenshot and recorded frame written since the
+    /// last call: its path, or an `Err(String)` describing why it could not be saved.
+    pub fn take_capture_results(&mut self) -> Vec<Result<PathBuf, String>> {
+        self.capture_results.extend(self.capture.poll());
+        std::mem::take(&mut self.capture_results)
+    }
+
+    /// Waits until every queued screenshot and recorded frame has been written.
+    pub fn finish_captures(&mut self) {
+        let results = self.capture.flush();
+        self.capture_results.extend(results);
+    }
+
+    fn queue_captures(&mut self) {
+        let recorded = self.recording.as_mut().map(|recording| recording.next_path());
+        let paths: Vec<PathBuf> = self.screenshots.drain(..).chain(recorded).collect();
+        if !paths.is_empty() {
+            let (width, height) = self.get_framebuffer_size();
+            let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+            let output = match &self.surface {
+                Surface::Glfw { .. } => None,
+                Surface::Headless { offscreen, .. } => Some(&offscreen.framebuffer),
+            };
+            for path in paths {
+                self.capture.capture(output, width, height, path);
+            }
+        }
+        let results = self.capture.poll();
+        self.capture_results.extend(results);
+    }
+
+    /// Checks whether the window renders offscreen rather than to a visible surface.
+    pub fn is_headless(&self) -> bool {
+        matches!(self.surface, Surface::Headless { .. })
+    }
+
+    /// Returns the framebuffer frames are drawn into: the offscreen one of a headless
+    /// window, or `None` for the window's own.
+    pub fn get_output_framebuffer(&self) -> Option<&Framebuffer> {
+        match &self.surface {
+            Surface::Glfw { .. } => None,
+            Surface::Headless { offscreen, .. } => Some(&offscreen.framebuffer),
+        }
+    }
+
+    /// Reads back the last rendered frame.
+    ///
+    /// # Returns
+    ///
+    /// The output framebuffer's color as an RGBA image, top row first.
+    pub fn read_pixels(&self) -> RgbaImage {
+        let (width, height) = self.get_framebuffer_size();
+        let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+
+        Framebuffer::bind_target(self.get_output_framebuffer());
+        let pixels = Framebuffer::read_pixels(width, height);
+
+        let mut image = RgbaImage::from_raw(width, height, pixels).expect("Pixel buffer matches the frame size");
+        image::imageops::flip_vertical_in_place(&mut image);
+        image
+    }
+
     /// Updates the window, swapping buffers and polling for events.
     ///
     /// This function should be called each frame to maintain window responsiveness.
+    /// Headless windows have nothing to present or poll. Requested screenshots and
+    /// recorded frames are read back from the finished frame first.
     pub fn update(&mut self) {
-        self.pwindow.swap_buffers();
-        self.glfw.poll_events();
+        self.queue_captures();
+        if let Surface::Glfw { pwindow, glfw } = &mut self.surface {
+            pwindow.swap_buffers();
+            glfw.poll_events();
+        }
     }
 
-    /// Clears the window with the specified color.
+    /// Clears the window with the specified color and resets the depth and stencil buffers.
     ///
     /// # Arguments
     ///
@@ -72,9 +281,28 @@ impl Window {
     ///
     /// This function calls OpenGL directly, which is unsafe.
     pub fn clear(&self, red: f32, green: f32, blue: f32, alpha: f32) {
-        unsafe {
-            gl::ClearColor(red, green, blue, alpha);
-            gl::Clear(gl::COLOR_BUFFER_BIT);
+        self.clear_with(&ClearState::all(red, green, blue, alpha));
+    }
+
+    /// Clears only the buffers selected by `clear_state`.
+    ///
+    /// # Arguments
+    ///
+    /// * `clear_state` - The color, depth and stencil values to reset, each optional.
+    pub fn clear_with(&self, clear_state: &ClearState) {
+        Framebuffer::bind_target(self.get_output_framebuffer());
+        clear_state.apply();
+    }
+
+    /// Returns the size of the window's framebuffer in pixels.
+    ///
+    /// # Returns
+    ///
+    /// A `(width, height)` tuple.
+    pub fn get_framebuffer_size(&self) -> (i32, i32) {
+        match &self.surface {
+            Surface::Glfw { pwindow, .. } => pwindow.get_framebuffer_size(),
+            Surface::Headless { offscreen, .. } => (offscreen.width as i32, offscreen.height as i32),
         }
     }
 
@@ -94,7 +322,9 @@ impl Window {
     /// * `title` - The new title to set for the window.
     pub fn set_title(&mut self, title: &str) {
         self.title = String::from(title);
-        self.pwindow.set_title(title);
+        if let Surface::Glfw { pwindow, .. } = &mut self.surface {
+            pwindow.set_title(title);
+        }
     }
 
     /// Checks if the window should close.
@@ -103,7 +333,10 @@ impl Window {
     ///
     /// `true` if the window should close, `false` otherwise.
     pub fn should_close(&self) -> bool {
-        self.pwindow.should_close()
+        match &self.surface {
+            Surface::Glfw { pwindow, .. } => pwindow.should_close(),
+            Surface::Headless { should_close, .. } => *should_close,
+        }
     }
 
     /// Sets whether the window should close or not.
@@ -112,6 +345,9 @@ impl Window {
     ///
     /// * `value` - `true` if the window should close, `false` otherwise.
     pub fn set_should_close(&mut self, value: bool) {
-        self.pwindow.set_should_close(value);
+        match &mut self.surface {
+            Surface::Glfw { pwindow, .. } => pwindow.set_should_close(value),
+            Surface::Headless { should_close, .. } => *should_close = value,
+        }
     }
 }
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+
+use super::{BufferHandle, DrawCall, ExternalTexture, FramebufferHandle, ReleaseQueue, RenderDevice, RenderTarget};
+use super::{ShaderHandle, TextureHandle, UniformValue};
+use crate::opengl::{Attachment, BufferObject, BufferTarget, BufferTexture, BufferUsage, ClearState, Framebuffer};
+use crate::opengl::{PipelineState, Sampler, SamplerObject, ShaderProgram, Texture, TextureFormat, VertexArrayObject};
+use crate::opengl::VertexLayout;
+
+/// Vertex arrays are cached per combination of vertex buffer, index buffer, instance
+/// buffer and layouts.
+type VertexArrayKey = (BufferHandle, Option<BufferHandle>, VertexLayout, Option<(BufferHandle, VertexLayout)>);
+
+/// A buffer a `GlDevice` draws from by handle.
+enum GlBuffer {
+    Owned(BufferObject),
+    /// A buffer owned by the caller, by OpenGL target and name.
+    Imported(u32, u32),
+}
+
+impl GlBuffer {
+    fn bind(&self) {
+        match self {
+            GlBuffer::Owned(buffer) => buffer.bind(),
+            GlBuffer::Imported(target, id) => unsafe { gl::BindBuffer(*target, *id) },
+        }
+    }
+
+    fn bind_base(&self, index: u32) {
+        match self {
+            GlBuffer::Owned(buffer) => buffer.bind_base(index),
+            GlBuffer::Imported(target, id) => unsafe { gl::BindBufferBase(*target, index, *id) },
+        }
+    }
+}
+
+/// A texture a `GlDevice` binds by handle.
+enum GlTexture {
+    Texture(Texture),
+    Buffer(BufferTexture),
+    /// A texture owned by the caller, by OpenGL target and name.
+    Imported(u32, u32),
+}
+
+impl GlTexture {
+    /// Returns the OpenGL target and name of a 2D or cube map texture.
+    fn target_and_id(&self) -> Option<(u32, u32)> {
+        match self {
+            GlTexture::Texture(texture) => Some((gl::TEXTURE_2D, texture.id())),
+            GlTexture::Buffer(_) => None,
+            GlTexture::Imported(target, id) => Some((*target, *id)),
+        }
+    }
+
+    fn bind(&self, unit: u32) {
+        match self {
+            GlTexture::Texture(texture) => texture.bind(unit),
+            GlTexture::Buffer(texture) => texture.bind(unit),
+            GlTexture::Imported(target, id) => unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+                gl::BindTexture(*target, *id);
+            },
+        }
+    }
+}
+
+/// A framebuffer a `GlDevice` renders into by handle.
+enum GlFramebuffer {
+    Owned(Framebuffer),
+    /// A framebuffer owned by the caller, by OpenGL name.
+    Imported(u32),
+}
+
+impl GlFramebuffer {
+    fn id(&self) -> u32 {
+        match self {
+            GlFramebuffer::Owned(framebuffer) => framebuffer.id(),
+            GlFramebuffer::Imported(id) => *id,
+        }
+    }
+}
+
+/// A program a `GlDevice` draws with, and the locations looked up in it so far.
+///
+/// Locations of imported programs are looked up on every draw: their owner may delete
+/// them and OpenGL may hand the same name to a new program.
+struct GlProgram {
+    program: Option<ShaderProgram>,       // The program, or `None` if it was imported
+    id: u32,                              // OpenGL name of the program
+    uniforms: HashMap<String, i32>,       // Uniform locations by name, `-1` if inactive
+    blocks: HashMap<String, Option<u32>>, // Uniform block indices by name
+}
+
+impl GlProgram {
+    fn new(program: Option<ShaderProgram>, id: u32) -> GlProgram {
+        GlProgram {
+            program,
+            id,
+            uniforms: HashMap::new(),
+            blocks: HashMap::new(),
+        }
+    }
+
+    fn uniform_location(&mut self, name: &str) -> i32 {
+        let id = self.id;
+        let lookup = || match CString::new(name) {
+            Ok(name) => unsafe { gl::GetUniformLocation(id, name.as_ptr()) },
+            Err(_) => -1,
+        };
+        match self.program {
+            Some(_) => *self.uniforms.entry(name.to_string()).or_insert_with(lookup),
+            None => lookup(),
+        }
+    }
+
+    fn block_index(&mut self, name: &str) -> Option<u32> {
+        let id = self.id;
+        let lookup = || {
+            let name = CString::new(name).ok()?;
+            let index = unsafe { gl::GetUniformBlockIndex(id, name.as_ptr()) };
+            (index != gl::INVALID_INDEX).then_some(index)
+        };
+        match self.program {
+            Some(_) => *self.blocks.entry(name.to_string()).or_insert_with(lookup),
+            None => lookup(),
+        }
+    }
+}
+
+/// A `RenderDevice` drawing with the current OpenGL context through the `opengl` wrappers.
+#[derive(Default)]
+pub struct GlDevice {
+    buffers: HashMap<BufferHandle, GlBuffer>,                  // Buffers by handle
+    textures: HashMap<TextureHandle, GlTexture>,               // Textures by handle
+    shaders: HashMap<ShaderHandle, GlProgram>,                 // Programs by handle
+    framebuffers: HashMap<FramebufferHandle, GlFramebuffer>,   // Framebuffers by handle
+    imported_buffers: HashMap<u32, BufferHandle>,              // Imported buffers by name
+    imported_textures: HashMap<(u32, u32), TextureHandle>,     // Imported textures by target and name
+    imported_shaders: HashMap<u32, ShaderHandle>,              // Imported programs by name
+    imported_framebuffers: HashMap<u32, FramebufferHandle>,    // Imported framebuffers by name
+    vertex_arrays: HashMap<VertexArrayKey, VertexArrayObject>, // Attribute setups of past draws
+    samplers: HashMap<Sampler, SamplerObject>,                 // Sampler objects of past draws
+    framebuffer: Option<FramebufferHandle>,                    // The bound framebuffer
+    viewport: (i32, i32, u32, u32),                            // The last viewport set
+    release_queue: ReleaseQueue,                               // Resources of dropped owners
+    next_id: u32,                                              // Id of the next handle
+}
+
+impl GlDevice {
+    /// Creates a device for the OpenGL context current on this thread.
+    pub fn new() -> GlDevice {
+        GlDevice::default()
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    /// Returns the vertex array of a draw, setting one up the first time.
+    fn vertex_array(&mut self, draw: &DrawCall) -> &VertexArrayObject {
+        let index_buffer = draw.index_buffer.map(|(buffer, _)| buffer);
+        let key = (draw.vertex_buffer, index_buffer, draw.layout.clone(), draw.instance_buffer.clone());
+        let buffers = &self.buffers;
+        self.vertex_arrays.entry(key).or_insert_with(|| {
+            let vertex_array = VertexArrayObject::new();
+            vertex_array.bind();
+            if let Some(buffer) = buffers.get(&draw.vertex_buffer) {
+                buffer.bind();
+                draw.layout.apply();
+            }
+            if let Some((buffer, layout)) = &draw.instance_buffer {
+                if let Some(buffer) = buffers.get(buffer) {
+                    buffer.bind();
+                    layout.apply_instanced(1);
+                }
+            }
+            if let Some(buffer) = index_buffer.and_then(|buffer| buffers.get(&buffer)) {
+                buffer.bind();
+            }
+            vertex_array.unbind();
+            vertex_array
+        })
+    }
+
+    /// Binds a framebuffer by handle, or the window's with `None`.
+    fn bind_framebuffer_id(&self, target: u32, framebuffer: Option<FramebufferHandle>) {
+        let id = framebuffer.and_then(|framebuffer| self.framebuffers.get(&framebuffer)).map_or(0, GlFramebuffer::id);
+        unsafe { gl::BindFramebuffer(target, id) }
+    }
+}
+
+/// Sets a uniform of the bound program.
+fn set_uniform(location: i32, value: &UniformValue) {
+    unsafe {
+        match value {
+            UniformValue::Int(value) => gl::Uniform1i(location, *value),
+            UniformValue::Float(value) => gl::Uniform1f(location, *value),
+            UniformValue::Vec2(vector) => gl::Uniform2fv(location, 1, vector.as_ptr()),
+            UniformValue::Vec3(vector) => gl::Uniform3fv(location, 1, vector.as_ptr()),
+            UniformValue::Vec4(vector) => gl::Uniform4fv(location, 1, vector.as_ptr()),
+            UniformValue::Mat4(matrix) => gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr()),
+            UniformValue::FloatArray(values) => gl::Uniform1fv(location, values.len() as i32, values.as_ptr()),
+            UniformValue::Vec4Array(vectors) => {
+                let data: Vec<f32> = vectors.iter().flat_map(|vector| vector.iter().copied()).collect();
+                gl::Uniform4fv(location, vectors.len() as i32, data.as_ptr());
+            }
+        }
+    }
+}
+
+impl RenderDevice for GlDevice {
+    fn create_buffer(&mut self, target: BufferTarget, usage: BufferUsage, data: &[u8]) -> BufferHandle {
+        let buffer = BufferObject::new(target, usage);
+        buffer.bind();
+        buffer.data(data);
+        buffer.unbind();
+
+        let handle = BufferHandle(self.next_id());
+        self.buffers.insert(handle, GlBuffer::Owned(buffer));
+        handle
+    }
+
+    fn update_buffer(&mut self, buffer: BufferHandle, offset: usize, data: &[u8]) {
+        // Imported buffers are written by their owner.
+        if let Some(GlBuffer::Owned(buffer)) = self.buffers.get(&buffer) {
+            buffer.bind();
+            buffer.sub_data(offset, data);
+            buffer.unbind();
+        }
+    }
+
+    fn import_buffer(&mut self, buffer: &BufferObject) -> BufferHandle {
+        if let Some(&handle) = self.imported_buffers.get(&buffer.id()) {
+            return handle;
+        }
+        let handle = BufferHandle(self.next_id());
+        self.buffers.insert(handle, GlBuffer::Imported(buffer.get_target() as u32, buffer.id()));
+        self.imported_buffers.insert(buffer.id(), handle);
+        handle
+    }
+
+    fn destroy_buffer(&mut self, buffer: BufferHandle) {
+        if let Some(GlBuffer::Imported(_, id)) = self.buffers.remove(&buffer) {
+            self.imported_buffers.remove(&id);
+        }
+        self.vertex_arrays.retain(|(vertices, indices, _, instances), _| {
+            let instances = instances.as_ref().map(|(instances, _)| *instances);
+            *vertices != buffer && *indices != Some(buffer) && instances != Some(buffer)
+        });
+    }
+
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: Option<&[u8]>,
+    ) -> TextureHandle {
+        let texture = match data {
+            Some(data) => Texture::from_data(width, height, format, data),
+            None => Texture::new(width, height, format),
+        };
+        let handle = TextureHandle(self.next_id());
+        self.textures.insert(handle, GlTexture::Texture(texture));
+        handle
+    }
+
+    fn create_buffer_texture(&mut self, format: TextureFormat, data: &[u8]) -> TextureHandle {
+        let handle = TextureHandle(self.next_id());
+        self.textures.insert(handle, GlTexture::Buffer(BufferTexture::new(format, data)));
+        handle
+    }
+
+    fn import_texture(&mut self, texture: ExternalTexture<'_>) -> TextureHandle {
+        let (target, id) = texture.gl_target_and_id();
+        if let Some(&handle) = self.imported_textures.get(&(target, id)) {
+            return handle;
+        }
+        let handle = TextureHandle(self.next_id());
+        self.textures.insert(handle, GlTexture::Imported(target, id));
+        self.imported_textures.insert((target, id), handle);
+        handle
+    }
+
+    fn generate_mipmaps(&mut self, texture: TextureHandle) {
+        let Some((target, id)) = self.textures.get(&texture).and_then(GlTexture::target_and_id) else { return };
+        unsafe {
+            gl::BindTexture(target, id);
+            gl::GenerateMipmap(target);
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+            gl::BindTexture(target, 0);
+        }
+    }
+
+    fn destroy_texture(&mut self, texture: TextureHandle) {
+        if let Some(GlTexture::Imported(target, id)) = self.textures.remove(&texture) {
+            self.imported_textures.remove(&(target, id));
+        }
+    }
+
+    fn create_shader(&mut self, vertex_src: &str, fragment_src: &str) -> ShaderHandle {
+        let program = ShaderProgram::new(vertex_src, fragment_src);
+        let id = program.id();
+        let handle = ShaderHandle(self.next_id());
+        self.shaders.insert(handle, GlProgram::new(Some(program), id));
+        handle
+    }
+
+    fn import_shader(&mut self, program: &ShaderProgram) -> ShaderHandle {
+        if let Some(&handle) = self.imported_shaders.get(&program.id()) {
+            return handle;
+        }
+        let handle = ShaderHandle(self.next_id());
+        self.shaders.insert(handle, GlProgram::new(None, program.id()));
+        self.imported_shaders.insert(program.id(), handle);
+        handle
+    }
+
+    fn destroy_shader(&mut self, shader: ShaderHandle) {
+        if let Some(GlProgram { program: None, id, .. }) = self.shaders.remove(&shader) {
+            self.imported_shaders.remove(&id);
+        }
+    }
+
+    fn create_framebuffer(&mut self, targets: &[RenderTarget]) -> Result<FramebufferHandle, String> {
+        let framebuffer = Framebuffer::new();
+        framebuffer.bind();
+        for target in targets {
+            let Some((texture_target, id)) = self.textures.get(&target.texture).and_then(GlTexture::target_and_id)
+            else {
+                self.bind_framebuffer_id(gl::FRAMEBUFFER, self.framebuffer);
+                return Err(format!("Can't render into texture {:?}", target.texture));
+            };
+            let image_target = match target.face {
+                Some(face) => gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                None => texture_target,
+            };
+            framebuffer.attach_image(target.attachment, image_target, id, target.level as i32);
+        }
+        let colors = targets.iter().filter(|target| matches!(target.attachment, Attachment::Color(_))).count();
+        if colors != 1 {
+            framebuffer.set_draw_buffers(colors as u32);
+        }
+        let status = framebuffer.check_status();
+        self.bind_framebuffer_id(gl::FRAMEBUFFER, self.framebuffer);
+        status?;
+
+        let handle = FramebufferHandle(self.next_id());
+        self.framebuffers.insert(handle, GlFramebuffer::Owned(framebuffer));
+        Ok(handle)
+    }
+
+    fn import_framebuffer(&mut self, framebuffer: &Framebuffer) -> FramebufferHandle {
+        if let Some(&handle) = self.imported_framebuffers.get(&framebuffer.id()) {
+            return handle;
+        }
+        let handle = FramebufferHandle(self.next_id());
+        self.framebuffers.insert(handle, GlFramebuffer::Imported(framebuffer.id()));
+        self.imported_framebuffers.insert(framebuffer.id(), handle);
+        handle
+    }
+
+    fn destroy_framebuffer(&mut self, framebuffer: FramebufferHandle) {
+        if let Some(GlFramebuffer::Imported(id)) = self.framebuffers.remove(&framebuffer) {
+            self.imported_framebuffers.remove(&id);
+        }
+        if self.framebuffer == Some(framebuffer) {
+            self.bind_framebuffer(None);
+        }
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: Option<FramebufferHandle>) {
+        self.framebuffer = framebuffer;
+        self.bind_framebuffer_id(gl::FRAMEBUFFER, framebuffer);
+    }
+
+    fn get_framebuffer(&self) -> Option<FramebufferHandle> {
+        self.framebuffer
+    }
+
+    fn blit_framebuffer(
+        &mut self,
+        source: FramebufferHandle,
+        target: Option<FramebufferHandle>,
+        width: u32,
+        height: u32,
+        color: bool,
+        depth: bool,
+    ) {
+        let mut mask = 0;
+        if color {
+            mask |= gl::COLOR_BUFFER_BIT;
+        }
+        if depth {
+            mask |= gl::DEPTH_BUFFER_BIT;
+        }
+
+        self.bind_framebuffer_id(gl::READ_FRAMEBUFFER, Some(source));
+        self.bind_framebuffer_id(gl::DRAW_FRAMEBUFFER, target);
+        let (width, height) = (width as i32, height as i32);
+        unsafe { gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, mask, gl::NEAREST) };
+        self.bind_framebuffer(target);
+    }
+
+    fn set_pipeline_state(&mut self, state: &PipelineState) {
+        state.apply();
+    }
+
+    fn set_viewport(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.viewport = (x, y, width, height);
+        unsafe { gl::Viewport(x, y, width as i32, height as i32) };
+    }
+
+    fn get_viewport(&self) -> (i32, i32, u32, u32) {
+        self.viewport
+    }
+
+    fn clear(&mut self, clear: &ClearState) {
+        clear.apply();
+    }
+
+    fn draw(&mut self, draw: &DrawCall) {
+        let Some(program) = self.shaders.get_mut(&draw.shader) else { return };
+        unsafe { gl::UseProgram(program.id) };
+        for (name, value) in &draw.uniforms {
+            // Uniforms the program doesn't declare, or that were optimized out, are skipped.
+            let location = program.uniform_location(name);
+            if location >= 0 {
+                set_uniform(location, value);
+            }
+        }
+        for (binding, (name, buffer)) in draw.uniform_blocks.iter().enumerate() {
+            let (Some(index), Some(buffer)) = (program.block_index(name), self.buffers.get(buffer)) else {
+                continue;
+            };
+            unsafe { gl::UniformBlockBinding(program.id, index, binding as u32) };
+            buffer.bind_base(binding as u32);
+        }
+        for (unit, texture) in &draw.textures {
+            if let Some(texture) = self.textures.get(texture) {
+                texture.bind(*unit);
+            }
+        }
+        for (unit, sampler) in &draw.samplers {
+            self.samplers.entry(*sampler).or_insert_with(|| SamplerObject::new(sampler)).bind(*unit);
+        }
+
+        let mode = draw.topology.gl_mode();
+        let instances = draw.instances as i32;
+        let vertex_array = self.vertex_array(draw);
+        vertex_array.bind();
+        draw.topology.apply();
+        unsafe {
+            match draw.index_buffer {
+                Some((_, index_type)) => {
+                    let offset = (draw.first as usize * index_type.size()) as *const std::ffi::c_void;
+                    if draw.primitive_restart {
+                        gl::Enable(gl::PRIMITIVE_RESTART);
+                        gl::PrimitiveRestartIndex(index_type.restart_index());
+                    }
+                    let (count, index_type) = (draw.count as i32, index_type as u32);
+                    if draw.base_vertex == 0 {
+                        gl::DrawElementsInstanced(mode, count, index_type, offset, instances);
+                    } else {
+                        let base_vertex = draw.base_vertex;
+                        gl::DrawElementsInstancedBaseVertex(mode, count, index_type, offset, instances, base_vertex);
+                    }
+                    if draw.primitive_restart {
+                        gl::Disable(gl::PRIMITIVE_RESTART);
+                    }
+                }
+                None => gl::DrawArraysInstanced(mode, draw.first as i32, draw.count as i32, instances),
+            }
+        }
+        vertex_array.unbind();
+        for (unit, _) in &draw.samplers {
+            SamplerObject::unbind(*unit);
+        }
+    }
+
+    fn get_release_queue(&self) -> &ReleaseQueue {
+        &self.release_queue
+    }
+}
@@ -0,0 +1,424 @@
+mod gl_device;
+mod recording;
+mod release;
+
+pub use gl_device::GlDevice;
+pub use recording::{DeviceCommand, RecordingDevice, DEVICE_LOG_TARGET};
+pub use release::{ReleaseQueue, Resource};
+
+pub(crate) use release::{GpuCache, GpuResources};
+
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::opengl::{Attachment, BufferObject, BufferTarget, BufferUsage, ClearState, Cubemap, Framebuffer};
+use crate::opengl::{IndexType, PipelineState, Sampler, ShaderProgram, Texture, TextureFormat, Topology, VertexLayout};
+
+/// Identifies a buffer created by a `RenderDevice`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BufferHandle(u32);
+
+/// Identifies a texture created by a `RenderDevice`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(u32);
+
+/// Identifies a shader program created by a `RenderDevice`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderHandle(u32);
+
+/// Identifies a framebuffer created by a `RenderDevice`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FramebufferHandle(u32);
+
+/// An image a framebuffer renders into, see `RenderDevice::create_framebuffer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderTarget {
+    /// Where the image is attached.
+    pub attachment: Attachment,
+    /// The texture holding the image, created by or imported into the device.
+    pub texture: TextureHandle,
+    /// The face of a cube map texture, in `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z` order, or
+    /// `None` for 2D textures.
+    pub face: Option<u32>,
+    /// The mip level rendered into.
+    pub level: u32,
+}
+
+impl RenderTarget {
+    /// Creates a target rendering into level 0 of a 2D texture.
+    pub fn new(attachment: Attachment, texture: TextureHandle) -> RenderTarget {
+        RenderTarget {
+            attachment,
+            texture,
+            face: None,
+            level: 0,
+        }
+    }
+
+    /// Renders into a face of a cube map texture instead.
+    pub fn with_face(mut self, face: u32) -> RenderTarget {
+        self.face = Some(face);
+        self
+    }
+
+    /// Renders into another mip level.
+    pub fn with_level(mut self, level: u32) -> RenderTarget {
+        self.level = level;
+        self
+    }
+}
+
+/// A texture created outside the device, see `RenderDevice::import_texture`.
+#[derive(Clone, Copy)]
+pub enum ExternalTexture<'a> {
+    Texture(&'a Texture),
+    Cubemap(&'a Cubemap),
+}
+
+impl ExternalTexture<'_> {
+    /// Returns the OpenGL target and name of the texture.
+    pub(crate) fn gl_target_and_id(self) -> (u32, u32) {
+        match self {
+            ExternalTexture::Texture(texture) => (gl::TEXTURE_2D, texture.id()),
+            ExternalTexture::Cubemap(cubemap) => (gl::TEXTURE_CUBE_MAP, cubemap.id()),
+        }
+    }
+}
+
+impl GpuResources for BufferHandle {
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::Buffer(*self)]
+    }
+}
+
+impl GpuResources for TextureHandle {
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::Texture(*self)]
+    }
+}
+
+impl GpuResources for ShaderHandle {
+    fn resources(&self) -> Vec<Resource> {
+        vec![Resource::Shader(*self)]
+    }
+}
+
+/// The value of a shader uniform set by a `DrawCall`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UniformValue {
+    Int(i32),
+    Float(f32),
+    Vec2(Vec2),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+    FloatArray(Vec<f32>),
+    Vec4Array(Vec<Vec4>),
+}
+
+/// Everything a `RenderDevice` needs to issue one draw.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawCall {
+    /// The program the draw runs.
+    pub shader: ShaderHandle,
+    /// The interleaved vertices.
+    pub vertex_buffer: BufferHandle,
+    /// How `vertex_buffer` maps to shader attributes.
+    pub layout: VertexLayout,
+    /// A buffer of per-instance attributes and its layout, advanced once per instance.
+    pub instance_buffer: Option<(BufferHandle, VertexLayout)>,
+    /// The index buffer and the type of its indices, or `None` to draw vertices in order.
+    pub index_buffer: Option<(BufferHandle, IndexType)>,
+    /// How vertices are assembled into primitives.
+    pub topology: Topology,
+    /// The first index, or the first vertex for non-indexed draws.
+    pub first: u32,
+    /// How many indices, or vertices for non-indexed draws, to draw.
+    pub count: u32,
+    /// Added to every index before fetching the vertex. Ignored for non-indexed draws.
+    pub base_vertex: i32,
+    /// Whether the largest value of the index type restarts strips and fans.
+    pub primitive_restart: bool,
+    /// How many instances to draw.
+    pub instances: u32,
+    /// Uniforms set on `shader` before drawing. Ones the program doesn't declare are ignored.
+    pub uniforms: Vec<(String, UniformValue)>,
+    /// Textures bound to texture units before drawing.
+    pub textures: Vec<(u32, TextureHandle)>,
+    /// Filtering and wrapping of texture units, overriding that of their textures.
+    pub samplers: Vec<(u32, Sampler)>,
+    /// Uniform buffers read by the named uniform blocks of `shader`. Blocks the program
+    /// doesn't declare are ignored.
+    pub uniform_blocks: Vec<(String, BufferHandle)>,
+}
+
+impl DrawCall {
+    /// Creates a non-indexed, single-instance draw of `count` triangle vertices.
+    pub fn new(shader: ShaderHandle, vertex_buffer: BufferHandle, layout: VertexLayout, count: u32) -> DrawCall {
+        DrawCall {
+            shader,
+            vertex_buffer,
+            layout,
+            instance_buffer: None,
+            index_buffer: None,
+            topology: Topology::Triangles,
+            first: 0,
+            count,
+            base_vertex: 0,
+            primitive_restart: false,
+            instances: 1,
+            uniforms: Vec::new(),
+            textures: Vec::new(),
+            samplers: Vec::new(),
+            uniform_blocks: Vec::new(),
+        }
+    }
+
+    /// Reads per-instance attributes from `buffer`, laid out by `layout`.
+    pub fn with_instance_buffer(mut self, buffer: BufferHandle, layout: VertexLayout) -> DrawCall {
+        self.instance_buffer = Some((buffer, layout));
+        self
+    }
+
+    /// Draws `count` indices from `buffer` instead of the vertices in order.
+    pub fn with_indices(mut self, buffer: BufferHandle, index_type: IndexType) -> DrawCall {
+        self.index_buffer = Some((buffer, index_type));
+        self
+    }
+
+    /// Draws `count` indices, or vertices for non-indexed draws, starting at `first`.
+    pub fn with_range(mut self, first: u32, count: u32) -> DrawCall {
+        self.first = first;
+        self.count = count;
+        self
+    }
+
+    /// Sets the value added to every index before fetching the vertex.
+    pub fn with_base_vertex(mut self, base_vertex: i32) -> DrawCall {
+        self.base_vertex = base_vertex;
+        self
+    }
+
+    /// Sets whether the largest value of the index type restarts strips and fans.
+    pub fn with_primitive_restart(mut self, primitive_restart: bool) -> DrawCall {
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    /// Sets the topology the vertices are assembled with.
+    pub fn with_topology(mut self, topology: Topology) -> DrawCall {
+        self.topology = topology;
+        self
+    }
+
+    /// Sets how many instances are drawn.
+    pub fn with_instances(mut self, instances: u32) -> DrawCall {
+        self.instances = instances;
+        self
+    }
+
+    /// Adds a uniform set before drawing.
+    pub fn with_uniform(mut self, name: &str, value: UniformValue) -> DrawCall {
+        self.uniforms.push((name.to_string(), value));
+        self
+    }
+
+    /// Adds a texture bound to `unit` before drawing.
+    pub fn with_texture(mut self, unit: u32, texture: TextureHandle) -> DrawCall {
+        self.textures.push((unit, texture));
+        self
+    }
+
+    /// Samples the texture bound to `unit` with `sampler`.
+    pub fn with_sampler(mut self, unit: u32, sampler: Sampler) -> DrawCall {
+        self.samplers.push((unit, sampler));
+        self
+    }
+
+    /// Adds a uniform buffer read by the uniform block `name`.
+    pub fn with_uniform_block(mut self, name: &str, buffer: BufferHandle) -> DrawCall {
+        self.uniform_blocks.push((name.to_string(), buffer));
+        self
+    }
+
+    /// Returns the texture the draw binds to `unit`, if any.
+    pub fn get_texture(&self, unit: u32) -> Option<TextureHandle> {
+        self.textures.iter().find(|(bound, _)| *bound == unit).map(|(_, texture)| *texture)
+    }
+
+    /// Returns the sampler the draw uses for `unit`, if any.
+    pub fn get_sampler(&self, unit: u32) -> Option<Sampler> {
+        self.samplers.iter().find(|(bound, _)| *bound == unit).map(|(_, sampler)| *sampler)
+    }
+
+    /// Returns the value the draw sets for a uniform, if any.
+    pub fn get_uniform(&self, name: &str) -> Option<&UniformValue> {
+        self.uniforms.iter().find(|(uniform, _)| uniform == name).map(|(_, value)| value)
+    }
+}
+
+/// Creates GPU resources and issues draws, so rendering code can run against OpenGL or,
+/// in tests, against a `RecordingDevice` without a context.
+///
+/// Resources are referred to by handles and live until they are destroyed or the
+/// device is dropped. Owners that outlive their draws queue them on the device's
+/// `ReleaseQueue` instead, and `release_dropped` destroys them once per frame.
+pub trait RenderDevice {
+    /// Creates a buffer holding `data`.
+    fn create_buffer(&mut self, target: BufferTarget, usage: BufferUsage, data: &[u8]) -> BufferHandle;
+
+    /// Replaces part of a buffer's contents, which must fit in its current size.
+    fn update_buffer(&mut self, buffer: BufferHandle, offset: usize, data: &[u8]);
+
+    /// Returns a handle for drawing from a buffer that was created outside the device,
+    /// such as one written by transform feedback.
+    ///
+    /// The buffer stays owned by the caller, who must keep it alive while draws use it
+    /// and destroy the handle before dropping it. Importing the same buffer again
+    /// returns the same handle.
+    fn import_buffer(&mut self, buffer: &BufferObject) -> BufferHandle;
+
+    /// Deletes a buffer, or forgets an imported one.
+    fn destroy_buffer(&mut self, buffer: BufferHandle);
+
+    /// Creates a 2D texture, filled with `data` laid out row by row if given.
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        data: Option<&[u8]>,
+    ) -> TextureHandle;
+
+    /// Creates a buffer texture, read with `texelFetch` from a `samplerBuffer`.
+    fn create_buffer_texture(&mut self, format: TextureFormat, data: &[u8]) -> TextureHandle;
+
+    /// Returns a handle for binding a texture that was created outside the device.
+    ///
+    /// The texture stays owned by the caller, who must keep it alive while draws use it.
+    /// Importing the same texture again returns the same handle.
+    fn import_texture(&mut self, texture: ExternalTexture<'_>) -> TextureHandle;
+
+    /// Generates the mip chain of a texture from its level 0, and samples it trilinearly.
+    fn generate_mipmaps(&mut self, texture: TextureHandle);
+
+    /// Deletes a texture, or forgets an imported one.
+    fn destroy_texture(&mut self, texture: TextureHandle);
+
+    /// Compiles and links a shader program.
+    ///
+    /// # Panics
+    ///
+    /// Implementations compiling GLSL panic if compilation or linking fails, as
+    /// `ShaderProgram::new` does.
+    fn create_shader(&mut self, vertex_src: &str, fragment_src: &str) -> ShaderHandle;
+
+    /// Returns a handle for drawing with a program that was created outside the device,
+    /// such as a `Material::Custom` program.
+    ///
+    /// The program stays owned by the caller, who must keep it alive while draws use it.
+    /// Importing the same program again returns the same handle.
+    fn import_shader(&mut self, program: &ShaderProgram) -> ShaderHandle;
+
+    /// Deletes a shader program, or forgets an imported one.
+    fn destroy_shader(&mut self, shader: ShaderHandle);
+
+    /// Creates a framebuffer rendering into `targets`. Color outputs of fragment shaders
+    /// are written to the color attachments in order of their index.
+    ///
+    /// # Returns
+    ///
+    /// The framebuffer, or an `Err(String)` if the device can't render into this
+    /// combination of images.
+    fn create_framebuffer(&mut self, targets: &[RenderTarget]) -> Result<FramebufferHandle, String>;
+
+    /// Returns a handle for rendering into a framebuffer that was created outside the
+    /// device, such as the output of a headless window.
+    ///
+    /// The framebuffer stays owned by the caller, who must keep it alive while it is
+    /// bound. Importing the same framebuffer again returns the same handle.
+    fn import_framebuffer(&mut self, framebuffer: &Framebuffer) -> FramebufferHandle;
+
+    /// Deletes a framebuffer, or forgets an imported one.
+    fn destroy_framebuffer(&mut self, framebuffer: FramebufferHandle);
+
+    /// Selects the framebuffer the following draws and clears render into, or the
+    /// window's with `None`.
+    fn bind_framebuffer(&mut self, framebuffer: Option<FramebufferHandle>);
+
+    /// Returns the framebuffer the following draws render into, `None` for the window's.
+    fn get_framebuffer(&self) -> Option<FramebufferHandle>;
+
+    /// Copies the bottom left `width` by `height` pixels of `source` into `target`, or
+    /// into the window with `None`, without scaling. `target` is bound afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `color` - Whether to copy color attachment `0`.
+    /// * `depth` - Whether to copy the depth attachment. Both depth formats must match.
+    fn blit_framebuffer(
+        &mut self,
+        source: FramebufferHandle,
+        target: Option<FramebufferHandle>,
+        width: u32,
+        height: u32,
+        color: bool,
+        depth: bool,
+    );
+
+    /// Applies the depth, blend, cull and stencil state of the following draws.
+    fn set_pipeline_state(&mut self, state: &PipelineState);
+
+    /// Sets the rectangle of the framebuffer the following draws cover.
+    fn set_viewport(&mut self, x: i32, y: i32, width: u32, height: u32);
+
+    /// Returns the rectangle set by the last `set_viewport`, as `(x, y, width, height)`.
+    fn get_viewport(&self) -> (i32, i32, u32, u32);
+
+    /// Clears the bound framebuffer.
+    fn clear(&mut self, clear: &ClearState);
+
+    /// Issues a draw.
+    fn draw(&mut self, draw: &DrawCall);
+
+    /// Returns the queue owners put their resources in when they are dropped.
+    fn get_release_queue(&self) -> &ReleaseQueue;
+
+    /// Destroys the resources queued by dropped owners.
+    fn release_dropped(&mut self) {
+        for resource in self.get_release_queue().take() {
+            match resource {
+                Resource::Buffer(buffer) => self.destroy_buffer(buffer),
+                Resource::Texture(texture) => self.destroy_texture(texture),
+                Resource::Shader(shader) => self.destroy_shader(shader),
+                Resource::Framebuffer(framebuffer) => self.destroy_framebuffer(framebuffer),
+            }
+        }
+    }
+}
+
+/// Values `bytes_of` can view as bytes: scalars and the nalgebra vectors and matrices the
+/// engine uploads. None of them have padding, so every byte is initialized.
+///
+/// The trait is sealed; upload other types by flattening them into these first.
+pub trait Pod: Copy + sealed::Sealed {}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(
+            impl sealed::Sealed for $ty {}
+            impl Pod for $ty {}
+        )*
+    };
+}
+
+impl_pod!(u8, u16, u32, i8, i16, i32, f32, Vec2, Vec3, Vec4, Mat4);
+
+/// Returns the bytes of a slice of plain values, for uploading through a `RenderDevice`.
+pub fn bytes_of<T: Pod>(data: &[T]) -> &[u8] {
+    // SAFETY: `Pod` types have no padding bytes and any byte is a valid `u8`.
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
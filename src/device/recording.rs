@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use super::{BufferHandle, DrawCall, ExternalTexture, FramebufferHandle, ReleaseQueue, RenderDevice, RenderTarget};
+use super::{ShaderHandle, TextureHandle};
+use crate::opengl::{BufferObject, BufferTarget, BufferUsage, ClearState, Framebuffer, PipelineState, ShaderProgram};
+use crate::opengl::TextureFormat;
+
+/// Log target of the commands a `RecordingDevice` receives, logged at trace level.
+pub const DEVICE_LOG_TARGET: &str = "foux::device";
+
+/// One call made on a `RecordingDevice`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceCommand {
+    CreateBuffer {
+        buffer: BufferHandle,
+        target: BufferTarget,
+        usage: BufferUsage,
+        size: usize,
+    },
+    UpdateBuffer {
+        buffer: BufferHandle,
+        offset: usize,
+        size: usize,
+    },
+    ImportBuffer {
+        buffer: BufferHandle,
+        id: u32,
+    },
+    DestroyBuffer(BufferHandle),
+    CreateTexture {
+        texture: TextureHandle,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    },
+    CreateBufferTexture {
+        texture: TextureHandle,
+        format: TextureFormat,
+        size: usize,
+    },
+    ImportTexture {
+        texture: TextureHandle,
+        target: u32,
+        id: u32,
+    },
+    GenerateMipmaps(TextureHandle),
+    DestroyTexture(TextureHandle),
+    CreateShader {
+        shader: ShaderHandle,
+        vertex_src: String,
+        fragment_src: String,
+    },
+    ImportShader {
+        shader: ShaderHandle,
+        id: u32,
+    },
+    DestroyShader(ShaderHandle),
+    CreateFramebuffer {
+        framebuffer: FramebufferHandle,
+        targets: Vec<RenderTarget>,
+    },
+    ImportFramebuffer {
+        framebuffer: FramebufferHandle,
+        id: u32,
+    },
+    DestroyFramebuffer(FramebufferHandle),
+    BindFramebuffer(Option<FramebufferHandle>),
+    BlitFramebuffer {
+        source: FramebufferHandle,
+        target: Option<FramebufferHandle>,
+        width: u32,
+        height: u32,
+        color: bool,
+        depth: bool,
+    },
+    SetPipelineState(PipelineState),
+    SetViewport {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    Clear(ClearState),
+    Draw(DrawCall),
+}
+
+/// A `RenderDevice` that draws nothing and records every call instead, so rendering code
+/// can be tested without an OpenGL context.
+///
+/// Buffer contents are kept, so tests can also check what was uploaded. Imports are only
+/// recorded the first time, like a real device only creates their handle once.
+#[derive(Default)]
+pub struct RecordingDevice {
+    commands: Vec<DeviceCommand>,                           // Every call since the last `take_commands`
+    buffers: HashMap<BufferHandle, Vec<u8>>,                // Contents of the live buffers
+    imported_buffers: HashMap<u32, BufferHandle>,           // Imported buffers by name
+    imported_textures: HashMap<(u32, u32), TextureHandle>,  // Imported textures by target and name
+    imported_shaders: HashMap<u32, ShaderHandle>,           // Imported programs by name
+    imported_framebuffers: HashMap<u32, FramebufferHandle>, // Imported framebuffers by name
+    framebuffer: Option<FramebufferHandle>,                 // The bound framebuffer
+    viewport: (i32, i32, u32, u32),                         // The last viewport set
+    release_queue: ReleaseQueue,                            // Resources of dropped owners
+    next_id: u32,                                           // Id of the next handle
+}
+
+impl RecordingDevice {
+    /// Creates a device with an empty command stream.
+    pub fn new() -> RecordingDevice {
+        RecordingDevice::default()
+    }
+
+    /// Returns the calls recorded so far, in order.
+    pub fn get_commands(&self) -> &[DeviceCommand] {
+        &self.commands
+    }
+
+    /// Returns the calls recorded so far and starts a new stream, e.g. once per frame.
+    pub fn take_commands(&mut self) -> Vec<DeviceCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Returns the recorded draws, in order.
+    pub fn draws(&self) -> impl Iterator<Item = &DrawCall> {
+        self.commands.iter().filter_map(|command| match command {
+            DeviceCommand::Draw(draw) => Some(draw),
+            _ => None,
+        })
+    }
+
+    /// Returns the current contents of a buffer, or `None` if it doesn't exist.
+    pub fn get_buffer_data(&self, buffer: BufferHandle) -> Option<&[u8]> {
+        self.buffers.get(&buffer).map(Vec::as_slice)
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn record(&mut self, command: DeviceCommand) {
+        log::trace!(target: DEVICE_LOG_TARGET, "{command:?}");
+        self.commands.push(command);
+    }
+}
+
+impl RenderDevice for RecordingDevice {
+    fn create_buffer(&mut self, target: BufferTarget, usage: BufferUsage, data: &[u8]) -> BufferHandle {
+        let buffer = BufferHandle(self.next_id());
+        self.buffers.insert(buffer, data.to_vec());
+        self.record(DeviceCommand::CreateBuffer {
+            buffer,
+            target,
+            usage,
+            size: data.len(),
+        });
+        buffer
+    }
+
+    /// Records the update and applies it to the kept contents.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the data doesn't fit in the buffer, which would be an
+    /// error on a real device.
+    fn update_buffer(&mut self, buffer: BufferHandle, offset: usize, data: &[u8]) {
+        if let Some(contents) = self.buffers.get_mut(&buffer) {
+            assert!(offset + data.len() <= contents.len(), "update of {buffer:?} is out of bounds");
+            contents[offset..offset + data.len()].copy_from_slice(data);
+        }
+        self.record(DeviceCommand::UpdateBuffer {
+            buffer,
+            offset,
+            size: data.len(),
+        });
+    }
+
+    fn import_buffer(&mut self, buffer: &BufferObject) -> BufferHandle {
+        let id = buffer.id();
+        if let Some(&buffer) = self.imported_buffers.get(&id) {
+            return buffer;
+        }
+        let buffer = BufferHandle(self.next_id());
+        self.imported_buffers.insert(id, buffer);
+        self.record(DeviceCommand::ImportBuffer { buffer, id });
+        buffer
+    }
+
+    fn destroy_buffer(&mut self, buffer: BufferHandle) {
+        self.buffers.remove(&buffer);
+        self.imported_buffers.retain(|_, imported| *imported != buffer);
+        self.record(DeviceCommand::DestroyBuffer(buffer));
+    }
+
+    fn create_texture(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        _data: Option<&[u8]>,
+    ) -> TextureHandle {
+        let texture = TextureHandle(self.next_id());
+        self.record(DeviceCommand::CreateTexture {
+            texture,
+            width,
+            height,
+            format,
+        });
+        texture
+    }
+
+    fn create_buffer_texture(&mut self, format: TextureFormat, data: &[u8]) -> TextureHandle {
+        let texture = TextureHandle(self.next_id());
+        self.record(DeviceCommand::CreateBufferTexture {
+            texture,
+            format,
+            size: data.len(),
+        });
+        texture
+    }
+
+    fn import_texture(&mut self, texture: ExternalTexture<'_>) -> TextureHandle {
+        let (target, id) = texture.gl_target_and_id();
+        if let Some(&texture) = self.imported_textures.get(&(target, id)) {
+            return texture;
+        }
+        let texture = TextureHandle(self.next_id());
+        self.imported_textures.insert((target, id), texture);
+        self.record(DeviceCommand::ImportTexture { texture, target, id });
+        texture
+    }
+
+    fn generate_mipmaps(&mut self, texture: TextureHandle) {
+        self.record(DeviceCommand::GenerateMipmaps(texture));
+    }
+
+    fn destroy_texture(&mut self, texture: TextureHandle) {
+        self.imported_textures.retain(|_, imported| *imported != texture);
+        self.record(DeviceCommand::DestroyTexture(texture));
+    }
+
+    fn create_shader(&mut self, vertex_src: &str, fragment_src: &str) -> ShaderHandle {
+        let shader = ShaderHandle(self.next_id());
+        self.record(DeviceCommand::CreateShader {
+            shader,
+            vertex_src: vertex_src.to_string(),
+            fragment_src: fragment_src.to_string(),
+        });
+        shader
+    }
+
+    fn import_shader(&mut self, program: &ShaderProgram) -> ShaderHandle {
+        let id = program.id();
+        if let Some(&shader) = self.imported_shaders.get(&id) {
+            return shader;
+        }
+        let shader = ShaderHandle(self.next_id());
+        self.imported_shaders.insert(id, shader);
+        self.record(DeviceCommand::ImportShader { shader, id });
+        shader
+    }
+
+    fn destroy_shader(&mut self, shader: ShaderHandle) {
+        self.imported_shaders.retain(|_, imported| *imported != shader);
+        self.record(DeviceCommand::DestroyShader(shader));
+    }
+
+    fn create_framebuffer(&mut self, targets: &[RenderTarget]) -> Result<FramebufferHandle, String> {
+        let framebuffer = FramebufferHandle(self.next_id());
+        self.record(DeviceCommand::CreateFramebuffer {
+            framebuffer,
+            targets: targets.to_vec(),
+        });
+        Ok(framebuffer)
+    }
+
+    fn import_framebuffer(&mut self, framebuffer: &Framebuffer) -> FramebufferHandle {
+        let id = framebuffer.id();
+        if let Some(&framebuffer) = self.imported_framebuffers.get(&id) {
+            return framebuffer;
+        }
+        let framebuffer = FramebufferHandle(self.next_id());
+        self.imported_framebuffers.insert(id, framebuffer);
+        self.record(DeviceCommand::ImportFramebuffer { framebuffer, id });
+        framebuffer
+    }
+
+    fn destroy_framebuffer(&mut self, framebuffer: FramebufferHandle) {
+        self.imported_framebuffers.retain(|_, imported| *imported != framebuffer);
+        self.record(DeviceCommand::DestroyFramebuffer(framebuffer));
+    }
+
+    fn bind_framebuffer(&mut self, framebuffer: Option<FramebufferHandle>) {
+        self.framebuffer = framebuffer;
+        self.record(DeviceCommand::BindFramebuffer(framebuffer));
+    }
+
+    fn get_framebuffer(&self) -> Option<FramebufferHandle> {
+        self.framebuffer
+    }
+
+    fn blit_framebuffer(
+        &mut self,
+        source: FramebufferHandle,
+        target: Option<FramebufferHandle>,
+        width: u32,
+        height: u32,
+        color: bool,
+        depth: bool,
+    ) {
+        self.framebuffer = target;
+        self.record(DeviceCommand::BlitFramebuffer {
+            source,
+            target,
+            width,
+            height,
+            color,
+            depth,
+        });
+    }
+
+    fn set_pipeline_state(&mut self, state: &PipelineState) {
+        self.record(DeviceCommand::SetPipelineState(*state));
+    }
+
+    fn set_viewport(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.viewport = (x, y, width, height);
+        self.record(DeviceCommand::SetViewport { x, y, width, height });
+    }
+
+    fn get_viewport(&self) -> (i32, i32, u32, u32) {
+        self.viewport
+    }
+
+    fn clear(&mut self, clear: &ClearState) {
+        self.record(DeviceCommand::Clear(*clear));
+    }
+
+    fn draw(&mut self, draw: &DrawCall) {
+        self.record(DeviceCommand::Draw(draw.clone()));
+    }
+
+    fn get_release_queue(&self) -> &ReleaseQueue {
+        &self.release_queue
+    }
+}
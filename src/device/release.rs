@@ -0,0 +1,109 @@
+use std::cell::{RefCell, RefMut};
+use std::rc::{Rc, Weak};
+
+use super::{BufferHandle, FramebufferHandle, RenderDevice, ShaderHandle, TextureHandle};
+
+/// The shared list behind a `ReleaseQueue`, which caches hold weakly to tell devices apart.
+type Queue = Rc<RefCell<Vec<Resource>>>;
+type WeakQueue = Weak<RefCell<Vec<Resource>>>;
+
+/// A buffer, texture, shader program or framebuffer created by a `RenderDevice`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Buffer(BufferHandle),
+    Texture(TextureHandle),
+    Shader(ShaderHandle),
+    Framebuffer(FramebufferHandle),
+}
+
+/// Resources whose owners were dropped, waiting for their device to destroy them in
+/// `RenderDevice::release_dropped`.
+///
+/// Owners such as `Mesh` don't hold the device they were drawn with, so they queue their
+/// resources here instead of destroying them directly.
+#[derive(Default)]
+pub struct ReleaseQueue {
+    resources: Queue,
+}
+
+impl ReleaseQueue {
+    /// Creates an empty queue.
+    pub fn new() -> ReleaseQueue {
+        ReleaseQueue::default()
+    }
+
+    /// Queues a resource to be destroyed.
+    pub fn push(&self, resource: Resource) {
+        self.resources.borrow_mut().push(resource);
+    }
+
+    /// Returns the queued resources and empties the queue.
+    pub fn take(&self) -> Vec<Resource> {
+        std::mem::take(&mut self.resources.borrow_mut())
+    }
+}
+
+/// The resources an owner created on one device.
+pub(crate) trait GpuResources {
+    /// Returns every resource to destroy once the owner no longer uses them.
+    fn resources(&self) -> Vec<Resource>;
+}
+
+/// Resources an owner creates lazily on each device that draws it.
+///
+/// Entries are keyed by the device's `ReleaseQueue`. Clearing the cache or dropping it
+/// queues the resources for their device to destroy; entries of devices that were
+/// dropped in the meantime are forgotten, their resources went with them.
+pub(crate) struct GpuCache<T: GpuResources> {
+    entries: RefCell<Vec<(WeakQueue, T)>>, // Resources per device queue
+}
+
+impl<T: GpuResources> GpuCache<T> {
+    /// Creates a cache without resources on any device.
+    pub(crate) const fn new() -> GpuCache<T> {
+        GpuCache {
+            entries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns the resources created on `device`, creating them the first time.
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - The device the resources are used with.
+    /// * `create` - Creates the resources on `device`.
+    pub(crate) fn get_or_create(
+        &self,
+        device: &mut dyn RenderDevice,
+        create: impl FnOnce(&mut dyn RenderDevice) -> T,
+    ) -> RefMut<'_, T> {
+        let queue = Rc::downgrade(&device.get_release_queue().resources);
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|(queue, _)| queue.strong_count() > 0);
+
+        let position = match entries.iter().position(|(entry, _)| entry.ptr_eq(&queue)) {
+            Some(position) => position,
+            None => {
+                entries.push((queue, create(device)));
+                entries.len() - 1
+            }
+        };
+        RefMut::map(entries, |entries| &mut entries[position].1)
+    }
+
+    /// Queues the resources on every device for destruction, so the next
+    /// `get_or_create` creates them anew.
+    pub(crate) fn clear(&self) {
+        for (queue, entry) in self.entries.borrow_mut().drain(..) {
+            if let Some(queue) = queue.upgrade() {
+                queue.borrow_mut().extend(entry.resources());
+            }
+        }
+    }
+}
+
+impl<T: GpuResources> Drop for GpuCache<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -1,3 +1,5 @@
 #![cfg_attr(debug_assertions, allow(dead_code, unused))]
 pub mod core;
+pub mod device;
 pub mod opengl;
+pub mod testing;
@@ -2,7 +2,7 @@ use gl::types::*;
 
 /// Represents the target types that can be used for OpenGL buffer objects.
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BufferTarget {
     ArrayBuffer = 0x8892,             // GL_ARRAY_BUFFER
     CopyReadBuffer = 0x8F36,          // GL_COPY_READ_BUFFER
@@ -17,7 +17,7 @@ pub enum BufferTarget {
 
 /// Represents the usage types for OpenGL buffer objects.
 #[repr(u32)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BufferUsage {
     StreamDraw = 0x88E0,  // GL_STREAM_DRAW
     StreamRead = 0x88E1,  // GL_STREAM_READ
@@ -72,12 +72,22 @@ impl BufferObject {
         unsafe { gl::BindBuffer(self.target as u32, 0) }
     }
 
+    /// Binds the buffer object to an indexed binding point of its target, such as the
+    /// binding point a uniform block reads from.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The binding point index.
+    pub fn bind_base(&self, index: u32) {
+        unsafe { gl::BindBufferBase(self.target as u32, index, self.id) }
+    }
+
     /// Uploads the provided data to the buffer object.
     ///
     /// # Arguments
     ///
     /// * `data` - A slice of data to be stored in the buffer. The type `T` must implement
-    ///           the `Copy` trait, as the function copies the data into the buffer.
+    ///   the `Copy` trait, as the function copies the data into the buffer.
     ///
     /// # Safety
     ///
@@ -93,6 +103,71 @@ impl BufferObject {
             );
         }
     }
+
+    /// Replaces part of the buffer's contents without reallocating it. The buffer must
+    /// be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Byte offset of the first byte to replace.
+    /// * `data` - The new contents, which must fit in the allocated storage.
+    pub fn sub_data<T>(&self, offset: usize, data: &[T]) {
+        unsafe {
+            gl::BufferSubData(
+                self.target as GLenum,
+                offset as GLintptr,
+                std::mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const GLvoid,
+            );
+        }
+    }
+
+    /// Allocates uninitialized storage for the buffer object, discarding its contents.
+    /// The buffer must be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the storage in bytes.
+    pub fn allocate(&self, size: usize) {
+        unsafe { gl::BufferData(self.target as GLenum, size as GLsizeiptr, std::ptr::null(), self.usage as GLenum) }
+    }
+
+    /// Maps the first `size` bytes of the buffer for reading. The buffer must be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - How many bytes to map.
+    /// * `read` - Receives the mapped bytes; the mapping ends when it returns.
+    ///
+    /// # Returns
+    ///
+    /// What `read` returned, or `None` if the buffer could not be mapped.
+    pub fn map_read<R>(&self, size: usize, read: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        unsafe {
+            let pointer = gl::MapBufferRange(self.target as GLenum, 0, size as GLsizeiptr, gl::MAP_READ_BIT);
+            if pointer.is_null() {
+                return None;
+            }
+            let result = read(std::slice::from_raw_parts(pointer as *const u8, size));
+            gl::UnmapBuffer(self.target as GLenum);
+            Some(result)
+        }
+    }
+
+    /// Returns the OpenGL name of the buffer object.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the target the buffer object is bound to.
+    pub fn get_target(&self) -> BufferTarget {
+        self.target
+    }
+
+    /// Labels the buffer object for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Buffer, self.id, label);
+    }
 }
 
 impl Drop for BufferObject {
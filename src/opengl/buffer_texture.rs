@@ -0,0 +1,86 @@
+use super::{BufferObject, BufferTarget, BufferUsage, TextureFormat};
+
+/// Represents an OpenGL buffer texture, whose texels are read straight from a buffer
+/// object.
+///
+/// Shaders read it as a `samplerBuffer` with `texelFetch`, which suits large arrays of
+/// per-vertex data that do not fit in uniforms.
+pub struct BufferTexture {
+    buffer: BufferObject,
+    format: TextureFormat,
+    id: u32,
+}
+
+impl BufferTexture {
+    /// Creates a buffer texture holding `data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - The format of each texel, such as `TextureFormat::Rgba32F`.
+    /// * `data` - Tightly packed texels matching the pixel format and type of `format`.
+    pub fn new<T>(format: TextureFormat, data: &[T]) -> BufferTexture {
+        let buffer = BufferObject::new(BufferTarget::TextureBuffer, BufferUsage::StaticDraw);
+        buffer.bind();
+        buffer.data(data);
+        buffer.unbind();
+
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_BUFFER, id);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, format.gl_formats().0, buffer.id());
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+        }
+        BufferTexture { buffer, format, id }
+    }
+
+    /// Replaces the texels of the texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Tightly packed texels matching the format of the texture.
+    pub fn set_data<T>(&self, data: &[T]) {
+        self.buffer.bind();
+        self.buffer.data(data);
+        self.buffer.unbind();
+    }
+
+    /// Binds the texture to the given texture unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Index of the texture unit (`0` is `GL_TEXTURE0`).
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_BUFFER, self.id);
+        }
+    }
+
+    /// Unbinds any buffer texture from the given texture unit.
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+        }
+    }
+
+    /// Returns the format of each texel.
+    pub fn get_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Labels the texture and its buffer for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Texture, self.id, label);
+        self.buffer.set_label(label);
+    }
+}
+
+impl Drop for BufferTexture {
+    /// Deletes the OpenGL texture when the `BufferTexture` is dropped. The buffer is
+    /// deleted with it.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
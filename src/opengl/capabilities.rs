@@ -0,0 +1,29 @@
+use std::ffi::CStr;
+
+use gl::types::*;
+
+/// Returns the OpenGL version of the current context as `(major, minor)`.
+pub fn get_version() -> (u32, u32) {
+    let (mut major, mut minor) = (0, 0);
+    unsafe {
+        gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
+    }
+    (major as u32, minor as u32)
+}
+
+/// Checks whether the current context is at least the given OpenGL version.
+pub fn is_version_at_least(major: u32, minor: u32) -> bool {
+    get_version() >= (major, minor)
+}
+
+/// Checks whether the current context exposes an extension, such as
+/// `GL_EXT_texture_compression_s3tc`.
+pub fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+    (0..count as GLuint).any(|index| {
+        let extension = unsafe { gl::GetStringi(gl::EXTENSIONS, index) };
+        !extension.is_null() && unsafe { CStr::from_ptr(extension as *const _) }.to_bytes() == name.as_bytes()
+    })
+}
@@ -0,0 +1,291 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use gl::types::*;
+use nalgebra_glm::Vec3;
+
+use super::TextureFormat;
+
+/// Represents an OpenGL cube map texture.
+///
+/// Faces are always stored in OpenGL order: `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z`.
+pub struct Cubemap {
+    size: u32,
+    id: u32,
+}
+
+impl Cubemap {
+    /// Creates a cube map with uninitialized storage, typically used as a render target.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Edge length in texels of each face at mip level 0.
+    /// * `format` - The storage format of the texels.
+    /// * `mip_levels` - Number of mip levels to allocate; `1` disables mipmapping.
+    pub fn new(size: u32, format: TextureFormat, mip_levels: u32) -> Cubemap {
+        let (internal_format, pixel_format, data_type) = format.gl_formats();
+        let cubemap = Cubemap::allocate(size);
+        cubemap.bind(0);
+        for level in 0..mip_levels.max(1) {
+            let level_size = (size >> level).max(1) as GLsizei;
+            for face in 0..6 {
+                unsafe {
+                    gl::TexImage2D(
+                        gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                        level as GLint,
+                        internal_format as GLint,
+                        level_size,
+                        level_size,
+                        0,
+                        pixel_format,
+                        data_type,
+                        std::ptr::null(),
+                    );
+                }
+            }
+        }
+        if mip_levels > 1 {
+            unsafe {
+                let min_filter = gl::LINEAR_MIPMAP_LINEAR as GLint;
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter);
+                gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAX_LEVEL, mip_levels as GLint - 1);
+            }
+        }
+        cubemap.unbind(0);
+        cubemap
+    }
+
+    /// Creates a cube map from six square images of the same size.
+    ///
+    /// # Arguments
+    ///
+    /// * `faces` - Paths to the face images, ordered `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z`.
+    ///
+    /// # Returns
+    ///
+    /// The uploaded `Cubemap`, or an `Err(String)` if an image cannot be decoded or
+    /// the faces are not square images of the same size.
+    pub fn from_images<P: AsRef<Path>>(faces: [P; 6]) -> Result<Cubemap, String> {
+        let mut images = Vec::with_capacity(6);
+        for face in faces.iter() {
+            let path = face.as_ref();
+            let image = image::open(path)
+                .map_err(|err| format!("Cannot load cubemap face {}: {err}", path.display()))?
+                .to_rgba8();
+            images.push(image);
+        }
+
+        let size = images[0].width();
+        if images.iter().any(|image| image.width() != size || image.height() != size) {
+            return Err(String::from("Cubemap faces must be square images of the same size"));
+        }
+
+        let cubemap = Cubemap::allocate(size);
+        cubemap.bind(0);
+        for (face, image) in images.iter().enumerate() {
+            unsafe {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                    0,
+                    gl::SRGB8_ALPHA8 as GLint,
+                    size as GLsizei,
+                    size as GLsizei,
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    image.as_ptr() as *const GLvoid,
+                );
+            }
+        }
+        cubemap.unbind(0);
+
+        Ok(cubemap)
+    }
+
+    /// Creates a cube map by projecting an equirectangular (latitude/longitude) image
+    /// onto the six faces.
+    ///
+    /// The image keeps its dynamic range, so `.hdr` environments are stored as
+    /// half-float texels.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the equirectangular image.
+    /// * `size` - Edge length in texels of each generated face.
+    ///
+    /// # Returns
+    ///
+    /// The uploaded `Cubemap`, or an `Err(String)` if the image cannot be decoded.
+    pub fn from_equirectangular<P: AsRef<Path>>(path: P, size: u32) -> Result<Cubemap, String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|err| format!("Cannot load environment {}: {err}", path.display()))?
+            .to_rgb32f();
+
+        let (width, height) = image.dimensions();
+        Ok(Cubemap::from_equirectangular_data(image.as_raw(), width, height, size))
+    }
+
+    /// Creates a cube map by projecting decoded equirectangular texels onto the six faces,
+    /// stored as half-float texels.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels` - Linear RGB texels laid out row by row from the top of the image.
+    /// * `width` - Width of the image in texels.
+    /// * `height` - Height of the image in texels.
+    /// * `size` - Edge length in texels of each generated face.
+    pub fn from_equirectangular_data(pixels: &[f32], width: u32, height: u32, size: u32) -> Cubemap {
+        let cubemap = Cubemap::allocate(size);
+        cubemap.bind(0);
+        for face in 0..6 {
+            let texels = project_equirectangular(pixels, width, height, face, size);
+            unsafe {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    0,
+                    gl::RGB16F as GLint,
+                    size as GLsizei,
+                    size as GLsizei,
+                    0,
+                    gl::RGB,
+                    gl::FLOAT,
+                    texels.as_ptr() as *const GLvoid,
+                );
+            }
+        }
+        cubemap.unbind(0);
+        cubemap
+    }
+
+    /// Generates the texture object and sets the sampling state shared by every cube map.
+    fn allocate(size: u32) -> Cubemap {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as GLint);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+        Cubemap { id, size }
+    }
+
+    /// Binds the cube map to the given texture unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Index of the texture unit (`0` is `GL_TEXTURE0`).
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.id);
+        }
+    }
+
+    /// Unbinds any cube map from the given texture unit.
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+        }
+    }
+
+    /// Generates the full mip chain from level 0 and enables trilinear filtering.
+    pub fn generate_mipmaps(&self) {
+        self.bind(0);
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+            let min_filter = gl::LINEAR_MIPMAP_LINEAR as GLint;
+            gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter);
+        }
+        self.unbind(0);
+    }
+
+    /// Returns the OpenGL name of the cube map.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the edge length in texels of each face.
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Labels the cube map for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Texture, self.id, label);
+    }
+}
+
+impl Drop for Cubemap {
+    /// Deletes the OpenGL texture when the `Cubemap` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+/// Returns the world-space direction through the centre of a cube map texel.
+///
+/// `s` and `t` are the face coordinates in `[-1, 1]`, with `t = -1` on the first row.
+pub(crate) fn cubemap_direction(face: u32, s: f32, t: f32) -> Vec3 {
+    let direction = match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    };
+    direction.normalize()
+}
+
+/// Resamples one cube map face from an RGB equirectangular image.
+fn project_equirectangular(pixels: &[f32], width: u32, height: u32, face: u32, size: u32) -> Vec<f32> {
+    let mut texels = Vec::with_capacity((size * size * 3) as usize);
+
+    for y in 0..size {
+        for x in 0..size {
+            let s = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+            let t = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+            let direction = cubemap_direction(face, s, t);
+
+            let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+            let v = 0.5 - direction.y.clamp(-1.0, 1.0).asin() / PI;
+            texels.extend_from_slice(&sample_bilinear(pixels, width, height, u, v));
+        }
+    }
+
+    texels
+}
+
+/// Bilinearly samples an RGB image, wrapping horizontally and clamping vertically.
+fn sample_bilinear(pixels: &[f32], width: u32, height: u32, u: f32, v: f32) -> [f32; 3] {
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, height as f32 - 1.0);
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let column = |x: f32| (x as i64).rem_euclid(width as i64) as usize;
+    let row = |y: f32| (y as usize).min(height as usize - 1);
+    let texel = |x: usize, y: usize| {
+        let index = (y * width as usize + x) * 3;
+        [pixels[index], pixels[index + 1], pixels[index + 2]]
+    };
+
+    let (c0, c1) = (column(x0), column(x0 + 1.0));
+    let (r0, r1) = (row(y0), row(y0 + 1.0));
+    let (a, b, c, d) = (texel(c0, r0), texel(c1, r0), texel(c0, r1), texel(c1, r1));
+
+    let mut result = [0.0; 3];
+    for i in 0..3 {
+        let top = a[i] + (b[i] - a[i]) * fx;
+        let bottom = c[i] + (d[i] - c[i]) * fx;
+        result[i] = top + (bottom - top) * fy;
+    }
+    result
+}
@@ -0,0 +1,130 @@
+use std::ffi::{c_void, CStr, CString};
+
+/// Log target of messages reported by the OpenGL driver.
+pub const DEBUG_LOG_TARGET: &str = "foux::opengl";
+
+/// Represents the kind of OpenGL object a label is attached to.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ObjectKind {
+    Buffer = 0x82E0,       // GL_BUFFER
+    Program = 0x82E2,      // GL_PROGRAM
+    Query = 0x82E3,        // GL_QUERY
+    VertexArray = 0x8074,  // GL_VERTEX_ARRAY
+    Texture = 0x1702,      // GL_TEXTURE
+    Renderbuffer = 0x8D41, // GL_RENDERBUFFER
+    Framebuffer = 0x8D40,  // GL_FRAMEBUFFER
+}
+
+/// Checks whether the context exposes `KHR_debug`, either as OpenGL 4.3 core or as an
+/// extension.
+pub fn is_debug_output_supported() -> bool {
+    gl::DebugMessageCallback::is_loaded()
+}
+
+/// Forwards OpenGL debug messages into the `log` facade.
+///
+/// Messages are logged under the `foux::opengl` target with their source, type and id as
+/// key-values. Severity maps to the log level: high to `error`, medium to `warn`, low
+/// to `info` and notifications to `trace`. Output is synchronous, so a message is logged
+/// from inside the call that caused it, which keeps backtraces meaningful.
+///
+/// # Returns
+///
+/// `false` if the context does not support `KHR_debug`, in which case nothing changes.
+pub fn enable_debug_output() -> bool {
+    if !is_debug_output_supported() {
+        return false;
+    }
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
+    }
+    true
+}
+
+/// Drains the OpenGL error flags, for contexts without `KHR_debug`.
+///
+/// # Returns
+///
+/// `Ok(())` if no error was raised since the last check, or an `Err(String)` naming
+/// every raised error.
+pub fn check_error() -> Result<(), String> {
+    let mut errors = Vec::new();
+    loop {
+        let error = match unsafe { gl::GetError() } {
+            gl::NO_ERROR => break,
+            gl::INVALID_ENUM => "GL_INVALID_ENUM",
+            gl::INVALID_VALUE => "GL_INVALID_VALUE",
+            gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+            gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+            gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+            _ => "unknown OpenGL error",
+        };
+        errors.push(error);
+        // Lost contexts report errors forever; stop before looping endlessly.
+        if errors.len() >= 16 {
+            break;
+        }
+    }
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors.join(", ")),
+    }
+}
+
+/// Attaches a human-readable label to an OpenGL object, so debug messages and graphics
+/// debuggers name it. Does nothing without `KHR_debug`.
+///
+/// # Arguments
+///
+/// * `kind` - The kind of object `id` names.
+/// * `id` - The OpenGL name of the object.
+/// * `label` - The label. Interior NUL bytes truncate it.
+pub(crate) fn label_object(kind: ObjectKind, id: u32, label: &str) {
+    if !gl::ObjectLabel::is_loaded() {
+        return;
+    }
+    let label = label.split('\0').next().unwrap_or_default();
+    let label = CString::new(label).unwrap_or_default();
+    unsafe { gl::ObjectLabel(kind as u32, id, -1, label.as_ptr()) }
+}
+
+extern "system" fn debug_callback(
+    source: u32,
+    kind: u32,
+    id: u32,
+    severity: u32,
+    _length: i32,
+    message: *const std::ffi::c_char,
+    _user_param: *mut c_void,
+) {
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        _ => log::Level::Trace,
+    };
+    let source = match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window_system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader_compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third_party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let kind = match kind {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined_behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push_group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop_group",
+        _ => "other",
+    };
+    log::log!(target: DEBUG_LOG_TARGET, level, source, kind, id; "{message}");
+}
@@ -0,0 +1,41 @@
+use super::{BufferObject, BufferTarget};
+
+/// Captures the outputs of a `ShaderProgram::with_feedback` program into a buffer while
+/// drawing points, with rasterization disabled.
+///
+/// Capturing ends when the `FeedbackCapture` is dropped.
+pub struct FeedbackCapture {
+    _private: (),
+}
+
+impl FeedbackCapture {
+    /// Binds `buffer` as the capture target and starts capturing points.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - Receives the captured varyings, which must fit in its storage.
+    pub fn begin(buffer: &BufferObject) -> FeedbackCapture {
+        unsafe {
+            gl::BindBufferBase(BufferTarget::TransformFeedbackBuffer as u32, 0, buffer.id());
+            gl::Enable(gl::RASTERIZER_DISCARD);
+            gl::BeginTransformFeedback(gl::POINTS);
+        }
+        FeedbackCapture { _private: () }
+    }
+
+    /// Checks whether the driver exposes transform feedback.
+    pub fn is_supported() -> bool {
+        gl::BeginTransformFeedback::is_loaded() && gl::TransformFeedbackVaryings::is_loaded()
+    }
+}
+
+impl Drop for FeedbackCapture {
+    /// Ends the capture and re-enables rasterization.
+    fn drop(&mut self) {
+        unsafe {
+            gl::EndTransformFeedback();
+            gl::Disable(gl::RASTERIZER_DISCARD);
+            gl::BindBufferBase(BufferTarget::TransformFeedbackBuffer as u32, 0, 0);
+        }
+    }
+}
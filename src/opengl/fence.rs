@@ -0,0 +1,45 @@
+/// Represents an OpenGL fence sync object, signaled once the GPU has finished every
+/// command issued before it.
+pub struct Fence {
+    sync: gl::types::GLsync,
+}
+
+impl Fence {
+    /// Inserts a fence after the commands issued so far.
+    pub fn new() -> Fence {
+        let sync = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        Fence { sync }
+    }
+
+    /// Checks whether the GPU has reached the fence, without waiting.
+    pub fn is_signaled(&self) -> bool {
+        self.wait(0)
+    }
+
+    /// Waits for the GPU to reach the fence.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - How long to wait, in nanoseconds. `0` only polls.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the fence was signaled within the timeout.
+    pub fn wait(&self, timeout: u64) -> bool {
+        let status = unsafe { gl::ClientWaitSync(self.sync, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+    }
+}
+
+impl Default for Fence {
+    fn default() -> Self {
+        Fence::new()
+    }
+}
+
+impl Drop for Fence {
+    /// Deletes the OpenGL sync object when the `Fence` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSync(self.sync) }
+    }
+}
@@ -0,0 +1,197 @@
+use super::{BufferObject, Cubemap, Renderbuffer, Texture};
+
+/// Represents the attachment points of a framebuffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attachment {
+    /// The color attachment with the given index (`GL_COLOR_ATTACHMENTi`).
+    Color(u32),
+    Depth,
+    DepthStencil,
+}
+
+impl Attachment {
+    fn gl_enum(self) -> u32 {
+        match self {
+            Attachment::Color(index) => gl::COLOR_ATTACHMENT0 + index,
+            Attachment::Depth => gl::DEPTH_ATTACHMENT,
+            Attachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+        }
+    }
+}
+
+/// Represents an OpenGL framebuffer object used for offscreen rendering.
+///
+/// Attachments are borrowed, not owned; the textures and renderbuffers must outlive
+/// any rendering into the framebuffer.
+pub struct Framebuffer {
+    id: u32,
+}
+
+impl Framebuffer {
+    /// Creates a new framebuffer with no attachments.
+    pub fn new() -> Framebuffer {
+        Framebuffer::default()
+    }
+
+    /// Binds the framebuffer as the target for both drawing and reading.
+    pub fn bind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, self.id) }
+    }
+
+    /// Binds the default framebuffer, i.e. the window.
+    pub fn unbind(&self) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) }
+    }
+
+    /// Binds `target`, or the default framebuffer when `target` is `None`.
+    pub fn bind_target(target: Option<&Framebuffer>) {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, target.map_or(0, |target| target.id)) }
+    }
+
+    /// Reads RGBA8 pixels from the first color attachment of the bound framebuffer.
+    ///
+    /// # Returns
+    ///
+    /// `width * height * 4` bytes, with the bottom row first as OpenGL stores them.
+    pub fn read_pixels(width: u32, height: u32) -> Vec<u8> {
+        let mut pixels = vec![0u8; width as usize * height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        pixels
+    }
+
+    /// Starts copying RGBA8 pixels from the bound framebuffer into a pixel pack buffer.
+    ///
+    /// The copy runs asynchronously on the GPU; place a `Fence` after it and map the
+    /// buffer once the fence is signaled to avoid stalling.
+    ///
+    /// # Arguments
+    ///
+    /// * `buffer` - A `PixelPackBuffer` with at least `width * height * 4` bytes of storage.
+    /// * `width` - Width of the region in pixels.
+    /// * `height` - Height of the region in pixels.
+    pub fn read_pixels_into(buffer: &BufferObject, width: u32, height: u32) {
+        buffer.bind();
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, std::ptr::null_mut());
+        }
+        buffer.unbind();
+    }
+
+    /// Attaches a mip level of a texture. The framebuffer must be bound.
+    pub fn attach_texture(&self, attachment: Attachment, texture: &Texture, level: i32) {
+        self.attach_image(attachment, gl::TEXTURE_2D, texture.id(), level);
+    }
+
+    /// Attaches a mip level of one cube map face. The framebuffer must be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `face` - Index of the face, in `+X`, `-X`, `+Y`, `-Y`, `+Z`, `-Z` order.
+    pub fn attach_cubemap_face(&self, attachment: Attachment, cubemap: &Cubemap, face: u32, level: i32) {
+        self.attach_image(attachment, gl::TEXTURE_CUBE_MAP_POSITIVE_X + face, cubemap.id(), level);
+    }
+
+    /// Attaches a mip level of a texture by name. The framebuffer must be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - `GL_TEXTURE_2D`, or the `GL_TEXTURE_CUBE_MAP_*` target of a cube map face.
+    /// * `id` - OpenGL name of the texture.
+    pub(crate) fn attach_image(&self, attachment: Attachment, target: u32, id: u32, level: i32) {
+        unsafe { gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment.gl_enum(), target, id, level) }
+    }
+
+    /// Attaches a renderbuffer. The framebuffer must be bound.
+    pub fn attach_renderbuffer(&self, attachment: Attachment, renderbuffer: &Renderbuffer) {
+        unsafe {
+            gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment.gl_enum(), gl::RENDERBUFFER, renderbuffer.id());
+        }
+    }
+
+    /// Selects how many color attachments fragment shader outputs are written to,
+    /// starting from `Color(0)`. The framebuffer must be bound.
+    pub fn set_draw_buffers(&self, count: u32) {
+        let buffers: Vec<u32> = (0..count).map(|index| gl::COLOR_ATTACHMENT0 + index).collect();
+        unsafe { gl::DrawBuffers(count as i32, buffers.as_ptr()) }
+    }
+
+    /// Copies a region from this framebuffer into `target`, or into the window if
+    /// `target` is `None`, without scaling. `target` is left bound afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The destination framebuffer, or `None` for the default framebuffer.
+    /// * `width` - Width of the copied region in pixels.
+    /// * `height` - Height of the copied region in pixels.
+    /// * `color` - Whether to copy color attachment `0`.
+    /// * `depth` - Whether to copy the depth attachment. Both depth formats must match.
+    pub fn blit_to(&self, target: Option<&Framebuffer>, width: u32, height: u32, color: bool, depth: bool) {
+        let mut mask = 0;
+        if color {
+            mask |= gl::COLOR_BUFFER_BIT;
+        }
+        if depth {
+            mask |= gl::DEPTH_BUFFER_BIT;
+        }
+
+        let (width, height) = (width as i32, height as i32);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.map_or(0, |target| target.id));
+            gl::BlitFramebuffer(0, 0, width, height, 0, 0, width, height, mask, gl::NEAREST);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.map_or(0, |target| target.id));
+        }
+    }
+
+    /// Checks that the bound framebuffer is complete.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the framebuffer can be rendered to, or an `Err(String)` naming the
+    /// OpenGL status otherwise.
+    pub fn check_status(&self) -> Result<(), String> {
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            return Ok(());
+        }
+        Err(format!("Framebuffer is incomplete: status 0x{status:X}"))
+    }
+
+    /// Returns the OpenGL name of the framebuffer.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Labels the framebuffer for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Framebuffer, self.id, label);
+    }
+}
+
+impl Default for Framebuffer {
+    /// Creates a default `Framebuffer` by generating a new OpenGL framebuffer object.
+    fn default() -> Self {
+        let mut id = 0;
+        unsafe { gl::GenFramebuffers(1, &mut id) };
+        Framebuffer { id }
+    }
+}
+
+impl Drop for Framebuffer {
+    /// Deletes the OpenGL framebuffer when the `Framebuffer` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteFramebuffers(1, &self.id) }
+    }
+}
@@ -1,9 +1,41 @@
 mod buffer_object;
+mod buffer_texture;
+mod capabilities;
+mod cubemap;
+mod debug;
+mod feedback_capture;
+mod fence;
+mod framebuffer;
+mod pipeline_state;
+mod query;
+mod renderbuffer;
+mod sampler;
 mod shader_program;
+mod texture;
+mod topology;
 mod vertex_array_object;
 mod vertex_attrib_pointer;
+mod vertex_layout;
 
 pub use buffer_object::{BufferObject, BufferTarget, BufferUsage};
+pub use buffer_texture::BufferTexture;
+pub use capabilities::{get_version, has_extension, is_version_at_least};
+pub use cubemap::Cubemap;
+pub use debug::{check_error, enable_debug_output, is_debug_output_supported, ObjectKind, DEBUG_LOG_TARGET};
+pub(crate) use debug::label_object;
+pub use feedback_capture::FeedbackCapture;
+pub use fence::Fence;
+pub use framebuffer::{Attachment, Framebuffer};
+pub use pipeline_state::{
+    BlendEquation, BlendFactor, BlendState, ClearState, CompareFunction, CullMode, FrontFace, PipelineState,
+    PolygonMode, StencilOp, StencilState,
+};
+pub use query::{Query, QueryTarget};
+pub use renderbuffer::Renderbuffer;
+pub use sampler::{Sampler, SamplerObject};
 pub use shader_program::ShaderProgram;
+pub use texture::{CompressedFormat, Texture, TextureFilter, TextureFormat, TextureWrap};
+pub use topology::{IndexType, Topology};
 pub use vertex_array_object::VertexArrayObject;
 pub use vertex_attrib_pointer::{VertexAttribPointer, DataType};
+pub use vertex_layout::{VertexAttribute, VertexLayout};
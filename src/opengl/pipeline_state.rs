@@ -0,0 +1,335 @@
+/// Represents the comparison used by the depth and stencil tests.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareFunction {
+    Never = 0x0200,        // GL_NEVER
+    Less = 0x0201,         // GL_LESS
+    Equal = 0x0202,        // GL_EQUAL
+    LessEqual = 0x0203,    // GL_LEQUAL
+    Greater = 0x0204,      // GL_GREATER
+    NotEqual = 0x0205,     // GL_NOTEQUAL
+    GreaterEqual = 0x0206, // GL_GEQUAL
+    Always = 0x0207,       // GL_ALWAYS
+}
+
+/// Represents which faces are discarded before rasterization.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+/// Represents the winding order of front-facing triangles.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontFace {
+    Clockwise = 0x0900,        // GL_CW
+    CounterClockwise = 0x0901, // GL_CCW
+}
+
+/// Represents the factors a blend equation scales the source and destination by.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero = 0x0000,                  // GL_ZERO
+    One = 0x0001,                   // GL_ONE
+    SrcColor = 0x0300,              // GL_SRC_COLOR
+    OneMinusSrcColor = 0x0301,      // GL_ONE_MINUS_SRC_COLOR
+    SrcAlpha = 0x0302,              // GL_SRC_ALPHA
+    OneMinusSrcAlpha = 0x0303,      // GL_ONE_MINUS_SRC_ALPHA
+    DstAlpha = 0x0304,              // GL_DST_ALPHA
+    OneMinusDstAlpha = 0x0305,      // GL_ONE_MINUS_DST_ALPHA
+    DstColor = 0x0306,              // GL_DST_COLOR
+    OneMinusDstColor = 0x0307,      // GL_ONE_MINUS_DST_COLOR
+    SrcAlphaSaturate = 0x0308,      // GL_SRC_ALPHA_SATURATE
+    ConstantColor = 0x8001,         // GL_CONSTANT_COLOR
+    OneMinusConstantColor = 0x8002, // GL_ONE_MINUS_CONSTANT_COLOR
+    ConstantAlpha = 0x8003,         // GL_CONSTANT_ALPHA
+    OneMinusConstantAlpha = 0x8004, // GL_ONE_MINUS_CONSTANT_ALPHA
+}
+
+/// Represents how the scaled source and destination are combined.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendEquation {
+    Add = 0x8006,             // GL_FUNC_ADD
+    Subtract = 0x800A,        // GL_FUNC_SUBTRACT
+    ReverseSubtract = 0x800B, // GL_FUNC_REVERSE_SUBTRACT
+    Min = 0x8007,             // GL_MIN
+    Max = 0x8008,             // GL_MAX
+}
+
+/// Represents the action taken on the stencil buffer after a test.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep = 0x1E00,          // GL_KEEP
+    Zero = 0x0000,          // GL_ZERO
+    Replace = 0x1E01,       // GL_REPLACE
+    Increment = 0x1E02,     // GL_INCR
+    IncrementWrap = 0x8507, // GL_INCR_WRAP
+    Decrement = 0x1E03,     // GL_DECR
+    DecrementWrap = 0x8508, // GL_DECR_WRAP
+    Invert = 0x150A,        // GL_INVERT
+}
+
+/// Represents how polygons are rasterized.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolygonMode {
+    Point = 0x1B00, // GL_POINT
+    Line = 0x1B01,  // GL_LINE
+    Fill = 0x1B02,  // GL_FILL
+}
+
+/// Blending between the fragment color (source) and the framebuffer (destination).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlendState {
+    pub color_equation: BlendEquation,
+    pub src_color: BlendFactor,
+    pub dst_color: BlendFactor,
+    pub alpha_equation: BlendEquation,
+    pub src_alpha: BlendFactor,
+    pub dst_alpha: BlendFactor,
+}
+
+impl BlendState {
+    /// Creates a blend state applying the same equation and factors to color and alpha.
+    pub fn new(equation: BlendEquation, src: BlendFactor, dst: BlendFactor) -> BlendState {
+        BlendState {
+            color_equation: equation,
+            src_color: src,
+            dst_color: dst,
+            alpha_equation: equation,
+            src_alpha: src,
+            dst_alpha: dst,
+        }
+    }
+
+    /// Returns classic `src * alpha + dst * (1 - alpha)` blending.
+    pub fn alpha() -> BlendState {
+        BlendState::new(BlendEquation::Add, BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha)
+    }
+
+    /// Returns `src * alpha + dst` blending, used for glows and particles.
+    pub fn additive() -> BlendState {
+        BlendState::new(BlendEquation::Add, BlendFactor::SrcAlpha, BlendFactor::One)
+    }
+
+    /// Returns `src * dst` blending.
+    pub fn multiply() -> BlendState {
+        BlendState::new(BlendEquation::Add, BlendFactor::DstColor, BlendFactor::Zero)
+    }
+}
+
+/// Stencil test and update, applied to both front and back faces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StencilState {
+    pub compare: CompareFunction,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    /// Action when the stencil test fails.
+    pub stencil_fail: StencilOp,
+    /// Action when the stencil test passes but the depth test fails.
+    pub depth_fail: StencilOp,
+    /// Action when both tests pass.
+    pub pass: StencilOp,
+}
+
+impl Default for StencilState {
+    /// Always passes and never modifies the stencil buffer.
+    fn default() -> Self {
+        StencilState {
+            compare: CompareFunction::Always,
+            reference: 0,
+            read_mask: 0xFF,
+            write_mask: 0xFF,
+            stencil_fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+/// Describes the fixed-function state a draw call is issued with.
+///
+/// Every draw applies its complete state, so draws never depend on what the previous
+/// one left behind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PipelineState {
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_compare: CompareFunction,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    /// Blending, or `None` to overwrite the framebuffer.
+    pub blend: Option<BlendState>,
+    /// Stencil testing, or `None` to disable it.
+    pub stencil: Option<StencilState>,
+    pub polygon_mode: PolygonMode,
+    /// Which of the red, green, blue and alpha channels are written.
+    pub color_mask: [bool; 4],
+}
+
+impl PipelineState {
+    /// Returns the state for opaque geometry: depth tested and written, no blending,
+    /// no culling.
+    pub fn opaque() -> PipelineState {
+        PipelineState {
+            depth_test: true,
+            depth_write: true,
+            depth_compare: CompareFunction::Less,
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            blend: None,
+            stencil: None,
+            polygon_mode: PolygonMode::Fill,
+            color_mask: [true; 4],
+        }
+    }
+
+    /// Returns the state for alpha blended geometry: depth tested but not written.
+    pub fn transparent() -> PipelineState {
+        PipelineState {
+            depth_write: false,
+            blend: Some(BlendState::alpha()),
+            ..PipelineState::opaque()
+        }
+    }
+
+    /// Returns the state for fullscreen passes: no depth test or write.
+    pub fn fullscreen() -> PipelineState {
+        PipelineState {
+            depth_test: false,
+            depth_write: false,
+            ..PipelineState::opaque()
+        }
+    }
+
+    /// Returns a copy drawing outlines instead of filled polygons.
+    pub fn wireframe(self) -> PipelineState {
+        PipelineState {
+            polygon_mode: PolygonMode::Line,
+            ..self
+        }
+    }
+
+    /// Applies the complete state to the OpenGL context.
+    pub fn apply(&self) {
+        unsafe {
+            set_capability(gl::DEPTH_TEST, self.depth_test);
+            gl::DepthMask(gl_bool(self.depth_write));
+            gl::DepthFunc(self.depth_compare as u32);
+
+            match self.cull_mode {
+                CullMode::None => gl::Disable(gl::CULL_FACE),
+                mode => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(match mode {
+                        CullMode::Front => gl::FRONT,
+                        CullMode::Back => gl::BACK,
+                        _ => gl::FRONT_AND_BACK,
+                    });
+                }
+            }
+            gl::FrontFace(self.front_face as u32);
+
+            set_capability(gl::BLEND, self.blend.is_some());
+            if let Some(blend) = self.blend {
+                gl::BlendEquationSeparate(blend.color_equation as u32, blend.alpha_equation as u32);
+                gl::BlendFuncSeparate(
+                    blend.src_color as u32,
+                    blend.dst_color as u32,
+                    blend.src_alpha as u32,
+                    blend.dst_alpha as u32,
+                );
+            }
+
+            set_capability(gl::STENCIL_TEST, self.stencil.is_some());
+            if let Some(stencil) = self.stencil {
+                gl::StencilFunc(stencil.compare as u32, stencil.reference, stencil.read_mask);
+                gl::StencilMask(stencil.write_mask);
+                gl::StencilOp(stencil.stencil_fail as u32, stencil.depth_fail as u32, stencil.pass as u32);
+            }
+
+            gl::PolygonMode(gl::FRONT_AND_BACK, self.polygon_mode as u32);
+
+            let [red, green, blue, alpha] = self.color_mask.map(gl_bool);
+            gl::ColorMask(red, green, blue, alpha);
+        }
+    }
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        PipelineState::opaque()
+    }
+}
+
+/// Describes which framebuffer attachments a clear resets, and to what.
+///
+/// `None` leaves the matching attachment untouched.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClearState {
+    pub color: Option<[f32; 4]>,
+    pub depth: Option<f32>,
+    pub stencil: Option<i32>,
+}
+
+impl ClearState {
+    /// Returns a clear resetting color, depth to `1.0` and stencil to `0`.
+    pub fn all(red: f32, green: f32, blue: f32, alpha: f32) -> ClearState {
+        ClearState {
+            color: Some([red, green, blue, alpha]),
+            depth: Some(1.0),
+            stencil: Some(0),
+        }
+    }
+
+    /// Clears the bound framebuffer.
+    ///
+    /// Depth, stencil and color writes are re-enabled first, since masked writes would
+    /// otherwise also mask the clear.
+    pub fn apply(&self) {
+        let mut mask = 0;
+        unsafe {
+            if let Some([red, green, blue, alpha]) = self.color {
+                gl::ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+                gl::ClearColor(red, green, blue, alpha);
+                mask |= gl::COLOR_BUFFER_BIT;
+            }
+            if let Some(depth) = self.depth {
+                gl::DepthMask(gl::TRUE);
+                gl::ClearDepth(depth as f64);
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+            if let Some(stencil) = self.stencil {
+                gl::StencilMask(0xFF);
+                gl::ClearStencil(stencil);
+                mask |= gl::STENCIL_BUFFER_BIT;
+            }
+            if mask != 0 {
+                gl::Clear(mask);
+            }
+        }
+    }
+}
+
+fn gl_bool(value: bool) -> u8 {
+    if value {
+        gl::TRUE
+    } else {
+        gl::FALSE
+    }
+}
+
+unsafe fn set_capability(capability: u32, enabled: bool) {
+    if enabled {
+        gl::Enable(capability);
+    } else {
+        gl::Disable(capability);
+    }
+}
@@ -0,0 +1,75 @@
+/// Represents what a query object measures.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QueryTarget {
+    TimeElapsed = 0x88BF,         // GL_TIME_ELAPSED
+    Timestamp = 0x8E28,           // GL_TIMESTAMP
+    SamplesPassed = 0x8914,       // GL_SAMPLES_PASSED
+    AnySamplesPassed = 0x8C2F,    // GL_ANY_SAMPLES_PASSED
+    PrimitivesGenerated = 0x8C87, // GL_PRIMITIVES_GENERATED
+}
+
+/// Represents an OpenGL query object.
+///
+/// Results arrive asynchronously; check `is_available` a few frames after issuing the
+/// query instead of reading the result right away, which would stall until the GPU
+/// catches up.
+pub struct Query {
+    id: u32,
+}
+
+impl Query {
+    /// Creates a new query object.
+    pub fn new() -> Query {
+        let mut id = 0;
+        unsafe { gl::GenQueries(1, &mut id) };
+        Query { id }
+    }
+
+    /// Starts measuring `target`. Only one query per target may be active at a time,
+    /// so `TimeElapsed` queries cannot be nested; use `record_timestamp` for that.
+    pub fn begin(&self, target: QueryTarget) {
+        unsafe { gl::BeginQuery(target as u32, self.id) }
+    }
+
+    /// Stops measuring `target`.
+    pub fn end(&self, target: QueryTarget) {
+        unsafe { gl::EndQuery(target as u32) }
+    }
+
+    /// Records the GPU time, in nanoseconds, at which all previous commands have completed.
+    pub fn record_timestamp(&self) {
+        unsafe { gl::QueryCounter(self.id, gl::TIMESTAMP) }
+    }
+
+    /// Checks whether the result can be read without waiting for the GPU.
+    pub fn is_available(&self) -> bool {
+        let mut available = 0;
+        unsafe { gl::GetQueryObjectiv(self.id, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+        available != 0
+    }
+
+    /// Returns the result of the query, waiting for the GPU if it is not available yet.
+    ///
+    /// # Returns
+    ///
+    /// Nanoseconds for time queries, or a count for sample and primitive queries.
+    pub fn get_result(&self) -> u64 {
+        let mut result = 0;
+        unsafe { gl::GetQueryObjectui64v(self.id, gl::QUERY_RESULT, &mut result) };
+        result
+    }
+}
+
+impl Default for Query {
+    fn default() -> Self {
+        Query::new()
+    }
+}
+
+impl Drop for Query {
+    /// Deletes the OpenGL query object when the `Query` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteQueries(1, &self.id) }
+    }
+}
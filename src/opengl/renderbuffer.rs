@@ -0,0 +1,44 @@
+use super::TextureFormat;
+
+/// Represents an OpenGL renderbuffer, a render target that is never sampled.
+pub struct Renderbuffer {
+    id: u32,
+}
+
+impl Renderbuffer {
+    /// Creates a renderbuffer with storage of the given size and format.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the renderbuffer in pixels.
+    /// * `height` - Height of the renderbuffer in pixels.
+    /// * `format` - The storage format, usually `Depth24` or `Depth24Stencil8`.
+    pub fn new(width: u32, height: u32, format: TextureFormat) -> Renderbuffer {
+        let mut id = 0;
+        let (internal_format, _, _) = format.gl_formats();
+        unsafe {
+            gl::GenRenderbuffers(1, &mut id);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, id);
+            gl::RenderbufferStorage(gl::RENDERBUFFER, internal_format, width as i32, height as i32);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+        Renderbuffer { id }
+    }
+
+    /// Returns the OpenGL name of the renderbuffer.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Labels the renderbuffer for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Renderbuffer, self.id, label);
+    }
+}
+
+impl Drop for Renderbuffer {
+    /// Deletes the OpenGL renderbuffer when the `Renderbuffer` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteRenderbuffers(1, &self.id) }
+    }
+}
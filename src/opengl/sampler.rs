@@ -0,0 +1,75 @@
+use gl::types::*;
+
+use super::{TextureFilter, TextureWrap};
+
+/// How a texture unit filters and wraps what it samples, overriding the parameters of the
+/// texture bound to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Sampler {
+    /// Filter used when the texture is minified.
+    pub min_filter: TextureFilter,
+    /// Filter used when the texture is magnified.
+    pub mag_filter: TextureFilter,
+    /// Wrap mode on both axes.
+    pub wrap: TextureWrap,
+}
+
+impl Sampler {
+    /// Creates a sampler with the given filters and wrap mode.
+    pub fn new(min_filter: TextureFilter, mag_filter: TextureFilter, wrap: TextureWrap) -> Sampler {
+        Sampler {
+            min_filter,
+            mag_filter,
+            wrap,
+        }
+    }
+
+    /// Returns a sampler reading the nearest texel of level 0, such as for pixel art or
+    /// render targets read back one texel per pixel.
+    pub fn nearest(wrap: TextureWrap) -> Sampler {
+        Sampler::new(TextureFilter::Nearest, TextureFilter::Nearest, wrap)
+    }
+}
+
+/// Represents an OpenGL sampler object.
+pub struct SamplerObject {
+    id: u32,
+}
+
+impl SamplerObject {
+    /// Creates a sampler object with the parameters of `sampler`.
+    pub fn new(sampler: &Sampler) -> SamplerObject {
+        let mut id = 0;
+        unsafe {
+            gl::GenSamplers(1, &mut id);
+            gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, sampler.min_filter as GLint);
+            gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, sampler.mag_filter as GLint);
+            for axis in [gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+                gl::SamplerParameteri(id, axis, sampler.wrap as GLint);
+            }
+        }
+        SamplerObject { id }
+    }
+
+    /// Binds the sampler object to the given texture unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Index of the texture unit (`0` is `GL_TEXTURE0`).
+    pub fn bind(&self, unit: u32) {
+        unsafe { gl::BindSampler(unit, self.id) }
+    }
+
+    /// Unbinds any sampler object from the given texture unit, so it samples with the
+    /// parameters of its texture again.
+    pub fn unbind(unit: u32) {
+        unsafe { gl::BindSampler(unit, 0) }
+    }
+}
+
+impl Drop for SamplerObject {
+    /// Deletes the OpenGL sampler object when the `SamplerObject` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteSamplers(1, &self.id) }
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::ffi::{c_void, CString};
 use std::ptr;
 
-use nalgebra_glm::Mat4;
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
 
 /// Represents an OpenGL shader program, providing utilities for shader management
 /// and uniform variable handling.
@@ -25,27 +25,69 @@ impl ShaderProgram {
     ///
     /// This function will panic if shader compilation or program linking fails.
     pub fn new(vertex_src: &str, fragment_src: &str) -> ShaderProgram {
+        ShaderProgram::try_new(vertex_src, fragment_src).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new `ShaderProgram` like `new`, for sources that may not compile, such
+    /// as ones loaded from files.
+    ///
+    /// # Returns
+    ///
+    /// The linked program, or an `Err(String)` with the compile or link log.
+    pub fn try_new(vertex_src: &str, fragment_src: &str) -> Result<ShaderProgram, String> {
         unsafe {
-            let vertex_shader = gl::CreateShader(gl::VERTEX_SHADER);
-            let c_str_vert = CString::new(vertex_src).unwrap();
-            gl::ShaderSource(vertex_shader, 1, &c_str_vert.as_ptr(), ptr::null());
-            gl::CompileShader(vertex_shader);
-            Self::check_shader_compile_status(vertex_shader);
-
-            let fragment_shader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let c_str_frag = CString::new(fragment_src).unwrap();
-            gl::ShaderSource(fragment_shader, 1, &c_str_frag.as_ptr(), ptr::null());
-            gl::CompileShader(fragment_shader);
-            Self::check_shader_compile_status(fragment_shader);
+            let vertex_shader = Self::compile_shader(gl::VERTEX_SHADER, vertex_src)?;
+            let fragment_shader = match Self::compile_shader(gl::FRAGMENT_SHADER, fragment_src) {
+                Ok(fragment_shader) => fragment_shader,
+                Err(err) => {
+                    gl::DeleteShader(vertex_shader);
+                    return Err(err);
+                }
+            };
 
             let program = gl::CreateProgram();
             gl::AttachShader(program, vertex_shader);
             gl::AttachShader(program, fragment_shader);
             gl::LinkProgram(program);
-            Self::check_program_link_status(program);
-
             gl::DeleteShader(vertex_shader);
             gl::DeleteShader(fragment_shader);
+            if let Err(err) = Self::check_program_link_status(program) {
+                gl::DeleteProgram(program);
+                return Err(err);
+            }
+
+            Ok(ShaderProgram {
+                uniforms_ids: HashMap::new(),
+                program,
+            })
+        }
+    }
+
+    /// Creates a program from a vertex shader alone whose outputs are captured with
+    /// transform feedback, see `FeedbackCapture`.
+    ///
+    /// # Arguments
+    ///
+    /// * `vertex_src` - GLSL source code for the vertex shader.
+    /// * `varyings` - The vertex shader outputs to capture, interleaved in this order.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if shader compilation or program linking fails.
+    pub fn with_feedback(vertex_src: &str, varyings: &[&str]) -> ShaderProgram {
+        let names: Vec<CString> = varyings.iter().map(|varying| CString::new(*varying).unwrap()).collect();
+        let pointers: Vec<*const i8> = names.iter().map(|name| name.as_ptr()).collect();
+        unsafe {
+            let vertex_shader =
+                Self::compile_shader(gl::VERTEX_SHADER, vertex_src).unwrap_or_else(|err| panic!("{err}"));
+
+            let program = gl::CreateProgram();
+            gl::AttachShader(program, vertex_shader);
+            gl::TransformFeedbackVaryings(program, pointers.len() as i32, pointers.as_ptr(), gl::INTERLEAVED_ATTRIBS);
+            gl::LinkProgram(program);
+            Self::check_program_link_status(program).unwrap_or_else(|err| panic!("{err}"));
+
+            gl::DeleteShader(vertex_shader);
 
             ShaderProgram {
                 uniforms_ids: HashMap::new(),
@@ -54,12 +96,29 @@ impl ShaderProgram {
         }
     }
 
-    /// Checks the compile status of a shader and panics if compilation failed.
+    /// Compiles a shader of the given kind, deleting it again if compilation fails.
+    ///
+    /// # Safety
+    ///
+    /// This function calls unsafe OpenGL functions.
+    unsafe fn compile_shader(kind: u32, src: &str) -> Result<u32, String> {
+        let c_str = CString::new(src).map_err(|_| "Shader source contains a nul byte".to_string())?;
+        let shader = gl::CreateShader(kind);
+        gl::ShaderSource(shader, 1, &c_str.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+        if let Err(err) = Self::check_shader_compile_status(shader) {
+            gl::DeleteShader(shader);
+            return Err(err);
+        }
+        Ok(shader)
+    }
+
+    /// Checks the compile status of a shader, returning its log if compilation failed.
     ///
     /// # Safety
     ///
     /// This function calls unsafe OpenGL functions.
-    unsafe fn check_shader_compile_status(shader: u32) {
+    unsafe fn check_shader_compile_status(shader: u32) -> Result<(), String> {
         let mut success = gl::FALSE as i32;
         gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
         if success != gl::TRUE as i32 {
@@ -67,19 +126,20 @@ impl ShaderProgram {
             gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
             let mut log = vec![0; len as usize];
             gl::GetShaderInfoLog(shader, len, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
-            panic!(
+            return Err(format!(
                 "Shader compilation failed: {}",
                 String::from_utf8_lossy(&log)
-            );
+            ));
         }
+        Ok(())
     }
 
-    /// Checks the link status of the shader program and panics if linking failed.
+    /// Checks the link status of the shader program, returning its log if linking failed.
     ///
     /// # Safety
     ///
     /// This function calls unsafe OpenGL functions.
-    unsafe fn check_program_link_status(program: u32) {
+    unsafe fn check_program_link_status(program: u32) -> Result<(), String> {
         let mut success = gl::FALSE as i32;
         gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
         if success != gl::TRUE as i32 {
@@ -87,8 +147,9 @@ impl ShaderProgram {
             gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
             let mut log = vec![0; len as usize];
             gl::GetProgramInfoLog(program, len, ptr::null_mut(), log.as_mut_ptr() as *mut i8);
-            panic!("Program linking failed: {}", String::from_utf8_lossy(&log));
+            return Err(format!("Program linking failed: {}", String::from_utf8_lossy(&log)));
         }
+        Ok(())
     }
 
     /// Creates a uniform variable and stores its location for later use.
@@ -127,11 +188,157 @@ impl ShaderProgram {
     ///
     /// This function will panic if the uniform is not found in the `uniforms_ids` map.
     pub fn set_matrix4fv_uniform(&self, uniform_name: &str, matrix: &Mat4) {
-        if let Some(&location) = self.uniforms_ids.get(uniform_name) {
-            unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr()) };
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr()) };
+    }
+
+    /// Sets the value of a `vec3` uniform variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    /// * `vector` - A reference to a `Vec3` containing the new value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_3fv_uniform(&self, uniform_name: &str, vector: &Vec3) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform3fv(location, 1, vector.as_ptr()) };
+    }
+
+    /// Sets the value of an `int` uniform variable, such as a sampler's texture unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    /// * `value` - The new value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_1i_uniform(&self, uniform_name: &str, value: i32) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform1i(location, value) };
+    }
+
+    /// Sets the value of a `float` uniform variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    /// * `value` - The new value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_1f_uniform(&self, uniform_name: &str, value: f32) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform1f(location, value) };
+    }
+
+    /// Sets the value of a `vec2` uniform variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    /// * `vector` - A reference to a `Vec2` containing the new value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_2fv_uniform(&self, uniform_name: &str, vector: &Vec2) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform2fv(location, 1, vector.as_ptr()) };
+    }
+
+    /// Sets the value of a `vec4` uniform variable.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    /// * `vector` - A reference to a `Vec4` containing the new value.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_4fv_uniform(&self, uniform_name: &str, vector: &Vec4) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform4fv(location, 1, vector.as_ptr()) };
+    }
+
+    /// Sets the leading elements of a `float[]` uniform array.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform array, without an index.
+    /// * `values` - The values for elements `0..values.len()`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_1fv_array_uniform(&self, uniform_name: &str, values: &[f32]) {
+        let location = self.uniform_location(uniform_name);
+        unsafe { gl::Uniform1fv(location, values.len() as i32, values.as_ptr()) };
+    }
+
+    /// Sets the leading elements of a `vec4[]` uniform array.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform array, without an index.
+    /// * `vectors` - The values for elements `0..vectors.len()`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    pub fn set_4fv_array_uniform(&self, uniform_name: &str, vectors: &[Vec4]) {
+        let location = self.uniform_location(uniform_name);
+        let data: Vec<f32> = vectors.iter().flat_map(|vector| vector.iter().copied()).collect();
+        unsafe { gl::Uniform4fv(location, vectors.len() as i32, data.as_ptr()) };
+    }
+
+    /// Returns the location stored by `create_uniform`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the uniform is not found in the `uniforms_ids` map.
+    fn uniform_location(&self, uniform_name: &str) -> i32 {
+        match self.uniforms_ids.get(uniform_name) {
+            Some(&location) => location,
+            None => panic!("Uniform '{uniform_name}' not found. Did you forget to call `create_uniform`?"),
         }
+    }
+
+    /// Checks whether a uniform variable has been located with `create_uniform`.
+    ///
+    /// # Arguments
+    ///
+    /// * `uniform_name` - The name of the uniform variable.
+    pub fn has_uniform(&self, uniform_name: &str) -> bool {
+        self.uniforms_ids.contains_key(uniform_name)
+    }
 
-        panic!("Uniform '{uniform_name}' not found. Did you forget to call `create_uniform`?");
+    /// Connects a uniform block to an indexed uniform buffer binding point.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_name` - The name of the uniform block in the shader.
+    /// * `binding` - The binding point the block reads from, see `BufferObject::bind_base`.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the program has no active uniform block with that name.
+    pub fn bind_uniform_block(&self, block_name: &str, binding: u32) -> bool {
+        let name = CString::new(block_name).unwrap();
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.program, name.as_ptr());
+            if index == gl::INVALID_INDEX {
+                return false;
+            }
+            gl::UniformBlockBinding(self.program, index, binding);
+        }
+        true
     }
 
     /// Binds the shader program for use in the OpenGL pipeline.
@@ -143,6 +350,16 @@ impl ShaderProgram {
     pub fn unbind(&self) {
         unsafe { gl::UseProgram(0) }
     }
+
+    /// Returns the OpenGL name of the program.
+    pub(crate) fn id(&self) -> u32 {
+        self.program
+    }
+
+    /// Labels the shader program for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Program, self.program, label);
+    }
 }
 
 impl Drop for ShaderProgram {
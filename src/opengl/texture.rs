@@ -0,0 +1,427 @@
+use std::path::Path;
+
+use gl::types::*;
+
+use super::capabilities::{has_extension, is_version_at_least};
+
+/// Represents the storage formats a texture can be allocated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgba8,
+    Srgb8Alpha8,
+    R16F,
+    Rg16F,
+    Rgb16F,
+    Rgba16F,
+    Rgba32F,
+    Depth24,
+    Depth24Stencil8,
+    /// Blocks of texels compressed by the GPU, which can only be created with
+    /// `Texture::from_compressed`.
+    Compressed(CompressedFormat),
+}
+
+impl TextureFormat {
+    /// Returns the `(internal format, pixel format, pixel type)` triple used to allocate
+    /// or upload texels of this format. Compressed formats have neither a pixel format
+    /// nor a pixel type, and return `GL_NONE` for both.
+    pub fn gl_formats(self) -> (GLenum, GLenum, GLenum) {
+        match self {
+            TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::Rg8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::Srgb8Alpha8 => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R16F => (gl::R16F, gl::RED, gl::FLOAT),
+            TextureFormat::Rg16F => (gl::RG16F, gl::RG, gl::FLOAT),
+            TextureFormat::Rgb16F => (gl::RGB16F, gl::RGB, gl::FLOAT),
+            TextureFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            TextureFormat::Rgba32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+            TextureFormat::Depth24 => (gl::DEPTH_COMPONENT24, gl::DEPTH_COMPONENT, gl::FLOAT),
+            TextureFormat::Depth24Stencil8 => (gl::DEPTH24_STENCIL8, gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+            TextureFormat::Compressed(format) => (format as GLenum, gl::NONE, gl::NONE),
+        }
+    }
+}
+
+/// Represents the block-compressed formats a texture can be uploaded in.
+///
+/// Every format stores the texels in blocks of 4x4, which stay compressed in video
+/// memory and are decoded by the GPU when sampled. Drivers don't support every format;
+/// check `is_supported` before uploading.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    Bc1 = 0x83F1,            // GL_COMPRESSED_RGBA_S3TC_DXT1_EXT
+    Bc1Srgb = 0x8C4D,        // GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT
+    Bc2 = 0x83F2,            // GL_COMPRESSED_RGBA_S3TC_DXT3_EXT
+    Bc2Srgb = 0x8C4E,        // GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT
+    Bc3 = 0x83F3,            // GL_COMPRESSED_RGBA_S3TC_DXT5_EXT
+    Bc3Srgb = 0x8C4F,        // GL_COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT
+    Bc4 = 0x8DBB,            // GL_COMPRESSED_RED_RGTC1
+    Bc4Snorm = 0x8DBC,       // GL_COMPRESSED_SIGNED_RED_RGTC1
+    Bc5 = 0x8DBD,            // GL_COMPRESSED_RG_RGTC2
+    Bc5Snorm = 0x8DBE,       // GL_COMPRESSED_SIGNED_RG_RGTC2
+    Bc6hUfloat = 0x8E8F,     // GL_COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT
+    Bc6hSfloat = 0x8E8E,     // GL_COMPRESSED_RGB_BPTC_SIGNED_FLOAT
+    Bc7 = 0x8E8C,            // GL_COMPRESSED_RGBA_BPTC_UNORM
+    Bc7Srgb = 0x8E8D,        // GL_COMPRESSED_SRGB_ALPHA_BPTC_UNORM
+    Etc2Rgb8 = 0x9274,       // GL_COMPRESSED_RGB8_ETC2
+    Etc2Rgb8Srgb = 0x9275,   // GL_COMPRESSED_SRGB8_ETC2
+    Etc2Rgb8A1 = 0x9276,     // GL_COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2
+    Etc2Rgb8A1Srgb = 0x9277, // GL_COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2
+    Etc2Rgba8 = 0x9278,      // GL_COMPRESSED_RGBA8_ETC2_EAC
+    Etc2Rgba8Srgb = 0x9279,  // GL_COMPRESSED_SRGB8_ALPHA8_ETC2_EAC
+    EacR11 = 0x9270,         // GL_COMPRESSED_R11_EAC
+    EacR11Snorm = 0x9271,    // GL_COMPRESSED_SIGNED_R11_EAC
+    EacRg11 = 0x9272,        // GL_COMPRESSED_RG11_EAC
+    EacRg11Snorm = 0x9273,   // GL_COMPRESSED_SIGNED_RG11_EAC
+}
+
+impl CompressedFormat {
+    /// Returns the size in bytes of one 4x4 block.
+    pub fn block_size(self) -> usize {
+        use CompressedFormat::*;
+        match self {
+            Bc1 | Bc1Srgb | Bc4 | Bc4Snorm | Etc2Rgb8 | Etc2Rgb8Srgb | Etc2Rgb8A1 | Etc2Rgb8A1Srgb | EacR11
+            | EacR11Snorm => 8,
+            _ => 16,
+        }
+    }
+
+    /// Returns the size in bytes of an image of the given size, in whole blocks.
+    pub fn image_size(self, width: u32, height: u32) -> usize {
+        width.div_ceil(4) as usize * height.div_ceil(4) as usize * self.block_size()
+    }
+
+    /// Checks whether the texels are sRGB-encoded colors, decoded to linear when sampled.
+    pub fn is_srgb(self) -> bool {
+        use CompressedFormat::*;
+        matches!(self, Bc1Srgb | Bc2Srgb | Bc3Srgb | Bc7Srgb | Etc2Rgb8Srgb | Etc2Rgb8A1Srgb | Etc2Rgba8Srgb)
+    }
+
+    /// Checks whether the current context can sample textures of this format: S3TC
+    /// (BC1 to BC3) through `GL_EXT_texture_compression_s3tc`, RGTC (BC4 and BC5) since
+    /// OpenGL 3.0, BPTC (BC6H and BC7) since OpenGL 4.2 and ETC2/EAC since OpenGL 4.3,
+    /// or through the matching ARB extensions.
+    pub fn is_supported(self) -> bool {
+        use CompressedFormat::*;
+        match self {
+            Bc1 | Bc2 | Bc3 => has_extension("GL_EXT_texture_compression_s3tc"),
+            Bc1Srgb | Bc2Srgb | Bc3Srgb => {
+                has_extension("GL_EXT_texture_compression_s3tc")
+                    && (has_extension("GL_EXT_texture_sRGB") || has_extension("GL_EXT_texture_compression_s3tc_srgb"))
+            }
+            Bc4 | Bc4Snorm | Bc5 | Bc5Snorm => true,
+            Bc6hUfloat | Bc6hSfloat | Bc7 | Bc7Srgb => {
+                is_version_at_least(4, 2) || has_extension("GL_ARB_texture_compression_bptc")
+            }
+            _ => is_version_at_least(4, 3) || has_extension("GL_ARB_ES3_compatibility"),
+        }
+    }
+}
+
+/// Represents the filtering applied when a texture is sampled.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    Nearest = 0x2600,              // GL_NEAREST
+    Linear = 0x2601,               // GL_LINEAR
+    LinearMipmapLinear = 0x2703,   // GL_LINEAR_MIPMAP_LINEAR
+    NearestMipmapNearest = 0x2700, // GL_NEAREST_MIPMAP_NEAREST
+}
+
+/// Represents how texture coordinates outside `[0, 1]` are resolved.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureWrap {
+    Repeat = 0x2901,         // GL_REPEAT
+    MirroredRepeat = 0x8370, // GL_MIRRORED_REPEAT
+    ClampToEdge = 0x812F,    // GL_CLAMP_TO_EDGE
+}
+
+/// Represents an OpenGL two-dimensional texture.
+pub struct Texture {
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    id: u32,
+}
+
+impl Texture {
+    /// Creates a texture with uninitialized storage, typically used as a render target.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the texture in texels.
+    /// * `height` - Height of the texture in texels.
+    /// * `format` - The storage format of the texels.
+    pub fn new(width: u32, height: u32, format: TextureFormat) -> Texture {
+        let texture = Texture::allocate(width, height, format);
+        texture.bind(0);
+        texture.upload::<u8>(None);
+        texture.set_filter(TextureFilter::Linear, TextureFilter::Linear);
+        texture.set_wrap(TextureWrap::ClampToEdge);
+        texture.unbind(0);
+        texture
+    }
+
+    /// Creates a texture from tightly packed texels and generates its mipmaps.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of the texture in texels.
+    /// * `height` - Height of the texture in texels.
+    /// * `format` - The storage format of the texels.
+    /// * `data` - Texel data laid out row by row, matching the pixel format and type of `format`.
+    pub fn from_data<T>(width: u32, height: u32, format: TextureFormat, data: &[T]) -> Texture {
+        let texture = Texture::allocate(width, height, format);
+        texture.bind(0);
+        texture.upload(Some(data));
+        texture.set_filter(TextureFilter::LinearMipmapLinear, TextureFilter::Linear);
+        texture.set_wrap(TextureWrap::Repeat);
+        unsafe { gl::GenerateMipmap(gl::TEXTURE_2D) };
+        texture.unbind(0);
+        texture
+    }
+
+    /// Creates a texture from a chain of mip levels, such as levels decoded from a file
+    /// that stores them. Levels past the first halve the size of the previous one,
+    /// rounding down but never below one texel.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of level 0 in texels.
+    /// * `height` - Height of level 0 in texels.
+    /// * `format` - The storage format of the texels.
+    /// * `levels` - Texel data of each level from level 0, laid out row by row.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `levels` is empty or `format` is compressed.
+    pub fn from_mip_chain<T>(width: u32, height: u32, format: TextureFormat, levels: &[&[T]]) -> Texture {
+        assert!(!levels.is_empty(), "a texture needs at least one mip level");
+        assert!(!matches!(format, TextureFormat::Compressed(_)), "use Texture::from_compressed");
+        let (internal_format, pixel_format, data_type) = format.gl_formats();
+        let texture = Texture::allocate(width, height, format);
+        texture.bind(0);
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1) };
+        for (level, data) in levels.iter().enumerate() {
+            let (level_width, level_height) = mip_size(width, height, level);
+            unsafe {
+                gl::TexImage2D(
+                    gl::TEXTURE_2D,
+                    level as GLint,
+                    internal_format as GLint,
+                    level_width as GLsizei,
+                    level_height as GLsizei,
+                    0,
+                    pixel_format,
+                    data_type,
+                    data.as_ptr() as *const GLvoid,
+                );
+            }
+        }
+        unsafe { gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4) };
+        texture.finish_mip_chain(levels.len());
+        texture
+    }
+
+    /// Creates a texture from block-compressed mip levels, which are uploaded as they are
+    /// and stay compressed in video memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width of level 0 in texels.
+    /// * `height` - Height of level 0 in texels.
+    /// * `format` - The block format, which the context must support.
+    /// * `levels` - The blocks of each level from level 0, row by row, each level
+    ///   `CompressedFormat::image_size` bytes long.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `levels` is empty.
+    pub fn from_compressed(width: u32, height: u32, format: CompressedFormat, levels: &[&[u8]]) -> Texture {
+        assert!(!levels.is_empty(), "a texture needs at least one mip level");
+        let texture = Texture::allocate(width, height, TextureFormat::Compressed(format));
+        texture.bind(0);
+        for (level, data) in levels.iter().enumerate() {
+            let (level_width, level_height) = mip_size(width, height, level);
+            unsafe {
+                gl::CompressedTexImage2D(
+                    gl::TEXTURE_2D,
+                    level as GLint,
+                    format as GLenum,
+                    level_width as GLsizei,
+                    level_height as GLsizei,
+                    0,
+                    data.len() as GLsizei,
+                    data.as_ptr() as *const GLvoid,
+                );
+            }
+        }
+        texture.finish_mip_chain(levels.len());
+        texture
+    }
+
+    /// Loads an image file into a mipmapped RGBA texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the image file.
+    /// * `srgb` - `true` for color data such as albedo maps, `false` for linear data
+    ///   such as normal or roughness maps.
+    ///
+    /// # Returns
+    ///
+    /// The uploaded `Texture`, or an `Err(String)` if the image cannot be decoded.
+    pub fn from_image<P: AsRef<Path>>(path: P, srgb: bool) -> Result<Texture, String> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|err| format!("Cannot load texture {}: {err}", path.display()))?
+            .to_rgba8();
+
+        let format = if srgb { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+        Ok(Texture::from_data(image.width(), image.height(), format, image.as_raw()))
+    }
+
+    /// Limits sampling to the uploaded levels, sets the filters accordingly and unbinds
+    /// the texture.
+    fn finish_mip_chain(&self, levels: usize) {
+        unsafe { gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, levels as GLint - 1) };
+        let min = if levels > 1 { TextureFilter::LinearMipmapLinear } else { TextureFilter::Linear };
+        self.set_filter(min, TextureFilter::Linear);
+        self.set_wrap(TextureWrap::Repeat);
+        self.unbind(0);
+    }
+
+    /// Generates the texture object without allocating storage.
+    fn allocate(width: u32, height: u32, format: TextureFormat) -> Texture {
+        let mut id = 0;
+        unsafe { gl::GenTextures(1, &mut id) };
+        Texture { format, width, height, id }
+    }
+
+    /// Allocates level 0 of the bound texture, optionally filling it with `data`.
+    fn upload<T>(&self, data: Option<&[T]>) {
+        let (internal_format, format, data_type) = self.format.gl_formats();
+        let pointer = data.map_or(std::ptr::null(), |data| data.as_ptr() as *const GLvoid);
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as GLint,
+                self.width as GLsizei,
+                self.height as GLsizei,
+                0,
+                format,
+                data_type,
+                pointer,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    /// Replaces a rectangle of level 0 with tightly packed texels, leaving mipmaps as they
+    /// are. The texture must be bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `x`, `y` - Texel coordinates of the rectangle's corner nearest to the origin.
+    /// * `width`, `height` - Size of the rectangle in texels.
+    /// * `data` - Texel data laid out row by row, matching the pixel format and type of the
+    ///   texture's format.
+    pub fn update_region<T>(&self, x: u32, y: u32, width: u32, height: u32, data: &[T]) {
+        let (_, format, data_type) = self.format.gl_formats();
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as GLint,
+                y as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                format,
+                data_type,
+                data.as_ptr() as *const GLvoid,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    /// Sets the minification and magnification filters. The texture must be bound.
+    pub fn set_filter(&self, min: TextureFilter, mag: TextureFilter) {
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag as GLint);
+        }
+    }
+
+    /// Sets the wrap mode on both axes. The texture must be bound.
+    pub fn set_wrap(&self, wrap: TextureWrap) {
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as GLint);
+        }
+    }
+
+    /// Binds the texture to the given texture unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Index of the texture unit (`0` is `GL_TEXTURE0`).
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+        }
+    }
+
+    /// Unbinds any texture from the given texture unit.
+    pub fn unbind(&self, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    /// Returns the width of the texture in texels.
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the texture in texels.
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the storage format of the texture.
+    pub fn get_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    /// Returns the OpenGL name of the texture.
+    pub(crate) fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Labels the texture for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::Texture, self.id, label);
+    }
+}
+
+impl Drop for Texture {
+    /// Deletes the OpenGL texture when the `Texture` is dropped.
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) }
+    }
+}
+
+/// Returns the size of a mip level of a texture whose level 0 is `width` by `height`.
+pub(crate) fn mip_size(width: u32, height: u32, level: usize) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
@@ -0,0 +1,67 @@
+/// Represents how vertices are assembled into primitives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    #[default]
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    /// Patches for tessellation shaders, with the given number of vertices each.
+    /// Requires OpenGL 4.0.
+    Patches(u32),
+}
+
+impl Topology {
+    /// Returns the OpenGL primitive mode.
+    pub(crate) fn gl_mode(self) -> u32 {
+        match self {
+            Topology::Points => gl::POINTS,
+            Topology::Lines => gl::LINES,
+            Topology::LineStrip => gl::LINE_STRIP,
+            Topology::LineLoop => gl::LINE_LOOP,
+            Topology::Triangles => gl::TRIANGLES,
+            Topology::TriangleStrip => gl::TRIANGLE_STRIP,
+            Topology::TriangleFan => gl::TRIANGLE_FAN,
+            Topology::Patches(_) => gl::PATCHES,
+        }
+    }
+
+    /// Sets the patch size for `Patches`; does nothing for other topologies.
+    pub(crate) fn apply(self) {
+        if let Topology::Patches(vertices) = self {
+            unsafe { gl::PatchParameteri(gl::PATCH_VERTICES, vertices as i32) }
+        }
+    }
+}
+
+/// Represents the integer type of an index buffer.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexType {
+    UnsignedByte = 0x1401,  // GL_UNSIGNED_BYTE
+    UnsignedShort = 0x1403, // GL_UNSIGNED_SHORT
+    UnsignedInt = 0x1405,   // GL_UNSIGNED_INT
+}
+
+impl IndexType {
+    /// Returns the size of one index in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            IndexType::UnsignedByte => 1,
+            IndexType::UnsignedShort => 2,
+            IndexType::UnsignedInt => 4,
+        }
+    }
+
+    /// Returns the largest index, which ends a strip when primitive restart is enabled.
+    pub fn restart_index(self) -> u32 {
+        match self {
+            IndexType::UnsignedByte => u8::MAX as u32,
+            IndexType::UnsignedShort => u16::MAX as u32,
+            IndexType::UnsignedInt => u32::MAX,
+        }
+    }
+}
@@ -57,6 +57,11 @@ impl VertexArrayObject {
     pub fn unbind(&self) {
         unsafe { gl::BindVertexArray(0) }
     }
+
+    /// Labels the vertex array object for debug messages and graphics debuggers.
+    pub fn set_label(&self, label: &str) {
+        super::label_object(super::ObjectKind::VertexArray, self.id, label);
+    }
 }
 
 impl Default for VertexArrayObject {
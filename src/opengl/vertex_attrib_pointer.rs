@@ -1,5 +1,4 @@
 use std::ffi::c_void;
-use std::ptr;
 
 #[repr(u32)]
 #[derive(Clone, Copy, Debug)]
@@ -47,6 +46,30 @@ impl VertexAttribPointer {
         data_type: DataType,
         normalized: bool,
         stride: i32,
+    ) -> VertexAttribPointer {
+        VertexAttribPointer::with_offset(index, size, data_type, normalized, stride, 0)
+    }
+
+    /// Creates a new `VertexAttribPointer` reading from a byte offset into each vertex.
+    ///
+    /// This is used for interleaved vertex data, where attributes such as normals
+    /// follow the position inside the same vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the vertex attribute.
+    /// * `size` - Number of components per vertex attribute (1-4).
+    /// * `data_type` - Data type of the attribute components.
+    /// * `normalized` - Whether fixed-point data values should be normalized.
+    /// * `stride` - Byte offset between consecutive attributes.
+    /// * `offset` - Byte offset of the first component inside the bound buffer.
+    pub fn with_offset(
+        index: u32,
+        size: i32,
+        data_type: DataType,
+        normalized: bool,
+        stride: i32,
+        offset: usize,
     ) -> VertexAttribPointer {
         let normalized = if normalized { gl::TRUE } else { gl::FALSE };
         unsafe {
@@ -56,7 +79,7 @@ impl VertexAttribPointer {
                 data_type as u32,
                 normalized,
                 stride,
-                ptr::null(),
+                offset as *const c_void,
             )
         };
         VertexAttribPointer { index }
@@ -67,6 +90,12 @@ impl VertexAttribPointer {
         unsafe { gl::EnableVertexAttribArray(self.index) };
     }
 
+    /// Sets how many instances share each value of the attribute, `0` advancing it per
+    /// vertex as usual.
+    pub fn set_divisor(&self, divisor: u32) {
+        unsafe { gl::VertexAttribDivisor(self.index, divisor) };
+    }
+
     /// Disables the vertex attribute array.
     pub fn disable(&self) {
         unsafe { gl::DisableVertexAttribArray(self.index) };
@@ -0,0 +1,130 @@
+use std::mem::size_of;
+
+use super::{DataType, VertexAttribPointer};
+
+/// A single `f32` attribute inside an interleaved vertex.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    /// The shader attribute location (`layout (location = N)`).
+    pub location: u32,
+    /// Number of `f32` components (1-4).
+    pub components: i32,
+}
+
+/// Describes how the `f32`s of an interleaved vertex buffer map to shader attributes.
+///
+/// Attributes are packed in the order they are added. The built-in shaders use the
+/// locations defined by the associated constants, e.g. `VertexLayout::NORMAL`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    /// Location of the `vec3` position attribute.
+    pub const POSITION: u32 = 0;
+    /// Location of the `vec3` normal attribute.
+    pub const NORMAL: u32 = 1;
+    /// Location of the `vec2` texture coordinate attribute.
+    pub const UV: u32 = 2;
+    /// Location of the `vec4` tangent attribute, with the bitangent sign in `w`.
+    pub const TANGENT: u32 = 3;
+    /// Location of the `vec4` vertex color attribute.
+    pub const COLOR: u32 = 4;
+    /// Location of the `vec4` skin joint indices attribute, stored as `f32`s.
+    pub const JOINTS: u32 = 5;
+    /// Location of the `vec4` skin joint weights attribute.
+    pub const WEIGHTS: u32 = 6;
+
+    /// Creates an empty layout.
+    pub fn new() -> VertexLayout {
+        VertexLayout { attributes: Vec::new() }
+    }
+
+    /// Appends an attribute at the end of the vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The shader attribute location.
+    /// * `components` - Number of `f32` components (1-4).
+    pub fn with(mut self, location: u32, components: i32) -> VertexLayout {
+        self.attributes.push(VertexAttribute { location, components });
+        self
+    }
+
+    /// Returns the position-only layout `Mesh::new` uses.
+    pub fn position() -> VertexLayout {
+        VertexLayout::new().with(VertexLayout::POSITION, 3)
+    }
+
+    /// Returns the position, normal, UV and tangent layout used by the built-in materials.
+    pub fn standard() -> VertexLayout {
+        VertexLayout::position()
+            .with(VertexLayout::NORMAL, 3)
+            .with(VertexLayout::UV, 2)
+            .with(VertexLayout::TANGENT, 4)
+    }
+
+    /// Returns the attributes in the order they appear inside a vertex.
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    /// Checks whether an attribute is bound to `location`.
+    pub fn contains(&self, location: u32) -> bool {
+        self.attributes.iter().any(|attribute| attribute.location == location)
+    }
+
+    /// Returns the number of `f32`s in one vertex.
+    pub fn floats_per_vertex(&self) -> usize {
+        self.attributes.iter().map(|attribute| attribute.components as usize).sum()
+    }
+
+    /// Returns the offset, in `f32`s, of the attribute at `location` inside a vertex.
+    pub fn offset_of(&self, location: u32) -> Option<usize> {
+        let mut offset = 0;
+        for attribute in self.attributes.iter() {
+            if attribute.location == location {
+                return Some(offset);
+            }
+            offset += attribute.components as usize;
+        }
+        None
+    }
+
+    /// Sets up and enables the attribute pointers for the bound vertex array and buffer.
+    pub fn apply(&self) {
+        self.apply_instanced(0);
+    }
+
+    /// Sets up and enables the attribute pointers for the bound vertex array and buffer,
+    /// advancing them once every `divisor` instances instead of once per vertex.
+    ///
+    /// # Arguments
+    ///
+    /// * `divisor` - How many instances share each vertex of the buffer, `0` for per-vertex data.
+    pub fn apply_instanced(&self, divisor: u32) {
+        let stride = (self.floats_per_vertex() * size_of::<f32>()) as i32;
+        let mut offset = 0;
+        for attribute in self.attributes.iter() {
+            let pointer = VertexAttribPointer::with_offset(
+                attribute.location,
+                attribute.components,
+                DataType::Float,
+                false,
+                stride,
+                offset * size_of::<f32>(),
+            );
+            pointer.enable();
+            pointer.set_divisor(divisor);
+            offset += attribute.components as usize;
+        }
+    }
+}
+
+impl Default for VertexLayout {
+    /// Returns the position-only layout.
+    fn default() -> Self {
+        VertexLayout::position()
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+/// Environment variable that makes `compare_golden` write the actual frame as the new
+/// reference instead of comparing against it.
+pub const UPDATE_GOLDEN_ENV: &str = "FOUX_UPDATE_GOLDEN";
+
+/// How far a frame may stray from its reference image and still match.
+///
+/// Software rasterizers and drivers round slightly differently, so exact comparisons
+/// are rarely useful.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference allowed in any channel of a pixel, `0..=255`.
+    pub channel: u8,
+    /// Fraction of pixels, `0.0..=1.0`, allowed to exceed `channel`.
+    pub pixels: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            channel: 2,
+            pixels: 0.001,
+        }
+    }
+}
+
+/// Describes a frame that did not match its reference image.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoldenMismatch {
+    /// How many pixels exceeded the channel tolerance.
+    pub mismatched: usize,
+    /// How many pixels were compared.
+    pub total: usize,
+    /// The largest channel difference found.
+    pub max_difference: u8,
+    /// Where the actual frame was written.
+    pub actual_path: PathBuf,
+    /// Where the diff image was written.
+    pub diff_path: PathBuf,
+}
+
+impl fmt::Display for GoldenMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} pixels differ (max channel difference {}); actual frame at {}, diff at {}",
+            self.mismatched,
+            self.total,
+            self.max_difference,
+            self.actual_path.display(),
+            self.diff_path.display()
+        )
+    }
+}
+
+/// Why a frame failed its golden-image comparison.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GoldenError {
+    /// The frame differs from the reference beyond the tolerance.
+    Mismatch(GoldenMismatch),
+    /// An image could not be read or written, or the sizes differ.
+    Image(String),
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Mismatch(mismatch) => mismatch.fmt(f),
+            GoldenError::Image(error) => f.write_str(error),
+        }
+    }
+}
+
+/// Compares a rendered frame against a reference PNG.
+///
+/// When the frame does not match, it is written next to the reference as
+/// `<name>.actual.png` together with `<name>.diff.png`, which shows mismatched pixels
+/// in red over a dimmed copy of the frame. When `FOUX_UPDATE_GOLDEN` is set, or the
+/// reference does not exist yet, the frame is written as the reference instead.
+///
+/// # Arguments
+///
+/// * `actual` - The rendered frame, e.g. from `Engine::run_headless`.
+/// * `reference` - Path to the reference PNG.
+/// * `tolerance` - How far the frame may differ and still match.
+///
+/// # Returns
+///
+/// `Ok(())` if the frame matches, or a `GoldenError` describing why it does not.
+pub fn compare_golden<P: AsRef<Path>>(
+    actual: &RgbaImage,
+    reference: P,
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    let reference = reference.as_ref();
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some() || !reference.exists() {
+        return save(actual, reference).map_err(GoldenError::Image);
+    }
+
+    let expected = image::open(reference)
+        .map_err(|error| GoldenError::Image(format!("Couldn't read reference image {}: {error}", reference.display())))?
+        .to_rgba8();
+    if expected.dimensions() != actual.dimensions() {
+        return Err(GoldenError::Image(format!(
+            "Frame is {:?} but reference image {} is {:?}",
+            actual.dimensions(),
+            reference.display(),
+            expected.dimensions()
+        )));
+    }
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    for ((pixel, expected), diff_pixel) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let difference = (0..4).map(|c| pixel[c].abs_diff(expected[c])).max().unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        *diff_pixel = if difference > tolerance.channel {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let luma = (pixel[0] as u32 * 54 + pixel[1] as u32 * 183 + pixel[2] as u32 * 19) >> 8;
+            let dimmed = (luma / 4) as u8;
+            Rgba([dimmed, dimmed, dimmed, 255])
+        };
+    }
+
+    let total = (actual.width() * actual.height()) as usize;
+    if mismatched as f32 <= tolerance.pixels * total as f32 {
+        return Ok(());
+    }
+
+    let actual_path = sibling(reference, "actual");
+    let diff_path = sibling(reference, "diff");
+    save(actual, &actual_path).map_err(GoldenError::Image)?;
+    save(&diff, &diff_path).map_err(GoldenError::Image)?;
+
+    Err(GoldenError::Mismatch(GoldenMismatch {
+        mismatched,
+        total,
+        max_difference,
+        actual_path,
+        diff_path,
+    }))
+}
+
+/// Compares a rendered frame against a reference PNG like `compare_golden`.
+///
+/// # Panics
+///
+/// This function will panic if the frame does not match or an image could not be read
+/// or written, so it can be used directly inside `#[test]` functions.
+pub fn assert_golden<P: AsRef<Path>>(actual: &RgbaImage, reference: P, tolerance: Tolerance) {
+    let reference = reference.as_ref();
+    if let Err(error) = compare_golden(actual, reference, tolerance) {
+        panic!("Frame doesn't match {}: {error}", reference.display());
+    }
+}
+
+/// Returns `<dir>/<name>.<suffix>.png` for a reference at `<dir>/<name>.png`.
+fn sibling(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference.file_stem().unwrap_or_default().to_string_lossy();
+    reference.with_file_name(format!("{stem}.{suffix}.png"))
+}
+
+fn save(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|error| format!("Couldn't create {}: {error}", parent.display()))?;
+    }
+    image
+        .save(path)
+        .map_err(|error| format!("Couldn't write {}: {error}", path.display()))
+}
@@ -0,0 +1,3 @@
+mod golden;
+
+pub use golden::{assert_golden, compare_golden, GoldenError, GoldenMismatch, Tolerance, UPDATE_GOLDEN_ENV};
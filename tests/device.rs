@@ -0,0 +1,262 @@
+// Records the passes that draw GL-owned textures and buffers on a `RecordingDevice`. The
+// objects themselves need a context, so each test first creates a headless EGL window.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use foux::core::assets::{AssetEventKind, AssetServer};
+use foux::core::components::Skybox;
+use foux::core::material::{Environment, PbrMaterial};
+use foux::core::mesh::MeshBuilder;
+use foux::core::particles::{ParticleEmitter, ParticleSimulation};
+use foux::core::tilemap::{Orientation, Tile, TileLayer, Tilemap, Tileset};
+use foux::core::{Camera, HeadlessApi, RenderContext, Window};
+use foux::device::{DeviceCommand, DrawCall, RecordingDevice, RenderDevice, UniformValue};
+use foux::opengl::{CompareFunction, Cubemap, Sampler, Texture, TextureFormat, TextureWrap, Topology};
+
+use image::{Rgba, RgbaImage};
+use nalgebra_glm::Vec3;
+
+fn window() -> Window {
+    Window::headless(16, 16, HeadlessApi::Egl).expect("Couldn't create a headless EGL context")
+}
+
+fn context(camera: &Camera) -> RenderContext<'_> {
+    RenderContext {
+        camera,
+        lights: Vec::new(),
+        environment: None,
+        reflection: None,
+    }
+}
+
+fn draws(commands: &[DeviceCommand]) -> Vec<&DrawCall> {
+    commands
+        .iter()
+        .filter_map(|command| match command {
+            DeviceCommand::Draw(draw) => Some(draw),
+            _ => None,
+        })
+        .collect()
+}
+
+fn imported_textures(commands: &[DeviceCommand]) -> usize {
+    commands.iter().filter(|command| matches!(command, DeviceCommand::ImportTexture { .. })).count()
+}
+
+/// Returns the GL id of the texture each draw samples on `unit`.
+fn drawn_texture_ids(commands: &[DeviceCommand], unit: u32) -> Vec<u32> {
+    draws(commands)
+        .iter()
+        .filter_map(|draw| draw.get_texture(unit))
+        .filter_map(|handle| {
+            commands.iter().find_map(|command| match command {
+                DeviceCommand::ImportTexture { texture, id, .. } if *texture == handle => Some(*id),
+                _ => None,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn skybox_draws_the_cube_behind_geometry() {
+    let _window = window();
+    let skybox = Skybox::new(AssetServer::new().add(Cubemap::new(8, TextureFormat::Rgba8, 1)));
+    let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros());
+    let mut device = RecordingDevice::new();
+
+    skybox.render(&mut device, &camera);
+    let commands = device.take_commands();
+    let Some(DeviceCommand::ImportTexture { texture, target, .. }) = commands
+        .iter()
+        .find(|command| matches!(command, DeviceCommand::ImportTexture { .. }))
+    else {
+        panic!("the cube map wasn't imported");
+    };
+    assert_eq!(*target, gl::TEXTURE_CUBE_MAP);
+    let Some(DeviceCommand::SetPipelineState(state)) = commands.get(commands.len() - 2) else {
+        panic!("no pipeline state before the draw");
+    };
+    assert!(!state.depth_write);
+    assert_eq!(state.depth_compare, CompareFunction::LessEqual);
+    let drawn = draws(&commands);
+    assert_eq!(drawn.len(), 1);
+    assert_eq!((drawn[0].count, drawn[0].get_texture(0)), (36, Some(*texture)));
+    assert_eq!(drawn[0].get_uniform("u_view"), Some(&UniformValue::Mat4(camera.view_matrix())));
+
+    // The cube map and the cube are only imported and uploaded once.
+    skybox.render(&mut device, &camera);
+    let commands = device.take_commands();
+    assert_eq!(imported_textures(&commands), 0);
+    assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::CreateBuffer { .. })));
+    assert_eq!(draws(&commands).len(), 1);
+}
+
+#[test]
+fn tilemap_draws_visible_chunks() {
+    let _window = window();
+    let mut tilemap = Tilemap::new(Orientation::Orthogonal, 4, 4, 16, 16);
+    let texture = AssetServer::new().add(Texture::new(32, 32, TextureFormat::Rgba8));
+    tilemap.add_tileset(Tileset::new(texture, 16, 16));
+    let mut layer = TileLayer::new("ground", 4, 4);
+    layer.set_tile(0, 0, Some(Tile::new(0, 0)));
+    layer.set_tile(3, 3, Some(Tile::new(0, 3)));
+    tilemap.add_layer(layer);
+    tilemap.update(0.0);
+    let camera = Camera::new(Vec3::new(0.0, 0.0, 500.0), Vec3::zeros());
+    let mut device = RecordingDevice::new();
+
+    tilemap.render(&mut device, &context(&camera));
+    let commands = device.take_commands();
+    assert_eq!(imported_textures(&commands), 1);
+    let created = commands.iter().filter(|command| matches!(command, DeviceCommand::CreateBuffer { .. }));
+    assert_eq!(created.count(), 1);
+    let drawn = draws(&commands);
+    assert_eq!(drawn.len(), 1);
+    assert_eq!((drawn[0].first, drawn[0].count), (0, 12));
+    assert_eq!(drawn[0].textures.len(), 1);
+    let unit = drawn[0].textures[0].0;
+    assert_eq!(drawn[0].get_sampler(unit), Some(Sampler::nearest(TextureWrap::ClampToEdge)));
+    assert_eq!(drawn[0].get_uniform("u_opacity"), Some(&UniformValue::Float(1.0)));
+
+    // Chunks are uploaded once, and culled when the camera looks away.
+    let away = Camera::new(Vec3::new(5000.0, 0.0, 10.0), Vec3::new(5000.0, 0.0, 0.0));
+    tilemap.render(&mut device, &context(&away));
+    let commands = device.take_commands();
+    assert!(draws(&commands).is_empty());
+    assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::CreateBuffer { .. })));
+
+    tilemap.get_layer_mut(0).unwrap().visible = false;
+    tilemap.render(&mut device, &context(&camera));
+    assert!(draws(&device.take_commands()).is_empty());
+}
+
+#[test]
+fn gpu_particles_are_drawn_from_the_simulation_buffers() {
+    let _window = window();
+    let mut emitter = ParticleEmitter::new(8, ParticleSimulation::Gpu);
+    if emitter.get_simulation() != ParticleSimulation::Gpu {
+        return;
+    }
+    emitter.burst(2);
+    let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros());
+    let mut device = RecordingDevice::new();
+
+    emitter.render(&mut device, &context(&camera));
+    let commands = device.take_commands();
+    let imported: Vec<_> = (commands.iter())
+        .filter_map(|command| match command {
+            DeviceCommand::ImportBuffer { buffer, .. } => Some(*buffer),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(imported.len(), 2);
+    assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::UpdateBuffer { .. })));
+    let drawn = draws(&commands);
+    assert_eq!(drawn.len(), 1);
+    let (instances, _) = drawn[0].instance_buffer.clone().expect("no instance buffer");
+    assert!(imported.contains(&instances));
+    // Dead slots are drawn too and discarded by the shader.
+    assert_eq!((drawn[0].topology, drawn[0].instances), (Topology::TriangleStrip, 8));
+
+    // Each simulation step swaps the buffer drawn from.
+    emitter.simulate(0.01);
+    emitter.render(&mut device, &context(&camera));
+    let commands = device.take_commands();
+    assert!(!commands.iter().any(|command| matches!(command, DeviceCommand::ImportBuffer { .. })));
+    let (swapped, _) = draws(&commands)[0].instance_buffer.clone().unwrap();
+    assert_ne!(swapped, instances);
+    assert!(imported.contains(&swapped));
+}
+
+#[test]
+fn environment_is_baked_once() {
+    let _window = window();
+    let cubemap = AssetServer::new().add(Cubemap::new(16, TextureFormat::Rgba16F, 1));
+    let environment = Environment::new(cubemap.clone());
+    let mut device = RecordingDevice::new();
+    device.set_viewport(0, 0, 64, 32);
+    device.take_commands();
+
+    environment.render(&mut device).expect("Couldn't bake the environment");
+    let commands = device.take_commands();
+    let Some(DeviceCommand::ImportTexture { texture: source, .. }) = commands
+        .iter()
+        .find(|command| matches!(command, DeviceCommand::ImportTexture { .. }))
+    else {
+        panic!("the source cube map wasn't imported");
+    };
+    assert!(commands.contains(&DeviceCommand::GenerateMipmaps(*source)));
+
+    // Every face of the irradiance map and of each prefiltered level, then the BRDF table.
+    let levels = environment.get_prefiltered_max_lod() as u32 + 1;
+    let targets: Vec<_> = (commands.iter())
+        .filter_map(|command| match command {
+            DeviceCommand::CreateFramebuffer { targets, .. } => Some(targets[0]),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(targets.len() as u32, 6 + 6 * levels + 1);
+    for (index, target) in targets[..targets.len() - 1].iter().enumerate() {
+        assert_eq!(target.face, Some(index as u32 % 6));
+        assert_eq!(target.level, (index as u32 / 6).saturating_sub(1));
+    }
+    assert_eq!(targets[targets.len() - 1].face, None);
+    let destroyed = commands.iter().filter(|command| matches!(command, DeviceCommand::DestroyFramebuffer(_)));
+    assert_eq!(destroyed.count(), targets.len());
+    let drawn = draws(&commands);
+    assert_eq!(drawn.len(), targets.len());
+    assert!(drawn[..6].iter().all(|draw| draw.get_texture(0) == Some(*source)));
+
+    // The previous framebuffer and viewport are restored.
+    assert_eq!(device.get_framebuffer(), None);
+    assert_eq!(device.get_viewport(), (0, 0, 64, 32));
+
+    environment.render(&mut device).unwrap();
+    assert!(device.take_commands().is_empty());
+
+    // Reloading the source cube map bakes the maps again.
+    cubemap.set(Cubemap::new(16, TextureFormat::Rgba16F, 1));
+    environment.render(&mut device).unwrap();
+    assert_eq!(draws(&device.take_commands()).len(), targets.len() - 1);
+}
+
+#[cfg(feature = "hot-reload")]
+#[test]
+fn materials_draw_reloaded_textures() {
+    let _window = window();
+    let dir = std::env::temp_dir().join(format!("foux-reload-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("albedo.png");
+    RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255])).save(&path).unwrap();
+
+    let mut assets = AssetServer::new();
+    assets.set_hot_reload(true);
+    assets.set_reload_debounce(Duration::from_millis(10));
+    let mut material = PbrMaterial::new();
+    material.albedo_map = Some(assets.load_texture(&path, true).unwrap());
+    let render = MeshBuilder::cube(1.0).build(&mut assets, material);
+    let camera = Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros());
+    let mut device = RecordingDevice::new();
+
+    render.render(&mut device, &context(&camera));
+    let before = drawn_texture_ids(&device.take_commands(), 0);
+    assert_eq!(before.len(), 1);
+
+    RgbaImage::from_pixel(4, 4, Rgba([0, 0, 255, 255])).save(&path).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !assets.get_events().iter().any(|event| event.kind == AssetEventKind::Reloaded) {
+        assert!(Instant::now() < deadline, "the texture wasn't reloaded");
+        thread::sleep(Duration::from_millis(10));
+        assets.update();
+    }
+
+    // The material's handle resolves to the new texture on the next draw.
+    render.render(&mut device, &context(&camera));
+    let after = drawn_texture_ids(&device.take_commands(), 0);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(after.len(), 1);
+    assert_ne!(after, before);
+}
@@ -0,0 +1,64 @@
+// Renders small scenes on Mesa's surfaceless EGL platform and compares them against the
+// PNGs in `tests/golden`. Set `FOUX_UPDATE_GOLDEN=1` to re-bless them after an intended change.
+#![cfg(target_os = "linux")]
+
+use foux::core::ecs::SystemType;
+use foux::core::material::PbrMaterial;
+use foux::core::mesh::MeshBuilder;
+use foux::core::renderer::RenderPath;
+use foux::core::{Camera, Engine, HeadlessApi, Light};
+use foux::testing::{assert_golden, Tolerance};
+
+use nalgebra_glm::{translation, vec3, vec4};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 96;
+
+/// A lit sphere and cube on a floor, seen from slightly above.
+fn shapes(render_path: RenderPath) -> Engine {
+    let mut engine = Engine::default();
+    engine.set_render_path(render_path);
+    engine.add_system(SystemType::Startup, |_, scene| {
+        scene.camera = Camera::new(vec3(0.0, 2.5, 5.0), vec3(0.0, 0.5, 0.0));
+        scene.lights.push(Light::directional(vec3(-0.4, -1.0, -0.6), vec3(1.0, 0.95, 0.9), 3.0));
+        scene.lights.push(Light::point(vec3(1.5, 1.5, 1.5), 6.0, vec3(0.3, 0.5, 1.0), 8.0));
+
+        let mut floor = PbrMaterial::new();
+        floor.albedo_factor = vec4(0.6, 0.6, 0.6, 1.0);
+        scene.renders.push(MeshBuilder::plane(6.0, 6.0, 1).build(&mut scene.assets, floor));
+
+        let mut red = PbrMaterial::new();
+        red.albedo_factor = vec4(0.8, 0.1, 0.1, 1.0);
+        red.roughness_factor = 0.4;
+        let sphere = MeshBuilder::uv_sphere(0.75, 24, 16).transformed(&translation(&vec3(-1.0, 0.75, 0.0)));
+        scene.renders.push(sphere.build(&mut scene.assets, red));
+
+        let mut gold = PbrMaterial::new();
+        gold.albedo_factor = vec4(1.0, 0.8, 0.3, 1.0);
+        gold.metallic_factor = 1.0;
+        gold.roughness_factor = 0.3;
+        let cube = MeshBuilder::cube(1.0).transformed(&translation(&vec3(1.0, 0.5, 0.0)));
+        scene.renders.push(cube.build(&mut scene.assets, gold));
+    });
+    engine
+}
+
+fn reference(name: &str) -> String {
+    format!("{}/tests/golden/{name}.png", env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn forward_shapes() {
+    let frame = shapes(RenderPath::Forward)
+        .run_headless(WIDTH, HEIGHT, 1, HeadlessApi::Egl)
+        .expect("Couldn't create a headless EGL context");
+    assert_golden(&frame, reference("forward_shapes"), Tolerance::default());
+}
+
+#[test]
+fn deferred_shapes() {
+    let frame = shapes(RenderPath::Deferred)
+        .run_headless(WIDTH, HEIGHT, 1, HeadlessApi::Egl)
+        .expect("Couldn't create a headless EGL context");
+    assert_golden(&frame, reference("deferred_shapes"), Tolerance::default());
+}